@@ -3,38 +3,8 @@
 use libfuzzer_sys::fuzz_target;
 
 use arbitrary::Arbitrary;
-use iso7816::command::{class, BufferFull, Command, CommandBuilder, CommandView};
-
-use std::ops::Deref;
-
-#[derive(Debug)]
-struct WriteMock {
-    buffer: [u8; 4096],
-    written: usize,
-    capacity: usize,
-}
-
-impl Deref for WriteMock {
-    type Target = [u8];
-    fn deref(&self) -> &[u8] {
-        &self.buffer[..self.written]
-    }
-}
-
-impl iso7816::command::Writer for WriteMock {
-    type Error = BufferFull;
-    fn write(&mut self, data: &[u8]) -> Result<usize, BufferFull> {
-        let available = self.capacity - self.written;
-        let written = available.min(data.len());
-        self.buffer[self.written..][..written].copy_from_slice(&data[..written]);
-        self.written += written;
-        if written == 0 {
-            Err(BufferFull::BufferFull)
-        } else {
-            Ok(written)
-        }
-    }
-}
+use iso7816::command::{class, Command, CommandBuilder, CommandView};
+use iso7816::testing::WriteMock;
 
 #[derive(Debug, Arbitrary)]
 struct Input<'a> {
@@ -89,11 +59,7 @@ fuzz_target!(|data: Input| {
         }
     }
 
-    let mut buffer = WriteMock {
-        buffer: [0; 4096],
-        written: 0,
-        capacity: buf_len,
-    };
+    let mut buffer = WriteMock::new(buf_len);
 
     if !supports_extended {
         let mut acc: Option<Command<4096>> = None;
@@ -101,7 +67,7 @@ fuzz_target!(|data: Input| {
             CommandBuilder::new_non_extended(class, ins, p1, p2, data, le, Some(buf_len))
                 .peekable();
         while let Some(cmd) = iter.next() {
-            buffer.written = 0;
+            buffer.clear();
             let (cla, le) = if iter.peek().is_some() {
                 (class.as_chained(), 0)
             } else {
@@ -123,7 +89,7 @@ fuzz_target!(|data: Input| {
         }
         assert_eq!(acc.unwrap().as_view(), command);
     } else {
-        match command.should_split(buffer.capacity) {
+        match command.should_split(buffer.capacity()) {
             None => {
                 command.clone().serialize_into(&mut buffer).unwrap();
                 let view = CommandView::try_from(&*buffer).unwrap();
@@ -136,11 +102,7 @@ fuzz_target!(|data: Input| {
                 assert_eq!(parsed_command.as_view(), current_command);
 
                 loop {
-                    let mut buffer = WriteMock {
-                        buffer: [0; 4096],
-                        written: 0,
-                        capacity: buf_len,
-                    };
+                    let mut buffer = WriteMock::new(buf_len);
 
                     let Some((left, rem)) = remaining_command.should_split(buf_len) else {
                         remaining_command.clone().serialize_into(&mut buffer).unwrap();
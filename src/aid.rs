@@ -96,6 +96,19 @@ impl core::ops::Deref for Aid {
     }
 }
 
+/// AIDs of 1 to 16 bytes, truncatable to at least one byte
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Aid {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u.int_in_range(1..=Self::MAX_LEN)?;
+        let truncated_len = u.int_in_range(1..=len)?;
+        let mut bytes = [0; Self::MAX_LEN];
+        u.fill_buffer(&mut bytes[..len])?;
+        Self::try_new_truncatable(&bytes[..len], truncated_len)
+            .map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
 impl Aid {
     const MAX_LEN: usize = 16;
 
@@ -0,0 +1,539 @@
+//! Answer-to-Reset (ISO 7816-3 8.2)
+//!
+//! [`Atr`] parses the ATR sent by a card, [`AtrBuilder`] assembles one for card emulators.
+
+pub mod historical;
+
+use historical::{CardCapabilities, HistoricalBytes};
+
+/// Maximum length of an ATR, including TS
+pub const MAX_LEN: usize = 33;
+
+/// Maximum number of interface byte groups
+const MAX_GROUPS: usize = 16;
+
+/// Clock rate conversion integer Fi, indexed by the high nibble of TA1. `0` values are RFU.
+pub const FI: [u16; 16] = [
+    372, 372, 558, 744, 1116, 1488, 1860, 0, 0, 512, 768, 1024, 1536, 2048, 0, 0,
+];
+
+/// Maximum clock frequency in kHz, indexed by the high nibble of TA1. `0` values are RFU.
+pub const F_MAX: [u16; 16] = [
+    4000, 5000, 6000, 8000, 12000, 16000, 20000, 0, 0, 5000, 7500, 10000, 15000, 20000, 0, 0,
+];
+
+/// Baud rate adjustment integer Di, indexed by the low nibble of TA1. `0` values are RFU.
+pub const DI: [u8; 16] = [0, 1, 2, 4, 8, 16, 32, 64, 12, 20, 0, 0, 0, 0, 0, 0];
+
+/// Default value of TA1 (Fi = 372, Di = 1)
+pub const DEFAULT_TA1: u8 = 0x11;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AtrError {
+    /// The ATR ends before all the bytes announced by T0 and the TDi bytes
+    Truncated,
+    /// The ATR has bytes after the historical bytes and TCK
+    TooLong,
+    /// TS is neither `3B` nor `3F`
+    InvalidTs,
+    /// The check byte does not match
+    InvalidTck,
+    /// More than 15 historical bytes
+    TooManyHistoricalBytes,
+    /// The COMPACT-TLV data objects or the status indicator are malformed
+    InvalidHistoricalBytes,
+}
+
+/// Encoding convention indicated by TS
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Convention {
+    /// TS = `3B`
+    Direct,
+    /// TS = `3F`
+    Inverse,
+}
+
+impl Convention {
+    pub const fn ts(self) -> u8 {
+        match self {
+            Self::Direct => 0x3B,
+            Self::Inverse => 0x3F,
+        }
+    }
+}
+
+/// Group of interface bytes TAi, TBi, TCi, TDi
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InterfaceBytes {
+    pub ta: Option<u8>,
+    pub tb: Option<u8>,
+    pub tc: Option<u8>,
+    pub td: Option<u8>,
+}
+
+impl InterfaceBytes {
+    /// Protocol indicated by TDi
+    pub fn protocol(&self) -> Option<u8> {
+        self.td.map(|td| td & 0x0F)
+    }
+
+    fn presence(&self) -> u8 {
+        (self.ta.is_some() as u8)
+            | (self.tb.is_some() as u8) << 1
+            | (self.tc.is_some() as u8) << 2
+            | (self.td.is_some() as u8) << 3
+    }
+}
+
+/// Parsed ATR
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Atr<'a> {
+    convention: Convention,
+    interface: heapless::Vec<InterfaceBytes, MAX_GROUPS>,
+    historical_bytes: &'a [u8],
+    tck: Option<u8>,
+}
+
+impl<'a> Atr<'a> {
+    pub fn parse(atr: &'a [u8]) -> Result<Self, AtrError> {
+        let (&ts, rest) = atr.split_first().ok_or(AtrError::Truncated)?;
+        let convention = match ts {
+            0x3B => Convention::Direct,
+            0x3F => Convention::Inverse,
+            _ => return Err(AtrError::InvalidTs),
+        };
+        let (&t0, mut rest) = rest.split_first().ok_or(AtrError::Truncated)?;
+        let k = usize::from(t0 & 0x0F);
+        let mut y = t0 >> 4;
+        let mut interface = heapless::Vec::new();
+        let mut needs_tck = false;
+        loop {
+            let mut group = InterfaceBytes::default();
+            for (bit, byte) in [&mut group.ta, &mut group.tb, &mut group.tc, &mut group.td]
+                .into_iter()
+                .enumerate()
+            {
+                if y & (1 << bit) != 0 {
+                    let (&value, next) = rest.split_first().ok_or(AtrError::Truncated)?;
+                    *byte = Some(value);
+                    rest = next;
+                }
+            }
+            needs_tck |= group.protocol().is_some_and(|t| t != 0);
+            interface.push(group).map_err(|_| AtrError::TooLong)?;
+            match group.td {
+                Some(td) => y = td >> 4,
+                None => break,
+            }
+        }
+        if rest.len() < k {
+            return Err(AtrError::Truncated);
+        }
+        let (historical_bytes, rest) = rest.split_at(k);
+        let tck = match (needs_tck, rest) {
+            (false, []) => None,
+            (true, [tck]) => {
+                let check = atr[1..].iter().fold(0, |acc, b| acc ^ b);
+                if check != 0 {
+                    return Err(AtrError::InvalidTck);
+                }
+                Some(*tck)
+            }
+            (true, []) => return Err(AtrError::Truncated),
+            _ => return Err(AtrError::TooLong),
+        };
+        Ok(Self {
+            convention,
+            interface,
+            historical_bytes,
+            tck,
+        })
+    }
+
+    pub fn convention(&self) -> Convention {
+        self.convention
+    }
+
+    /// Groups of interface bytes, starting with TA1, TB1, TC1, TD1
+    pub fn interface_bytes(&self) -> &[InterfaceBytes] {
+        &self.interface
+    }
+
+    pub fn historical_bytes(&self) -> &'a [u8] {
+        self.historical_bytes
+    }
+
+    /// Parse the historical bytes
+    pub fn historical(&self) -> Result<HistoricalBytes<'a>, AtrError> {
+        HistoricalBytes::parse(self.historical_bytes)
+    }
+
+    /// Card capabilities advertised in the historical bytes, or the defaults if they can't be parsed
+    pub fn card_capabilities(&self) -> CardCapabilities {
+        self.historical()
+            .map(|historical| historical.card_capabilities())
+            .unwrap_or_default()
+    }
+
+    pub fn tck(&self) -> Option<u8> {
+        self.tck
+    }
+
+    fn group(&self, i: usize) -> InterfaceBytes {
+        self.interface.get(i - 1).copied().unwrap_or_default()
+    }
+
+    /// Protocols offered by the card, in the order of the TDi bytes.
+    ///
+    /// If TD1 is absent, only T=0 is offered. T=15 is not a protocol and is skipped.
+    pub fn protocols(&self) -> impl Iterator<Item = u8> + '_ {
+        let implicit_t0 = self.group(1).td.is_none().then_some(0);
+        implicit_t0.into_iter().chain(
+            self.interface
+                .iter()
+                .filter_map(InterfaceBytes::protocol)
+                .filter(|&t| t != 15)
+                .scan(0u16, |seen, t| {
+                    let new = *seen & (1 << t) == 0;
+                    *seen |= 1 << t;
+                    Some(new.then_some(t))
+                })
+                .flatten(),
+        )
+    }
+
+    /// Protocol to use without PPS: the first offered protocol
+    pub fn default_protocol(&self) -> u8 {
+        self.protocols().next().unwrap_or(0)
+    }
+
+    /// TA1, encoding Fi and Di
+    pub fn ta1(&self) -> u8 {
+        self.group(1).ta.unwrap_or(DEFAULT_TA1)
+    }
+
+    /// Clock rate conversion integer, `None` if RFU
+    pub fn fi(&self) -> Option<u16> {
+        Some(FI[usize::from(self.ta1() >> 4)]).filter(|&fi| fi != 0)
+    }
+
+    /// Baud rate adjustment integer, `None` if RFU
+    pub fn di(&self) -> Option<u8> {
+        Some(DI[usize::from(self.ta1() & 0x0F)]).filter(|&di| di != 0)
+    }
+
+    /// Extra guard time integer (TC1)
+    pub fn extra_guard_time(&self) -> u8 {
+        self.group(1).tc.unwrap_or(0)
+    }
+
+    /// TA2: the card is in specific mode if present
+    pub fn specific_mode(&self) -> Option<u8> {
+        self.group(2).ta
+    }
+
+    /// Waiting time integer for T=0 (TC2)
+    pub fn wi(&self) -> u8 {
+        self.group(2).tc.unwrap_or(10)
+    }
+
+    /// First group of interface bytes specific to `protocol` (i > 2)
+    fn specific(&self, protocol: u8) -> Option<InterfaceBytes> {
+        self.interface
+            .iter()
+            .enumerate()
+            .skip(1)
+            .find(|(_, group)| group.protocol() == Some(protocol))
+            .map(|(i, _)| self.group(i + 2))
+    }
+
+    /// Parameters for T=1, using the default values for the absent bytes
+    pub fn t1_parameters(&self) -> T1Parameters {
+        let group = self.specific(1).unwrap_or_default();
+        let default = T1Parameters::default();
+        T1Parameters {
+            ifsc: group.ta.unwrap_or(default.ifsc),
+            bwi: group.tb.map(|tb| tb >> 4).unwrap_or(default.bwi),
+            cwi: group.tb.map(|tb| tb & 0x0F).unwrap_or(default.cwi),
+            crc: group.tc.map(|tc| tc & 1 != 0).unwrap_or(default.crc),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Atr<'a> {
+    type Error = AtrError;
+
+    fn try_from(atr: &'a [u8]) -> Result<Self, AtrError> {
+        Self::parse(atr)
+    }
+}
+
+/// Protocol parameters for T=1 (ISO 7816-3 11.4)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct T1Parameters {
+    /// Information field size for the card
+    pub ifsc: u8,
+    /// Block waiting time integer
+    pub bwi: u8,
+    /// Character waiting time integer
+    pub cwi: u8,
+    /// Use CRC instead of LRC as error detection code
+    pub crc: bool,
+}
+
+impl Default for T1Parameters {
+    fn default() -> Self {
+        Self {
+            ifsc: 32,
+            bwi: 4,
+            cwi: 13,
+            crc: false,
+        }
+    }
+}
+
+/// Builder for ATRs
+///
+/// ```
+/// use iso7816::atr::{Atr, AtrBuilder};
+/// let atr = AtrBuilder::new(b"iso7816").t0().t1().ta1(0x96).build().unwrap();
+/// let parsed = Atr::parse(&atr).unwrap();
+/// assert!(parsed.protocols().eq([0, 1]));
+/// assert_eq!(parsed.historical_bytes(), b"iso7816");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtrBuilder<'a> {
+    convention: Convention,
+    ta1: Option<u8>,
+    tc1: Option<u8>,
+    ta2: Option<u8>,
+    wi: Option<u8>,
+    t0: bool,
+    t1: bool,
+    t1_parameters: Option<T1Parameters>,
+    historical_bytes: &'a [u8],
+}
+
+impl<'a> AtrBuilder<'a> {
+    /// Without any protocol selected, the ATR offers T=0
+    pub fn new(historical_bytes: &'a [u8]) -> Self {
+        Self {
+            convention: Convention::Direct,
+            ta1: None,
+            tc1: None,
+            ta2: None,
+            wi: None,
+            t0: false,
+            t1: false,
+            t1_parameters: None,
+            historical_bytes,
+        }
+    }
+
+    pub fn convention(mut self, convention: Convention) -> Self {
+        self.convention = convention;
+        self
+    }
+
+    /// Offer T=0
+    pub fn t0(mut self) -> Self {
+        self.t0 = true;
+        self
+    }
+
+    /// Offer T=1
+    pub fn t1(mut self) -> Self {
+        self.t1 = true;
+        self
+    }
+
+    /// Offer T=1 with non-default parameters
+    pub fn t1_parameters(mut self, parameters: T1Parameters) -> Self {
+        self.t1 = true;
+        self.t1_parameters = Some(parameters);
+        self
+    }
+
+    /// Fi (high nibble) and Di (low nibble) indices
+    pub fn ta1(mut self, ta1: u8) -> Self {
+        self.ta1 = Some(ta1);
+        self
+    }
+
+    /// Fi and Di values, `None` if they are not in the [`FI`] and [`DI`] tables
+    pub fn fi_di(self, fi: u16, di: u8) -> Option<Self> {
+        let fi = FI.iter().position(|&v| v == fi)?;
+        let di = DI.iter().position(|&v| v == di && v != 0)?;
+        Some(self.ta1(((fi as u8) << 4) | di as u8))
+    }
+
+    /// Extra guard time integer
+    pub fn extra_guard_time(mut self, n: u8) -> Self {
+        self.tc1 = Some(n);
+        self
+    }
+
+    /// Indicate the specific mode (TA2) for the given protocol
+    pub fn specific_mode(mut self, protocol: u8) -> Self {
+        self.ta2 = Some(protocol & 0x0F);
+        self
+    }
+
+    /// Waiting time integer for T=0
+    pub fn wi(mut self, wi: u8) -> Self {
+        self.wi = Some(wi);
+        self
+    }
+
+    pub fn build(&self) -> Result<heapless::Vec<u8, MAX_LEN>, AtrError> {
+        let k = u8::try_from(self.historical_bytes.len())
+            .ok()
+            .filter(|&k| k <= 15)
+            .ok_or(AtrError::TooManyHistoricalBytes)?;
+
+        let mut protocols: heapless::Vec<u8, 3> = heapless::Vec::new();
+        if self.t0 || !self.t1 {
+            protocols.push(0).ok();
+        }
+        if self.t1 {
+            protocols.push(1).ok();
+            if protocols.len() == 1 && self.t1_parameters.is_some() {
+                // T=1 specific bytes are only allowed after TDi with i >= 2
+                protocols.push(1).ok();
+            }
+        }
+        let group2 = self.ta2.is_some() || self.wi.is_some();
+        if protocols == [0] && !group2 {
+            protocols.clear();
+        }
+
+        let mut groups: heapless::Vec<InterfaceBytes, 4> = heapless::Vec::new();
+        groups
+            .push(InterfaceBytes {
+                ta: self.ta1,
+                tc: self.tc1,
+                ..Default::default()
+            })
+            .ok();
+        for (i, &protocol) in protocols.iter().enumerate() {
+            let mut group = InterfaceBytes::default();
+            if i == 0 {
+                group.ta = self.ta2;
+                group.tc = self.wi;
+            } else if protocol == 1 {
+                if let Some(parameters) = self.t1_parameters {
+                    group.ta = Some(parameters.ifsc);
+                    group.tb = Some((parameters.bwi << 4) | (parameters.cwi & 0x0F));
+                    group.tc = parameters.crc.then_some(1);
+                }
+            }
+            groups.push(group).ok();
+        }
+        for i in (0..protocols.len()).rev() {
+            let y = groups[i + 1].presence();
+            groups[i].td = Some((y << 4) | protocols[i]);
+        }
+
+        let mut atr = heapless::Vec::new();
+        let push =
+            |atr: &mut heapless::Vec<u8, MAX_LEN>, b| atr.push(b).map_err(|_| AtrError::TooLong);
+        push(&mut atr, self.convention.ts())?;
+        push(&mut atr, (groups[0].presence() << 4) | k)?;
+        for group in &groups {
+            for byte in [group.ta, group.tb, group.tc, group.td]
+                .into_iter()
+                .flatten()
+            {
+                push(&mut atr, byte)?;
+            }
+        }
+        atr.extend_from_slice(self.historical_bytes)
+            .map_err(|_| AtrError::TooLong)?;
+        if protocols.iter().any(|&t| t != 0) {
+            let tck = atr[1..].iter().fold(0, |acc, b| acc ^ b);
+            push(&mut atr, tck)?;
+        }
+        Ok(atr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn parse() {
+        // Yubikey 5 NFC
+        let raw = hex!("3B FD 13 00 00 81 31 FE 15 80 73 C0 21 C0 57 59 75 62 69 4B 65 79 40");
+        let atr = Atr::parse(&raw).unwrap();
+        assert_eq!(atr.convention(), Convention::Direct);
+        assert!(atr.protocols().eq([1]));
+        assert_eq!(atr.fi(), Some(372));
+        assert_eq!(atr.di(), Some(4));
+        assert_eq!(atr.t1_parameters().ifsc, 0xFE);
+        assert_eq!(atr.t1_parameters().bwi, 1);
+        assert_eq!(atr.t1_parameters().cwi, 5);
+        assert_eq!(atr.historical_bytes(), &raw[9..22]);
+        assert_eq!(atr.tck(), Some(0x40));
+        assert!(atr.card_capabilities().extended_length);
+
+        let atr = Atr::parse(&hex!("3B 02 14 50")).unwrap();
+        assert!(atr.protocols().eq([0]));
+        assert_eq!(atr.historical_bytes(), &hex!("1450"));
+
+        assert_eq!(Atr::parse(&hex!("3B 02 14")), Err(AtrError::Truncated));
+        assert_eq!(Atr::parse(&hex!("3B 02 14 50 00")), Err(AtrError::TooLong));
+        assert_eq!(Atr::parse(&hex!("3A 00")), Err(AtrError::InvalidTs));
+        assert_eq!(Atr::parse(&hex!("3B 80 01 00")), Err(AtrError::InvalidTck));
+    }
+
+    #[test]
+    fn build() {
+        assert_eq!(
+            &*AtrBuilder::new(&hex!("1450")).build().unwrap(),
+            &hex!("3B 02 14 50")
+        );
+
+        let historical = hex!("80 73 C0 21 C0 57 59 75 62 69 4B 65 79");
+        let parameters = T1Parameters {
+            ifsc: 0xFE,
+            bwi: 1,
+            cwi: 5,
+            crc: false,
+        };
+        let raw = AtrBuilder::new(&historical)
+            .ta1(0x13)
+            .t1_parameters(parameters)
+            .build()
+            .unwrap();
+        assert_eq!(&raw[..7], &hex!("3B 9D 13 81 31 FE 15"));
+        let atr = Atr::parse(&raw).unwrap();
+        assert!(atr.protocols().eq([1]));
+        assert_eq!(atr.t1_parameters(), parameters);
+        assert_eq!(atr.historical_bytes(), &historical);
+
+        let raw = AtrBuilder::new(&[])
+            .convention(Convention::Inverse)
+            .t0()
+            .t1()
+            .wi(20)
+            .extra_guard_time(2)
+            .build()
+            .unwrap();
+        let atr = Atr::parse(&raw).unwrap();
+        assert_eq!(atr.convention(), Convention::Inverse);
+        assert!(atr.protocols().eq([0, 1]));
+        assert_eq!(atr.wi(), 20);
+        assert_eq!(atr.extra_guard_time(), 2);
+        assert_eq!(atr.ta1(), DEFAULT_TA1);
+
+        let builder = AtrBuilder::new(&[]).fi_di(512, 8).unwrap();
+        assert_eq!(Atr::parse(&builder.build().unwrap()).unwrap().ta1(), 0x94);
+        assert!(AtrBuilder::new(&[]).fi_di(500, 8).is_none());
+        assert_eq!(
+            AtrBuilder::new(&[0; 16]).build(),
+            Err(AtrError::TooManyHistoricalBytes)
+        );
+    }
+}
@@ -0,0 +1,369 @@
+//! Historical bytes (ISO 7816-4 8.1.1)
+//!
+//! The historical bytes start with a category indicator.
+//! For categories `00` and `80`, they contain COMPACT-TLV data objects, each starting with a
+//! byte encoding the tag (high nibble) and the length (low nibble) of the value.
+
+use super::AtrError;
+use crate::Status;
+
+/// Category indicator, the first historical byte
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    /// `00`: COMPACT-TLV data objects followed by a mandatory status indicator
+    CompactTlvWithStatus,
+    /// `80`: COMPACT-TLV data objects, possibly including a status indicator
+    CompactTlv,
+    /// `10`: reference to a DIR data, given by the next byte
+    DirDataReference(u8),
+    /// Any other value: proprietary format
+    Proprietary(u8),
+}
+
+/// COMPACT-TLV data object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompactTlv<'a> {
+    /// Tag number, the `4X` tag is returned as `4`
+    pub tag: u8,
+    pub value: &'a [u8],
+}
+
+pub mod tag {
+    pub const COUNTRY_CODE: u8 = 0x1;
+    pub const ISSUER_IDENTIFICATION_NUMBER: u8 = 0x2;
+    pub const CARD_SERVICE_DATA: u8 = 0x3;
+    pub const INITIAL_ACCESS_DATA: u8 = 0x4;
+    pub const CARD_ISSUER_DATA: u8 = 0x5;
+    pub const PRE_ISSUING_DATA: u8 = 0x6;
+    pub const CARD_CAPABILITIES: u8 = 0x7;
+    pub const STATUS_INDICATOR: u8 = 0x8;
+    pub const APPLICATION_IDENTIFIER: u8 = 0xF;
+}
+
+/// Iterator over COMPACT-TLV data objects, see [`HistoricalBytes::objects`]
+#[derive(Clone, Debug)]
+pub struct CompactTlvIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for CompactTlvIter<'a> {
+    type Item = CompactTlv<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&first, rest) = self.data.split_first()?;
+        let len = usize::from(first & 0x0F);
+        let value = rest.get(..len)?;
+        self.data = &rest[len..];
+        Some(CompactTlv {
+            tag: first >> 4,
+            value,
+        })
+    }
+}
+
+fn validate_compact_tlv(mut data: &[u8]) -> Result<(), AtrError> {
+    while let Some((&first, rest)) = data.split_first() {
+        let len = usize::from(first & 0x0F);
+        if rest.len() < len {
+            return Err(AtrError::InvalidHistoricalBytes);
+        }
+        data = &rest[len..];
+    }
+    Ok(())
+}
+
+/// Card service data byte (tag `3`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CardServiceData(pub u8);
+
+impl CardServiceData {
+    pub fn selection_by_full_df_name(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    pub fn selection_by_partial_df_name(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+
+    /// BER-TLV data objects are available in EF.DIR
+    pub fn data_objects_in_ef_dir(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    /// BER-TLV data objects are available in EF.ATR/INFO
+    pub fn data_objects_in_ef_atr(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    /// EF.DIR and EF.ATR/INFO access services (b4 to b2)
+    pub fn access_services(&self) -> u8 {
+        (self.0 >> 1) & 0b111
+    }
+
+    pub fn has_master_file(&self) -> bool {
+        self.0 & 0x01 == 0
+    }
+}
+
+/// Card capabilities (tag `7`)
+///
+/// Absent software function tables are treated as all zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CardCapabilities {
+    /// First software function table: selection methods
+    pub selection_methods: u8,
+    /// Second software function table: data coding byte
+    pub data_coding: u8,
+    pub command_chaining: bool,
+    pub extended_length: bool,
+    /// Extended length information is available in EF.ATR/INFO
+    pub extended_length_info: bool,
+    /// Logical channel numbers can be assigned by the card
+    pub card_assigned_channels: bool,
+    /// Logical channel numbers can be assigned by the interface device
+    pub interface_assigned_channels: bool,
+    /// Maximum number of logical channels, including the basic channel
+    pub logical_channels: u8,
+}
+
+impl Default for CardCapabilities {
+    /// Capabilities of a card that does not advertise any
+    fn default() -> Self {
+        Self::from_bytes(&[])
+    }
+}
+
+impl CardCapabilities {
+    pub fn from_bytes(value: &[u8]) -> Self {
+        let table = |i: usize| value.get(i).copied().unwrap_or(0);
+        let third = table(2);
+        Self {
+            selection_methods: table(0),
+            data_coding: table(1),
+            command_chaining: third & 0x80 != 0,
+            extended_length: third & 0x40 != 0,
+            extended_length_info: third & 0x20 != 0,
+            card_assigned_channels: third & 0x10 != 0,
+            interface_assigned_channels: third & 0x08 != 0,
+            logical_channels: (third & 0b111) + 1,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 3] {
+        let third = (self.command_chaining as u8) << 7
+            | (self.extended_length as u8) << 6
+            | (self.extended_length_info as u8) << 5
+            | (self.card_assigned_channels as u8) << 4
+            | (self.interface_assigned_channels as u8) << 3
+            | (self.logical_channels.clamp(1, 8) - 1);
+        [self.selection_methods, self.data_coding, third]
+    }
+}
+
+/// Status indicator (tag `8`, or the last three bytes for category `00`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatusIndicator {
+    /// Life cycle status byte
+    pub life_cycle: Option<u8>,
+    pub status: Option<Status>,
+}
+
+impl StatusIndicator {
+    fn from_bytes(value: &[u8]) -> Result<Self, AtrError> {
+        match *value {
+            [lcs] => Ok(Self {
+                life_cycle: Some(lcs),
+                status: None,
+            }),
+            [sw1, sw2] => Ok(Self {
+                life_cycle: None,
+                status: Some((sw1, sw2).into()),
+            }),
+            [lcs, sw1, sw2] => Ok(Self {
+                life_cycle: Some(lcs),
+                status: Some((sw1, sw2).into()),
+            }),
+            _ => Err(AtrError::InvalidHistoricalBytes),
+        }
+    }
+}
+
+/// Parsed historical bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistoricalBytes<'a> {
+    category: Category,
+    /// COMPACT-TLV data objects, or proprietary data
+    data: &'a [u8],
+    status: Option<StatusIndicator>,
+}
+
+impl<'a> HistoricalBytes<'a> {
+    /// Empty historical bytes are parsed as an empty proprietary category.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, AtrError> {
+        let Some((&first, rest)) = bytes.split_first() else {
+            return Ok(Self {
+                category: Category::Proprietary(0),
+                data: &[],
+                status: None,
+            });
+        };
+        let historical = match first {
+            0x00 => {
+                let split = rest
+                    .len()
+                    .checked_sub(3)
+                    .ok_or(AtrError::InvalidHistoricalBytes)?;
+                let (data, status) = rest.split_at(split);
+                validate_compact_tlv(data)?;
+                Self {
+                    category: Category::CompactTlvWithStatus,
+                    data,
+                    status: Some(StatusIndicator::from_bytes(status)?),
+                }
+            }
+            0x80 => {
+                let mut historical = Self {
+                    category: Category::CompactTlv,
+                    data: rest,
+                    status: None,
+                };
+                validate_compact_tlv(rest)?;
+                historical.status = historical
+                    .find(tag::STATUS_INDICATOR)
+                    .map(StatusIndicator::from_bytes)
+                    .transpose()?;
+                historical
+            }
+            0x10 => {
+                let (&reference, data) =
+                    rest.split_first().ok_or(AtrError::InvalidHistoricalBytes)?;
+                Self {
+                    category: Category::DirDataReference(reference),
+                    data,
+                    status: None,
+                }
+            }
+            other => Self {
+                category: Category::Proprietary(other),
+                data: rest,
+                status: None,
+            },
+        };
+        Ok(historical)
+    }
+
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    /// Data following the category indicator, excluding the status indicator of category `00`
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn is_compact_tlv(&self) -> bool {
+        matches!(
+            self.category,
+            Category::CompactTlv | Category::CompactTlvWithStatus
+        )
+    }
+
+    /// COMPACT-TLV data objects, empty if the category does not use COMPACT-TLV
+    pub fn objects(&self) -> CompactTlvIter<'a> {
+        CompactTlvIter {
+            data: if self.is_compact_tlv() {
+                self.data
+            } else {
+                &[]
+            },
+        }
+    }
+
+    /// Value of the first data object with the given tag
+    pub fn find(&self, tag: u8) -> Option<&'a [u8]> {
+        self.objects()
+            .find(|object| object.tag == tag)
+            .map(|object| object.value)
+    }
+
+    pub fn card_service_data(&self) -> Option<CardServiceData> {
+        self.find(tag::CARD_SERVICE_DATA)
+            .and_then(|value| value.first())
+            .map(|&byte| CardServiceData(byte))
+    }
+
+    pub fn initial_access_data(&self) -> Option<&'a [u8]> {
+        self.find(tag::INITIAL_ACCESS_DATA)
+    }
+
+    pub fn pre_issuing_data(&self) -> Option<&'a [u8]> {
+        self.find(tag::PRE_ISSUING_DATA)
+    }
+
+    pub fn application_identifier(&self) -> Option<&'a [u8]> {
+        self.find(tag::APPLICATION_IDENTIFIER)
+    }
+
+    /// Card capabilities, using [`CardCapabilities::default`] if they are absent
+    pub fn card_capabilities(&self) -> CardCapabilities {
+        self.find(tag::CARD_CAPABILITIES)
+            .map(CardCapabilities::from_bytes)
+            .unwrap_or_default()
+    }
+
+    pub fn status_indicator(&self) -> Option<StatusIndicator> {
+        self.status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn compact_tlv() {
+        // Yubikey 5 NFC
+        let bytes = hex!("80 73 C0 21 C0 57 59 75 62 69 4B 65 79");
+        let historical = HistoricalBytes::parse(&bytes).unwrap();
+        assert_eq!(historical.category(), Category::CompactTlv);
+        let capabilities = historical.card_capabilities();
+        assert!(capabilities.command_chaining);
+        assert!(capabilities.extended_length);
+        assert_eq!(capabilities.logical_channels, 1);
+        assert_eq!(capabilities.to_bytes(), hex!("C0 21 C0"));
+        let objects: Vec<_> = historical.objects().map(|object| object.tag).collect();
+        assert_eq!(objects, [0x7, 0x5]);
+        assert_eq!(historical.status_indicator(), None);
+
+        let bytes = hex!("00 31 C0 73 00 00 13 82 90 00");
+        let historical = HistoricalBytes::parse(&bytes).unwrap();
+        assert_eq!(historical.category(), Category::CompactTlvWithStatus);
+        let service = historical.card_service_data().unwrap();
+        assert!(service.selection_by_full_df_name());
+        assert!(service.has_master_file());
+        let capabilities = historical.card_capabilities();
+        assert!(!capabilities.command_chaining);
+        assert!(capabilities.card_assigned_channels);
+        assert_eq!(capabilities.logical_channels, 4);
+        assert_eq!(
+            historical.status_indicator(),
+            Some(StatusIndicator {
+                life_cycle: Some(0x82),
+                status: Some(Status::Success),
+            })
+        );
+
+        assert_eq!(
+            HistoricalBytes::parse(&hex!("80 73 C0")),
+            Err(AtrError::InvalidHistoricalBytes)
+        );
+        assert_eq!(
+            HistoricalBytes::parse(&hex!("00 90 00")),
+            Err(AtrError::InvalidHistoricalBytes)
+        );
+        let proprietary = HistoricalBytes::parse(b"iso7816").unwrap();
+        assert_eq!(proprietary.category(), Category::Proprietary(b'i'));
+        assert_eq!(proprietary.card_capabilities(), CardCapabilities::default());
+    }
+}
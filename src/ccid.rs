@@ -0,0 +1,548 @@
+//! USB CCID bulk messages (CCID 1.1 6.1, 6.2)
+//!
+//! Each bulk message starts with a ten bytes header: message type, length of the data (little
+//! endian), slot, sequence number and three message-specific bytes. Messages longer than the
+//! bulk endpoint packet size span several USB packets and are reassembled by a [`Receiver`].
+//!
+//! With the extended APDU level of exchange, APDUs longer than the maximum message length of the
+//! reader are split across several messages, as indicated by their [`ChainParameter`].
+//! [`chain`] splits an APDU, [`ApduBuffer`] reassembles it.
+
+/// Length of the message header
+pub const HEADER_LEN: usize = 10;
+
+pub mod message_type {
+    pub const PC_TO_RDR_ICC_POWER_ON: u8 = 0x62;
+    pub const PC_TO_RDR_ICC_POWER_OFF: u8 = 0x63;
+    pub const PC_TO_RDR_GET_SLOT_STATUS: u8 = 0x65;
+    pub const PC_TO_RDR_XFR_BLOCK: u8 = 0x6F;
+    pub const RDR_TO_PC_DATA_BLOCK: u8 = 0x80;
+    pub const RDR_TO_PC_SLOT_STATUS: u8 = 0x81;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CcidError {
+    /// The message is shorter than its header or than the length it indicates
+    Truncated,
+    /// The message or APDU does not fit in the buffer
+    BufferFull,
+    UnsupportedMessage(u8),
+    InvalidChainParameter,
+    /// Reserved value of `bStatus`
+    InvalidStatus(u8),
+}
+
+/// Message header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub message_type: u8,
+    /// Length of the data following the header
+    pub length: u32,
+    pub slot: u8,
+    pub seq: u8,
+    /// Message-specific bytes
+    pub specific: [u8; 3],
+}
+
+impl Header {
+    pub fn parse(message: &[u8]) -> Result<Self, CcidError> {
+        let header: &[u8; HEADER_LEN] = message
+            .get(..HEADER_LEN)
+            .and_then(|header| header.try_into().ok())
+            .ok_or(CcidError::Truncated)?;
+        Ok(Self {
+            message_type: header[0],
+            length: u32::from_le_bytes([header[1], header[2], header[3], header[4]]),
+            slot: header[5],
+            seq: header[6],
+            specific: [header[7], header[8], header[9]],
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let length = self.length.to_le_bytes();
+        [
+            self.message_type,
+            length[0],
+            length[1],
+            length[2],
+            length[3],
+            self.slot,
+            self.seq,
+            self.specific[0],
+            self.specific[1],
+            self.specific[2],
+        ]
+    }
+
+    /// Length of the full message, `None` if it overflows `usize`
+    pub fn message_len(&self) -> Option<usize> {
+        usize::try_from(self.length)
+            .ok()
+            .and_then(|len| len.checked_add(HEADER_LEN))
+    }
+}
+
+/// Position of the data in an APDU split across several messages
+///
+/// This is `wLevelParameter` for PC_to_RDR_XfrBlock and `bChainParameter` for RDR_to_PC_DataBlock.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChainParameter {
+    /// The APDU begins and ends with this message
+    #[default]
+    BeginsAndEnds,
+    BeginsAndContinues,
+    ContinuesAndEnds,
+    Continues,
+    /// Empty message requesting the next part of the response
+    ContinuationRequested,
+}
+
+impl ChainParameter {
+    pub fn begins(self) -> bool {
+        matches!(self, Self::BeginsAndEnds | Self::BeginsAndContinues)
+    }
+
+    pub fn ends(self) -> bool {
+        matches!(self, Self::BeginsAndEnds | Self::ContinuesAndEnds)
+    }
+}
+
+impl TryFrom<u16> for ChainParameter {
+    type Error = CcidError;
+
+    fn try_from(value: u16) -> Result<Self, CcidError> {
+        Ok(match value {
+            0x00 => Self::BeginsAndEnds,
+            0x01 => Self::BeginsAndContinues,
+            0x02 => Self::ContinuesAndEnds,
+            0x03 => Self::Continues,
+            0x10 => Self::ContinuationRequested,
+            _ => return Err(CcidError::InvalidChainParameter),
+        })
+    }
+}
+
+impl From<ChainParameter> for u16 {
+    fn from(value: ChainParameter) -> u16 {
+        match value {
+            ChainParameter::BeginsAndEnds => 0x00,
+            ChainParameter::BeginsAndContinues => 0x01,
+            ChainParameter::ContinuesAndEnds => 0x02,
+            ChainParameter::Continues => 0x03,
+            ChainParameter::ContinuationRequested => 0x10,
+        }
+    }
+}
+
+/// State of the ICC, bits 0-1 of `bStatus`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IccStatus {
+    Active,
+    Inactive,
+    Absent,
+}
+
+/// Result of the command, bits 6-7 of `bStatus`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandStatus {
+    Processed,
+    /// The reason is given by `bError`
+    Failed,
+    /// The card requested more time, `bError` is the BWT multiplier
+    TimeExtension,
+}
+
+/// `bStatus` field of the responses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotStatus {
+    pub icc: IccStatus,
+    pub command: CommandStatus,
+}
+
+impl SlotStatus {
+    pub const OK: Self = Self {
+        icc: IccStatus::Active,
+        command: CommandStatus::Processed,
+    };
+
+    pub fn to_byte(self) -> u8 {
+        let icc = match self.icc {
+            IccStatus::Active => 0,
+            IccStatus::Inactive => 1,
+            IccStatus::Absent => 2,
+        };
+        let command = match self.command {
+            CommandStatus::Processed => 0,
+            CommandStatus::Failed => 1,
+            CommandStatus::TimeExtension => 2,
+        };
+        icc | command << 6
+    }
+}
+
+impl TryFrom<u8> for SlotStatus {
+    type Error = CcidError;
+
+    fn try_from(value: u8) -> Result<Self, CcidError> {
+        let icc = match value & 0b11 {
+            0 => IccStatus::Active,
+            1 => IccStatus::Inactive,
+            2 => IccStatus::Absent,
+            _ => return Err(CcidError::InvalidStatus(value)),
+        };
+        let command = match value >> 6 {
+            0 => CommandStatus::Processed,
+            1 => CommandStatus::Failed,
+            2 => CommandStatus::TimeExtension,
+            _ => return Err(CcidError::InvalidStatus(value)),
+        };
+        Ok(Self { icc, command })
+    }
+}
+
+/// Bulk message, without its slot and sequence number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message<'a> {
+    IccPowerOn {
+        power_select: u8,
+    },
+    IccPowerOff,
+    GetSlotStatus,
+    XfrBlock {
+        /// Block waiting time extension for T=1
+        bwi: u8,
+        level: ChainParameter,
+        data: &'a [u8],
+    },
+    DataBlock {
+        status: SlotStatus,
+        error: u8,
+        chain: ChainParameter,
+        data: &'a [u8],
+    },
+    SlotStatus {
+        status: SlotStatus,
+        error: u8,
+        clock_status: u8,
+    },
+}
+
+/// Bulk message with its addressing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub slot: u8,
+    pub seq: u8,
+    pub message: Message<'a>,
+}
+
+impl<'a> Frame<'a> {
+    pub fn parse(frame: &'a [u8]) -> Result<Self, CcidError> {
+        use message_type::*;
+
+        let header = Header::parse(frame)?;
+        let data = header
+            .message_len()
+            .and_then(|len| frame.get(HEADER_LEN..len))
+            .ok_or(CcidError::Truncated)?;
+        let [b7, b8, b9] = header.specific;
+        let message = match header.message_type {
+            PC_TO_RDR_ICC_POWER_ON => Message::IccPowerOn { power_select: b7 },
+            PC_TO_RDR_ICC_POWER_OFF => Message::IccPowerOff,
+            PC_TO_RDR_GET_SLOT_STATUS => Message::GetSlotStatus,
+            PC_TO_RDR_XFR_BLOCK => Message::XfrBlock {
+                bwi: b7,
+                level: u16::from_le_bytes([b8, b9]).try_into()?,
+                data,
+            },
+            RDR_TO_PC_DATA_BLOCK => Message::DataBlock {
+                status: b7.try_into()?,
+                error: b8,
+                chain: u16::from(b9).try_into()?,
+                data,
+            },
+            RDR_TO_PC_SLOT_STATUS => Message::SlotStatus {
+                status: b7.try_into()?,
+                error: b8,
+                clock_status: b9,
+            },
+            other => return Err(CcidError::UnsupportedMessage(other)),
+        };
+        Ok(Self {
+            slot: header.slot,
+            seq: header.seq,
+            message,
+        })
+    }
+
+    /// Serialize the message to `out`, returning its length
+    pub fn serialize(&self, out: &mut [u8]) -> Result<usize, CcidError> {
+        use message_type::*;
+
+        let (message_type, specific, data): (u8, [u8; 3], &[u8]) = match self.message {
+            Message::IccPowerOn { power_select } => {
+                (PC_TO_RDR_ICC_POWER_ON, [power_select, 0, 0], &[])
+            }
+            Message::IccPowerOff => (PC_TO_RDR_ICC_POWER_OFF, [0; 3], &[]),
+            Message::GetSlotStatus => (PC_TO_RDR_GET_SLOT_STATUS, [0; 3], &[]),
+            Message::XfrBlock { bwi, level, data } => {
+                let [l0, l1] = u16::from(level).to_le_bytes();
+                (PC_TO_RDR_XFR_BLOCK, [bwi, l0, l1], data)
+            }
+            Message::DataBlock {
+                status,
+                error,
+                chain,
+                data,
+            } => (
+                RDR_TO_PC_DATA_BLOCK,
+                [status.to_byte(), error, u16::from(chain) as u8],
+                data,
+            ),
+            Message::SlotStatus {
+                status,
+                error,
+                clock_status,
+            } => (
+                RDR_TO_PC_SLOT_STATUS,
+                [status.to_byte(), error, clock_status],
+                &[],
+            ),
+        };
+        let header = Header {
+            message_type,
+            length: data.len() as u32,
+            slot: self.slot,
+            seq: self.seq,
+            specific,
+        };
+        let len = HEADER_LEN + data.len();
+        let out = out.get_mut(..len).ok_or(CcidError::BufferFull)?;
+        out[..HEADER_LEN].copy_from_slice(&header.to_bytes());
+        out[HEADER_LEN..].copy_from_slice(data);
+        Ok(len)
+    }
+}
+
+/// Reassembly of bulk messages spanning several USB packets
+#[derive(Clone, Debug, Default)]
+pub struct Receiver<const N: usize> {
+    buffer: heapless::Vec<u8, N>,
+    complete: bool,
+}
+
+impl<const N: usize> Receiver<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a USB packet, returning the message once it is complete.
+    ///
+    /// The next packet starts a new message. On error, the partial message is discarded.
+    pub fn push(&mut self, packet: &[u8]) -> Result<Option<Frame<'_>>, CcidError> {
+        if self.complete {
+            self.reset();
+        }
+        if self.buffer.extend_from_slice(packet).is_err() {
+            self.reset();
+            return Err(CcidError::BufferFull);
+        }
+        let Ok(header) = Header::parse(&self.buffer) else {
+            return Ok(None);
+        };
+        let Some(len) = header.message_len().filter(|&len| len <= N) else {
+            self.reset();
+            return Err(CcidError::BufferFull);
+        };
+        if self.buffer.len() < len {
+            return Ok(None);
+        }
+        self.complete = true;
+        Frame::parse(&self.buffer).map(Some)
+    }
+
+    /// Discard the partial message
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.complete = false;
+    }
+}
+
+/// Iterator over the parts of an APDU, see [`chain`]
+#[derive(Clone, Debug)]
+pub struct Chain<'a> {
+    data: &'a [u8],
+    max_len: usize,
+    first: bool,
+    done: bool,
+}
+
+/// Split `data` in parts of at most `max_len` bytes, with their chain parameter
+pub fn chain(data: &[u8], max_len: usize) -> Chain<'_> {
+    Chain {
+        data,
+        max_len: max_len.max(1),
+        first: true,
+        done: false,
+    }
+}
+
+impl<'a> Iterator for Chain<'a> {
+    type Item = (ChainParameter, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let (part, rest) = self.data.split_at(self.max_len.min(self.data.len()));
+        self.data = rest;
+        let parameter = match (self.first, rest.is_empty()) {
+            (true, true) => ChainParameter::BeginsAndEnds,
+            (true, false) => ChainParameter::BeginsAndContinues,
+            (false, true) => ChainParameter::ContinuesAndEnds,
+            (false, false) => ChainParameter::Continues,
+        };
+        self.first = false;
+        self.done = rest.is_empty();
+        Some((parameter, part))
+    }
+}
+
+/// Reassembly of an APDU split across several messages
+#[derive(Clone, Debug, Default)]
+pub struct ApduBuffer<const N: usize> {
+    apdu: heapless::Vec<u8, N>,
+    complete: bool,
+}
+
+impl<const N: usize> ApduBuffer<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the data of a message, returning the APDU once complete.
+    ///
+    /// The next call starts a new APDU.
+    pub fn push(
+        &mut self,
+        parameter: ChainParameter,
+        data: &[u8],
+    ) -> Result<Option<&[u8]>, CcidError> {
+        if parameter.begins() || self.complete {
+            self.apdu.clear();
+            self.complete = false;
+        }
+        if !parameter.begins()
+            && (self.apdu.is_empty() || parameter == ChainParameter::ContinuationRequested)
+        {
+            self.apdu.clear();
+            return Err(CcidError::InvalidChainParameter);
+        }
+        if self.apdu.extend_from_slice(data).is_err() {
+            self.apdu.clear();
+            return Err(CcidError::BufferFull);
+        }
+        self.complete = parameter.ends();
+        Ok(self.complete.then_some(&self.apdu[..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn frames() {
+        let frame = Frame {
+            slot: 0,
+            seq: 1,
+            message: Message::XfrBlock {
+                bwi: 0,
+                level: ChainParameter::BeginsAndEnds,
+                data: &hex!("00A40400"),
+            },
+        };
+        let mut buffer = [0; 64];
+        let len = frame.serialize(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &hex!("6F 04000000 00 01 00 0000 00A40400"));
+        assert_eq!(Frame::parse(&buffer[..len]), Ok(frame));
+
+        let frame = Frame::parse(&hex!("80 02000000 00 01 00 00 00 9000")).unwrap();
+        assert_eq!(
+            frame.message,
+            Message::DataBlock {
+                status: SlotStatus::OK,
+                error: 0,
+                chain: ChainParameter::BeginsAndEnds,
+                data: &hex!("9000"),
+            }
+        );
+        assert_eq!(
+            Frame::parse(&hex!("80 03000000 00 01 00 00 00 9000")),
+            Err(CcidError::Truncated)
+        );
+        assert_eq!(
+            Frame::parse(&hex!("50 00000000 00 01 00 00 00")),
+            Err(CcidError::UnsupportedMessage(0x50))
+        );
+    }
+
+    #[test]
+    fn packets() {
+        let apdu = [0x42; 100];
+        let mut buffer = [0; 128];
+        let len = Frame {
+            slot: 0,
+            seq: 2,
+            message: Message::XfrBlock {
+                bwi: 0,
+                level: ChainParameter::BeginsAndEnds,
+                data: &apdu,
+            },
+        }
+        .serialize(&mut buffer)
+        .unwrap();
+        let mut receiver = Receiver::<128>::new();
+        let mut packets = buffer[..len].chunks(64);
+        assert_eq!(receiver.push(packets.next().unwrap()), Ok(None));
+        let frame = receiver.push(packets.next().unwrap()).unwrap().unwrap();
+        assert_eq!(frame.seq, 2);
+        assert!(matches!(frame.message, Message::XfrBlock { data, .. } if data == apdu));
+
+        let mut small = Receiver::<64>::new();
+        assert_eq!(small.push(&buffer[..64]), Err(CcidError::BufferFull));
+
+        // dwLength overflowing the message length
+        let header = hex!("6F FFFFFFFF 00 01 00 00 00");
+        assert_eq!(receiver.push(&header), Err(CcidError::BufferFull));
+        assert_eq!(Frame::parse(&header), Err(CcidError::Truncated));
+    }
+
+    #[test]
+    fn apdu_chaining() {
+        let apdu = [0x42; 10];
+        let parts: Vec<_> = chain(&apdu, 4).collect();
+        assert_eq!(
+            parts.iter().map(|(p, d)| (*p, d.len())).collect::<Vec<_>>(),
+            [
+                (ChainParameter::BeginsAndContinues, 4),
+                (ChainParameter::Continues, 4),
+                (ChainParameter::ContinuesAndEnds, 2)
+            ]
+        );
+        assert_eq!(
+            chain(&[], 4).collect::<Vec<_>>(),
+            [(ChainParameter::BeginsAndEnds, &[][..])]
+        );
+
+        let mut buffer = ApduBuffer::<16>::new();
+        assert_eq!(buffer.push(parts[0].0, parts[0].1), Ok(None));
+        assert_eq!(buffer.push(parts[1].0, parts[1].1), Ok(None));
+        assert_eq!(buffer.push(parts[2].0, parts[2].1), Ok(Some(&apdu[..])));
+        assert_eq!(
+            buffer.push(ChainParameter::Continues, &apdu),
+            Err(CcidError::InvalidChainParameter)
+        );
+    }
+}
@@ -1,14 +1,33 @@
 use crate::Data;
 
+pub mod binary;
+pub mod challenge;
 pub mod class;
+pub mod data_objects;
 pub mod instruction;
+pub mod key_pair;
+pub mod key_reference;
 pub use instruction::Instruction;
+pub use key_reference::KeyReference;
+
+pub mod mse;
+pub mod pin;
+pub mod record;
+pub mod select;
 
 pub mod writer;
-pub use writer::{BufferFull, Writer};
+pub use writer::{Backpatch, BufferFull, Writer, WriterExt};
+
+mod batch;
+#[cfg(any(feature = "std", test))]
+pub use batch::serialize_batch_to_vec;
+pub use batch::{serialize_batch, BatchEntry};
+
+mod buf;
+pub use buf::CommandBuf;
 
 mod datasource;
-pub use datasource::{DataSource, DataStream};
+pub use datasource::{CachedLen, Chain, DataSource, DataStream, Fill, FnStream};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Command<const S: usize> {
@@ -111,21 +130,34 @@ pub struct CommandView<'a> {
 }
 
 impl<'a> CommandView<'a> {
-    pub fn class(&self) -> class::Class {
+    pub const fn class(&self) -> class::Class {
         self.class
     }
 
-    pub fn instruction(&self) -> Instruction {
+    pub const fn instruction(&self) -> Instruction {
         self.instruction
     }
 
-    pub fn data(&self) -> &'a [u8] {
+    pub const fn data(&self) -> &'a [u8] {
         self.data
     }
 
-    pub fn expected(&self) -> usize {
+    pub const fn expected(&self) -> usize {
         self.le
     }
+
+    /// Command with the same header, and the given data field and expected length
+    pub(crate) fn with_body<'b>(&self, data: &'b [u8], le: usize) -> CommandView<'b> {
+        CommandView {
+            class: self.class,
+            instruction: self.instruction,
+            p1: self.p1,
+            p2: self.p2,
+            data,
+            le,
+            extended: data.len() > 255 || le > 256,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -143,7 +175,7 @@ pub struct CommandBuilder<D> {
     pub p1: u8,
     pub p2: u8,
 
-    data: D,
+    data: CachedLen<D>,
 
     le: ExpectedLen,
     extended_length: ExtendedLen,
@@ -208,6 +240,7 @@ impl<D: DataSource> CommandBuilder<D> {
         data: D,
         le: impl Into<ExpectedLen>,
     ) -> Self {
+        let data = CachedLen::new(data);
         assert!(data.len() <= u16::MAX as usize);
         Self {
             class,
@@ -228,31 +261,160 @@ impl<D: DataSource> CommandBuilder<D> {
         self
     }
 
+    /// Replace the expected length of the response
+    pub fn with_expected_len(mut self, le: impl Into<ExpectedLen>) -> Self {
+        self.le = le.into();
+        self
+    }
+
+    pub fn class(&self) -> class::Class {
+        self.class
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        self.instruction
+    }
+
     pub fn data(&self) -> D
     where
         D: Copy,
     {
-        self.data
+        *self.data.get()
     }
 
     fn header_data(&self) -> BuildingHeaderData {
+        BuildingHeaderData::new(self.data.len(), self.le, self.extended_length)
+    }
+
+    /// Required length for serialization in only one command.
+    /// Assumes extended length support
+    ///
+    /// This can be useful to get the necessary dimension for the buffer to provide to [serialize_into](Self::serialize_into)
+    pub fn required_len(&self) -> usize {
+        self.required_len_with(&self.header_data())
+    }
+
+    fn required_len_with(&self, header_data: &BuildingHeaderData) -> usize {
+        let length_len = header_data.data_len.len + header_data.expected_data_len.len;
+        HEADER_LEN + length_len + self.data.len()
+    }
+
+    /// Serialize into one vector with assuming support for extended length information
+    #[cfg(any(feature = "std", test))]
+    pub fn serialize_to_vec(self) -> Vec<u8>
+    where
+        D: DataStream<Vec<u8>>,
+    {
+        let required_len = self.required_len();
+        let mut buffer = Vec::with_capacity(required_len);
+        self.serialize_into(&mut buffer).unwrap();
+        debug_assert_eq!(required_len, buffer.len());
+        buffer
+    }
+
+    /// This assumes that the writer has enough space to encode the APDU.
+    /// If that might not be the case, first use [`should_split`](Self::should_split)
+    pub fn serialize_into<W: Writer>(&self, writer: &mut W) -> Result<(), W::Error>
+    where
+        D: DataStream<W>,
+    {
+        self.serialize_with(&self.header_data(), writer)
+    }
+
+    fn serialize_with<W: Writer>(
+        &self,
+        header_data: &BuildingHeaderData,
+        writer: &mut W,
+    ) -> Result<(), W::Error>
+    where
+        D: DataStream<W>,
+    {
+        let BuildingHeaderData {
+            data_len,
+            expected_data_len,
+            ..
+        } = header_data;
+
+        let header = [
+            self.class.into_inner(),
+            self.instruction.into(),
+            self.p1,
+            self.p2,
+        ];
+        writer.write_all_vectored(&[&header, data_len.as_slice()])?;
+        self.data.to_writer(writer)?;
+        writer.write_all(expected_data_len.as_slice())?;
+        Ok(())
+    }
+}
+
+/// Encoded Lc or Le field, stored inline
+#[derive(Clone, Copy)]
+struct LengthField {
+    bytes: [u8; 3],
+    len: usize,
+}
+
+impl LengthField {
+    const EMPTY: Self = Self {
+        bytes: [0; 3],
+        len: 0,
+    };
+
+    const fn short(len: u8) -> Self {
+        Self {
+            bytes: [len, 0, 0],
+            len: 1,
+        }
+    }
+
+    /// Extended Lc, or extended Le without Lc
+    const fn extended(len: u16) -> Self {
+        let [high, low] = len.to_be_bytes();
+        Self {
+            bytes: [0, high, low],
+            len: 3,
+        }
+    }
+
+    /// Extended Le following an extended Lc
+    const fn extended_le(len: u16) -> Self {
+        let [high, low] = len.to_be_bytes();
+        Self {
+            bytes: [high, low, 0],
+            len: 2,
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+struct BuildingHeaderData {
+    le: ExpectedLen,
+    data_len: LengthField,
+    expected_data_len: LengthField,
+}
+
+impl BuildingHeaderData {
+    /// Length fields of a command, computed independently of its data type to avoid
+    /// duplicating this code for each of them
+    fn new(data_len: usize, le: ExpectedLen, extended_length: ExtendedLen) -> Self {
         /// Returns (data, len of data, and is_extended)
         fn serialize_data_len(
             len: u16,
             expected_len: ExpectedLen,
             extended: ExtendedLen,
-        ) -> (heapless::Vec<u8, 3>, bool) {
+        ) -> (LengthField, bool) {
             let expected_is_extended =
                 matches!(expected_len, ExpectedLen::Ne(257..) | ExpectedLen::Max);
             match (len, expected_is_extended, extended) {
-                (0, _, _) => (Default::default(), false),
+                (0, _, _) => (LengthField::EMPTY, false),
                 (1..=255, false, ExtendedLen::Unsupported | ExtendedLen::Supported) => {
-                    ([len as u8].as_slice().try_into().unwrap(), false)
-                }
-                _ => {
-                    let l = len.to_be_bytes();
-                    ([0, l[0], l[1]].as_slice().try_into().unwrap(), true)
+                    (LengthField::short(len as u8), false)
                 }
+                _ => (LengthField::extended(len), true),
             }
         }
 
@@ -261,31 +423,25 @@ impl<D: DataSource> CommandBuilder<D> {
             lc_extended: bool,
             data_is_empty: bool,
             extended: ExtendedLen,
-        ) -> heapless::Vec<u8, 3> {
+        ) -> LengthField {
             match (len, lc_extended, data_is_empty, extended) {
-                (ExpectedLen::Ne(0), _, _, _) => Default::default(),
+                (ExpectedLen::Ne(0), _, _, _) => LengthField::EMPTY,
                 (
                     ExpectedLen::Ne(len @ 1..=255),
                     false,
                     _,
                     ExtendedLen::Unsupported | ExtendedLen::Supported,
-                ) => [len as u8].as_slice().try_into().unwrap(),
+                ) => LengthField::short(len as u8),
                 (
                     ExpectedLen::Ne(256),
                     false,
                     _,
                     ExtendedLen::Unsupported | ExtendedLen::Supported,
-                ) => [0].as_slice().try_into().unwrap(),
-                (ExpectedLen::Ne(len), true, false, _) => {
-                    let l = len.to_be_bytes();
-                    [l[0], l[1]].as_slice().try_into().unwrap()
-                }
-                (ExpectedLen::Max, true, false, _) => [0, 0].as_slice().try_into().unwrap(),
-                (ExpectedLen::Ne(len), false, true, _) => {
-                    let l = len.to_be_bytes();
-                    [0, l[0], l[1]].as_slice().try_into().unwrap()
-                }
-                (ExpectedLen::Max, false, true, _) => [0, 0, 0].as_slice().try_into().unwrap(),
+                ) => LengthField::short(0),
+                (ExpectedLen::Ne(len), true, false, _) => LengthField::extended_le(len),
+                (ExpectedLen::Max, true, false, _) => LengthField::extended_le(0),
+                (ExpectedLen::Ne(len), false, true, _) => LengthField::extended(len),
+                (ExpectedLen::Max, false, true, _) => LengthField::extended(0),
                 (ExpectedLen::Ne(257..) | ExpectedLen::Max, false, false, _)
                 | (_, false, false, ExtendedLen::Forced) => {
                     unreachable!("Can't have non extended Lc and extended Le")
@@ -296,82 +452,24 @@ impl<D: DataSource> CommandBuilder<D> {
             }
         }
 
-        let le = if self.extended_length == ExtendedLen::Unsupported {
-            self.le.min(256.into())
+        let le = if extended_length == ExtendedLen::Unsupported {
+            le.min(256.into())
         } else {
-            self.le
+            le
         };
 
         // Safe to unwrap because of check in `new`
-        let (data_len, lc_extended) = serialize_data_len(
-            self.data.len().try_into().unwrap(),
-            le,
-            self.extended_length,
-        );
+        let (lc, lc_extended) =
+            serialize_data_len(data_len.try_into().unwrap(), le, extended_length);
 
         let expected_data_len =
-            serialize_expected_len(le, lc_extended, self.data.is_empty(), self.extended_length);
+            serialize_expected_len(le, lc_extended, data_len == 0, extended_length);
         BuildingHeaderData {
             le,
-            data_len,
+            data_len: lc,
             expected_data_len,
         }
     }
-
-    /// Required length for serialization in only one command.
-    /// Assumes extended length support
-    ///
-    /// This can be useful to get the necessary dimension for the buffer to provide to [serialize_into](Self::serialize_into)
-    pub fn required_len(&self) -> usize {
-        let header_data = self.header_data();
-        let header_len = 4;
-        let length_len = header_data.data_len.len() + header_data.expected_data_len.len();
-        header_len + length_len + self.data.len()
-    }
-
-    /// Serialize into one vector with assuming support for extended length information
-    #[cfg(any(feature = "std", test))]
-    pub fn serialize_to_vec(self) -> Vec<u8>
-    where
-        D: DataStream<Vec<u8>>,
-    {
-        let required_len = self.required_len();
-        let mut buffer = Vec::with_capacity(required_len);
-        self.serialize_into(&mut buffer).unwrap();
-        debug_assert_eq!(required_len, buffer.len());
-        buffer
-    }
-
-    /// This assumes that the writer has enough space to encode the APDU.
-    /// If that might not be the case, first use [`should_split`](Self::should_split)
-    pub fn serialize_into<W: Writer>(&self, writer: &mut W) -> Result<(), W::Error>
-    where
-        D: DataStream<W>,
-    {
-        let BuildingHeaderData {
-            data_len,
-            expected_data_len,
-            ..
-        } = self.header_data();
-
-        writer.write_all(&[
-            self.class.into_inner(),
-            self.instruction.into(),
-            self.p1,
-            self.p2,
-        ])?;
-
-        writer.write_all(&data_len)?;
-        self.data.to_writer(writer)?;
-        writer.write_all(&expected_data_len)?;
-        Ok(())
-    }
-}
-
-struct BuildingHeaderData {
-    le: ExpectedLen,
-    data_len: heapless::Vec<u8, 3>,
-    expected_data_len: heapless::Vec<u8, 3>,
 }
 
 impl<'a, D: PartialEq<&'a [u8]>> PartialEq<CommandView<'a>> for CommandBuilder<D> {
@@ -390,7 +488,7 @@ impl<'a, D: PartialEq<&'a [u8]>> PartialEq<CommandView<'a>> for CommandBuilder<D
             && instruction == &other.instruction
             && p1 == &other.p1
             && p2 == &other.p2
-            && data == &other.data
+            && data.get() == &other.data
             && le == other.le
     }
 }
@@ -415,7 +513,7 @@ impl<'a> CommandBuilder<&'a [u8]> {
                 instruction,
                 p1,
                 p2,
-                data,
+                data: CachedLen::new(data),
                 le: le.into(),
                 extended_length: ExtendedLen::Unsupported,
             }),
@@ -424,6 +522,23 @@ impl<'a> CommandBuilder<&'a [u8]> {
         }
     }
 
+    /// Split the command into chained commands that each fit within `available_len`.
+    ///
+    /// When `extended_length` is false, the commands are encoded as with [`new_non_extended`](Self::new_non_extended)
+    pub(crate) fn into_chained(
+        mut self,
+        extended_length: bool,
+        available_len: usize,
+    ) -> ChainedCommandIterator<'a> {
+        if !extended_length {
+            self.extended_length = ExtendedLen::Unsupported;
+        }
+        ChainedCommandIterator {
+            command: Some(self),
+            available_len,
+        }
+    }
+
     /// Given the available length and the extended length support, split the command in 2 commands that use command chaining to be sent
     ///
     /// `None` means that the command can we serialized withinn `available_len` without needing Chaining
@@ -435,11 +550,19 @@ impl<'a> CommandBuilder<&'a [u8]> {
             panic!("Commands cannot be encoded to fit in buffers smaller than 9 bytes");
         }
 
-        let BuildingHeaderData {
+        self.split_with(&self.header_data(), available_len)
+    }
+
+    fn split_with(
+        &self,
+        header_data: &BuildingHeaderData,
+        available_len: usize,
+    ) -> Option<(Self, Self)> {
+        let &BuildingHeaderData {
             le,
             data_len,
             expected_data_len,
-        } = self.header_data();
+        } = header_data;
 
         let mut max_data_len = u16::MAX as usize;
         if self.extended_length == ExtendedLen::Unsupported {
@@ -447,7 +570,7 @@ impl<'a> CommandBuilder<&'a [u8]> {
         }
 
         let available_data_len = (available_len - HEADER_LEN)
-            .saturating_sub(data_len.len() + expected_data_len.len())
+            .saturating_sub(data_len.len + expected_data_len.len)
             .min(max_data_len);
         if available_data_len >= self.data.len() {
             // slitting not necessary
@@ -459,14 +582,14 @@ impl<'a> CommandBuilder<&'a [u8]> {
             panic!("Commands cannot be encoded to fit in buffers smaller than 9 bytes");
         }
 
-        let (send_now, send_later) = self.data.split_at(available_data_len);
+        let (send_now, send_later) = self.data.get().split_at(available_data_len);
 
         let send_now = Self {
             class: self.class.as_chained(),
             instruction: self.instruction,
             p1: self.p1,
             p2: self.p2,
-            data: send_now,
+            data: CachedLen::new(send_now),
             le: 0.into(),
             extended_length: self.extended_length,
         };
@@ -475,12 +598,99 @@ impl<'a> CommandBuilder<&'a [u8]> {
             instruction: self.instruction,
             p1: self.p1,
             p2: self.p2,
-            data: send_later,
+            data: CachedLen::new(send_later),
             le,
             extended_length: self.extended_length,
         };
         Some((send_now, send_later))
     }
+
+    /// Split the command like [`should_split`](Self::should_split) and serialize each command of the chain, in one pass.
+    ///
+    /// For each command, `writer_factory` is called with its length to get the writer to serialize it into,
+    /// and the writer is then passed to `send` along with whether it is the last command of the chain.
+    /// Returns the number of commands sent.
+    ///
+    /// The length fields of each command of the chain are shared between computing its length and
+    /// serializing it, where splitting with [`should_split`](Self::should_split) and serializing with
+    /// [`serialize_into`](Self::serialize_into) computes them again at each step.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `available_len` is too small to fit the header, the length fields and at least
+    /// one byte of data of a command that needs to be split, like [`should_split`](Self::should_split).
+    ///
+    /// ```
+    /// use iso7816::command::{writer::BufferFull, CommandBuilder};
+    /// let command = CommandBuilder::new(0.try_into().unwrap(), 0xDB.into(), 0x3F, 0xFF, &[0x42; 300][..], 0);
+    /// let mut frames = Vec::new();
+    /// let count = command
+    ///     .serialize_chunked(261, |_| heapless::Vec::<u8, 261>::new(), |frame, last| {
+    ///         frames.push((frame.len(), last));
+    ///         Ok::<_, BufferFull>(())
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(count, 2);
+    /// assert_eq!(frames, [(259, false), (51, true)]);
+    /// ```
+    pub fn serialize_chunked<W, E>(
+        &self,
+        available_len: usize,
+        mut writer_factory: impl FnMut(usize) -> W,
+        mut send: impl FnMut(W, bool) -> Result<(), E>,
+    ) -> Result<usize, E>
+    where
+        W: Writer,
+        E: From<W::Error>,
+    {
+        if available_len < HEADER_LEN {
+            panic!("Commands cannot be encoded to fit in buffers smaller than 9 bytes");
+        }
+        let mut command = self.clone();
+        let mut sent = 0;
+        loop {
+            let header_data = command.header_data();
+            let (current, current_header, next) =
+                match command.split_with(&header_data, available_len) {
+                    Some((current, next)) => {
+                        let current_header = current.header_data();
+                        (current, current_header, Some(next))
+                    }
+                    None => (command, header_data, None),
+                };
+            let mut writer = writer_factory(current.required_len_with(&current_header));
+            current.serialize_with(&current_header, &mut writer)?;
+            send(writer, next.is_none())?;
+            sent += 1;
+            match next {
+                Some(next) => command = next,
+                None => return Ok(sent),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for CommandBuilder<&'a [u8]> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let class = u.arbitrary()?;
+        let instruction = u.arbitrary()?;
+        let p1 = u.arbitrary()?;
+        let p2 = u.arbitrary()?;
+        let data: &[u8] = u.arbitrary()?;
+        let data = &data[..data.len().min(u16::MAX as usize)];
+        let le = if u.arbitrary()? {
+            ExpectedLen::Max
+        } else {
+            ExpectedLen::Ne(u.arbitrary()?)
+        };
+        let command = Self::new(class, instruction, p1, p2, data, le);
+        if u.arbitrary()? {
+            Ok(command.force_extended())
+        } else {
+            Ok(command)
+        }
+    }
 }
 
 impl<D: DataSource> DataSource for CommandBuilder<D> {
@@ -523,18 +733,44 @@ impl From<class::InvalidClass> for FromSliceError {
 impl<'a> TryFrom<&'a [u8]> for CommandView<'a> {
     type Error = FromSliceError;
     fn try_from(apdu: &'a [u8]) -> core::result::Result<Self, Self::Error> {
+        Self::parse(apdu)
+    }
+}
+
+impl<'a> CommandView<'a> {
+    /// Parse a command APDU, like [`TryFrom`], in `const` contexts
+    ///
+    /// This allows validating fixed commands at compile time:
+    ///
+    /// ```
+    /// use iso7816::command::CommandView;
+    /// const SELECT_PIV: CommandView<'static> = match CommandView::parse(&[
+    ///     0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08, 0x00,
+    /// ]) {
+    ///     Ok(command) => command,
+    ///     Err(_) => panic!("invalid SELECT command"),
+    /// };
+    /// assert_eq!(SELECT_PIV.data(), [0xA0, 0x00, 0x00, 0x03, 0x08]);
+    /// assert_eq!(SELECT_PIV.expected(), 256);
+    /// ```
+    pub const fn parse(apdu: &'a [u8]) -> Result<Self, FromSliceError> {
         if apdu.len() < 4 {
             return Err(FromSliceError::TooShort);
         }
-        #[cfg(test)]
-        println!("{}", apdu.len());
         let (header, body) = apdu.split_at(4);
-        let class = class::Class::try_from(header[0])?;
-        let instruction = Instruction::from(header[1]);
+        let class = match class::Class::from_byte(header[0]) {
+            Ok(class) => class,
+            Err(_) => return Err(FromSliceError::InvalidClass),
+        };
+        let instruction = Instruction::from_byte(header[1]);
         let p1 = header[2];
         let p2 = header[3];
-        let parsed = parse_lengths(body)?;
-        let data = &body[parsed.offset..][..parsed.lc];
+        let parsed = match parse_lengths(body) {
+            Ok(parsed) => parsed,
+            Err(err) => return Err(err),
+        };
+        let (_, data) = body.split_at(parsed.offset);
+        let (data, _) = data.split_at(parsed.lc);
 
         Ok(Self {
             // header
@@ -549,9 +785,7 @@ impl<'a> TryFrom<&'a [u8]> for CommandView<'a> {
             extended: parsed.extended,
         })
     }
-}
 
-impl<'a> CommandView<'a> {
     pub fn to_owned<const S: usize>(&self) -> Result<Command<S>, FromSliceError> {
         let &CommandView {
             class,
@@ -592,6 +826,68 @@ impl<const S: usize> TryFrom<&[u8]> for Command<S> {
     }
 }
 
+#[cfg(feature = "heapless-bytes")]
+impl<const S: usize, const N: usize> TryFrom<&heapless_bytes::Bytes<N>> for Command<S> {
+    type Error = FromSliceError;
+    fn try_from(apdu: &heapless_bytes::Bytes<N>) -> core::result::Result<Self, Self::Error> {
+        let apdu: &[u8] = apdu;
+        apdu.try_into()
+    }
+}
+
+/// Serialize the command, keeping its length encoding
+#[cfg(feature = "heapless-bytes")]
+impl<const S: usize, const N: usize> TryFrom<&Command<S>> for heapless_bytes::Bytes<N> {
+    type Error = BufferFull;
+    fn try_from(command: &Command<S>) -> core::result::Result<Self, Self::Error> {
+        let le = match command.le {
+            65536.. => ExpectedLen::Max,
+            le => ExpectedLen::Ne(le as u16),
+        };
+        let mut builder = CommandBuilder::new(
+            command.class,
+            command.instruction,
+            command.p1,
+            command.p2,
+            command.data.as_slice(),
+            le,
+        );
+        if command.extended {
+            builder = builder.force_extended();
+        }
+        let mut bytes = Self::new();
+        builder.serialize_into(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Commands as they would be parsed from a valid encoding, with up to `S` bytes of data
+#[cfg(feature = "arbitrary")]
+impl<'a, const S: usize> arbitrary::Arbitrary<'a> for Command<S> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let class = u.arbitrary()?;
+        let instruction = u.arbitrary()?;
+        let p1 = u.arbitrary()?;
+        let p2 = u.arbitrary()?;
+        let data: &[u8] = u.arbitrary()?;
+        let data = &data[..data.len().min(S).min(u16::MAX as usize)];
+        let le = u.int_in_range(0..=u16::MAX as usize + 1)?;
+        let extended = data.len() > 255 || le > 256 || {
+            let can_be_extended = !data.is_empty() || le != 0;
+            can_be_extended && u.arbitrary()?
+        };
+        Ok(Self {
+            class,
+            instruction,
+            p1,
+            p2,
+            data: Data::from_slice(data).unwrap(),
+            le,
+            extended,
+        })
+    }
+}
+
 // cf. ISO 7816-3, 12.1.3: Decoding conventions for command APDUs
 // freely available version:
 // http://www.ttfn.net/techno/smartcards/iso7816_4.html#table5
@@ -605,7 +901,7 @@ struct ParsedLengths {
 }
 
 #[inline(always)]
-fn replace_zero(value: usize, replacement: usize) -> usize {
+const fn replace_zero(value: usize, replacement: usize) -> usize {
     if value == 0 {
         replacement
     } else {
@@ -613,7 +909,7 @@ fn replace_zero(value: usize, replacement: usize) -> usize {
     }
 }
 #[inline]
-fn parse_lengths(body: &[u8]) -> Result<ParsedLengths, FromSliceError> {
+const fn parse_lengths(body: &[u8]) -> Result<ParsedLengths, FromSliceError> {
     // Encoding rules:
     // - Lc or Le = 0 => leave out
     // - short + extended length fields shall not be combined
@@ -621,7 +917,12 @@ fn parse_lengths(body: &[u8]) -> Result<ParsedLengths, FromSliceError> {
 
     let l = body.len();
 
-    let mut parsed: ParsedLengths = Default::default();
+    let mut parsed = ParsedLengths {
+        lc: 0,
+        le: 0,
+        offset: 0,
+        extended: false,
+    };
 
     // Case 1
     if l == 0 {
@@ -631,9 +932,6 @@ fn parse_lengths(body: &[u8]) -> Result<ParsedLengths, FromSliceError> {
     // the reference starts indexing at 1
     let b1 = body[0] as usize;
 
-    #[cfg(test)]
-    println!("l = {}, b1 = {}", l, b1);
-
     // Case 2S
     if l == 1 {
         parsed.lc = 0;
@@ -707,12 +1005,32 @@ mod test {
     use super::*;
     use hex_literal::hex;
     use quickcheck_macros::quickcheck;
+    use writer::{BufferFull, SerializationError};
 
     #[quickcheck]
     fn parse_no_panic(data: Vec<u8>) {
         let _ = parse_lengths(&data);
     }
 
+    #[cfg(feature = "arbitrary")]
+    #[quickcheck]
+    fn arbitrary(raw: Vec<u8>) {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let mut u = Unstructured::new(&raw);
+        if let Ok(builder) = CommandBuilder::<&[u8]>::arbitrary(&mut u) {
+            let serialized = builder.clone().serialize_to_vec();
+            let view = CommandView::try_from(&*serialized).unwrap();
+            assert_eq!(view.class(), builder.class());
+            assert_eq!(view.instruction(), builder.instruction());
+            assert_eq!(view.data(), builder.data());
+        }
+        if let Ok(command) = Command::<300>::arbitrary(&mut u) {
+            assert!(command.data().len() <= 300);
+            assert!(command.extended || command.data().len() <= 255 && command.expected() <= 256);
+        }
+    }
+
     #[quickcheck]
     fn lengths(lc: u16, le: Option<u16>) {
         let extended =
@@ -952,6 +1270,70 @@ mod test {
         );
     }
 
+    #[test]
+    fn const_parse() {
+        const APDU: &[u8] = &hex!("00CB3FFF 05 5C035FC102 00");
+        const VIEW: CommandView<'static> = match CommandView::parse(APDU) {
+            Ok(view) => view,
+            Err(_) => panic!(),
+        };
+        const _: () = assert!(matches!(
+            CommandView::parse(&hex!("00CB3FFF 05 5C03")),
+            Err(FromSliceError::InvalidFirstBodyByteForExtended)
+        ));
+        assert_eq!(Ok(VIEW), CommandView::try_from(APDU));
+        assert_eq!(VIEW.instruction(), Instruction::GetData);
+        assert_eq!(VIEW.data(), hex!("5C035FC102"));
+    }
+
+    #[test]
+    fn serialize_chunked() {
+        let cla = 0x00.try_into().unwrap();
+        let ins = 0x01.into();
+        let data = [0x42; 700];
+        for (data, le, extended, available_len) in [
+            (&data[..0], 0, true, 9),
+            (&data[..300], 256, false, 105),
+            (&data[..300], 256, false, 261),
+            (&data[..700], 0x1000, true, 300),
+            (&data[..700], 0x1000, true, 1024),
+        ] {
+            let command = CommandBuilder::new(cla, ins, 2, 3, data, le);
+            let expected: Vec<_> = command
+                .clone()
+                .into_chained(extended, available_len)
+                .map(|command| command.serialize_to_vec())
+                .collect();
+            let command = command
+                .into_chained(extended, available_len)
+                .command
+                .unwrap();
+            let mut frames = Vec::new();
+            let count = command
+                .serialize_chunked(available_len, Vec::with_capacity, |frame, last| {
+                    assert!(frame.len() <= available_len);
+                    frames.push((frame, last));
+                    Ok::<_, SerializationError>(())
+                })
+                .unwrap();
+            assert_eq!(count, expected.len());
+            assert_eq!(
+                frames.iter().map(|(frame, _)| frame).collect::<Vec<_>>(),
+                expected.iter().collect::<Vec<_>>()
+            );
+            assert!(frames.iter().rev().skip(1).all(|(_, last)| !last));
+            assert!(frames.last().unwrap().1);
+        }
+
+        let command = CommandBuilder::new(cla, ins, 2, 3, &data[..300], 0);
+        let err = command.serialize_chunked(
+            105,
+            |_| heapless::Vec::<u8, 100>::new(),
+            |_, _| Ok::<_, BufferFull>(()),
+        );
+        assert!(matches!(err, Err(BufferFull::BufferFull)));
+    }
+
     #[test]
     fn nested_commands() {
         let cla = 0x00.try_into().unwrap();
@@ -0,0 +1,124 @@
+use core::ops::Range;
+
+use super::writer::{Error, Writer};
+use super::{CommandBuilder, DataStream};
+
+/// Position of a command APDU in the buffer filled by [`serialize_batch`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchEntry {
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl BatchEntry {
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.len
+    }
+}
+
+/// Serialize `commands` one after the other into `writer`, for transports submitting several
+/// commands in one transfer
+///
+/// The position of each command APDU is stored in `index`, and the number of commands is returned.
+/// Fails with [`failed_serialization`](Error::failed_serialization) if `index` is too short.
+///
+/// ```
+/// use iso7816::command::{serialize_batch, BatchEntry, CommandBuilder};
+/// let class = 0.try_into().unwrap();
+/// let commands = [
+///     CommandBuilder::new(class, 0xA4.into(), 0x04, 0x00, &[0xA0, 0x00, 0x00, 0x03, 0x08][..], 0),
+///     CommandBuilder::new(class, 0xCB.into(), 0x3F, 0xFF, &[0x5C, 0x01, 0x7E][..], 256),
+/// ];
+/// let mut buffer = heapless::Vec::<u8, 64>::new();
+/// let mut index = [BatchEntry::default(); 4];
+/// let count = serialize_batch(commands, &mut buffer, &mut index).unwrap();
+/// assert_eq!(count, 2);
+/// assert_eq!(&buffer[index[1].range()], [0x00, 0xCB, 0x3F, 0xFF, 0x03, 0x5C, 0x01, 0x7E, 0x00]);
+/// ```
+pub fn serialize_batch<W, D, I>(
+    commands: I,
+    writer: &mut W,
+    index: &mut [BatchEntry],
+) -> Result<usize, W::Error>
+where
+    W: Writer,
+    D: DataStream<W>,
+    I: IntoIterator<Item = CommandBuilder<D>>,
+{
+    let mut offset = 0;
+    let mut count = 0;
+    for command in commands {
+        let entry = index
+            .get_mut(count)
+            .ok_or_else(|| W::Error::failed_serialization("Too many commands for the index"))?;
+        let len = command.required_len();
+        command.serialize_into(writer)?;
+        *entry = BatchEntry { offset, len };
+        offset += len;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Same as [`serialize_batch`], into a new vector with one allocation
+#[cfg(any(feature = "std", test))]
+pub fn serialize_batch_to_vec<D, I>(commands: I) -> (Vec<u8>, Vec<BatchEntry>)
+where
+    D: DataStream<Vec<u8>>,
+    I: IntoIterator<Item = CommandBuilder<D>>,
+    I::IntoIter: Clone,
+{
+    let commands = commands.into_iter();
+    let (len, count) = commands.clone().fold((0, 0), |(len, count), command| {
+        (len + command.required_len(), count + 1)
+    });
+    let mut buffer = Vec::with_capacity(len);
+    let mut index = vec![BatchEntry::default(); count];
+    serialize_batch(commands, &mut buffer, &mut index).unwrap();
+    debug_assert_eq!(buffer.len(), len);
+    (buffer, index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::writer::BufferFull;
+    use hex_literal::hex;
+
+    #[test]
+    fn batch() {
+        let class = 0.try_into().unwrap();
+        let data = [0x42; 300];
+        let commands = [
+            CommandBuilder::new(class, 0xB0.into(), 0, 0, &data[..0], 0),
+            CommandBuilder::new(class, 0xD6.into(), 0, 0, &data[..300], 0),
+            CommandBuilder::new(class, 0xB0.into(), 0, 0, &data[..0], 256),
+        ];
+        let (buffer, index) = serialize_batch_to_vec(commands.clone());
+        assert_eq!(
+            index,
+            [
+                BatchEntry { offset: 0, len: 4 },
+                BatchEntry {
+                    offset: 4,
+                    len: 307
+                },
+                BatchEntry {
+                    offset: 311,
+                    len: 5
+                },
+            ]
+        );
+        for (command, entry) in commands.iter().zip(&index) {
+            assert_eq!(buffer[entry.range()], command.clone().serialize_to_vec());
+        }
+        assert_eq!(buffer[311..], hex!("00B0000000"));
+
+        let mut buffer = heapless::Vec::<u8, 512>::new();
+        let mut index = [BatchEntry::default(); 2];
+        assert!(matches!(
+            serialize_batch(commands, &mut buffer, &mut index),
+            Err(BufferFull::Serialization(_))
+        ));
+    }
+}
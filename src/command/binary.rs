@@ -0,0 +1,401 @@
+//! READ BINARY, UPDATE BINARY and WRITE BINARY (ISO 7816-4 11.3)
+//!
+//! With an even instruction, the EF and the offset are encoded in P1-P2:
+//!
+//! - if bit 8 of P1 is 0, P1-P2 is a 15 bits offset in the current EF,
+//! - if bit 8 of P1 is 1, bits 5 to 1 of P1 are a short EF identifier and P2 is an 8 bits offset.
+//!
+//! With an odd instruction, P1-P2 identifies the EF and the offset is sent in the `54` data object,
+//! the data to write being sent in the `53` data object. [`Binary::command`] uses the odd
+//! instruction only when the offset or the EF can't be encoded with the even one.
+//!
+//! ```
+//! use iso7816::command::binary::{Binary, Ef};
+//! use iso7816::file::ShortEf;
+//! let mut command = heapless::Vec::<u8, 16>::new();
+//! Binary::read(0x10, 8)
+//!     .ef(Ef::Short(ShortEf::new(2).unwrap()))
+//!     .command(0.try_into().unwrap())
+//!     .serialize_into(&mut command)
+//!     .unwrap();
+//! assert_eq!(&*command, &[0x00, 0xB0, 0x82, 0x10, 0x08]);
+//! ```
+
+use super::class::Class;
+use super::{
+    CommandBuilder, CommandView, DataSource, DataStream, ExpectedLen, Instruction, Writer,
+};
+use crate::file::{FileId, ShortEf};
+use crate::tlv::{take_data_object, Tag, Tlv};
+use crate::Status;
+
+/// Offset data object of the odd instructions
+pub const OFFSET: u8 = 0x54;
+/// Discretionary data object of the odd instructions
+pub const DATA: u8 = 0x53;
+
+/// Largest offset encoded in P1-P2 of the even instructions
+pub const MAX_EVEN_OFFSET: u32 = 0x7FFF;
+
+/// Binary command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Read,
+    Update,
+    Write,
+}
+
+impl Operation {
+    /// Even instruction
+    pub fn instruction(self) -> Instruction {
+        match self {
+            Self::Read => Instruction::ReadBinary,
+            Self::Update => Instruction::UpdateBinary,
+            Self::Write => Instruction::WriteBinary,
+        }
+    }
+
+    /// Operation and whether the instruction is odd
+    fn from_instruction(instruction: Instruction) -> Option<(Self, bool)> {
+        let ins: u8 = instruction.into();
+        let operation = match ins & 0xFE {
+            0xB0 => Self::Read,
+            0xD6 => Self::Update,
+            0xD0 => Self::Write,
+            _ => return None,
+        };
+        Some((operation, ins & 1 == 1))
+    }
+}
+
+/// Elementary file targeted by a binary command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ef {
+    /// Currently selected EF
+    Current,
+    Short(ShortEf),
+    /// EF identified by its file identifier, which requires the odd instruction
+    ///
+    /// Identifiers `0001` to `001E` are interpreted as short EF identifiers by the card.
+    Id(FileId),
+}
+
+impl Ef {
+    /// P1-P2 of the odd instructions
+    fn odd_p1p2(self) -> [u8; 2] {
+        match self {
+            Self::Current => [0, 0],
+            Self::Short(id) => [0, id.get()],
+            Self::Id(id) => id.to_bytes(),
+        }
+    }
+
+    fn from_odd_p1p2(p1p2: [u8; 2]) -> Result<Self, BinaryError> {
+        match u16::from_be_bytes(p1p2) {
+            0 => Ok(Self::Current),
+            id @ 1..=0x1E => Ok(Self::Short(ShortEf::new(id as u8)?)),
+            id => Ok(Self::Id(FileId::new(id)?)),
+        }
+    }
+}
+
+/// READ BINARY, UPDATE BINARY or WRITE BINARY command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Binary<'a> {
+    operation: Operation,
+    ef: Ef,
+    offset: u32,
+    data: &'a [u8],
+    le: usize,
+}
+
+impl<'a> Binary<'a> {
+    /// Read `le` bytes at `offset` in the current EF
+    pub fn read(offset: u32, le: usize) -> Self {
+        Self {
+            operation: Operation::Read,
+            ef: Ef::Current,
+            offset,
+            data: &[],
+            le,
+        }
+    }
+
+    /// Replace the bytes at `offset` in the current EF with `data`
+    pub fn update(offset: u32, data: &'a [u8]) -> Self {
+        Self {
+            operation: Operation::Update,
+            ef: Ef::Current,
+            offset,
+            data,
+            le: 0,
+        }
+    }
+
+    /// Write `data` at `offset` in the current EF, according to the data coding of the EF
+    pub fn write(offset: u32, data: &'a [u8]) -> Self {
+        Self {
+            operation: Operation::Write,
+            ..Self::update(offset, data)
+        }
+    }
+
+    pub fn ef(mut self, ef: Ef) -> Self {
+        self.ef = ef;
+        self
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    pub fn target(&self) -> Ef {
+        self.ef
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Data to write, empty for READ BINARY
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Expected length of READ BINARY
+    pub fn expected(&self) -> usize {
+        self.le
+    }
+
+    /// Whether the command requires the odd instruction
+    pub fn is_odd(&self) -> bool {
+        match self.ef {
+            Ef::Current => self.offset > MAX_EVEN_OFFSET,
+            Ef::Short(_) => self.offset > 0xFF,
+            Ef::Id(_) => true,
+        }
+    }
+
+    fn p1p2(&self) -> [u8; 2] {
+        match self.ef {
+            _ if self.is_odd() => self.ef.odd_p1p2(),
+            Ef::Short(id) => [id.read_binary_p1(), self.offset as u8],
+            _ => (self.offset as u16).to_be_bytes(),
+        }
+    }
+
+    /// Command APDU
+    ///
+    /// The response to an odd READ BINARY is wrapped in a `53` data object, see [`odd_response`].
+    pub fn command(self, class: Class) -> CommandBuilder<Self> {
+        let mut ins: u8 = self.operation.instruction().into();
+        if self.is_odd() {
+            ins |= 1;
+        }
+        let [p1, p2] = self.p1p2();
+        let le = u16::try_from(self.le).map_or(ExpectedLen::Max, ExpectedLen::Ne);
+        CommandBuilder::new(class, ins.into(), p1, p2, self, le)
+    }
+
+    fn odd_data(&self) -> (Tlv<Offset>, Option<Tlv<&'a [u8]>>) {
+        (
+            Tlv::new(Tag::from_u8(OFFSET), Offset(self.offset)),
+            (self.operation != Operation::Read).then(|| Tlv::new(Tag::from_u8(DATA), self.data)),
+        )
+    }
+}
+
+/// Value of the offset data object, on as few bytes as possible
+struct Offset(u32);
+
+impl Offset {
+    fn bytes(&self) -> [u8; 4] {
+        self.0.to_be_bytes()
+    }
+}
+
+impl DataSource for Offset {
+    fn len(&self) -> usize {
+        4 - self.bytes().iter().take(3).take_while(|&&b| b == 0).count()
+    }
+
+    fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+impl<W: Writer> DataStream<W> for Offset {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        writer.write_all(&self.bytes()[4 - self.len()..])
+    }
+}
+
+impl DataSource for Binary<'_> {
+    fn len(&self) -> usize {
+        if self.is_odd() {
+            self.odd_data().len()
+        } else {
+            self.data.len()
+        }
+    }
+}
+
+impl<W: Writer> DataStream<W> for Binary<'_> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        if self.is_odd() {
+            self.odd_data().to_writer(writer)
+        } else {
+            writer.write_all(self.data)
+        }
+    }
+}
+
+/// Data of the response to an odd READ BINARY, the value of the `53` data object
+pub fn odd_response(data: &[u8]) -> Option<&[u8]> {
+    match take_data_object(data)? {
+        (tag, value, []) if tag == Tag::from_u8(DATA) => Some(value),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryError {
+    /// The command is not a binary command
+    WrongInstruction,
+    /// P1-P2 is not a valid EF reference or offset
+    InvalidP1P2,
+    /// The data field does not match the command
+    InvalidData,
+}
+
+impl From<crate::file::FileError> for BinaryError {
+    fn from(_: crate::file::FileError) -> Self {
+        Self::InvalidP1P2
+    }
+}
+
+impl From<BinaryError> for Status {
+    fn from(value: BinaryError) -> Self {
+        match value {
+            BinaryError::WrongInstruction => Status::InstructionNotSupportedOrInvalid,
+            BinaryError::InvalidP1P2 => Status::IncorrectP1OrP2Parameter,
+            BinaryError::InvalidData => Status::IncorrectDataParameter,
+        }
+    }
+}
+
+impl<'a> TryFrom<&CommandView<'a>> for Binary<'a> {
+    type Error = BinaryError;
+
+    fn try_from(command: &CommandView<'a>) -> Result<Self, Self::Error> {
+        let (operation, odd) = Operation::from_instruction(command.instruction())
+            .ok_or(BinaryError::WrongInstruction)?;
+        let (p1, p2) = (command.p1, command.p2);
+        let (ef, offset, data) = if odd {
+            let ef = Ef::from_odd_p1p2([p1, p2])?;
+            let (tag, offset, mut data) =
+                take_data_object(command.data()).ok_or(BinaryError::InvalidData)?;
+            if tag != Tag::from_u8(OFFSET) || offset.is_empty() || offset.len() > 4 {
+                return Err(BinaryError::InvalidData);
+            }
+            let offset = offset.iter().fold(0, |acc, &b| acc << 8 | u32::from(b));
+            if operation != Operation::Read {
+                data = match take_data_object(data) {
+                    Some((tag, value, [])) if tag == Tag::from_u8(DATA) => value,
+                    _ => return Err(BinaryError::InvalidData),
+                }
+            }
+            (ef, offset, data)
+        } else if p1 & 0x80 != 0 {
+            let ef = ShortEf::from_read_binary_p1(p1).ok_or(BinaryError::InvalidP1P2)?;
+            (Ef::Short(ef), p2.into(), command.data())
+        } else {
+            let offset = u16::from_be_bytes([p1, p2]).into();
+            (Ef::Current, offset, command.data())
+        };
+        if operation == Operation::Read && !data.is_empty() {
+            return Err(BinaryError::InvalidData);
+        }
+        Ok(Self {
+            operation,
+            ef,
+            offset,
+            data,
+            le: command.expected(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    fn roundtrip(binary: Binary<'_>, apdu: &[u8]) {
+        let command = binary.command(0.try_into().unwrap()).serialize_to_vec();
+        assert_eq!(command, apdu);
+        let view = CommandView::try_from(apdu).unwrap();
+        assert_eq!(Binary::try_from(&view), Ok(binary));
+    }
+
+    #[test]
+    fn even() {
+        roundtrip(Binary::read(0, 256), &hex!("00B00000 00"));
+        roundtrip(Binary::read(0x7FFF, 0x10), &hex!("00B07FFF 10"));
+        roundtrip(
+            Binary::update(0x0102, &hex!("AABB")),
+            &hex!("00D60102 02 AABB"),
+        );
+        roundtrip(
+            Binary::write(0xFF, &hex!("01")).ef(Ef::Short(ShortEf::new(0x1E).unwrap())),
+            &hex!("00D09EFF 01 01"),
+        );
+    }
+
+    #[test]
+    fn odd() {
+        roundtrip(Binary::read(0x8000, 0x20), &hex!("00B10000 04 54028000 20"));
+        roundtrip(
+            Binary::read(0x100, 0).ef(Ef::Short(ShortEf::new(3).unwrap())),
+            &hex!("00B10003 04 54020100"),
+        );
+        roundtrip(
+            Binary::update(0x012345, &hex!("AABB")).ef(Ef::Id(FileId::new(0x2F00).unwrap())),
+            &hex!("00D72F00 09 5403012345 5302AABB"),
+        );
+        roundtrip(
+            Binary::read(0, 256).ef(Ef::Id(FileId::new(0x2F00).unwrap())),
+            &hex!("00B12F00 03 540100 00"),
+        );
+        assert_eq!(odd_response(&hex!("5302AABB")), Some(&hex!("AABB")[..]));
+        assert_eq!(odd_response(&hex!("5402AABB")), None);
+    }
+
+    #[test]
+    fn errors() {
+        fn parse(apdu: &[u8]) -> Result<Binary<'_>, BinaryError> {
+            Binary::try_from(&CommandView::try_from(apdu).unwrap())
+        }
+        assert_eq!(parse(&hex!("00A40000")), Err(BinaryError::WrongInstruction));
+        // Bits 7 and 6 of P1 must be 0 with a short EF identifier
+        assert_eq!(parse(&hex!("00B0C100")), Err(BinaryError::InvalidP1P2));
+        assert_eq!(parse(&hex!("00B09F00")), Err(BinaryError::InvalidP1P2));
+        assert_eq!(
+            parse(&hex!("00B13FFF 03 540100")),
+            Err(BinaryError::InvalidP1P2)
+        );
+        assert_eq!(parse(&hex!("00B10000")), Err(BinaryError::InvalidData));
+        assert_eq!(
+            parse(&hex!("00D70000 03 540100")),
+            Err(BinaryError::InvalidData)
+        );
+        assert_eq!(
+            parse(&hex!("00B00000 01 00")),
+            Err(BinaryError::InvalidData)
+        );
+        assert_eq!(
+            Status::from(BinaryError::InvalidP1P2),
+            Status::IncorrectP1OrP2Parameter
+        );
+    }
+}
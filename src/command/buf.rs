@@ -0,0 +1,156 @@
+use super::{class::Class, parse_lengths, CommandView, FromSliceError, Instruction};
+
+/// Command owning its raw APDU buffer
+///
+/// The buffer is parsed once and kept as is: the data field is accessed through its offset in the
+/// buffer, without being copied like in [`Command`](super::Command) nor borrowed like in
+/// [`CommandView`]. This allows moving the received buffer along a processing pipeline.
+///
+/// ```
+/// use iso7816::command::CommandBuf;
+/// let apdu = vec![0x00, 0xDA, 0x3F, 0xFF, 0x03, 0x01, 0x02, 0x03];
+/// let mut command = CommandBuf::new(apdu).unwrap();
+/// assert_eq!(command.data(), [0x01, 0x02, 0x03]);
+/// // The data field can be processed in place, for example to decrypt it
+/// command.data_mut().reverse();
+/// assert_eq!(command.into_inner(), [0x00, 0xDA, 0x3F, 0xFF, 0x03, 0x03, 0x02, 0x01]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandBuf<B> {
+    buffer: B,
+    class: Class,
+    instruction: Instruction,
+    data_offset: usize,
+    data_len: usize,
+    le: usize,
+    extended: bool,
+}
+
+impl<B: AsRef<[u8]>> CommandBuf<B> {
+    /// Parse the command APDU in `buffer`
+    pub fn new(buffer: B) -> Result<Self, FromSliceError> {
+        let apdu = buffer.as_ref();
+        if apdu.len() < 4 {
+            return Err(FromSliceError::TooShort);
+        }
+        let class = Class::try_from(apdu[0])?;
+        let instruction = Instruction::from(apdu[1]);
+        let parsed = parse_lengths(&apdu[4..])?;
+        Ok(Self {
+            buffer,
+            class,
+            instruction,
+            data_offset: 4 + parsed.offset,
+            data_len: parsed.lc,
+            le: parsed.le,
+            extended: parsed.extended,
+        })
+    }
+
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        self.instruction
+    }
+
+    pub fn p1(&self) -> u8 {
+        self.buffer.as_ref()[2]
+    }
+
+    pub fn p2(&self) -> u8 {
+        self.buffer.as_ref()[3]
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.buffer.as_ref()[self.data_offset..][..self.data_len]
+    }
+
+    pub(crate) fn data_range(&self) -> core::ops::Range<usize> {
+        self.data_offset..self.data_offset + self.data_len
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8]
+    where
+        B: AsMut<[u8]>,
+    {
+        &mut self.buffer.as_mut()[self.data_offset..][..self.data_len]
+    }
+
+    pub fn expected(&self) -> usize {
+        self.le
+    }
+
+    /// Whether the command uses the extended length encoding
+    pub fn extended(&self) -> bool {
+        self.extended
+    }
+
+    /// The raw command APDU
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+
+    pub fn as_view(&self) -> CommandView<'_> {
+        CommandView {
+            class: self.class,
+            instruction: self.instruction,
+            p1: self.p1(),
+            p2: self.p2(),
+            data: self.data(),
+            le: self.le,
+            extended: self.extended,
+        }
+    }
+
+    /// Return the buffer holding the command APDU
+    pub fn into_inner(self) -> B {
+        self.buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn command_buf() {
+        for apdu in [
+            &hex!("00B00000")[..],
+            &hex!("00B0000010"),
+            &hex!("00DB3FFF 03 010203"),
+            &hex!("00DB3FFF 03 010203 00"),
+            &hex!("00DB3FFF 000003 010203 0100"),
+        ] {
+            let buf = CommandBuf::new(apdu).unwrap();
+            assert_eq!(buf.as_view(), CommandView::try_from(apdu).unwrap());
+            assert_eq!(buf.as_bytes(), apdu);
+        }
+
+        let mut buf = CommandBuf::new(
+            heapless::Vec::<u8, 16>::from_slice(&hex!("80DA3FFF 02 0102")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(buf.class().into_inner(), 0x80);
+        assert_eq!((buf.p1(), buf.p2()), (0x3F, 0xFF));
+        buf.data_mut().copy_from_slice(&hex!("0304"));
+        assert_eq!(buf.data(), hex!("0304"));
+        assert!(!buf.extended());
+        assert_eq!(*buf.into_inner(), hex!("80DA3FFF 02 0304"));
+
+        assert_eq!(
+            CommandBuf::new(&hex!("00B0")),
+            Err(FromSliceError::TooShort)
+        );
+        assert_eq!(
+            CommandBuf::new(&hex!("FFB00000")),
+            Err(FromSliceError::InvalidClass)
+        );
+        assert_eq!(
+            CommandBuf::new(&hex!("00DB3FFF 03 0102")),
+            Err(FromSliceError::InvalidFirstBodyByteForExtended)
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! GET CHALLENGE (ISO 7816-4 11.5.3)
+//!
+//! [`get_challenge`] builds the command, the length of the challenge being requested through Le.
+//! [`Challenge`] checks the length of the response data. On the card side, [`Challenger`] serves
+//! challenges drawn from a [`Rng`] and keeps the last one for the EXTERNAL AUTHENTICATE or MUTUAL
+//! AUTHENTICATE command that follows.
+
+use super::class::Class;
+use super::{CommandBuilder, CommandView, Instruction};
+use crate::{Data, Status};
+
+/// Source of random challenges
+pub trait Rng {
+    fn fill_bytes(&mut self, bytes: &mut [u8]);
+}
+
+impl<R: Rng + ?Sized> Rng for &mut R {
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        R::fill_bytes(self, bytes)
+    }
+}
+
+/// GET CHALLENGE command requesting a challenge of `len` bytes
+pub fn get_challenge(class: Class, len: u16) -> CommandBuilder<&'static [u8]> {
+    CommandBuilder::new(class, Instruction::GetChallenge, 0, 0, &[][..], len)
+}
+
+/// Challenge of `N` bytes returned by the card
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Challenge<const N: usize>(pub [u8; N]);
+
+impl<const N: usize> Challenge<N> {
+    pub fn as_bytes(&self) -> &[u8; N] {
+        &self.0
+    }
+}
+
+/// The response data is not `N` bytes long
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidChallenge;
+
+impl<const N: usize> TryFrom<&[u8]> for Challenge<N> {
+    type Error = InvalidChallenge;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        data.try_into().map(Self).map_err(|_| InvalidChallenge)
+    }
+}
+
+/// Card-side GET CHALLENGE handling, with challenges of `N` bytes
+///
+/// `N` can't be larger than 256, the length that can be indicated by a `6CXX` status.
+pub struct Challenger<R, const N: usize> {
+    rng: R,
+    last: Option<[u8; N]>,
+}
+
+impl<R: Rng, const N: usize> Challenger<R, N> {
+    pub fn new(rng: R) -> Self {
+        const { assert!(N <= 256, "Challenges can't be longer than 256 bytes") };
+        Self { rng, last: None }
+    }
+
+    /// Last challenge sent, if it has not been taken yet
+    pub fn last(&self) -> Option<&[u8; N]> {
+        self.last.as_ref()
+    }
+
+    /// Take the last challenge, so that it can't be used twice
+    pub fn take(&mut self) -> Option<[u8; N]> {
+        self.last.take()
+    }
+
+    /// Forget the last challenge, for example on reset or application deselection
+    pub fn clear(&mut self) {
+        self.last = None;
+    }
+
+    pub fn into_inner(self) -> R {
+        self.rng
+    }
+
+    /// Process a GET CHALLENGE command, writing the new challenge to `response`
+    ///
+    /// The previous challenge is discarded, even if the command is rejected.
+    pub fn call<const S: usize>(
+        &mut self,
+        command: &CommandView<'_>,
+        response: &mut Data<S>,
+    ) -> crate::Result {
+        self.last = None;
+        response.clear();
+        if command.instruction() != Instruction::GetChallenge {
+            return Err(Status::InstructionNotSupportedOrInvalid);
+        }
+        if command.p1 != 0 || command.p2 != 0 {
+            return Err(Status::IncorrectP1OrP2Parameter);
+        }
+        if !command.data().is_empty() {
+            return Err(Status::WrongLength);
+        }
+        if command.expected() != N {
+            // SW2 = 00 stands for 256 bytes, as Le = 00 does
+            return Err(Status::WrongLeField((N % 256) as u8));
+        }
+        let mut challenge = [0; N];
+        self.rng.fill_bytes(&mut challenge);
+        response
+            .extend_from_slice(&challenge)
+            .map_err(|_| Status::NotEnoughMemory)?;
+        self.last = Some(challenge);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    struct Counter(u8);
+
+    impl Rng for Counter {
+        fn fill_bytes(&mut self, bytes: &mut [u8]) {
+            for byte in bytes {
+                self.0 = self.0.wrapping_add(1);
+                *byte = self.0;
+            }
+        }
+    }
+
+    #[test]
+    fn command() {
+        let command = get_challenge(0.try_into().unwrap(), 8).serialize_to_vec();
+        assert_eq!(command, hex!("00840000 08"));
+        assert_eq!(
+            Challenge::<8>::try_from(&hex!("0102030405060708")[..]),
+            Ok(Challenge(hex!("0102030405060708")))
+        );
+        assert_eq!(
+            Challenge::<8>::try_from(&hex!("01020304")[..]),
+            Err(InvalidChallenge)
+        );
+    }
+
+    #[test]
+    fn challenger() {
+        let mut challenger = Challenger::<_, 4>::new(Counter(0));
+        let mut response = Data::<16>::new();
+        let command = CommandView::try_from(&hex!("00840000 04")[..]).unwrap();
+        challenger.call(&command, &mut response).unwrap();
+        assert_eq!(&*response, &hex!("01020304"));
+        assert_eq!(challenger.last(), Some(&hex!("01020304")));
+
+        challenger.call(&command, &mut response).unwrap();
+        assert_eq!(challenger.take(), Some(hex!("05060708")));
+        assert_eq!(challenger.last(), None);
+
+        challenger.call(&command, &mut response).unwrap();
+        let command = CommandView::try_from(&hex!("00840000 08")[..]).unwrap();
+        assert_eq!(
+            challenger.call(&command, &mut response),
+            Err(Status::WrongLeField(4))
+        );
+        assert!(response.is_empty());
+        assert_eq!(challenger.last(), None);
+
+        let command = CommandView::try_from(&hex!("00840100 04")[..]).unwrap();
+        assert_eq!(
+            challenger.call(&command, &mut response),
+            Err(Status::IncorrectP1OrP2Parameter)
+        );
+
+        let mut challenger = Challenger::<_, 256>::new(Counter(0));
+        let mut response = Data::<256>::new();
+        let command = CommandView::try_from(&hex!("00840000 04")[..]).unwrap();
+        assert_eq!(
+            challenger.call(&command, &mut response),
+            Err(Status::WrongLeField(0))
+        );
+        let command = CommandView::try_from(&hex!("00840000 00")[..]).unwrap();
+        challenger.call(&command, &mut response).unwrap();
+        assert_eq!(response.len(), 256);
+    }
+}
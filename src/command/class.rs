@@ -26,8 +26,8 @@ pub enum SecureMessaging {
 }
 
 impl SecureMessaging {
-    pub fn none(&self) -> bool {
-        *self == SecureMessaging::None
+    pub const fn none(&self) -> bool {
+        matches!(self, SecureMessaging::None)
     }
 }
 
@@ -96,6 +96,38 @@ impl Class {
         self
     }
 
+    /// Set the secure messaging indication bits.
+    ///
+    /// In the further interindustry range, only [`SecureMessaging::None`] and [`SecureMessaging::Standard`] can be encoded,
+    /// any other indication is encoded as `Standard`.
+    /// Proprietary classes use the same bits as the first interindustry range (as done by GlobalPlatform).
+    /// Reserved classes, and proprietary classes for which the result would be the invalid `0xFF`, are returned unchanged.
+    pub const fn with_secure_messaging(mut self, sm: SecureMessaging) -> Self {
+        match self.range {
+            Range::Interindustry(Interindustry::First) | Range::Proprietary => {
+                let bits = match sm {
+                    SecureMessaging::None | SecureMessaging::Unknown => 0b00,
+                    SecureMessaging::Proprietary => 0b01,
+                    SecureMessaging::Standard => 0b10,
+                    SecureMessaging::Authenticated => 0b11,
+                };
+                let cla = (self.cla & !0b1100) | (bits << 2);
+                if cla != 0xFF {
+                    self.cla = cla;
+                }
+            }
+            Range::Interindustry(Interindustry::Further) => {
+                if sm.none() {
+                    self.cla &= !(1 << 5);
+                } else {
+                    self.cla |= 1 << 5;
+                }
+            }
+            Range::Interindustry(Interindustry::Reserved) => {}
+        }
+        self
+    }
+
     #[inline]
     pub const fn channel(&self) -> Option<u8> {
         Some(match self.range {
@@ -123,6 +155,19 @@ impl TryFrom<u8> for Class {
     }
 }
 
+/// Any class byte except the invalid `FF`
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Class {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let cla = u.int_in_range(0..=0xFE)?;
+        Self::try_from(cla).map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(1))
+    }
+}
+
 // impl core::ops::Deref for Class {
 //     type Target = u8;
 //     fn deref(&self) -> &Self::Target {
@@ -0,0 +1,435 @@
+//! GET DATA and PUT DATA (ISO 7816-4 11.5.4 and 11.5.6)
+//!
+//! With the even instructions (`CA` and `DA`), P1-P2 is the tag of the data object and the data
+//! field is its value. With the odd instructions (`CB` and `DB`), P1-P2 identifies the file and the
+//! data field is BER-TLV encoded: a tag list (`5C`) or an extended header list (`4D`) for GET DATA,
+//! the data objects to store for PUT DATA.
+//!
+//! [`objects`] splits the response to GET DATA back into data objects.
+//!
+//! ```
+//! use iso7816::command::data_objects::{objects, GetData};
+//! use iso7816::tlv::Tag;
+//! let mut command = heapless::Vec::<u8, 16>::new();
+//! GetData::tag_list(&[0x5F, 0xC1, 0x02], 256)
+//!     .command(0.try_into().unwrap())
+//!     .serialize_into(&mut command)
+//!     .unwrap();
+//! assert_eq!(&*command, &[0x00, 0xCB, 0x3F, 0xFF, 0x05, 0x5C, 0x03, 0x5F, 0xC1, 0x02, 0x00]);
+//!
+//! let response = [0x53, 0x02, 0x01, 0x02];
+//! let mut objects = objects(&response);
+//! assert_eq!(objects.next(), Some(Ok((Tag::from_u8(0x53), &[0x01, 0x02][..]))));
+//! assert_eq!(objects.next(), None);
+//! ```
+
+use super::class::Class;
+use super::{CommandBuilder, CommandView, DataSource, DataStream, ExpectedLen, Writer};
+use crate::tlv::{take_data_object, take_tag, Tag, Tlv};
+use crate::Status;
+
+/// Tag list
+pub const TAG_LIST: u8 = 0x5C;
+/// Extended header list
+pub const HEADER_LIST: u8 = 0x4D;
+
+/// P1-P2 of the odd instructions referencing the current DF
+pub const CURRENT_DF: u16 = 0x3FFF;
+/// P1-P2 of the odd instructions referencing the current EF
+pub const CURRENT_EF: u16 = 0x0000;
+
+const GET_DATA: u8 = 0xCA;
+const PUT_DATA: u8 = 0xDA;
+
+fn le(le: usize) -> ExpectedLen {
+    u16::try_from(le).map_or(ExpectedLen::Max, ExpectedLen::Ne)
+}
+
+/// Data objects requested by GET DATA
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Query<'a> {
+    /// Tag in P1-P2, with the even instruction
+    Tag(u16),
+    /// Encoded tags of the `5C` tag list, with the odd instruction
+    TagList(&'a [u8]),
+    /// Value of the `4D` extended header list, with the odd instruction
+    HeaderList(&'a [u8]),
+}
+
+impl DataSource for Query<'_> {
+    fn len(&self) -> usize {
+        match *self {
+            Self::Tag(_) => 0,
+            Self::TagList(tags) => Tlv::new(Tag::from_u8(TAG_LIST), tags).len(),
+            Self::HeaderList(list) => Tlv::new(Tag::from_u8(HEADER_LIST), list).len(),
+        }
+    }
+}
+
+impl<W: Writer> DataStream<W> for Query<'_> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        match *self {
+            Self::Tag(_) => Ok(()),
+            Self::TagList(tags) => Tlv::new(Tag::from_u8(TAG_LIST), tags).to_writer(writer),
+            Self::HeaderList(list) => Tlv::new(Tag::from_u8(HEADER_LIST), list).to_writer(writer),
+        }
+    }
+}
+
+/// GET DATA command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GetData<'a> {
+    file: u16,
+    query: Query<'a>,
+    le: usize,
+}
+
+impl<'a> GetData<'a> {
+    /// Data object with the tag in P1-P2 (`00XX` for one byte tags)
+    pub fn tag(tag: u16, le: usize) -> Self {
+        Self {
+            file: CURRENT_DF,
+            query: Query::Tag(tag),
+            le,
+        }
+    }
+
+    /// Data objects of the tag list, `tags` being the concatenation of the encoded tags
+    pub fn tag_list(tags: &'a [u8], le: usize) -> Self {
+        Self {
+            file: CURRENT_DF,
+            query: Query::TagList(tags),
+            le,
+        }
+    }
+
+    /// Data objects selected by an extended header list
+    pub fn header_list(list: &'a [u8], le: usize) -> Self {
+        Self {
+            file: CURRENT_DF,
+            query: Query::HeaderList(list),
+            le,
+        }
+    }
+
+    /// File queried by the odd instruction (default: [`CURRENT_DF`])
+    pub fn file(mut self, file: u16) -> Self {
+        self.file = file;
+        self
+    }
+
+    pub fn query(&self) -> Query<'a> {
+        self.query
+    }
+
+    /// File queried by the odd instruction, `None` with the even instruction
+    pub fn file_id(&self) -> Option<u16> {
+        match self.query {
+            Query::Tag(_) => None,
+            _ => Some(self.file),
+        }
+    }
+
+    /// Tags of the tag list, stopping at the first invalid one
+    pub fn tags(&self) -> impl Iterator<Item = Tag> + 'a {
+        let mut tags = match self.query {
+            Query::TagList(tags) => tags,
+            _ => &[],
+        };
+        core::iter::from_fn(move || {
+            let (tag, rest) = take_tag(tags)?;
+            tags = rest;
+            Some(tag)
+        })
+    }
+
+    pub fn expected(&self) -> usize {
+        self.le
+    }
+
+    pub fn command(self, class: Class) -> CommandBuilder<Query<'a>> {
+        let (ins, [p1, p2]) = match self.query {
+            Query::Tag(tag) => (GET_DATA, tag.to_be_bytes()),
+            _ => (GET_DATA | 1, self.file.to_be_bytes()),
+        };
+        CommandBuilder::new(class, ins.into(), p1, p2, self.query, le(self.le))
+    }
+}
+
+/// PUT DATA command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PutData<'a> {
+    tag: Option<u16>,
+    file: u16,
+    data: &'a [u8],
+}
+
+impl<'a> PutData<'a> {
+    /// Store `value` in the data object with the tag in P1-P2, with the even instruction
+    pub fn tag(tag: u16, value: &'a [u8]) -> Self {
+        Self {
+            tag: Some(tag),
+            file: CURRENT_DF,
+            data: value,
+        }
+    }
+
+    /// Store BER-TLV encoded data objects, with the odd instruction
+    pub fn objects(objects: &'a [u8]) -> Self {
+        Self {
+            tag: None,
+            file: CURRENT_DF,
+            data: objects,
+        }
+    }
+
+    /// File updated by the odd instruction (default: [`CURRENT_DF`])
+    pub fn file(mut self, file: u16) -> Self {
+        self.file = file;
+        self
+    }
+
+    /// Tag of P1-P2, `None` with the odd instruction
+    pub fn data_tag(&self) -> Option<u16> {
+        self.tag
+    }
+
+    /// File updated by the odd instruction, `None` with the even instruction
+    pub fn file_id(&self) -> Option<u16> {
+        match self.tag {
+            Some(_) => None,
+            None => Some(self.file),
+        }
+    }
+
+    /// Value with the even instruction, data objects with the odd instruction
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Data objects to store, with the odd instruction
+    pub fn objects_iter(&self) -> Objects<'a> {
+        match self.tag {
+            Some(_) => objects(&[]),
+            None => objects(self.data),
+        }
+    }
+
+    pub fn command(self, class: Class) -> CommandBuilder<&'a [u8]> {
+        let (ins, [p1, p2]) = match self.tag {
+            Some(tag) => (PUT_DATA, tag.to_be_bytes()),
+            None => (PUT_DATA | 1, self.file.to_be_bytes()),
+        };
+        CommandBuilder::new(class, ins.into(), p1, p2, self.data, 0)
+    }
+}
+
+/// The data field is not valid BER-TLV
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidData;
+
+/// Iterator over the data objects of a response, see [`objects`]
+#[derive(Clone, Debug)]
+pub struct Objects<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Objects<'a> {
+    type Item = Result<(Tag, &'a [u8]), InvalidData>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        match take_data_object(self.data) {
+            Some((tag, value, rest)) => {
+                self.data = rest;
+                Some(Ok((tag, value)))
+            }
+            None => {
+                self.data = &[];
+                Some(Err(InvalidData))
+            }
+        }
+    }
+}
+
+/// Split BER-TLV data into (tag, value) pairs
+///
+/// The iterator returns an error and stops at the first malformed data object.
+pub fn objects(data: &[u8]) -> Objects<'_> {
+    Objects { data }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataObjectError {
+    /// The command is not a GET DATA or PUT DATA command
+    WrongInstruction,
+    /// The data field does not match the instruction
+    InvalidData,
+}
+
+impl From<InvalidData> for DataObjectError {
+    fn from(_: InvalidData) -> Self {
+        Self::InvalidData
+    }
+}
+
+impl From<DataObjectError> for Status {
+    fn from(value: DataObjectError) -> Self {
+        match value {
+            DataObjectError::WrongInstruction => Status::InstructionNotSupportedOrInvalid,
+            DataObjectError::InvalidData => Status::IncorrectDataParameter,
+        }
+    }
+}
+
+fn p1p2(command: &CommandView<'_>) -> u16 {
+    u16::from_be_bytes([command.p1, command.p2])
+}
+
+impl<'a> TryFrom<&CommandView<'a>> for GetData<'a> {
+    type Error = DataObjectError;
+
+    fn try_from(command: &CommandView<'a>) -> Result<Self, Self::Error> {
+        let data = command.data();
+        let le = command.expected();
+        match u8::from(command.instruction()) {
+            GET_DATA if data.is_empty() => Ok(Self::tag(p1p2(command), le)),
+            GET_DATA => Err(DataObjectError::InvalidData),
+            ins if ins == GET_DATA | 1 => {
+                let (tag, value, rest) = take_data_object(data).ok_or(InvalidData)?;
+                let get_data = if !rest.is_empty() {
+                    return Err(DataObjectError::InvalidData);
+                } else if tag == Tag::from_u8(TAG_LIST) {
+                    Self::tag_list(value, le)
+                } else if tag == Tag::from_u8(HEADER_LIST) {
+                    Self::header_list(value, le)
+                } else {
+                    return Err(DataObjectError::InvalidData);
+                };
+                Ok(get_data.file(p1p2(command)))
+            }
+            _ => Err(DataObjectError::WrongInstruction),
+        }
+    }
+}
+
+impl<'a> TryFrom<&CommandView<'a>> for PutData<'a> {
+    type Error = DataObjectError;
+
+    fn try_from(command: &CommandView<'a>) -> Result<Self, Self::Error> {
+        let data = command.data();
+        match u8::from(command.instruction()) {
+            PUT_DATA => Ok(Self::tag(p1p2(command), data)),
+            ins if ins == PUT_DATA | 1 => {
+                objects(data).try_for_each(|object| object.map(drop))?;
+                Ok(Self::objects(data).file(p1p2(command)))
+            }
+            _ => Err(DataObjectError::WrongInstruction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    fn serialize(command: CommandBuilder<impl DataStream<Vec<u8>>>) -> Vec<u8> {
+        command.serialize_to_vec()
+    }
+
+    #[test]
+    fn get_data() {
+        let class = 0.try_into().unwrap();
+        let get_data = GetData::tag(0x006E, 256);
+        let apdu = serialize(get_data.command(class));
+        assert_eq!(apdu, hex!("00CA006E 00"));
+        let view = CommandView::try_from(&apdu[..]).unwrap();
+        assert_eq!(GetData::try_from(&view), Ok(get_data));
+        assert_eq!(get_data.file_id(), None);
+
+        let get_data = GetData::tag_list(&hex!("5FC102 7F49"), 256).file(0x0101);
+        let apdu = serialize(get_data.command(class));
+        assert_eq!(apdu, hex!("00CB0101 07 5C05 5FC1027F49 00"));
+        let view = CommandView::try_from(&apdu[..]).unwrap();
+        let parsed = GetData::try_from(&view).unwrap();
+        assert_eq!(parsed, get_data);
+        assert_eq!(parsed.file_id(), Some(0x0101));
+        assert_eq!(
+            parsed.tags().collect::<Vec<_>>(),
+            [Tag::from_3([0x5F, 0xC1, 0x02]), Tag::from_u16(0x7F49)]
+        );
+
+        let get_data = GetData::header_list(&hex!("7F4902 8100"), 256);
+        let apdu = serialize(get_data.command(class));
+        assert_eq!(apdu, hex!("00CB3FFF 07 4D05 7F49028100 00"));
+        let view = CommandView::try_from(&apdu[..]).unwrap();
+        assert_eq!(GetData::try_from(&view), Ok(get_data));
+        assert_eq!(get_data.tags().count(), 0);
+    }
+
+    #[test]
+    fn put_data() {
+        let class = 0.try_into().unwrap();
+        let put_data = PutData::tag(0x005B, b"Doe<<John");
+        let apdu = serialize(put_data.command(class));
+        assert_eq!(apdu, hex!("00DA005B 09 446F653C3C4A6F686E"));
+        let view = CommandView::try_from(&apdu[..]).unwrap();
+        assert_eq!(PutData::try_from(&view), Ok(put_data));
+
+        let put_data = PutData::objects(&hex!("5C03 5FC102 5302 0102"));
+        let apdu = serialize(put_data.command(class));
+        assert_eq!(apdu, hex!("00DB3FFF 09 5C035FC102 53020102"));
+        let view = CommandView::try_from(&apdu[..]).unwrap();
+        let parsed = PutData::try_from(&view).unwrap();
+        assert_eq!(parsed, put_data);
+        assert_eq!(
+            parsed.objects_iter().collect::<Vec<_>>(),
+            [
+                Ok((Tag::from_u8(0x5C), &hex!("5FC102")[..])),
+                Ok((Tag::from_u8(0x53), &hex!("0102")[..]))
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        fn get(apdu: &[u8]) -> Result<GetData<'_>, DataObjectError> {
+            GetData::try_from(&CommandView::try_from(apdu).unwrap())
+        }
+        fn put(apdu: &[u8]) -> Result<PutData<'_>, DataObjectError> {
+            PutData::try_from(&CommandView::try_from(apdu).unwrap())
+        }
+        assert_eq!(
+            get(&hex!("00DA005B")),
+            Err(DataObjectError::WrongInstruction)
+        );
+        assert_eq!(
+            get(&hex!("00CA006E 01 00")),
+            Err(DataObjectError::InvalidData)
+        );
+        assert_eq!(get(&hex!("00CB3FFF")), Err(DataObjectError::InvalidData));
+        assert_eq!(
+            get(&hex!("00CB3FFF 03 530100")),
+            Err(DataObjectError::InvalidData)
+        );
+        assert_eq!(
+            get(&hex!("00CB3FFF 04 5C01 7E 00")),
+            Err(DataObjectError::InvalidData)
+        );
+        assert_eq!(
+            put(&hex!("00CA006E")),
+            Err(DataObjectError::WrongInstruction)
+        );
+        assert_eq!(
+            put(&hex!("00DB3FFF 03 530201")),
+            Err(DataObjectError::InvalidData)
+        );
+        assert_eq!(
+            objects(&hex!("5301 01 53")).collect::<Vec<_>>(),
+            [Ok((Tag::from_u8(0x53), &hex!("01")[..])), Err(InvalidData)]
+        );
+    }
+}
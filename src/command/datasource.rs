@@ -1,3 +1,15 @@
+/// Data whose length is known before serialization, see [`DataStream`]
+///
+/// Integers are serialized in big endian, so numeric fields can be composed with other data:
+///
+/// ```
+/// use iso7816::command::{DataSource, DataStream};
+/// let data = (0x01u8, 0x0203u16, 0x04050607u32);
+/// assert_eq!(data.len(), 7);
+/// let mut buf = heapless::Vec::<u8, 7>::new();
+/// data.to_writer(&mut buf).unwrap();
+/// assert_eq!(&buf, &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07]);
+/// ```
 pub trait DataSource {
     /// Length of the serialized data
     fn len(&self) -> usize;
@@ -5,6 +17,33 @@ pub trait DataSource {
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Concatenation of `self` and `other`
+    ///
+    /// This is the same as the tuple `(self, other)`, for when the pieces are chosen at runtime:
+    ///
+    /// ```
+    /// use iso7816::command::{DataSource, DataStream};
+    /// let extended = true;
+    /// let header = [0x01, 0x02];
+    /// let data = header.chain(extended.then_some(0x0304u16));
+    /// assert_eq!(data.len(), 4);
+    /// let mut buf = heapless::Vec::<u8, 4>::new();
+    /// data.to_writer(&mut buf).unwrap();
+    /// assert_eq!(&buf, &[0x01, 0x02, 0x03, 0x04]);
+    /// ```
+    ///
+    /// It is provided by `DataSource` rather than [`DataStream`] so that the writer type does not
+    /// need to be known when building the data.
+    fn chain<B: DataSource>(self, other: B) -> Chain<Self, B>
+    where
+        Self: Sized,
+    {
+        Chain {
+            first: self,
+            second: other,
+        }
+    }
 }
 
 /// Datasource for APDU serialization
@@ -49,6 +88,57 @@ impl<W: super::Writer> DataStream<W> for [u8] {
     }
 }
 
+/// UTF-8 encoding, for textual data objects like labels, names or URLs
+impl DataSource for str {
+    fn len(&self) -> usize {
+        str::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        str::is_empty(self)
+    }
+}
+
+impl<W: super::Writer> DataStream<W> for str {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        writer.write_all(self.as_bytes())
+    }
+}
+
+impl<const N: usize> DataSource for heapless::String<N> {
+    fn len(&self) -> usize {
+        self.as_str().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.as_str().is_empty()
+    }
+}
+
+impl<W: super::Writer, const N: usize> DataStream<W> for heapless::String<N> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        writer.write_all(self.as_bytes())
+    }
+}
+
+#[cfg(feature = "heapless-bytes")]
+impl<const N: usize> DataSource for heapless_bytes::Bytes<N> {
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        <[u8]>::is_empty(self)
+    }
+}
+
+#[cfg(feature = "heapless-bytes")]
+impl<W: super::Writer, const N: usize> DataStream<W> for heapless_bytes::Bytes<N> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        writer.write_all(self)
+    }
+}
+
 impl DataSource for [&dyn DataSource] {
     fn len(&self) -> usize {
         self.iter().map(|item| item.len()).sum()
@@ -129,6 +219,183 @@ impl<W: super::Writer, T: DataStream<W> + ?Sized> DataStream<W> for &T {
     }
 }
 
+/// Data produced by a closure at serialization time
+///
+/// The closure must write exactly `len` bytes. This avoids materializing data that can be read or
+/// generated directly into the writer, for example from flash:
+///
+/// ```
+/// use iso7816::command::{DataStream, FnStream, Writer};
+/// let flash = [0x11; 300];
+/// let data = FnStream::new(4, |writer: &mut heapless::Vec<u8, 8>| writer.write_all(&flash[296..]));
+/// let mut buf = heapless::Vec::new();
+/// data.to_writer(&mut buf).unwrap();
+/// assert_eq!(&buf, &[0x11; 4]);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct FnStream<F> {
+    len: usize,
+    f: F,
+}
+
+impl<F> FnStream<F> {
+    pub const fn new(len: usize, f: F) -> Self {
+        Self { len, f }
+    }
+}
+
+impl<F> DataSource for FnStream<F> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<W, F> DataStream<W> for FnStream<F>
+where
+    W: super::Writer,
+    F: Fn(&mut W) -> Result<(), W::Error>,
+{
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        (self.f)(writer)
+    }
+}
+
+/// Concatenation of two data sources, see [`DataSource::chain`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> Chain<A, B> {
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: DataSource, B: DataSource> DataSource for Chain<A, B> {
+    fn len(&self) -> usize {
+        self.first.len() + self.second.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.first.is_empty() && self.second.is_empty()
+    }
+}
+
+impl<W: super::Writer, A: DataStream<W>, B: DataStream<W>> DataStream<W> for Chain<A, B> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        self.first.to_writer(writer)?;
+        self.second.to_writer(writer)
+    }
+}
+
+/// `len` times `byte`, for padding and fixed-size fields
+///
+/// ```
+/// use iso7816::command::{DataSource, DataStream, Fill};
+/// // ISO 9564 format 2 PIN block
+/// let pin = [0x21, 0x23, 0x4F];
+/// let block = pin.chain(Fill { byte: 0xFF, len: 8 - pin.len() });
+/// let mut buf = heapless::Vec::<u8, 8>::new();
+/// block.to_writer(&mut buf).unwrap();
+/// assert_eq!(&buf, &[0x21, 0x23, 0x4F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fill {
+    pub byte: u8,
+    pub len: usize,
+}
+
+impl DataSource for Fill {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<W: super::Writer> DataStream<W> for Fill {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        let chunk = [self.byte; 16];
+        let mut remaining = self.len;
+        while remaining > 0 {
+            let amt = remaining.min(chunk.len());
+            writer.write_all(&chunk[..amt])?;
+            remaining -= amt;
+        }
+        Ok(())
+    }
+}
+
+/// Data source whose length is computed once, when wrapping it
+///
+/// The length of nested data, like data objects inside data objects inside a command, is otherwise
+/// computed again by each level, during serialization and when splitting commands. [`Tlv`] and
+/// [`CommandBuilder`] already cache the length of their data this way.
+///
+/// [`Tlv`]: crate::tlv::Tlv
+/// [`CommandBuilder`]: super::CommandBuilder
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CachedLen<S> {
+    inner: S,
+    len: usize,
+}
+
+impl<S: DataSource> CachedLen<S> {
+    pub fn new(inner: S) -> Self {
+        let len = inner.len();
+        Self { inner, len }
+    }
+}
+
+impl<S> CachedLen<S> {
+    pub fn get(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S> DataSource for CachedLen<S> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<W: super::Writer, S: DataStream<W>> DataStream<W> for CachedLen<S> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        self.inner.to_writer(writer)
+    }
+}
+
+/// Big endian encoding of integers
+mod int_impls {
+    use super::{DataSource, DataStream};
+
+    macro_rules! int_impl {
+        ($($t:ty)*) => {$(
+            impl DataSource for $t {
+                fn len(&self) -> usize {
+                    core::mem::size_of::<$t>()
+                }
+
+                fn is_empty(&self) -> bool {
+                    false
+                }
+            }
+
+            impl<W: crate::command::Writer> DataStream<W> for $t {
+                fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+                    writer.write_all(&self.to_be_bytes())
+                }
+            }
+        )*};
+    }
+
+    int_impl!(u8 u16 u32);
+}
+
 mod tuple_impls {
     use super::*;
 
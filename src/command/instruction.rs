@@ -1,30 +1,45 @@
 use core::ops::{BitAnd, BitOr};
 
+/// Instruction byte
+///
+/// Instructions without a variant are represented by [`Unknown`](Self::Unknown). New variants
+/// may be added in minor releases, which changes the value returned by [`From<u8>`] for their
+/// instruction byte.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum Instruction {
     Select,
     GetData,
     Verify,
     ChangeReferenceData,
     ResetRetryCounter,
+    ManageSecurityEnvironment,
+    GetChallenge,
     GeneralAuthenticate,
     PutData,
     GenerateAsymmetricKeyPair,
     GetResponse,
     ReadBinary,
     WriteBinary,
+    UpdateBinary,
+    ReadRecord,
+    UpdateRecord,
+    AppendRecord,
+    SearchRecord,
     // Unknown(BinaryInstruction),
     Unknown(u8),
 }
 
 pub struct UnknownInstruction {}
 
-impl From<u8> for Instruction {
-    fn from(ins: u8) -> Self {
+impl Instruction {
+    pub const fn from_byte(ins: u8) -> Self {
         match ins {
             0x20 => Instruction::Verify,
             0x24 => Instruction::ChangeReferenceData,
             0x2c => Instruction::ResetRetryCounter,
+            0x22 => Instruction::ManageSecurityEnvironment,
+            0x84 => Instruction::GetChallenge,
             0x47 => Instruction::GenerateAsymmetricKeyPair,
             0x87 => Instruction::GeneralAuthenticate,
             0xa4 => Instruction::Select,
@@ -33,17 +48,41 @@ impl From<u8> for Instruction {
             0xdb => Instruction::PutData,
             0xb0 => Instruction::ReadBinary,
             0xd0 => Instruction::WriteBinary,
+            0xd6 => Instruction::UpdateBinary,
+            0xb2 => Instruction::ReadRecord,
+            0xdc => Instruction::UpdateRecord,
+            0xe2 => Instruction::AppendRecord,
+            0xa2 => Instruction::SearchRecord,
             ins => Instruction::Unknown(ins),
         }
     }
 }
 
+impl From<u8> for Instruction {
+    fn from(ins: u8) -> Self {
+        Self::from_byte(ins)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Instruction {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u8::arbitrary(u).map(Self::from)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        u8::size_hint(depth)
+    }
+}
+
 impl From<Instruction> for u8 {
     fn from(instruction: Instruction) -> u8 {
         match instruction {
             Instruction::Verify => 0x20,
             Instruction::ChangeReferenceData => 0x24,
             Instruction::ResetRetryCounter => 0x2c,
+            Instruction::ManageSecurityEnvironment => 0x22,
+            Instruction::GetChallenge => 0x84,
             Instruction::GenerateAsymmetricKeyPair => 0x47,
             Instruction::GeneralAuthenticate => 0x87,
             Instruction::Select => 0xa4,
@@ -52,6 +91,11 @@ impl From<Instruction> for u8 {
             Instruction::PutData => 0xdb,
             Instruction::ReadBinary => 0xb0,
             Instruction::WriteBinary => 0xd0,
+            Instruction::UpdateBinary => 0xd6,
+            Instruction::ReadRecord => 0xb2,
+            Instruction::UpdateRecord => 0xdc,
+            Instruction::AppendRecord => 0xe2,
+            Instruction::SearchRecord => 0xa2,
             Instruction::Unknown(ins) => ins,
         }
     }
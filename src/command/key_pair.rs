@@ -0,0 +1,474 @@
+//! GENERATE ASYMMETRIC KEY PAIR (ISO 7816-8 5.1)
+//!
+//! [`GenerateKeyPair`] builds the command: P1 selects the generation of a key pair or the reading
+//! of an existing public key, P2 is a key reference and the data field is an optional
+//! [`ControlReference`] template identifying the key and algorithm. [`PublicKey`] parses the
+//! `7F49` response template.
+//!
+//! ```
+//! use iso7816::command::key_pair::{ControlReference, GenerateKeyPair, Operation, PublicKey};
+//! use iso7816::command::KeyReference;
+//! let mut command = heapless::Vec::<u8, 16>::new();
+//! GenerateKeyPair::new(Operation::Unspecified, KeyReference::from_p2(0x9A), 256)
+//!     .template(ControlReference::new(0xAC).algorithm(&[0x11]))
+//!     .command(0.try_into().unwrap())
+//!     .serialize_into(&mut command)
+//!     .unwrap();
+//! assert_eq!(&*command, &[0x00, 0x47, 0x00, 0x9A, 0x05, 0xAC, 0x03, 0x80, 0x01, 0x11, 0x00]);
+//!
+//! let response = [0x7F, 0x49, 0x04, 0x86, 0x02, 0x04, 0x01];
+//! assert_eq!(PublicKey::parse(&response), Ok(PublicKey::Ec { point: &[0x04, 0x01] }));
+//! ```
+
+use super::class::Class;
+use super::mse::{ALGORITHM_REFERENCE, PRIVATE_KEY_REFERENCE};
+use super::{
+    CommandBuilder, CommandView, DataSource, DataStream, ExpectedLen, Instruction, KeyReference,
+    Writer,
+};
+use crate::tlv::{take_data_object, Tag, Tlv};
+use crate::Status;
+
+/// Public key template
+pub const PUBLIC_KEY: u16 = 0x7F49;
+/// RSA modulus, in the public key template
+pub const MODULUS: u8 = 0x81;
+/// RSA public exponent, in the public key template
+pub const EXPONENT: u8 = 0x82;
+/// Elliptic curve point, in the public key template
+pub const EC_POINT: u8 = 0x86;
+
+/// Control reference template for authentication
+pub const AUTHENTICATION_TEMPLATE: u8 = 0xA4;
+/// Control reference template for digital signature
+pub const DIGITAL_SIGNATURE_TEMPLATE: u8 = 0xB6;
+/// Control reference template for confidentiality
+pub const CONFIDENTIALITY_TEMPLATE: u8 = 0xB8;
+/// Cryptographic mechanism reference template, as used by PIV
+pub const MECHANISM_TEMPLATE: u8 = 0xAC;
+
+const UNSPECIFIED: u8 = 0x00;
+const GENERATE: u8 = 0x80;
+const READ_PUBLIC_KEY: u8 = 0x81;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// Generate a key pair (P1 `80`)
+    Generate,
+    /// No information given (P1 `00`), used by PIV cards to generate a key pair
+    Unspecified,
+    /// Read the public key of an existing key pair (P1 `81`)
+    ReadPublicKey,
+}
+
+/// Control reference template of the data field
+///
+/// Data objects of the template other than `80` and `84` are ignored when parsing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ControlReference<'a> {
+    tag: u8,
+    algorithm: Option<&'a [u8]>,
+    private_key: Option<&'a [u8]>,
+}
+
+impl<'a> ControlReference<'a> {
+    /// Empty template with this tag, see the `*_TEMPLATE` constants
+    pub fn new(tag: u8) -> Self {
+        Self {
+            tag,
+            algorithm: None,
+            private_key: None,
+        }
+    }
+
+    /// Algorithm reference (`80`)
+    pub fn algorithm(mut self, algorithm: &'a [u8]) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Reference of the private key (`84`)
+    pub fn private_key_reference(mut self, private_key: &'a [u8]) -> Self {
+        self.private_key = Some(private_key);
+        self
+    }
+
+    pub fn tag(&self) -> u8 {
+        self.tag
+    }
+
+    pub fn algorithm_reference(&self) -> Option<&'a [u8]> {
+        self.algorithm
+    }
+
+    pub fn private_key(&self) -> Option<&'a [u8]> {
+        self.private_key
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn template(&self) -> Tlv<(Option<Tlv<&'a [u8]>>, Option<Tlv<&'a [u8]>>)> {
+        let tlv = |tag, value: Option<&'a [u8]>| value.map(|v| Tlv::new(Tag::from_u8(tag), v));
+        Tlv::new(
+            Tag::from_u8(self.tag),
+            (
+                tlv(ALGORITHM_REFERENCE, self.algorithm),
+                tlv(PRIVATE_KEY_REFERENCE, self.private_key),
+            ),
+        )
+    }
+
+    fn parse(data: &'a [u8]) -> Result<Self, KeyPairError> {
+        let (tag, mut value, rest) = take_data_object(data).ok_or(KeyPairError::InvalidData)?;
+        let tag = match *tag.serialize() {
+            [tag] if rest.is_empty() => tag,
+            _ => return Err(KeyPairError::InvalidData),
+        };
+        let mut template = Self::new(tag);
+        while !value.is_empty() {
+            let (tag, value_, rest) = take_data_object(value).ok_or(KeyPairError::InvalidData)?;
+            value = rest;
+            let field = if tag == Tag::from_u8(ALGORITHM_REFERENCE) {
+                &mut template.algorithm
+            } else if tag == Tag::from_u8(PRIVATE_KEY_REFERENCE) {
+                &mut template.private_key
+            } else {
+                continue;
+            };
+            if field.replace(value_).is_some() {
+                return Err(KeyPairError::InvalidData);
+            }
+        }
+        Ok(template)
+    }
+}
+
+impl DataSource for ControlReference<'_> {
+    fn len(&self) -> usize {
+        self.template().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+impl<W: Writer> DataStream<W> for ControlReference<'_> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        self.template().to_writer(writer)
+    }
+}
+
+/// GENERATE ASYMMETRIC KEY PAIR command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GenerateKeyPair<'a> {
+    operation: Operation,
+    reference: KeyReference,
+    template: Option<ControlReference<'a>>,
+    le: usize,
+}
+
+impl<'a> GenerateKeyPair<'a> {
+    /// Command for the key pair with the reference in P2, [`KeyReference::NONE`] if it is given by
+    /// the template
+    pub fn new(operation: Operation, reference: KeyReference, le: usize) -> Self {
+        Self {
+            operation,
+            reference,
+            template: None,
+            le,
+        }
+    }
+
+    /// Generate the key pair with the reference in P2
+    pub fn generate(reference: KeyReference, le: usize) -> Self {
+        Self::new(Operation::Generate, reference, le)
+    }
+
+    /// Read the public key of the key pair with the reference in P2
+    pub fn read_public_key(reference: KeyReference, le: usize) -> Self {
+        Self::new(Operation::ReadPublicKey, reference, le)
+    }
+
+    pub fn template(mut self, template: ControlReference<'a>) -> Self {
+        self.template = Some(template);
+        self
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    pub fn reference(&self) -> KeyReference {
+        self.reference
+    }
+
+    pub fn control_reference(&self) -> Option<ControlReference<'a>> {
+        self.template
+    }
+
+    pub fn expected(&self) -> usize {
+        self.le
+    }
+
+    pub fn command(self, class: Class) -> CommandBuilder<Option<ControlReference<'a>>> {
+        let p1 = match self.operation {
+            Operation::Generate => GENERATE,
+            Operation::Unspecified => UNSPECIFIED,
+            Operation::ReadPublicKey => READ_PUBLIC_KEY,
+        };
+        let le = u16::try_from(self.le).map_or(ExpectedLen::Max, ExpectedLen::Ne);
+        CommandBuilder::new(
+            class,
+            Instruction::GenerateAsymmetricKeyPair,
+            p1,
+            self.reference.p2(),
+            self.template,
+            le,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyPairError {
+    /// The command is not a GENERATE ASYMMETRIC KEY PAIR command
+    WrongInstruction,
+    /// P1 is not `00`, `80` or `81`
+    InvalidP1,
+    /// The data field is not a single control reference template, or the response is not a
+    /// public key template
+    InvalidData,
+}
+
+impl From<KeyPairError> for Status {
+    fn from(value: KeyPairError) -> Self {
+        match value {
+            KeyPairError::WrongInstruction => Status::InstructionNotSupportedOrInvalid,
+            KeyPairError::InvalidP1 => Status::IncorrectP1OrP2Parameter,
+            KeyPairError::InvalidData => Status::IncorrectDataParameter,
+        }
+    }
+}
+
+impl<'a> TryFrom<&CommandView<'a>> for GenerateKeyPair<'a> {
+    type Error = KeyPairError;
+
+    fn try_from(command: &CommandView<'a>) -> Result<Self, Self::Error> {
+        if command.instruction() != Instruction::GenerateAsymmetricKeyPair {
+            return Err(KeyPairError::WrongInstruction);
+        }
+        let operation = match command.p1 {
+            GENERATE => Operation::Generate,
+            UNSPECIFIED => Operation::Unspecified,
+            READ_PUBLIC_KEY => Operation::ReadPublicKey,
+            _ => return Err(KeyPairError::InvalidP1),
+        };
+        let template = match command.data() {
+            [] => None,
+            data => Some(ControlReference::parse(data)?),
+        };
+        Ok(Self {
+            operation,
+            reference: KeyReference::from_p2(command.p2),
+            template,
+            le: command.expected(),
+        })
+    }
+}
+
+/// Public key template (`7F49`) of the response
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PublicKey<'a> {
+    Rsa {
+        modulus: &'a [u8],
+        exponent: &'a [u8],
+    },
+    Ec {
+        point: &'a [u8],
+    },
+}
+
+impl<'a> PublicKey<'a> {
+    /// Parse the public key template
+    ///
+    /// Data objects other than the modulus, exponent and point are ignored.
+    pub fn parse(response: &'a [u8]) -> Result<Self, KeyPairError> {
+        let (tag, mut value, rest) = take_data_object(response).ok_or(KeyPairError::InvalidData)?;
+        if tag != Tag::from_u16(PUBLIC_KEY) || !rest.is_empty() {
+            return Err(KeyPairError::InvalidData);
+        }
+        let (mut modulus, mut exponent, mut point) = (None, None, None);
+        while !value.is_empty() {
+            let (tag, value_, rest) = take_data_object(value).ok_or(KeyPairError::InvalidData)?;
+            value = rest;
+            let field = if tag == Tag::from_u8(MODULUS) {
+                &mut modulus
+            } else if tag == Tag::from_u8(EXPONENT) {
+                &mut exponent
+            } else if tag == Tag::from_u8(EC_POINT) {
+                &mut point
+            } else {
+                continue;
+            };
+            if field.replace(value_).is_some() {
+                return Err(KeyPairError::InvalidData);
+            }
+        }
+        match (modulus, exponent, point) {
+            (Some(modulus), Some(exponent), None) => Ok(Self::Rsa { modulus, exponent }),
+            (None, None, Some(point)) => Ok(Self::Ec { point }),
+            _ => Err(KeyPairError::InvalidData),
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn template(&self) -> Tlv<(Tlv<&'a [u8]>, Option<Tlv<&'a [u8]>>)> {
+        let content = match *self {
+            Self::Rsa { modulus, exponent } => (
+                Tlv::new(Tag::from_u8(MODULUS), modulus),
+                Some(Tlv::new(Tag::from_u8(EXPONENT), exponent)),
+            ),
+            Self::Ec { point } => (Tlv::new(Tag::from_u8(EC_POINT), point), None),
+        };
+        Tlv::new(Tag::from_u16(PUBLIC_KEY), content)
+    }
+}
+
+/// Public key template, for the card side
+impl DataSource for PublicKey<'_> {
+    fn len(&self) -> usize {
+        self.template().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+impl<W: Writer> DataStream<W> for PublicKey<'_> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        self.template().to_writer(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    fn roundtrip(command: GenerateKeyPair<'_>, apdu: &[u8]) {
+        let serialized = command.command(0.try_into().unwrap()).serialize_to_vec();
+        assert_eq!(serialized, apdu);
+        let view = CommandView::try_from(apdu).unwrap();
+        assert_eq!(GenerateKeyPair::try_from(&view), Ok(command));
+    }
+
+    #[test]
+    fn commands() {
+        roundtrip(
+            GenerateKeyPair::generate(KeyReference::NONE, 256)
+                .template(ControlReference::new(DIGITAL_SIGNATURE_TEMPLATE)),
+            &hex!("00478000 02 B600 00"),
+        );
+        roundtrip(
+            GenerateKeyPair::read_public_key(KeyReference::NONE, 0x10000)
+                .template(ControlReference::new(CONFIDENTIALITY_TEMPLATE)),
+            &hex!("00478100 000002 B800 0000"),
+        );
+        roundtrip(
+            GenerateKeyPair::new(Operation::Unspecified, KeyReference::from_p2(0x9C), 256)
+                .template(
+                    ControlReference::new(MECHANISM_TEMPLATE)
+                        .algorithm(&hex!("07"))
+                        .private_key_reference(KeyReference::from_p2(0x9C).as_slice()),
+                ),
+            &hex!("0047009C 08 AC06 800107 84019C 00"),
+        );
+        roundtrip(
+            GenerateKeyPair::read_public_key(KeyReference::from_p2(0x9A), 256),
+            &hex!("0047819A 00"),
+        );
+
+        // Unknown data objects of the template are skipped
+        let view = CommandView::try_from(&hex!("0047009A 08 AC06 800111 AA0102")[..]).unwrap();
+        let command = GenerateKeyPair::try_from(&view).unwrap();
+        assert_eq!(command.reference().number(), 0x1A);
+        let template = command.control_reference().unwrap();
+        assert_eq!(template.tag(), MECHANISM_TEMPLATE);
+        assert_eq!(template.algorithm_reference(), Some(&hex!("11")[..]));
+        assert_eq!(template.private_key(), None);
+    }
+
+    #[test]
+    fn errors() {
+        fn parse(apdu: &[u8]) -> Result<GenerateKeyPair<'_>, KeyPairError> {
+            GenerateKeyPair::try_from(&CommandView::try_from(apdu).unwrap())
+        }
+        assert_eq!(
+            parse(&hex!("00468000")),
+            Err(KeyPairError::WrongInstruction)
+        );
+        assert_eq!(parse(&hex!("00470100")), Err(KeyPairError::InvalidP1));
+        assert_eq!(
+            parse(&hex!("00478000 04 B600 B800")),
+            Err(KeyPairError::InvalidData)
+        );
+        assert_eq!(
+            parse(&hex!("00478000 03 B601 80")),
+            Err(KeyPairError::InvalidData)
+        );
+        assert_eq!(
+            parse(&hex!("00478000 08 AC06 800111 800111")),
+            Err(KeyPairError::InvalidData)
+        );
+        assert_eq!(
+            Status::from(KeyPairError::InvalidP1),
+            Status::IncorrectP1OrP2Parameter
+        );
+    }
+
+    #[test]
+    fn public_key() {
+        let rsa = PublicKey::Rsa {
+            modulus: &hex!("C0FFEE"),
+            exponent: &hex!("010001"),
+        };
+        let encoded = hex!("7F490A 8103C0FFEE 8203010001");
+        let mut buffer = Vec::new();
+        rsa.to_writer(&mut buffer).unwrap();
+        assert_eq!(buffer, encoded);
+        assert_eq!(PublicKey::parse(&encoded), Ok(rsa));
+
+        let ec = PublicKey::Ec {
+            point: &hex!("04 0102 0304"),
+        };
+        let encoded = hex!("7F4907 8605 0401020304");
+        let mut buffer = Vec::new();
+        ec.to_writer(&mut buffer).unwrap();
+        assert_eq!(buffer, encoded);
+        assert_eq!(PublicKey::parse(&encoded), Ok(ec));
+
+        // Other data objects, such as the curve OID, are skipped
+        assert_eq!(
+            PublicKey::parse(&hex!("7F490A 0603 2B6570 8603 040102")),
+            Ok(PublicKey::Ec {
+                point: &hex!("040102")
+            })
+        );
+        assert_eq!(
+            PublicKey::parse(&hex!("7F4905 8103C0FFEE")),
+            Err(KeyPairError::InvalidData)
+        );
+        assert_eq!(
+            PublicKey::parse(&hex!("7F4909 8103C0FFEE 8602 0401")),
+            Err(KeyPairError::InvalidData)
+        );
+        assert_eq!(
+            PublicKey::parse(&hex!("7F4904 8602 0401 00")),
+            Err(KeyPairError::InvalidData)
+        );
+        assert_eq!(
+            PublicKey::parse(&hex!("7F48 04 8602 0401")),
+            Err(KeyPairError::InvalidData)
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! Key and reference data references (ISO 7816-4 7.5.2)
+//!
+//! A [`KeyReference`] identifies a key or reference data (a PIN) on the card. It is sent as P2 of
+//! VERIFY, CHANGE REFERENCE DATA, RESET RETRY COUNTER, GENERAL AUTHENTICATE or GENERATE
+//! ASYMMETRIC KEY PAIR, and as the value of the key reference data objects of MANAGE SECURITY
+//! ENVIRONMENT. Bit 8 is the [`Qualifier`], telling whether the reference is global (card level)
+//! or specific (DF level), and bits 5 to 1 hold the reference number.
+//!
+//! ```
+//! use iso7816::command::key_reference::{KeyReference, Qualifier};
+//! let reference = KeyReference::specific(1);
+//! assert_eq!(reference.p2(), 0x81);
+//! assert_eq!(KeyReference::from_p2(0x81), reference);
+//! assert_eq!(reference.qualifier(), Qualifier::Specific);
+//! assert_eq!(reference.number(), 1);
+//! assert_eq!(reference.as_slice(), &[0x81]);
+//! ```
+
+const SPECIFIC: u8 = 0x80;
+const RESERVED: u8 = 0x60;
+const NUMBER: u8 = 0x1F;
+
+/// Whether a reference is global or specific, bit 8 of the reference
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Qualifier {
+    /// Card level reference, usable from any DF
+    Global,
+    /// Reference specific to the current DF
+    Specific,
+}
+
+/// Reference of a key or of reference data
+///
+/// The raw byte is kept as is, so that proprietary values using the reserved bits 7 and 6 survive
+/// a round trip.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct KeyReference([u8; 1]);
+
+impl KeyReference {
+    /// No particular reference, the card uses the implicit one (P2 `00`)
+    pub const NONE: Self = Self([0]);
+
+    /// Reference number `number`, in `0..=31`
+    pub const fn new(qualifier: Qualifier, number: u8) -> Self {
+        debug_assert!(number <= NUMBER);
+        let qualifier = match qualifier {
+            Qualifier::Global => 0,
+            Qualifier::Specific => SPECIFIC,
+        };
+        Self([qualifier | number & NUMBER])
+    }
+
+    /// Global (card level) reference number `number`, in `0..=31`
+    pub const fn global(number: u8) -> Self {
+        Self::new(Qualifier::Global, number)
+    }
+
+    /// Specific (DF level) reference number `number`, in `0..=31`
+    pub const fn specific(number: u8) -> Self {
+        Self::new(Qualifier::Specific, number)
+    }
+
+    pub const fn from_p2(p2: u8) -> Self {
+        Self([p2])
+    }
+
+    pub const fn p2(self) -> u8 {
+        self.0[0]
+    }
+
+    pub const fn qualifier(self) -> Qualifier {
+        if self.0[0] & SPECIFIC == 0 {
+            Qualifier::Global
+        } else {
+            Qualifier::Specific
+        }
+    }
+
+    pub const fn is_global(self) -> bool {
+        matches!(self.qualifier(), Qualifier::Global)
+    }
+
+    /// Whether bits 7 and 6, reserved for future use by ISO 7816-4, are zero
+    pub const fn is_standard(self) -> bool {
+        self.0[0] & RESERVED == 0
+    }
+
+    pub const fn number(self) -> u8 {
+        self.0[0] & NUMBER
+    }
+
+    /// Encoding as the value of a key reference data object (`83` or `84`)
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<u8> for KeyReference {
+    fn from(value: u8) -> Self {
+        Self::from_p2(value)
+    }
+}
+
+impl From<KeyReference> for u8 {
+    fn from(value: KeyReference) -> Self {
+        value.p2()
+    }
+}
+
+/// Parse the value of a key reference data object, which must be a single byte
+impl TryFrom<&[u8]> for KeyReference {
+    type Error = InvalidKeyReference;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        match *value {
+            [byte] => Ok(Self::from_p2(byte)),
+            _ => Err(InvalidKeyReference),
+        }
+    }
+}
+
+/// The key reference data object is not a single byte
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidKeyReference;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        assert_eq!(KeyReference::global(3).p2(), 0x03);
+        assert_eq!(KeyReference::specific(0x1F).p2(), 0x9F);
+        assert!(KeyReference::global(3).is_global());
+        assert!(KeyReference::NONE.is_global());
+        assert_eq!(
+            KeyReference::from_p2(0x02).qualifier(),
+            KeyReference::new(Qualifier::Global, 2).qualifier()
+        );
+        assert!(KeyReference::specific(2).is_standard());
+        assert!(!KeyReference::from_p2(0xC2).is_standard());
+
+        let piv = KeyReference::from(0x9A);
+        assert_eq!(piv.qualifier(), Qualifier::Specific);
+        assert_eq!(piv.number(), 0x1A);
+        assert_eq!(u8::from(piv), 0x9A);
+    }
+
+    #[test]
+    fn data_object() {
+        assert_eq!(
+            KeyReference::try_from(&[0x84][..]),
+            Ok(KeyReference::specific(4))
+        );
+        assert_eq!(KeyReference::try_from(&[][..]), Err(InvalidKeyReference));
+        assert_eq!(
+            KeyReference::try_from(&[0x84, 0x00][..]),
+            Err(InvalidKeyReference)
+        );
+    }
+}
@@ -0,0 +1,368 @@
+//! MANAGE SECURITY ENVIRONMENT (ISO 7816-4 11.5.11)
+//!
+//! [`Set`] builds MSE SET commands: P1 combines the [`Usage`] of the template with the SET
+//! operation, P2 is the tag of the control reference [`Template`] and the data field holds the
+//! control reference data objects of the template, without the template tag itself.
+//! [`restore`] builds MSE RESTORE commands. [`Mse`] parses both from a [`CommandView`].
+//!
+//! ```
+//! use iso7816::command::mse::{Mse, Set, Template, Usage};
+//! use iso7816::command::{CommandView, KeyReference};
+//! let mut command = heapless::Vec::<u8, 16>::new();
+//! let key = KeyReference::from_p2(0x9C);
+//! Set::new(Usage::COMPUTATION, Template::DigitalSignature)
+//!     .algorithm(&[0x54])
+//!     .private_key_reference(key.as_slice())
+//!     .command(0.try_into().unwrap())
+//!     .serialize_into(&mut command)
+//!     .unwrap();
+//! assert_eq!(&*command, &[0x00, 0x22, 0x41, 0xB6, 0x06, 0x80, 0x01, 0x54, 0x84, 0x01, 0x9C]);
+//!
+//! let view = CommandView::try_from(&command[..]).unwrap();
+//! let Ok(Mse::Set(set)) = Mse::try_from(&view) else { panic!() };
+//! assert_eq!(set.template(), Template::DigitalSignature);
+//! assert_eq!(set.private_key().map(KeyReference::try_from), Some(Ok(key)));
+//! ```
+
+use super::class::Class;
+use super::{CommandBuilder, CommandView, DataSource, DataStream, Instruction, Writer};
+use crate::tlv::{take_data_object, Tag, Tlv};
+use crate::Status;
+
+/// Algorithm reference
+pub const ALGORITHM_REFERENCE: u8 = 0x80;
+/// Reference of a secret or public key
+pub const KEY_REFERENCE: u8 = 0x83;
+/// Reference of a private key, or for computing a session key
+pub const PRIVATE_KEY_REFERENCE: u8 = 0x84;
+
+const SET: u8 = 0x01;
+const RESTORE: u8 = 0xF3;
+
+/// Control reference template, sent as P2 of MSE SET
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Template {
+    /// `A4`: authentication
+    Authentication,
+    /// `A6`: key agreement
+    KeyAgreement,
+    /// `B4`: cryptographic checksum
+    CryptographicChecksum,
+    /// `B6`: digital signature
+    DigitalSignature,
+    /// `B8`: confidentiality
+    Confidentiality,
+}
+
+impl TryFrom<u8> for Template {
+    type Error = MseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0xA4 => Self::Authentication,
+            0xA6 => Self::KeyAgreement,
+            0xB4 => Self::CryptographicChecksum,
+            0xB6 => Self::DigitalSignature,
+            0xB8 => Self::Confidentiality,
+            _ => return Err(MseError::UnsupportedTemplate(value)),
+        })
+    }
+}
+
+impl From<Template> for u8 {
+    fn from(value: Template) -> Self {
+        match value {
+            Template::Authentication => 0xA4,
+            Template::KeyAgreement => 0xA6,
+            Template::CryptographicChecksum => 0xB4,
+            Template::DigitalSignature => 0xB6,
+            Template::Confidentiality => 0xB8,
+        }
+    }
+}
+
+/// Usage of the template, in the high nibble of P1 of MSE SET
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Usage(pub u8);
+
+impl Usage {
+    /// Secure messaging in command data fields
+    pub const SECURE_MESSAGING_COMMAND: Self = Self(0x10);
+    /// Secure messaging in response data fields
+    pub const SECURE_MESSAGING_RESPONSE: Self = Self(0x20);
+    /// Computation, decipherment, internal authentication and key agreement
+    pub const COMPUTATION: Self = Self(0x40);
+    /// Verification, encipherment, external authentication and key agreement
+    pub const VERIFICATION: Self = Self(0x80);
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// MSE SET command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Set<'a> {
+    usage: Usage,
+    template: Template,
+    algorithm: Option<&'a [u8]>,
+    key: Option<&'a [u8]>,
+    private_key: Option<&'a [u8]>,
+}
+
+impl<'a> Set<'a> {
+    pub fn new(usage: Usage, template: Template) -> Self {
+        Self {
+            usage,
+            template,
+            algorithm: None,
+            key: None,
+            private_key: None,
+        }
+    }
+
+    /// Algorithm reference (`80`)
+    pub fn algorithm(mut self, algorithm: &'a [u8]) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Reference of a secret or public key (`83`), usually
+    /// [`KeyReference::as_slice`](super::KeyReference::as_slice)
+    pub fn key_reference(mut self, key: &'a [u8]) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Reference of a private key (`84`), usually
+    /// [`KeyReference::as_slice`](super::KeyReference::as_slice)
+    pub fn private_key_reference(mut self, private_key: &'a [u8]) -> Self {
+        self.private_key = Some(private_key);
+        self
+    }
+
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    pub fn template(&self) -> Template {
+        self.template
+    }
+
+    pub fn algorithm_reference(&self) -> Option<&'a [u8]> {
+        self.algorithm
+    }
+
+    pub fn key(&self) -> Option<&'a [u8]> {
+        self.key
+    }
+
+    pub fn private_key(&self) -> Option<&'a [u8]> {
+        self.private_key
+    }
+
+    pub fn command(self, class: Class) -> CommandBuilder<Self> {
+        CommandBuilder::new(
+            class,
+            Instruction::ManageSecurityEnvironment,
+            self.usage.0 & 0xF0 | SET,
+            self.template.into(),
+            self,
+            0,
+        )
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn data_objects(
+        &self,
+    ) -> (
+        Option<Tlv<&'a [u8]>>,
+        Option<Tlv<&'a [u8]>>,
+        Option<Tlv<&'a [u8]>>,
+    ) {
+        let tlv = |tag, value: Option<&'a [u8]>| value.map(|v| Tlv::new(Tag::from_u8(tag), v));
+        (
+            tlv(ALGORITHM_REFERENCE, self.algorithm),
+            tlv(KEY_REFERENCE, self.key),
+            tlv(PRIVATE_KEY_REFERENCE, self.private_key),
+        )
+    }
+
+    /// Parse the control reference data objects of the data field.
+    ///
+    /// Data objects other than `80`, `83` and `84` are ignored.
+    fn parse(usage: Usage, template: Template, mut data: &'a [u8]) -> Result<Self, MseError> {
+        let mut set = Self::new(usage, template);
+        while !data.is_empty() {
+            let (tag, value, rem) = take_data_object(data).ok_or(MseError::InvalidData)?;
+            data = rem;
+            let field = if tag == Tag::from_u8(ALGORITHM_REFERENCE) {
+                &mut set.algorithm
+            } else if tag == Tag::from_u8(KEY_REFERENCE) {
+                &mut set.key
+            } else if tag == Tag::from_u8(PRIVATE_KEY_REFERENCE) {
+                &mut set.private_key
+            } else {
+                continue;
+            };
+            if field.replace(value).is_some() {
+                return Err(MseError::InvalidData);
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl DataSource for Set<'_> {
+    fn len(&self) -> usize {
+        self.data_objects().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data_objects().is_empty()
+    }
+}
+
+impl<W: Writer> DataStream<W> for Set<'_> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        self.data_objects().to_writer(writer)
+    }
+}
+
+/// MSE RESTORE command, replacing the current security environment with the one identified by `seid`
+pub fn restore(class: Class, seid: u8) -> CommandBuilder<&'static [u8]> {
+    CommandBuilder::new(
+        class,
+        Instruction::ManageSecurityEnvironment,
+        RESTORE,
+        seid,
+        &[][..],
+        0,
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MseError {
+    /// The command is not a MANAGE SECURITY ENVIRONMENT command
+    WrongInstruction,
+    /// P1 is not a SET or RESTORE operation
+    UnsupportedOperation(u8),
+    /// P2 of a SET operation is not a supported control reference template
+    UnsupportedTemplate(u8),
+    /// The data field is not a valid sequence of control reference data objects
+    InvalidData,
+}
+
+impl From<MseError> for Status {
+    fn from(value: MseError) -> Self {
+        match value {
+            MseError::WrongInstruction => Status::InstructionNotSupportedOrInvalid,
+            MseError::UnsupportedOperation(_) | MseError::UnsupportedTemplate(_) => {
+                Status::IncorrectP1OrP2Parameter
+            }
+            MseError::InvalidData => Status::IncorrectDataParameter,
+        }
+    }
+}
+
+/// Parsed MANAGE SECURITY ENVIRONMENT command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mse<'a> {
+    Set(Set<'a>),
+    /// Identifier of the security environment to restore
+    Restore(u8),
+}
+
+impl<'a> TryFrom<&CommandView<'a>> for Mse<'a> {
+    type Error = MseError;
+
+    fn try_from(command: &CommandView<'a>) -> Result<Self, Self::Error> {
+        if command.instruction() != Instruction::ManageSecurityEnvironment {
+            return Err(MseError::WrongInstruction);
+        }
+        match command.p1 {
+            RESTORE if command.data().is_empty() => Ok(Self::Restore(command.p2)),
+            RESTORE => Err(MseError::InvalidData),
+            p1 if p1 & 0x0F == SET => {
+                Set::parse(Usage(p1 & 0xF0), command.p2.try_into()?, command.data()).map(Self::Set)
+            }
+            p1 => Err(MseError::UnsupportedOperation(p1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::KeyReference;
+    use hex_literal::hex;
+
+    fn parse(apdu: &[u8]) -> Result<Mse<'_>, MseError> {
+        Mse::try_from(&CommandView::try_from(apdu).unwrap())
+    }
+
+    #[test]
+    fn set() {
+        let class = 0.try_into().unwrap();
+        let key = KeyReference::specific(1);
+        let set = Set::new(
+            Usage::COMPUTATION.union(Usage::VERIFICATION),
+            Template::Authentication,
+        )
+        .algorithm(&hex!("FF20"))
+        .key_reference(key.as_slice());
+        let command = set.command(class).serialize_to_vec();
+        assert_eq!(command, hex!("0022C1A4 07 8002FF20 830181"));
+        assert_eq!(parse(&command), Ok(Mse::Set(set)));
+
+        let set = Set::new(Usage::SECURE_MESSAGING_COMMAND, Template::Confidentiality);
+        let command = set.command(class).serialize_to_vec();
+        assert_eq!(command, hex!("002211B8"));
+        assert_eq!(parse(&command), Ok(Mse::Set(set)));
+
+        // Unknown data objects are skipped
+        let Ok(Mse::Set(set)) = parse(&hex!("002241A6 08 910100 8001 02 8300")) else {
+            panic!()
+        };
+        assert_eq!(set.usage(), Usage::COMPUTATION);
+        assert!(!set.usage().contains(Usage::VERIFICATION));
+        assert_eq!(set.template(), Template::KeyAgreement);
+        assert_eq!(set.algorithm_reference(), Some(&hex!("02")[..]));
+        assert_eq!(set.key(), Some(&[][..]));
+        assert_eq!(set.private_key(), None);
+    }
+
+    #[test]
+    fn restore() {
+        let command = super::restore(0.try_into().unwrap(), 1).serialize_to_vec();
+        assert_eq!(command, hex!("0022F301"));
+        assert_eq!(parse(&command), Ok(Mse::Restore(1)));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse(&hex!("00CA0000")), Err(MseError::WrongInstruction));
+        assert_eq!(
+            parse(&hex!("0022F201")),
+            Err(MseError::UnsupportedOperation(0xF2))
+        );
+        assert_eq!(
+            parse(&hex!("002241AA")),
+            Err(MseError::UnsupportedTemplate(0xAA))
+        );
+        assert_eq!(
+            parse(&hex!("002241B6 06 800154 800154")),
+            Err(MseError::InvalidData)
+        );
+        assert_eq!(parse(&hex!("002241B6 02 8002")), Err(MseError::InvalidData));
+        assert_eq!(parse(&hex!("0022F301 01 00")), Err(MseError::InvalidData));
+        assert_eq!(
+            Status::from(MseError::UnsupportedTemplate(0xAA)),
+            Status::IncorrectP1OrP2Parameter
+        );
+    }
+}
@@ -0,0 +1,911 @@
+//! VERIFY, CHANGE REFERENCE DATA and RESET RETRY COUNTER (ISO 7816-4 11.5.6, 11.5.7 and 11.5.10)
+//!
+//! [`PinCommand`] builds and parses the three commands, P2 being the [`KeyReference`] of the
+//! reference data, whose [`Qualifier`] tells whether it is global to the card or specific to the
+//! current DF. [`PinFormat`] formats a PIN for the data field into a [`PinBlock`], which is
+//! zeroized when dropped. [`PinPolicy`] adds length and character rules, and [`PinVerifier`]
+//! checks presented PINs on the card side.
+//!
+//! ```
+//! use iso7816::command::pin::{PinCommand, PinFormat};
+//! use iso7816::command::KeyReference;
+//! let mut command = heapless::Vec::<u8, 16>::new();
+//! let pin = PinFormat::Padded(8).format(b"123456").unwrap();
+//! PinCommand::verify(KeyReference::specific(0), pin.as_bytes())
+//!     .command(0.try_into().unwrap())
+//!     .serialize_into(&mut command)
+//!     .unwrap();
+//! assert_eq!(
+//!     &*command,
+//!     &[0x00, 0x20, 0x00, 0x80, 0x08, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0xFF, 0xFF]
+//! );
+//! ```
+
+use core::fmt;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use super::class::Class;
+use super::key_reference::Qualifier;
+use super::{CommandBuilder, CommandView, Instruction, KeyReference};
+use crate::Status;
+
+/// Longest [`PinBlock`]
+pub const MAX_PIN_BLOCK_LEN: usize = 64;
+
+const RESET_VERIFICATION: u8 = 0xFF;
+
+/// VERIFY, CHANGE REFERENCE DATA or RESET RETRY COUNTER command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinCommand<'a> {
+    /// VERIFY (P1 `00`), without data to query the verification status
+    Verify {
+        reference: KeyReference,
+        pin: &'a [u8],
+    },
+    /// VERIFY with P1 `FF`, resetting the verification status
+    ResetVerification { reference: KeyReference },
+    /// CHANGE REFERENCE DATA, with the current reference data (P1 `00`) or without (P1 `01`)
+    ChangeReferenceData {
+        reference: KeyReference,
+        current: Option<&'a [u8]>,
+        new: &'a [u8],
+    },
+    /// RESET RETRY COUNTER, P1 indicating which of the resetting code and new reference data are
+    /// present
+    ResetRetryCounter {
+        reference: KeyReference,
+        resetting_code: Option<&'a [u8]>,
+        new: Option<&'a [u8]>,
+    },
+}
+
+impl<'a> PinCommand<'a> {
+    pub fn verify(reference: KeyReference, pin: &'a [u8]) -> Self {
+        Self::Verify { reference, pin }
+    }
+
+    /// VERIFY without data, the card answering with `9000` or the remaining tries in `63CX`
+    pub fn verification_status(reference: KeyReference) -> Self {
+        Self::Verify {
+            reference,
+            pin: &[],
+        }
+    }
+
+    pub fn reset_verification(reference: KeyReference) -> Self {
+        Self::ResetVerification { reference }
+    }
+
+    pub fn change_reference_data(
+        reference: KeyReference,
+        current: Option<&'a [u8]>,
+        new: &'a [u8],
+    ) -> Self {
+        Self::ChangeReferenceData {
+            reference,
+            current,
+            new,
+        }
+    }
+
+    pub fn reset_retry_counter(
+        reference: KeyReference,
+        resetting_code: Option<&'a [u8]>,
+        new: Option<&'a [u8]>,
+    ) -> Self {
+        Self::ResetRetryCounter {
+            reference,
+            resetting_code,
+            new,
+        }
+    }
+
+    pub fn reference(&self) -> KeyReference {
+        match *self {
+            Self::Verify { reference, .. }
+            | Self::ResetVerification { reference }
+            | Self::ChangeReferenceData { reference, .. }
+            | Self::ResetRetryCounter { reference, .. } => reference,
+        }
+    }
+
+    /// Whether the reference data is global or specific to the current DF
+    pub fn qualifier(&self) -> Qualifier {
+        self.reference().qualifier()
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        match self {
+            Self::Verify { .. } | Self::ResetVerification { .. } => Instruction::Verify,
+            Self::ChangeReferenceData { .. } => Instruction::ChangeReferenceData,
+            Self::ResetRetryCounter { .. } => Instruction::ResetRetryCounter,
+        }
+    }
+
+    /// P1 and the two parts of the data field
+    fn p1_data(&self) -> (u8, &'a [u8], &'a [u8]) {
+        match *self {
+            Self::Verify { pin, .. } => (0x00, pin, &[]),
+            Self::ResetVerification { .. } => (RESET_VERIFICATION, &[], &[]),
+            Self::ChangeReferenceData {
+                current: Some(current),
+                new,
+                ..
+            } => (0x00, current, new),
+            Self::ChangeReferenceData {
+                current: None, new, ..
+            } => (0x01, new, &[]),
+            Self::ResetRetryCounter {
+                resetting_code,
+                new,
+                ..
+            } => match (resetting_code, new) {
+                (Some(code), Some(new)) => (0x00, code, new),
+                (Some(code), None) => (0x01, code, &[]),
+                (None, Some(new)) => (0x02, new, &[]),
+                (None, None) => (0x03, &[], &[]),
+            },
+        }
+    }
+
+    pub fn command(self, class: Class) -> CommandBuilder<(&'a [u8], &'a [u8])> {
+        let (p1, first, second) = self.p1_data();
+        CommandBuilder::new(
+            class,
+            self.instruction(),
+            p1,
+            self.reference().p2(),
+            (first, second),
+            0,
+        )
+    }
+
+    /// Parse a command from the card side
+    ///
+    /// When the data field holds two values (current and new reference data, or resetting code
+    /// and new reference data), `first_len` is the length of the first one, which the card knows
+    /// from the format of its reference data.
+    ///
+    /// P2 must have bits 7 and 6 set to zero, see [`KeyReference::is_standard`].
+    pub fn parse(command: &CommandView<'a>, first_len: usize) -> Result<Self, PinError> {
+        if !matches!(
+            command.instruction(),
+            Instruction::Verify | Instruction::ChangeReferenceData | Instruction::ResetRetryCounter
+        ) {
+            return Err(PinError::WrongInstruction);
+        }
+        let reference = KeyReference::from_p2(command.p2);
+        if !reference.is_standard() {
+            return Err(PinError::InvalidP2);
+        }
+        let data = command.data();
+        let split = || match data.split_at_checked(first_len) {
+            Some((first, second)) if !first.is_empty() && !second.is_empty() => Ok((first, second)),
+            _ => Err(PinError::InvalidData),
+        };
+        let not_empty = || {
+            if data.is_empty() {
+                Err(PinError::InvalidData)
+            } else {
+                Ok(data)
+            }
+        };
+        match (command.instruction(), command.p1) {
+            (Instruction::Verify, 0x00) => Ok(Self::verify(reference, data)),
+            (Instruction::Verify, RESET_VERIFICATION) if data.is_empty() => {
+                Ok(Self::reset_verification(reference))
+            }
+            (Instruction::Verify, RESET_VERIFICATION) => Err(PinError::InvalidData),
+            (Instruction::ChangeReferenceData, 0x00) => {
+                let (current, new) = split()?;
+                Ok(Self::change_reference_data(reference, Some(current), new))
+            }
+            (Instruction::ChangeReferenceData, 0x01) => {
+                Ok(Self::change_reference_data(reference, None, not_empty()?))
+            }
+            (Instruction::ResetRetryCounter, 0x00) => {
+                let (code, new) = split()?;
+                Ok(Self::reset_retry_counter(reference, Some(code), Some(new)))
+            }
+            (Instruction::ResetRetryCounter, 0x01) => Ok(Self::reset_retry_counter(
+                reference,
+                Some(not_empty()?),
+                None,
+            )),
+            (Instruction::ResetRetryCounter, 0x02) => Ok(Self::reset_retry_counter(
+                reference,
+                None,
+                Some(not_empty()?),
+            )),
+            (Instruction::ResetRetryCounter, 0x03) if data.is_empty() => {
+                Ok(Self::reset_retry_counter(reference, None, None))
+            }
+            (Instruction::ResetRetryCounter, 0x03) => Err(PinError::InvalidData),
+            _ => Err(PinError::InvalidP1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinError {
+    /// The command is not VERIFY, CHANGE REFERENCE DATA or RESET RETRY COUNTER
+    WrongInstruction,
+    /// P1 is not defined for the instruction
+    InvalidP1,
+    /// P2 uses the reserved bits of the reference data qualifier
+    InvalidP2,
+    /// The data field does not match P1
+    InvalidData,
+}
+
+impl From<PinError> for Status {
+    fn from(value: PinError) -> Self {
+        match value {
+            PinError::WrongInstruction => Status::InstructionNotSupportedOrInvalid,
+            PinError::InvalidP1 | PinError::InvalidP2 => Status::IncorrectP1OrP2Parameter,
+            PinError::InvalidData => Status::IncorrectDataParameter,
+        }
+    }
+}
+
+/// Overwrite `bytes` with zeros, in a way that the compiler does not optimize away
+pub(crate) fn zeroize(bytes: &mut [u8]) {
+    for byte in bytes {
+        // SAFETY: `byte` is a valid, aligned and exclusive reference
+        unsafe { core::ptr::write_volatile(byte, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Formatted PIN, zeroized when dropped
+#[derive(Clone)]
+pub struct PinBlock {
+    bytes: [u8; MAX_PIN_BLOCK_LEN],
+    len: usize,
+}
+
+impl PinBlock {
+    fn new() -> Self {
+        Self {
+            bytes: [0; MAX_PIN_BLOCK_LEN],
+            len: 0,
+        }
+    }
+
+    fn from_slice(bytes: &[u8]) -> Result<Self, PinFormatError> {
+        let mut block = Self::new();
+        block
+            .bytes
+            .get_mut(..bytes.len())
+            .ok_or(PinFormatError::InvalidLength)?
+            .copy_from_slice(bytes);
+        block.len = bytes.len();
+        Ok(block)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl PartialEq for PinBlock {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for PinBlock {}
+
+/// The PIN is not printed
+impl fmt::Debug for PinBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PinBlock").field("len", &self.len).finish()
+    }
+}
+
+impl Drop for PinBlock {
+    fn drop(&mut self) {
+        zeroize(&mut self.bytes);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinFormatError {
+    /// The PIN is empty or too long for the format
+    InvalidLength,
+    /// The format only accepts decimal digits
+    InvalidCharacter,
+}
+
+/// PIN padded with `padding` to `len` bytes
+fn pad(pin: &[u8], len: usize, padding: u8) -> Result<PinBlock, PinFormatError> {
+    let mut block = PinBlock::from_slice(pin)?;
+    block
+        .bytes
+        .get_mut(pin.len()..len)
+        .ok_or(PinFormatError::InvalidLength)?
+        .fill(padding);
+    block.len = len;
+    Ok(block)
+}
+
+/// PIN without the trailing `padding` bytes, which must not be empty
+fn unpad(block: &[u8], padding: u8) -> Result<PinBlock, PinFormatError> {
+    let len = block
+        .iter()
+        .rposition(|&b| b != padding)
+        .map_or(0, |i| i + 1);
+    if len == 0 {
+        return Err(PinFormatError::InvalidLength);
+    }
+    PinBlock::from_slice(&block[..len])
+}
+
+/// Format of the PIN in the data field
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinFormat {
+    /// PIN bytes as given, usually ASCII
+    Ascii,
+    /// PIN bytes padded with `FF` to this length
+    Padded(usize),
+    /// ISO 9564 format 2 block: control nibble `2`, length nibble, BCD digits padded with `F` to
+    /// 8 bytes. PINs have 4 to 12 digits.
+    Iso2,
+}
+
+impl PinFormat {
+    /// Length of the formatted PIN, if it does not depend on the PIN
+    pub fn fixed_len(self) -> Option<usize> {
+        match self {
+            Self::Ascii => None,
+            Self::Padded(len) => Some(len),
+            Self::Iso2 => Some(8),
+        }
+    }
+
+    pub fn format(self, pin: &[u8]) -> Result<PinBlock, PinFormatError> {
+        match self {
+            Self::Ascii if !pin.is_empty() => PinBlock::from_slice(pin),
+            Self::Padded(len) if !pin.is_empty() => pad(pin, len, 0xFF),
+            Self::Iso2 if (4..=12).contains(&pin.len()) => {
+                if !pin.iter().all(u8::is_ascii_digit) {
+                    return Err(PinFormatError::InvalidCharacter);
+                }
+                let mut block = PinBlock::new();
+                block.bytes[..8].fill(0xFF);
+                block.bytes[0] = 0x20 | pin.len() as u8;
+                for (i, digit) in pin.iter().enumerate() {
+                    let byte = &mut block.bytes[1 + i / 2];
+                    if i % 2 == 0 {
+                        *byte = (digit - b'0') << 4 | 0x0F;
+                    } else {
+                        *byte = (*byte & 0xF0) | (digit - b'0');
+                    }
+                }
+                block.len = 8;
+                Ok(block)
+            }
+            _ => Err(PinFormatError::InvalidLength),
+        }
+    }
+
+    /// Recover the PIN from a formatted block, on the card side
+    pub fn decode(self, block: &[u8]) -> Result<PinBlock, PinFormatError> {
+        match self {
+            Self::Ascii if !block.is_empty() => PinBlock::from_slice(block),
+            Self::Padded(len) if block.len() == len => unpad(block, 0xFF),
+            Self::Iso2 if block.len() == 8 && block[0] >> 4 == 2 => {
+                let len = usize::from(block[0] & 0x0F);
+                if !(4..=12).contains(&len) {
+                    return Err(PinFormatError::InvalidLength);
+                }
+                let mut pin = PinBlock::new();
+                let nibbles = block[1..].iter().flat_map(|b| [b >> 4, b & 0x0F]);
+                for (i, nibble) in nibbles.enumerate() {
+                    match nibble {
+                        0..=9 if i < len => pin.bytes[i] = b'0' + nibble,
+                        0x0F if i >= len => {}
+                        _ => return Err(PinFormatError::InvalidCharacter),
+                    }
+                }
+                pin.len = len;
+                Ok(pin)
+            }
+            _ => Err(PinFormatError::InvalidLength),
+        }
+    }
+}
+
+/// Characters allowed in a PIN
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Charset {
+    /// Any byte
+    #[default]
+    Any,
+    /// ASCII decimal digits
+    Digits,
+    /// ASCII letters and digits
+    Alphanumeric,
+    /// Printable ASCII characters, space included
+    Printable,
+}
+
+impl Charset {
+    pub fn contains(self, byte: u8) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Digits => byte.is_ascii_digit(),
+            Self::Alphanumeric => byte.is_ascii_alphanumeric(),
+            Self::Printable => byte == b' ' || byte.is_ascii_graphic(),
+        }
+    }
+}
+
+/// Rules for the PINs of a reference
+///
+/// The host formats PINs with [`format`](Self::format), the card recovers them with
+/// [`decode`](Self::decode), and [`PinVerifier`] checks them against the reference PIN.
+///
+/// ```
+/// use iso7816::command::pin::{Charset, PinPolicy};
+/// // PIV application PIN
+/// let policy = PinPolicy::new(6, 8).padding(0xFF).charset(Charset::Digits);
+/// let block = policy.format(b"123456").unwrap();
+/// assert_eq!(block.as_bytes(), b"123456\xFF\xFF");
+/// assert_eq!(policy.decode(block.as_bytes()).unwrap().as_bytes(), b"123456");
+/// assert!(policy.format(b"12345").is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PinPolicy {
+    pub min_len: usize,
+    pub max_len: usize,
+    /// Byte padding PINs to `max_len` in the data field, if any
+    pub padding: Option<u8>,
+    pub charset: Charset,
+}
+
+impl PinPolicy {
+    /// PINs of `min_len` to `max_len` arbitrary bytes, without padding
+    pub const fn new(min_len: usize, max_len: usize) -> Self {
+        Self {
+            min_len,
+            max_len,
+            padding: None,
+            charset: Charset::Any,
+        }
+    }
+
+    pub const fn padding(mut self, padding: u8) -> Self {
+        self.padding = Some(padding);
+        self
+    }
+
+    pub const fn charset(mut self, charset: Charset) -> Self {
+        self.charset = charset;
+        self
+    }
+
+    /// Length of the formatted PINs, if they are padded
+    pub fn fixed_len(&self) -> Option<usize> {
+        self.padding.map(|_| self.max_len)
+    }
+
+    /// Check the length and characters of a PIN, before formatting
+    pub fn check(&self, pin: &[u8]) -> Result<(), PinFormatError> {
+        if pin.len() < self.min_len.max(1) || pin.len() > self.max_len {
+            return Err(PinFormatError::InvalidLength);
+        }
+        if !pin.iter().all(|&b| self.charset.contains(b)) {
+            return Err(PinFormatError::InvalidCharacter);
+        }
+        Ok(())
+    }
+
+    /// Format a PIN for the data field, on the host side
+    pub fn format(&self, pin: &[u8]) -> Result<PinBlock, PinFormatError> {
+        self.check(pin)?;
+        match self.padding {
+            Some(padding) => pad(pin, self.max_len, padding),
+            None => PinBlock::from_slice(pin),
+        }
+    }
+
+    /// Recover and check a PIN from the data field, on the card side
+    pub fn decode(&self, data: &[u8]) -> Result<PinBlock, PinFormatError> {
+        let pin = match self.padding {
+            Some(padding) if data.len() == self.max_len => unpad(data, padding)?,
+            Some(_) => return Err(PinFormatError::InvalidLength),
+            None => PinBlock::from_slice(data)?,
+        };
+        self.check(pin.as_bytes())?;
+        Ok(pin)
+    }
+}
+
+/// Card-side PIN verification with a retry counter
+///
+/// Presented PINs are decoded with the [`PinPolicy`]: invalid lengths are rejected with
+/// [`Status::WrongLength`] (`6700`) and do not count as failed attempts. A wrong PIN decrements
+/// the retry counter and is rejected with [`Status::RemainingRetries`] (`63CX`). Once the counter
+/// reaches zero, the PIN is blocked ([`Status::OperationBlocked`], `6983`) until
+/// [`unblock`](Self::unblock) is called.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PinVerifier {
+    policy: PinPolicy,
+    max_retries: u8,
+    remaining: u8,
+    verified: bool,
+}
+
+impl PinVerifier {
+    /// Verifier allowing `max_retries` consecutive failures, at most 15
+    pub fn new(policy: PinPolicy, max_retries: u8) -> Self {
+        let max_retries = max_retries.min(0x0F);
+        Self {
+            policy,
+            max_retries,
+            remaining: max_retries,
+            verified: false,
+        }
+    }
+
+    /// Verifier restoring a retry counter from persistent storage
+    pub fn with_remaining(mut self, remaining: u8) -> Self {
+        self.remaining = remaining.min(self.max_retries);
+        self
+    }
+
+    pub fn policy(&self) -> &PinPolicy {
+        &self.policy
+    }
+
+    pub fn remaining(&self) -> u8 {
+        self.remaining
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.remaining == 0
+    }
+
+    pub fn is_verified(&self) -> bool {
+        self.verified
+    }
+
+    /// Reset the verification status, for example on reset or application deselection
+    pub fn reset_verification(&mut self) {
+        self.verified = false;
+    }
+
+    /// Reset the retry counter, for example after RESET RETRY COUNTER
+    pub fn unblock(&mut self) {
+        self.remaining = self.max_retries;
+    }
+
+    /// Check the `presented` data field against the `reference` PIN, stored without padding
+    ///
+    /// An empty data field queries the verification status.
+    pub fn verify(&mut self, presented: &[u8], reference: &[u8]) -> crate::Result {
+        if presented.is_empty() {
+            return match (self.verified, self.remaining) {
+                (true, _) => Ok(()),
+                (false, 0) => Err(Status::OperationBlocked),
+                (false, remaining) => Err(Status::RemainingRetries(remaining)),
+            };
+        }
+        if self.is_blocked() {
+            return Err(Status::OperationBlocked);
+        }
+        let pin = self.policy.decode(presented).map_err(|err| match err {
+            PinFormatError::InvalidLength => Status::WrongLength,
+            PinFormatError::InvalidCharacter => Status::IncorrectDataParameter,
+        })?;
+        // Compare all the bytes, only the length of the reference PIN may leak through timing
+        let pin = pin.as_bytes();
+        let equal = pin.len() == reference.len()
+            && pin
+                .iter()
+                .zip(reference)
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0;
+        if equal {
+            self.remaining = self.max_retries;
+            self.verified = true;
+            Ok(())
+        } else {
+            self.remaining -= 1;
+            self.verified = false;
+            Err(Status::RemainingRetries(self.remaining))
+        }
+    }
+
+    /// Process a VERIFY command against the `reference` PIN
+    pub fn call(&mut self, command: &CommandView<'_>, reference: &[u8]) -> crate::Result {
+        if command.instruction() != Instruction::Verify {
+            return Err(Status::InstructionNotSupportedOrInvalid);
+        }
+        match PinCommand::parse(command, 0)? {
+            PinCommand::Verify { pin, .. } => self.verify(pin, reference),
+            _ => {
+                self.reset_verification();
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    const PIN: KeyReference = KeyReference::specific(1);
+
+    fn roundtrip(command: PinCommand<'_>, first_len: usize, apdu: &[u8]) {
+        let serialized = command.command(0.try_into().unwrap()).serialize_to_vec();
+        assert_eq!(serialized, apdu);
+        let view = CommandView::try_from(apdu).unwrap();
+        assert_eq!(PinCommand::parse(&view, first_len), Ok(command));
+    }
+
+    #[test]
+    fn commands() {
+        roundtrip(
+            PinCommand::verify(PIN, b"123456"),
+            0,
+            &hex!("00200081 06 313233343536"),
+        );
+        roundtrip(PinCommand::verification_status(PIN), 0, &hex!("00200081"));
+        roundtrip(PinCommand::reset_verification(PIN), 0, &hex!("0020FF81"));
+        roundtrip(
+            PinCommand::change_reference_data(PIN, Some(b"1234"), b"56789"),
+            4,
+            &hex!("00240081 09 31323334 3536373839"),
+        );
+        roundtrip(
+            PinCommand::change_reference_data(PIN, None, b"5678"),
+            4,
+            &hex!("00240181 04 35363738"),
+        );
+        roundtrip(
+            PinCommand::reset_retry_counter(PIN, Some(b"PUK"), Some(b"1234")),
+            3,
+            &hex!("002C0081 07 50554B 31323334"),
+        );
+        roundtrip(
+            PinCommand::reset_retry_counter(PIN, Some(b"PUK"), None),
+            3,
+            &hex!("002C0181 03 50554B"),
+        );
+        roundtrip(
+            PinCommand::reset_retry_counter(PIN, None, Some(b"1234")),
+            3,
+            &hex!("002C0281 04 31323334"),
+        );
+        roundtrip(
+            PinCommand::reset_retry_counter(PIN, None, None),
+            3,
+            &hex!("002C0381"),
+        );
+    }
+
+    #[test]
+    fn errors() {
+        fn parse(apdu: &[u8], first_len: usize) -> Result<PinCommand<'_>, PinError> {
+            PinCommand::parse(&CommandView::try_from(apdu).unwrap(), first_len)
+        }
+        assert_eq!(parse(&hex!("00B00000"), 0), Err(PinError::WrongInstruction));
+        assert_eq!(parse(&hex!("00200181"), 0), Err(PinError::InvalidP1));
+        assert_eq!(
+            parse(&hex!("0020FF81 01 31"), 0),
+            Err(PinError::InvalidData)
+        );
+        assert_eq!(
+            parse(&hex!("00240081 04 31323334"), 4),
+            Err(PinError::InvalidData)
+        );
+        assert_eq!(
+            parse(&hex!("00240081 04 31323334"), 0),
+            Err(PinError::InvalidData)
+        );
+        assert_eq!(parse(&hex!("00240181"), 0), Err(PinError::InvalidData));
+        assert_eq!(
+            parse(&hex!("002C0381 01 31"), 0),
+            Err(PinError::InvalidData)
+        );
+        assert_eq!(parse(&hex!("002C0481"), 0), Err(PinError::InvalidP1));
+        assert_eq!(parse(&hex!("002000C1"), 0), Err(PinError::InvalidP2));
+        assert_eq!(parse(&hex!("00CA00C1"), 0), Err(PinError::WrongInstruction));
+    }
+
+    #[test]
+    fn qualifier() {
+        // Route each command to the global PIN or to the PIN of the current DF
+        let route = |apdu: &[u8]| {
+            let view = CommandView::try_from(apdu).unwrap();
+            let command = PinCommand::parse(&view, 0).unwrap();
+            (command.qualifier(), command.reference().number())
+        };
+        assert_eq!(route(&hex!("00200001")), (Qualifier::Global, 1));
+        assert_eq!(route(&hex!("00200081")), (Qualifier::Specific, 1));
+        assert_eq!(route(&hex!("002C0382")), (Qualifier::Specific, 2));
+        assert_eq!(
+            PinCommand::verify(KeyReference::global(3), b"1234")
+                .command(0.try_into().unwrap())
+                .serialize_to_vec(),
+            hex!("00200003 04 31323334")
+        );
+    }
+
+    #[test]
+    fn formats() {
+        let block = PinFormat::Ascii.format(b"1234").unwrap();
+        assert_eq!(block.as_bytes(), b"1234");
+        assert_eq!(PinFormat::Ascii.decode(b"1234"), Ok(block));
+        assert_eq!(
+            PinFormat::Ascii.format(b""),
+            Err(PinFormatError::InvalidLength)
+        );
+
+        let padded = PinFormat::Padded(8);
+        let block = padded.format(b"1234").unwrap();
+        assert_eq!(block.as_bytes(), hex!("31323334FFFFFFFF"));
+        assert_eq!(padded.decode(block.as_bytes()).unwrap().as_bytes(), b"1234");
+        assert_eq!(
+            padded.format(b"123456789"),
+            Err(PinFormatError::InvalidLength)
+        );
+        assert_eq!(
+            padded.decode(&hex!("FFFFFFFFFFFFFFFF")),
+            Err(PinFormatError::InvalidLength)
+        );
+        assert_eq!(
+            padded.decode(&hex!("31323334FFFFFF")),
+            Err(PinFormatError::InvalidLength)
+        );
+        assert_eq!(
+            PinFormat::Padded(MAX_PIN_BLOCK_LEN + 1).format(b"1234"),
+            Err(PinFormatError::InvalidLength)
+        );
+
+        let block = PinFormat::Iso2.format(b"12345").unwrap();
+        assert_eq!(block.as_bytes(), hex!("25 12345FFFFFFFFF"));
+        assert_eq!(
+            PinFormat::Iso2.decode(block.as_bytes()).unwrap().as_bytes(),
+            b"12345"
+        );
+        let block = PinFormat::Iso2.format(b"123456789012").unwrap();
+        assert_eq!(block.as_bytes(), hex!("2C 123456789012FF"));
+        assert_eq!(
+            PinFormat::Iso2.decode(block.as_bytes()).unwrap().as_bytes(),
+            b"123456789012"
+        );
+        assert_eq!(
+            PinFormat::Iso2.format(b"123"),
+            Err(PinFormatError::InvalidLength)
+        );
+        assert_eq!(
+            PinFormat::Iso2.format(b"12a4"),
+            Err(PinFormatError::InvalidCharacter)
+        );
+        assert_eq!(
+            PinFormat::Iso2.decode(&hex!("24 1234AFFFFFFFFF")),
+            Err(PinFormatError::InvalidCharacter)
+        );
+        assert_eq!(
+            PinFormat::Iso2.decode(&hex!("24 123FFFFFFFFFFF")),
+            Err(PinFormatError::InvalidCharacter)
+        );
+        assert_eq!(format!("{block:?}"), "PinBlock { len: 8 }");
+    }
+
+    #[test]
+    fn zeroize_block() {
+        let mut bytes = *b"1234";
+        zeroize(&mut bytes);
+        assert_eq!(bytes, [0; 4]);
+    }
+
+    #[test]
+    fn policy() {
+        let policy = PinPolicy::new(4, 8).charset(Charset::Alphanumeric);
+        assert_eq!(policy.fixed_len(), None);
+        assert_eq!(policy.format(b"abc1").unwrap().as_bytes(), b"abc1");
+        assert_eq!(policy.format(b"abc"), Err(PinFormatError::InvalidLength));
+        assert_eq!(
+            policy.format(b"abc123456"),
+            Err(PinFormatError::InvalidLength)
+        );
+        assert_eq!(
+            policy.format(b"abc-"),
+            Err(PinFormatError::InvalidCharacter)
+        );
+        assert_eq!(
+            PinPolicy::new(0, 8).format(b""),
+            Err(PinFormatError::InvalidLength)
+        );
+
+        let policy = PinPolicy::new(6, 8).padding(0xFF).charset(Charset::Digits);
+        assert_eq!(policy.fixed_len(), Some(8));
+        assert_eq!(
+            policy
+                .decode(&hex!("31323334 3536FFFF"))
+                .unwrap()
+                .as_bytes(),
+            b"123456"
+        );
+        assert_eq!(
+            policy.decode(&hex!("31323334 35FFFFFF")),
+            Err(PinFormatError::InvalidLength)
+        );
+        assert_eq!(
+            policy.decode(&hex!("31323334 3536FF")),
+            Err(PinFormatError::InvalidLength)
+        );
+        assert_eq!(
+            policy.decode(&hex!("31323334 3536FF41")),
+            Err(PinFormatError::InvalidCharacter)
+        );
+    }
+
+    #[test]
+    fn verifier() {
+        let policy = PinPolicy::new(6, 8).padding(0xFF);
+        let mut verifier = PinVerifier::new(policy, 3);
+        let reference = b"123456";
+        let verify = |pin: &[u8]| {
+            let pin = policy.format(pin).unwrap();
+            PinCommand::verify(PIN, pin.as_bytes())
+                .command(0.try_into().unwrap())
+                .serialize_to_vec()
+        };
+        let call = |verifier: &mut PinVerifier, apdu: &[u8]| {
+            verifier.call(&CommandView::try_from(apdu).unwrap(), reference)
+        };
+
+        assert_eq!(
+            call(&mut verifier, &hex!("00200081")),
+            Err(Status::RemainingRetries(3))
+        );
+        assert_eq!(
+            call(&mut verifier, &verify(b"654321")),
+            Err(Status::RemainingRetries(2))
+        );
+        assert_eq!(
+            call(&mut verifier, &hex!("00200081 04 31323334")),
+            Err(Status::WrongLength)
+        );
+        assert_eq!(verifier.remaining(), 2);
+        assert_eq!(call(&mut verifier, &verify(b"123456")), Ok(()));
+        assert!(verifier.is_verified());
+        assert_eq!(verifier.remaining(), 3);
+        assert_eq!(call(&mut verifier, &hex!("00200081")), Ok(()));
+        assert_eq!(call(&mut verifier, &hex!("0020FF81")), Ok(()));
+        assert!(!verifier.is_verified());
+
+        for remaining in (0..3).rev() {
+            assert_eq!(
+                call(&mut verifier, &verify(b"1234567")),
+                Err(Status::RemainingRetries(remaining))
+            );
+        }
+        assert!(verifier.is_blocked());
+        assert_eq!(
+            call(&mut verifier, &verify(b"123456")),
+            Err(Status::OperationBlocked)
+        );
+        assert_eq!(
+            call(&mut verifier, &hex!("00200081")),
+            Err(Status::OperationBlocked)
+        );
+        verifier.unblock();
+        assert_eq!(call(&mut verifier, &verify(b"123456")), Ok(()));
+        assert_eq!(
+            call(&mut verifier, &hex!("00240081")),
+            Err(Status::InstructionNotSupportedOrInvalid)
+        );
+        assert_eq!(
+            PinVerifier::new(policy, 20).with_remaining(1).remaining(),
+            1
+        );
+    }
+}
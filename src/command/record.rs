@@ -0,0 +1,342 @@
+//! READ RECORD(S), UPDATE RECORD, APPEND RECORD and SEARCH RECORD (ISO 7816-4 11.4)
+//!
+//! P1 is a record number or a record identifier. P2 encodes the EF in bits 8 to 4 (a short EF
+//! identifier, or `00000` for the current EF) and the [`Records`] to process in bits 3 to 1:
+//!
+//! | Bits 3-1 | Records                                     |
+//! |----------|---------------------------------------------|
+//! | `000`    | First record with the identifier in P1      |
+//! | `001`    | Last record with the identifier in P1       |
+//! | `010`    | Next record with the identifier in P1       |
+//! | `011`    | Previous record with the identifier in P1   |
+//! | `100`    | Record number P1, `00` being the current record |
+//! | `101`    | All records from number P1 to the last      |
+//! | `110`    | All records from the last to number P1      |
+//!
+//! ```
+//! use iso7816::command::record::{Record, Records};
+//! use iso7816::file::ShortEf;
+//! let mut command = heapless::Vec::<u8, 16>::new();
+//! Record::read(Records::Number(1), 256)
+//!     .ef(ShortEf::new(1).unwrap())
+//!     .command(0.try_into().unwrap())
+//!     .serialize_into(&mut command)
+//!     .unwrap();
+//! assert_eq!(&*command, &[0x00, 0xB2, 0x01, 0x0C, 0x00]);
+//! ```
+
+use super::class::Class;
+use super::select::Occurrence;
+use super::{CommandBuilder, CommandView, ExpectedLen, Instruction};
+use crate::file::ShortEf;
+use crate::Status;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Read,
+    Update,
+    Append,
+    Search,
+}
+
+impl Operation {
+    pub fn instruction(self) -> Instruction {
+        match self {
+            Self::Read => Instruction::ReadRecord,
+            Self::Update => Instruction::UpdateRecord,
+            Self::Append => Instruction::AppendRecord,
+            Self::Search => Instruction::SearchRecord,
+        }
+    }
+
+    fn from_instruction(instruction: Instruction) -> Option<Self> {
+        match instruction {
+            Instruction::ReadRecord => Some(Self::Read),
+            Instruction::UpdateRecord => Some(Self::Update),
+            Instruction::AppendRecord => Some(Self::Append),
+            Instruction::SearchRecord => Some(Self::Search),
+            _ => None,
+        }
+    }
+}
+
+/// Records referenced by P1 and bits 3 to 1 of P2
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Records {
+    /// Record number, `0` being the current record
+    Number(u8),
+    /// All records from this number to the last
+    FromNumber(u8),
+    /// All records from the last to this number
+    FromLastTo(u8),
+    /// Occurrence of a record identifier
+    Id(u8, Occurrence),
+}
+
+impl Records {
+    fn p1(self) -> u8 {
+        match self {
+            Self::Number(p1) | Self::FromNumber(p1) | Self::FromLastTo(p1) | Self::Id(p1, _) => p1,
+        }
+    }
+
+    fn p2(self) -> u8 {
+        match self {
+            Self::Id(_, Occurrence::First) => 0b000,
+            Self::Id(_, Occurrence::Last) => 0b001,
+            Self::Id(_, Occurrence::Next) => 0b010,
+            Self::Id(_, Occurrence::Previous) => 0b011,
+            Self::Number(_) => 0b100,
+            Self::FromNumber(_) => 0b101,
+            Self::FromLastTo(_) => 0b110,
+        }
+    }
+
+    fn from_p1p2(p1: u8, p2: u8) -> Option<Self> {
+        Some(match p2 & 0b111 {
+            0b000 => Self::Id(p1, Occurrence::First),
+            0b001 => Self::Id(p1, Occurrence::Last),
+            0b010 => Self::Id(p1, Occurrence::Next),
+            0b011 => Self::Id(p1, Occurrence::Previous),
+            0b100 => Self::Number(p1),
+            0b101 => Self::FromNumber(p1),
+            0b110 => Self::FromLastTo(p1),
+            _ => return None,
+        })
+    }
+}
+
+/// Record command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Record<'a> {
+    operation: Operation,
+    ef: Option<ShortEf>,
+    records: Option<Records>,
+    data: &'a [u8],
+    le: usize,
+}
+
+impl<'a> Record<'a> {
+    /// READ RECORD(S) in the current EF
+    pub fn read(records: Records, le: usize) -> Self {
+        Self {
+            operation: Operation::Read,
+            ef: None,
+            records: Some(records),
+            data: &[],
+            le,
+        }
+    }
+
+    /// UPDATE RECORD in the current EF
+    pub fn update(records: Records, data: &'a [u8]) -> Self {
+        Self {
+            operation: Operation::Update,
+            ef: None,
+            records: Some(records),
+            data,
+            le: 0,
+        }
+    }
+
+    /// APPEND RECORD to the current EF
+    pub fn append(data: &'a [u8]) -> Self {
+        Self {
+            operation: Operation::Append,
+            ef: None,
+            records: None,
+            data,
+            le: 0,
+        }
+    }
+
+    /// SEARCH RECORD in the current EF, `data` being the search string
+    pub fn search(records: Records, data: &'a [u8], le: usize) -> Self {
+        Self {
+            operation: Operation::Search,
+            ef: None,
+            records: Some(records),
+            data,
+            le,
+        }
+    }
+
+    /// Process the EF with this short identifier instead of the current EF
+    pub fn ef(mut self, ef: ShortEf) -> Self {
+        self.ef = Some(ef);
+        self
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// Short identifier of the EF, `None` for the current EF
+    pub fn short_ef(&self) -> Option<ShortEf> {
+        self.ef
+    }
+
+    /// Records to process, `None` for APPEND RECORD
+    pub fn records(&self) -> Option<Records> {
+        self.records
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn expected(&self) -> usize {
+        self.le
+    }
+
+    pub fn command(self, class: Class) -> CommandBuilder<&'a [u8]> {
+        let sfi = self.ef.map(ShortEf::get).unwrap_or_default() << 3;
+        let (p1, p2) = match self.records {
+            Some(records) => (records.p1(), sfi | records.p2()),
+            None => (0, sfi),
+        };
+        let le = u16::try_from(self.le).map_or(ExpectedLen::Max, ExpectedLen::Ne);
+        CommandBuilder::new(class, self.operation.instruction(), p1, p2, self.data, le)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordError {
+    /// The command is not a record command
+    WrongInstruction,
+    /// P1-P2 is not a valid record reference for the command
+    InvalidP1P2,
+    /// The data field does not match the command
+    InvalidData,
+}
+
+impl From<RecordError> for Status {
+    fn from(value: RecordError) -> Self {
+        match value {
+            RecordError::WrongInstruction => Status::InstructionNotSupportedOrInvalid,
+            RecordError::InvalidP1P2 => Status::IncorrectP1OrP2Parameter,
+            RecordError::InvalidData => Status::IncorrectDataParameter,
+        }
+    }
+}
+
+impl<'a> TryFrom<&CommandView<'a>> for Record<'a> {
+    type Error = RecordError;
+
+    fn try_from(command: &CommandView<'a>) -> Result<Self, Self::Error> {
+        let operation = Operation::from_instruction(command.instruction())
+            .ok_or(RecordError::WrongInstruction)?;
+        let (p1, p2) = (command.p1, command.p2);
+        let ef = match p2 >> 3 {
+            0 => None,
+            sfi => Some(ShortEf::new(sfi).map_err(|_| RecordError::InvalidP1P2)?),
+        };
+        let records = match operation {
+            Operation::Append if p1 == 0 && p2 & 0b111 == 0 => None,
+            Operation::Append => return Err(RecordError::InvalidP1P2),
+            _ => Some(Records::from_p1p2(p1, p2).ok_or(RecordError::InvalidP1P2)?),
+        };
+        if operation == Operation::Update
+            && matches!(
+                records,
+                Some(Records::FromNumber(_) | Records::FromLastTo(_))
+            )
+        {
+            return Err(RecordError::InvalidP1P2);
+        }
+        let data = command.data();
+        if (operation == Operation::Read) != data.is_empty() {
+            return Err(RecordError::InvalidData);
+        }
+        Ok(Self {
+            operation,
+            ef,
+            records,
+            data,
+            le: command.expected(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    fn roundtrip(record: Record<'_>, apdu: &[u8]) {
+        let command = record.command(0.try_into().unwrap()).serialize_to_vec();
+        assert_eq!(command, apdu);
+        let view = CommandView::try_from(apdu).unwrap();
+        assert_eq!(Record::try_from(&view), Ok(record));
+    }
+
+    fn sfi(id: u8) -> ShortEf {
+        ShortEf::new(id).unwrap()
+    }
+
+    #[test]
+    fn read() {
+        roundtrip(Record::read(Records::Number(0), 256), &hex!("00B20004 00"));
+        roundtrip(
+            Record::read(Records::Number(2), 0x1C).ef(sfi(0x1E)),
+            &hex!("00B202F4 1C"),
+        );
+        roundtrip(
+            Record::read(Records::FromNumber(1), 256).ef(sfi(2)),
+            &hex!("00B20115 00"),
+        );
+        roundtrip(
+            Record::read(Records::FromLastTo(3), 256),
+            &hex!("00B20306 00"),
+        );
+        roundtrip(
+            Record::read(Records::Id(0x42, Occurrence::Next), 256),
+            &hex!("00B24202 00"),
+        );
+    }
+
+    #[test]
+    fn write() {
+        roundtrip(
+            Record::update(Records::Number(1), &hex!("AABB")).ef(sfi(1)),
+            &hex!("00DC010C 02 AABB"),
+        );
+        roundtrip(
+            Record::update(Records::Id(0x42, Occurrence::Previous), &hex!("42")),
+            &hex!("00DC4203 01 42"),
+        );
+        roundtrip(Record::append(&hex!("0102")), &hex!("00E20000 02 0102"));
+        roundtrip(
+            Record::append(&hex!("0102")).ef(sfi(3)),
+            &hex!("00E20018 02 0102"),
+        );
+        roundtrip(
+            Record::search(Records::Number(1), &hex!("3F"), 256),
+            &hex!("00A20104 01 3F 00"),
+        );
+    }
+
+    #[test]
+    fn errors() {
+        fn parse(apdu: &[u8]) -> Result<Record<'_>, RecordError> {
+            Record::try_from(&CommandView::try_from(apdu).unwrap())
+        }
+        assert_eq!(parse(&hex!("00B00000")), Err(RecordError::WrongInstruction));
+        assert_eq!(parse(&hex!("00B20107")), Err(RecordError::InvalidP1P2));
+        // Short EF identifier 31 is reserved
+        assert_eq!(parse(&hex!("00B201FC")), Err(RecordError::InvalidP1P2));
+        assert_eq!(
+            parse(&hex!("00E20100 01 01")),
+            Err(RecordError::InvalidP1P2)
+        );
+        assert_eq!(
+            parse(&hex!("00DC0105 01 01")),
+            Err(RecordError::InvalidP1P2)
+        );
+        assert_eq!(parse(&hex!("00DC0104")), Err(RecordError::InvalidData));
+        assert_eq!(
+            parse(&hex!("00B20104 01 01")),
+            Err(RecordError::InvalidData)
+        );
+    }
+}
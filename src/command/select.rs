@@ -0,0 +1,353 @@
+//! SELECT (ISO 7816-4 11.2.2)
+//!
+//! [`SelectTarget`] maps the file to select to P1 and to the data field, and [`Select`] adds the
+//! response template and file occurrence encoded in P2. [`Select`] can be parsed from a
+//! [`CommandView`] for card-side file systems.
+//!
+//! ```
+//! use iso7816::command::select::{Select, SelectResponse, SelectTarget};
+//! use iso7816::file::FileId;
+//! let mut command = heapless::Vec::<u8, 16>::new();
+//! Select::new(SelectTarget::FileId(FileId::new(0x2F00).unwrap()))
+//!     .response(SelectResponse::Fcp)
+//!     .command(0.try_into().unwrap())
+//!     .serialize_into(&mut command)
+//!     .unwrap();
+//! assert_eq!(&*command, &[0x00, 0xA4, 0x00, 0x04, 0x02, 0x2F, 0x00, 0x00]);
+//! ```
+
+use super::class::Class;
+use super::{CommandBuilder, CommandView, DataSource, DataStream, Instruction, Writer};
+use crate::file::{FileError, FileId, Path};
+use crate::Status;
+
+/// Longest DF name
+pub const MAX_DF_NAME_LEN: usize = 16;
+
+/// File to select, encoded in P1 and the data field
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SelectTarget<'a> {
+    /// Master file (P1 `00`, `3F00`)
+    Mf,
+    /// Any file by identifier (P1 `00`)
+    FileId(FileId),
+    /// Child DF of the current DF (P1 `01`)
+    ChildDf(FileId),
+    /// EF under the current DF (P1 `02`)
+    Ef(FileId),
+    /// Parent DF of the current DF (P1 `03`)
+    Parent,
+    /// DF name, usually an application identifier (P1 `04`)
+    DfName(&'a [u8]),
+    /// Path from the MF (P1 `08`) or from the current DF (P1 `09`)
+    Path(Path),
+}
+
+impl SelectTarget<'_> {
+    pub fn p1(&self) -> u8 {
+        match self {
+            Self::Mf | Self::FileId(_) => 0x00,
+            Self::ChildDf(_) => 0x01,
+            Self::Ef(_) => 0x02,
+            Self::Parent => 0x03,
+            Self::DfName(_) => 0x04,
+            Self::Path(path) => path.select_p1(),
+        }
+    }
+}
+
+impl DataSource for SelectTarget<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Self::Mf | Self::FileId(_) | Self::ChildDf(_) | Self::Ef(_) => 2,
+            Self::Parent => 0,
+            Self::DfName(name) => name.len(),
+            Self::Path(path) => DataSource::len(path.select_data()),
+        }
+    }
+}
+
+impl<W: Writer> DataStream<W> for SelectTarget<'_> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        match self {
+            Self::Mf => FileId::MF.to_writer(writer),
+            Self::FileId(id) | Self::ChildDf(id) | Self::Ef(id) => id.to_writer(writer),
+            Self::Parent => Ok(()),
+            Self::DfName(name) => writer.write_all(name),
+            Self::Path(path) => path.select_data().to_writer(writer),
+        }
+    }
+}
+
+/// Response template requested in P2
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SelectResponse {
+    /// File control information (`6F`)
+    #[default]
+    Fci,
+    /// File control parameters (`62`)
+    Fcp,
+    /// File management data (`64`)
+    Fmd,
+    /// No response data
+    None,
+}
+
+/// File occurrence requested in P2, when several DF names match
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Occurrence {
+    #[default]
+    First,
+    Last,
+    Next,
+    Previous,
+}
+
+/// SELECT command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Select<'a> {
+    target: SelectTarget<'a>,
+    response: SelectResponse,
+    occurrence: Occurrence,
+}
+
+impl<'a> Select<'a> {
+    /// Select `target`, returning its FCI
+    pub fn new(target: SelectTarget<'a>) -> Self {
+        Self {
+            target,
+            response: Default::default(),
+            occurrence: Default::default(),
+        }
+    }
+
+    pub fn response(mut self, response: SelectResponse) -> Self {
+        self.response = response;
+        self
+    }
+
+    pub fn occurrence(mut self, occurrence: Occurrence) -> Self {
+        self.occurrence = occurrence;
+        self
+    }
+
+    pub fn target(&self) -> &SelectTarget<'a> {
+        &self.target
+    }
+
+    pub fn into_target(self) -> SelectTarget<'a> {
+        self.target
+    }
+
+    pub fn response_template(&self) -> SelectResponse {
+        self.response
+    }
+
+    pub fn file_occurrence(&self) -> Occurrence {
+        self.occurrence
+    }
+
+    pub fn p2(&self) -> u8 {
+        let response = match self.response {
+            SelectResponse::Fci => 0x00,
+            SelectResponse::Fcp => 0x04,
+            SelectResponse::Fmd => 0x08,
+            SelectResponse::None => 0x0C,
+        };
+        let occurrence = match self.occurrence {
+            Occurrence::First => 0x00,
+            Occurrence::Last => 0x01,
+            Occurrence::Next => 0x02,
+            Occurrence::Previous => 0x03,
+        };
+        response | occurrence
+    }
+
+    /// SELECT command, expecting a response unless [`SelectResponse::None`] is requested
+    pub fn command(self, class: Class) -> CommandBuilder<SelectTarget<'a>> {
+        let le = if self.response == SelectResponse::None {
+            0
+        } else {
+            256
+        };
+        let (p1, p2) = (self.target.p1(), self.p2());
+        CommandBuilder::new(class, Instruction::Select, p1, p2, self.target, le)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectError {
+    /// The command is not a SELECT command
+    WrongInstruction,
+    /// Unknown selection method in P1, or unknown response template in P2
+    UnsupportedP1P2,
+    /// The data field does not match the selection method
+    InvalidData,
+}
+
+impl From<FileError> for SelectError {
+    fn from(_: FileError) -> Self {
+        Self::InvalidData
+    }
+}
+
+impl From<SelectError> for Status {
+    fn from(value: SelectError) -> Self {
+        match value {
+            SelectError::WrongInstruction => Status::InstructionNotSupportedOrInvalid,
+            SelectError::UnsupportedP1P2 => Status::IncorrectP1OrP2Parameter,
+            SelectError::InvalidData => Status::IncorrectDataParameter,
+        }
+    }
+}
+
+fn file_id(data: &[u8]) -> Result<FileId, SelectError> {
+    let id: [u8; 2] = data.try_into().map_err(|_| SelectError::InvalidData)?;
+    Ok(FileId::try_from(id)?)
+}
+
+impl<'a> TryFrom<&CommandView<'a>> for Select<'a> {
+    type Error = SelectError;
+
+    fn try_from(command: &CommandView<'a>) -> Result<Self, Self::Error> {
+        if command.instruction() != Instruction::Select {
+            return Err(SelectError::WrongInstruction);
+        }
+        let data = command.data();
+        let target = match command.p1 {
+            0x00 if data.is_empty() => SelectTarget::Mf,
+            0x00 => match file_id(data)? {
+                FileId::MF => SelectTarget::Mf,
+                id => SelectTarget::FileId(id),
+            },
+            0x01 => SelectTarget::ChildDf(file_id(data)?),
+            0x02 => SelectTarget::Ef(file_id(data)?),
+            0x03 if data.is_empty() => SelectTarget::Parent,
+            0x04 if !data.is_empty() && data.len() <= MAX_DF_NAME_LEN => SelectTarget::DfName(data),
+            0x08 | 0x09 if !data.is_empty() => {
+                // The MF and current DF identifiers are not part of the data field
+                let path = Path::from_bytes(data)?;
+                if path.ids().len() * 2 != data.len() {
+                    return Err(SelectError::InvalidData);
+                }
+                SelectTarget::Path(if command.p1 == 0x08 {
+                    Path::from_mf(path.ids())?
+                } else {
+                    Path::from_current_df(path.ids())?
+                })
+            }
+            0x00..=0x04 | 0x08 | 0x09 => return Err(SelectError::InvalidData),
+            _ => return Err(SelectError::UnsupportedP1P2),
+        };
+        let response = match command.p2 & 0xFC {
+            0x00 => SelectResponse::Fci,
+            0x04 => SelectResponse::Fcp,
+            0x08 => SelectResponse::Fmd,
+            0x0C => SelectResponse::None,
+            _ => return Err(SelectError::UnsupportedP1P2),
+        };
+        let occurrence = match command.p2 & 0x03 {
+            0x00 => Occurrence::First,
+            0x01 => Occurrence::Last,
+            0x02 => Occurrence::Next,
+            _ => Occurrence::Previous,
+        };
+        Ok(Self {
+            target,
+            response,
+            occurrence,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    fn id(id: u16) -> FileId {
+        FileId::new(id).unwrap()
+    }
+
+    fn roundtrip(select: Select<'_>, apdu: &[u8]) {
+        let command = select.clone().command(0.try_into().unwrap());
+        assert_eq!(command.serialize_to_vec(), apdu);
+        let view = CommandView::try_from(apdu).unwrap();
+        assert_eq!(Select::try_from(&view), Ok(select));
+    }
+
+    #[test]
+    fn targets() {
+        roundtrip(Select::new(SelectTarget::Mf), &hex!("00A40000 02 3F00 00"));
+        roundtrip(
+            Select::new(SelectTarget::FileId(id(0x2F00))).response(SelectResponse::None),
+            &hex!("00A4000C 02 2F00"),
+        );
+        roundtrip(
+            Select::new(SelectTarget::ChildDf(id(0x7F10))).response(SelectResponse::Fcp),
+            &hex!("00A40104 02 7F10 00"),
+        );
+        roundtrip(
+            Select::new(SelectTarget::Ef(id(0x6F3A))).response(SelectResponse::Fmd),
+            &hex!("00A40208 02 6F3A 00"),
+        );
+        roundtrip(
+            Select::new(SelectTarget::Parent).response(SelectResponse::None),
+            &hex!("00A4030C"),
+        );
+        roundtrip(
+            Select::new(SelectTarget::DfName(&hex!("D2760000850101"))).occurrence(Occurrence::Next),
+            &hex!("00A40402 07 D2760000850101 00"),
+        );
+        roundtrip(
+            Select::new(SelectTarget::Path(
+                Path::from_mf(&[id(0x7F10), id(0x6F3A)]).unwrap(),
+            )),
+            &hex!("00A40800 04 7F106F3A 00"),
+        );
+        roundtrip(
+            Select::new(SelectTarget::Path(
+                Path::from_current_df(&[id(0x5015)]).unwrap(),
+            ))
+            .response(SelectResponse::None),
+            &hex!("00A4090C 02 5015"),
+        );
+
+        let view = CommandView::try_from(&hex!("00A40000")[..]).unwrap();
+        assert_eq!(Select::try_from(&view).unwrap().target(), &SelectTarget::Mf);
+    }
+
+    #[test]
+    fn errors() {
+        fn parse(apdu: &[u8]) -> Result<Select<'_>, SelectError> {
+            Select::try_from(&CommandView::try_from(apdu).unwrap())
+        }
+        assert_eq!(parse(&hex!("00B00000")), Err(SelectError::WrongInstruction));
+        assert_eq!(parse(&hex!("00A40500")), Err(SelectError::UnsupportedP1P2));
+        assert_eq!(
+            parse(&hex!("00A40010 02 2F00")),
+            Err(SelectError::UnsupportedP1P2)
+        );
+        assert_eq!(
+            parse(&hex!("00A40000 01 2F")),
+            Err(SelectError::InvalidData)
+        );
+        assert_eq!(
+            parse(&hex!("00A40000 02 3FFF")),
+            Err(SelectError::InvalidData)
+        );
+        assert_eq!(parse(&hex!("00A40400")), Err(SelectError::InvalidData));
+        assert_eq!(
+            parse(&hex!("00A40300 02 2F00")),
+            Err(SelectError::InvalidData)
+        );
+        assert_eq!(
+            parse(&hex!("00A40800 04 3F007F10")),
+            Err(SelectError::InvalidData)
+        );
+        assert_eq!(
+            Status::from(SelectError::InvalidData),
+            Status::IncorrectDataParameter
+        );
+    }
+}
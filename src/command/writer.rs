@@ -1,6 +1,9 @@
 use core::fmt::{Debug, Display};
 use core::mem;
 
+use super::DataStream;
+use crate::tlv::{self, Tag};
+
 pub trait Error: Debug + Display {
     fn failed_serialization(cause: &'static str) -> Self;
 }
@@ -38,6 +41,338 @@ pub trait Writer {
         }
         Ok(())
     }
+    /// Write the concatenation of `data`
+    ///
+    /// Writers for which each call has a cost, like DMA or ring buffers, can override it to write
+    /// all the slices at once.
+    fn write_all_vectored(&mut self, data: &[&[u8]]) -> Result<(), Self::Error> {
+        data.iter().try_for_each(|data| self.write_all(data))
+    }
+    /// Number of bytes that can still be written, `usize::MAX` if the writer is not bounded
+    fn remaining_len(&self) -> usize {
+        usize::MAX
+    }
+}
+
+/// Convenience methods available on all [`Writer`]s
+pub trait WriterExt: Writer {
+    /// Write a data object made of `tag`, the length of `value` and `value`
+    ///
+    /// This is the same as writing a [`Tlv`](crate::tlv::Tlv), without building one for each field:
+    ///
+    /// ```
+    /// use iso7816::command::WriterExt;
+    /// use iso7816::tlv::Tag;
+    /// let mut buf = heapless::Vec::<u8, 16>::new();
+    /// buf.write_do(Tag::from_u8(0x87), &[0x01, 0xAA, 0xBB]).unwrap();
+    /// buf.write_do(Tag::from_u8(0x97), &[0x00]).unwrap();
+    /// assert_eq!(&buf, &[0x87, 0x03, 0x01, 0xAA, 0xBB, 0x97, 0x01, 0x00]);
+    /// ```
+    fn write_do<S>(&mut self, tag: Tag, value: &S) -> Result<(), Self::Error>
+    where
+        Self: Sized,
+        S: DataStream<Self> + ?Sized,
+    {
+        tlv::write_header(self, tag, value.len(), false)?;
+        value.to_writer(self)
+    }
+}
+
+impl<W: Writer + ?Sized> WriterExt for W {}
+
+/// Writers keeping the written data accessible, so that it can be modified afterwards
+///
+/// This allows writing data objects whose length is not known in advance, see
+/// [`StreamingTlv`](crate::tlv::StreamingTlv). A `&mut [u8]` writer does not implement it, as it
+/// only keeps the unwritten part of the buffer, use a [`Cursor`] instead.
+pub trait Backpatch: Writer {
+    /// Data written so far
+    fn written_mut(&mut self) -> &mut [u8];
+    /// Drop the written data after the first `len` bytes
+    fn truncate(&mut self, len: usize);
+}
+
+/// Forwarding implementation, for functions taking a writer by value
+///
+/// ```
+/// use iso7816::command::Writer;
+/// fn write_header<W: Writer>(mut writer: W) -> Result<(), W::Error> {
+///     writer.write_all(&[0x00, 0xA4, 0x04, 0x00])
+/// }
+/// let mut buf = heapless::Vec::<u8, 8>::new();
+/// write_header(&mut buf).unwrap();
+/// write_header(&mut buf).unwrap();
+/// assert_eq!(buf.len(), 8);
+/// ```
+impl<W: Writer + ?Sized> Writer for &mut W {
+    type Error = W::Error;
+    fn write(&mut self, data: &[u8]) -> Result<usize, Self::Error> {
+        (**self).write(data)
+    }
+    fn write_all(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        (**self).write_all(data)
+    }
+    fn write_all_vectored(&mut self, data: &[&[u8]]) -> Result<(), Self::Error> {
+        (**self).write_all_vectored(data)
+    }
+    fn remaining_len(&self) -> usize {
+        (**self).remaining_len()
+    }
+}
+
+/// Checks the [`remaining_len`](Writer::remaining_len) of `W`, so that buffers can be reused
+/// between commands:
+///
+/// ```
+/// use iso7816::command::writer::IntoWriter;
+/// let mut buf = heapless::Vec::<u8, 8>::new();
+/// assert!((&mut buf).into_writer(8).is_ok());
+/// assert!((&mut buf).into_writer(9).is_err());
+/// ```
+impl<W: Writer + ?Sized> IntoWriter for &mut W {
+    type Writer = Self;
+    fn into_writer(self, to_write: usize) -> Result<Self, W::Error> {
+        if self.remaining_len() < to_write {
+            Err(W::Error::failed_serialization(
+                "Not enough space in the writer",
+            ))
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+/// [`Writer`] converting the errors of `W` with `F`
+///
+/// This lets layered writers share one error type:
+///
+/// ```
+/// use iso7816::command::writer::{BufferFull, Error, MapErr};
+/// use iso7816::command::DataStream;
+/// use iso7816::tlv::{Tag, Tlv};
+///
+/// #[derive(Debug)]
+/// enum TransportError {
+///     Overflow,
+///     Encoding(&'static str),
+/// }
+/// # impl core::fmt::Display for TransportError {
+/// #     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+/// #         write!(f, "{self:?}")
+/// #     }
+/// # }
+/// impl Error for TransportError {
+///     fn failed_serialization(cause: &'static str) -> Self {
+///         Self::Encoding(cause)
+///     }
+/// }
+///
+/// let mut buf = [0; 2];
+/// let mut writer = MapErr::new(&mut buf[..], |error| match error {
+///     BufferFull::BufferFull => TransportError::Overflow,
+///     BufferFull::Serialization(cause) => TransportError::Encoding(cause),
+/// });
+/// let result = Tlv::new(Tag::from_u8(0x53), [0x01]).to_writer(&mut writer);
+/// assert!(matches!(result, Err(TransportError::Overflow)));
+/// ```
+#[derive(Debug)]
+pub struct MapErr<W, F> {
+    writer: W,
+    f: F,
+}
+
+impl<W, F> MapErr<W, F> {
+    pub fn new(writer: W, f: F) -> Self {
+        Self { writer, f }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W, F, E> Writer for MapErr<W, F>
+where
+    W: Writer,
+    F: FnMut(W::Error) -> E,
+    E: Error,
+{
+    type Error = E;
+    fn write(&mut self, data: &[u8]) -> Result<usize, E> {
+        self.writer.write(data).map_err(&mut self.f)
+    }
+    fn write_all(&mut self, data: &[u8]) -> Result<(), E> {
+        self.writer.write_all(data).map_err(&mut self.f)
+    }
+    fn write_all_vectored(&mut self, data: &[&[u8]]) -> Result<(), E> {
+        self.writer.write_all_vectored(data).map_err(&mut self.f)
+    }
+    fn remaining_len(&self) -> usize {
+        self.writer.remaining_len()
+    }
+}
+
+/// Error of a [`DynWriter`]
+///
+/// The error of the underlying writer is erased so that no allocation is needed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DynError {
+    /// The underlying writer failed
+    Writer,
+    Serialization(&'static str),
+}
+
+impl Error for DynError {
+    fn failed_serialization(cause: &'static str) -> Self {
+        Self::Serialization(cause)
+    }
+}
+
+impl Display for DynError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DynError::Writer => f.write_str("Writer failed"),
+            DynError::Serialization(cause) => f.write_str(cause),
+        }
+    }
+}
+
+/// Object safe version of [`Writer`], with the error erased
+trait ErasedWriter {
+    fn erased_write(&mut self, data: &[u8]) -> Result<usize, DynError>;
+    fn erased_write_all(&mut self, data: &[u8]) -> Result<(), DynError>;
+    fn erased_write_all_vectored(&mut self, data: &[&[u8]]) -> Result<(), DynError>;
+    fn erased_remaining_len(&self) -> usize;
+}
+
+impl<W: Writer + ?Sized> ErasedWriter for W {
+    fn erased_write(&mut self, data: &[u8]) -> Result<usize, DynError> {
+        Writer::write(self, data).map_err(|_| DynError::Writer)
+    }
+    fn erased_write_all(&mut self, data: &[u8]) -> Result<(), DynError> {
+        Writer::write_all(self, data).map_err(|_| DynError::Writer)
+    }
+    fn erased_write_all_vectored(&mut self, data: &[&[u8]]) -> Result<(), DynError> {
+        Writer::write_all_vectored(self, data).map_err(|_| DynError::Writer)
+    }
+    fn erased_remaining_len(&self) -> usize {
+        Writer::remaining_len(self)
+    }
+}
+
+/// Type-erased [`Writer`]
+///
+/// Traits that need to stay object safe can take a `DynWriter` instead of a generic `W: Writer`:
+///
+/// ```
+/// use iso7816::command::writer::{DynError, DynWriter};
+/// use iso7816::command::DataStream;
+/// use iso7816::tlv::{Tag, Tlv};
+///
+/// trait Respond {
+///     fn respond(&mut self, reply: &mut DynWriter<'_>) -> Result<(), DynError>;
+/// }
+///
+/// struct Serial;
+/// impl Respond for Serial {
+///     fn respond(&mut self, reply: &mut DynWriter<'_>) -> Result<(), DynError> {
+///         Tlv::new(Tag::from_u8(0x5A), [0x12, 0x34]).to_writer(reply)
+///     }
+/// }
+///
+/// let apps: &mut [&mut dyn Respond] = &mut [&mut Serial];
+/// let mut buf = heapless::Vec::<u8, 8>::new();
+/// apps[0].respond(&mut DynWriter::new(&mut buf)).unwrap();
+/// assert_eq!(buf, [0x5A, 0x02, 0x12, 0x34]);
+///
+/// let mut buf = [0; 2];
+/// let result = apps[0].respond(&mut DynWriter::new(&mut &mut buf[..]));
+/// assert_eq!(result, Err(DynError::Writer));
+/// ```
+pub struct DynWriter<'a> {
+    writer: &'a mut dyn ErasedWriter,
+}
+
+impl<'a> DynWriter<'a> {
+    pub fn new<W: Writer>(writer: &'a mut W) -> Self {
+        Self { writer }
+    }
+}
+
+impl Debug for DynWriter<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DynWriter")
+            .field("remaining_len", &self.writer.erased_remaining_len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Writer for DynWriter<'_> {
+    type Error = DynError;
+    fn write(&mut self, data: &[u8]) -> Result<usize, DynError> {
+        self.writer.erased_write(data)
+    }
+    fn write_all(&mut self, data: &[u8]) -> Result<(), DynError> {
+        self.writer.erased_write_all(data)
+    }
+    fn write_all_vectored(&mut self, data: &[&[u8]]) -> Result<(), DynError> {
+        self.writer.erased_write_all_vectored(data)
+    }
+    fn remaining_len(&self) -> usize {
+        self.writer.erased_remaining_len()
+    }
+}
+
+/// [`Writer`] passing the written data to `F` too, for example to hash or MAC it
+///
+/// Only the bytes accepted by the inner writer are passed to `F`, so it sees exactly the
+/// serialized data:
+///
+/// ```
+/// use iso7816::command::{writer::TeeWriter, DataStream};
+/// use iso7816::tlv::{Tag, Tlv};
+/// let mut checksum = 0u8;
+/// let mut buf = heapless::Vec::<u8, 8>::new();
+/// let mut writer = TeeWriter::new(&mut buf, |data: &[u8]| {
+///     checksum = data.iter().fold(checksum, |acc, b| acc ^ b)
+/// });
+/// Tlv::new(Tag::from_u8(0x87), [0x01, 0x02]).to_writer(&mut writer).unwrap();
+/// assert_eq!(checksum, 0x87 ^ 0x02 ^ 0x01 ^ 0x02);
+/// ```
+#[derive(Debug)]
+pub struct TeeWriter<W, F> {
+    writer: W,
+    f: F,
+}
+
+impl<W, F> TeeWriter<W, F> {
+    pub fn new(writer: W, f: F) -> Self {
+        Self { writer, f }
+    }
+
+    pub fn into_inner(self) -> (W, F) {
+        (self.writer, self.f)
+    }
+}
+
+impl<W: Writer, F: FnMut(&[u8])> Writer for TeeWriter<W, F> {
+    type Error = W::Error;
+    fn write(&mut self, data: &[u8]) -> Result<usize, W::Error> {
+        let written = self.writer.write(data)?;
+        (self.f)(&data[..written]);
+        Ok(written)
+    }
+    fn remaining_len(&self) -> usize {
+        self.writer.remaining_len()
+    }
 }
 
 impl<'a> Writer for &'a mut [u8] {
@@ -54,6 +389,9 @@ impl<'a> Writer for &'a mut [u8] {
         *self = b;
         Ok(amt)
     }
+    fn remaining_len(&self) -> usize {
+        self.len()
+    }
 }
 impl<'a> IntoWriter for &'a mut [u8] {
     type Writer = Self;
@@ -66,6 +404,97 @@ impl<'a> IntoWriter for &'a mut [u8] {
     }
 }
 
+/// [`Writer`] over a slice, keeping track of the position
+///
+/// Unlike the `&mut [u8]` writer, the written data stays accessible, so it can be patched or
+/// rewound to an earlier position:
+///
+/// ```
+/// use iso7816::command::{writer::Cursor, Writer};
+/// let mut buf = [0; 8];
+/// let mut cursor = Cursor::new(&mut buf);
+/// cursor.write_all(&[0x00, 0xA4]).unwrap();
+/// let checkpoint = cursor.position();
+/// cursor.write_all(&[0xFF; 4]).unwrap();
+/// cursor.rewind(checkpoint);
+/// cursor.write_all(&[0x04, 0x00]).unwrap();
+/// assert_eq!(cursor.written(), &[0x00, 0xA4, 0x04, 0x00]);
+/// assert_eq!(cursor.remaining(), 4);
+/// ```
+#[derive(Debug)]
+pub struct Cursor<'a> {
+    buf: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, position: 0 }
+    }
+
+    /// Number of bytes written
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn written(&self) -> &[u8] {
+        &self.buf[..self.position]
+    }
+
+    /// Number of bytes that can still be written
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.position
+    }
+
+    /// Go back to `position`, dropping the data written after it
+    ///
+    /// Panics if `position` is after the current position.
+    pub fn rewind(&mut self, position: usize) {
+        assert!(position <= self.position, "Cannot rewind forward");
+        self.position = position;
+    }
+
+    pub fn into_written(self) -> &'a mut [u8] {
+        &mut self.buf[..self.position]
+    }
+}
+
+impl Writer for Cursor<'_> {
+    type Error = BufferFull;
+    fn write(&mut self, data: &[u8]) -> Result<usize, BufferFull> {
+        let amt = data.len().min(self.remaining());
+
+        if amt == 0 {
+            return Err(BufferFull::BufferFull);
+        }
+
+        self.buf[self.position..][..amt].copy_from_slice(&data[..amt]);
+        self.position += amt;
+        Ok(amt)
+    }
+    fn remaining_len(&self) -> usize {
+        self.remaining()
+    }
+}
+impl Backpatch for Cursor<'_> {
+    fn written_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[..self.position]
+    }
+    fn truncate(&mut self, len: usize) {
+        self.position = self.position.min(len);
+    }
+}
+impl IntoWriter for Cursor<'_> {
+    type Writer = Self;
+    fn into_writer(self, to_write: usize) -> Result<Self, BufferFull> {
+        if self.remaining() < to_write {
+            Err(BufferFull::BufferFull)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
 impl<const N: usize> Writer for heapless::Vec<u8, N> {
     type Error = BufferFull;
     fn write(&mut self, data: &[u8]) -> Result<usize, BufferFull> {
@@ -78,6 +507,17 @@ impl<const N: usize> Writer for heapless::Vec<u8, N> {
         self.extend_from_slice(&data[..amt]).unwrap();
         Ok(amt)
     }
+    fn remaining_len(&self) -> usize {
+        self.capacity() - self.len()
+    }
+}
+impl<const N: usize> Backpatch for heapless::Vec<u8, N> {
+    fn written_mut(&mut self) -> &mut [u8] {
+        self
+    }
+    fn truncate(&mut self, len: usize) {
+        heapless::Vec::truncate(self, len)
+    }
 }
 impl<const N: usize> IntoWriter for heapless::Vec<u8, N> {
     type Writer = Self;
@@ -90,7 +530,45 @@ impl<const N: usize> IntoWriter for heapless::Vec<u8, N> {
     }
 }
 
-#[cfg(feature = "heapless_bytes")]
+#[cfg(feature = "arrayvec")]
+impl<const N: usize> Writer for arrayvec::ArrayVec<u8, N> {
+    type Error = BufferFull;
+    fn write(&mut self, data: &[u8]) -> Result<usize, BufferFull> {
+        let amt = data.len().min(self.remaining_capacity());
+
+        if amt == 0 {
+            return Err(BufferFull::BufferFull);
+        }
+
+        self.try_extend_from_slice(&data[..amt]).unwrap();
+        Ok(amt)
+    }
+    fn remaining_len(&self) -> usize {
+        self.remaining_capacity()
+    }
+}
+#[cfg(feature = "arrayvec")]
+impl<const N: usize> Backpatch for arrayvec::ArrayVec<u8, N> {
+    fn written_mut(&mut self) -> &mut [u8] {
+        self
+    }
+    fn truncate(&mut self, len: usize) {
+        arrayvec::ArrayVec::truncate(self, len)
+    }
+}
+#[cfg(feature = "arrayvec")]
+impl<const N: usize> IntoWriter for arrayvec::ArrayVec<u8, N> {
+    type Writer = Self;
+    fn into_writer(self, to_write: usize) -> Result<Self, BufferFull> {
+        if self.remaining_capacity() < to_write {
+            Err(BufferFull::BufferFull)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+#[cfg(feature = "heapless-bytes")]
 impl<const N: usize> Writer for heapless_bytes::Bytes<N> {
     type Error = BufferFull;
     fn write(&mut self, data: &[u8]) -> Result<usize, BufferFull> {
@@ -103,9 +581,12 @@ impl<const N: usize> Writer for heapless_bytes::Bytes<N> {
         self.extend_from_slice(&data[..amt]).unwrap();
         Ok(amt)
     }
+    fn remaining_len(&self) -> usize {
+        self.capacity() - self.len()
+    }
 }
 
-#[cfg(feature = "heapless_bytes")]
+#[cfg(feature = "heapless-bytes")]
 impl<const N: usize> IntoWriter for heapless_bytes::Bytes<N> {
     type Writer = Self;
     fn into_writer(self, to_write: usize) -> Result<Self, BufferFull> {
@@ -132,6 +613,135 @@ impl Error for SerializationError {
     }
 }
 
+/// [`Writer`] discarding the data, only counting its length
+///
+/// This gives the exact length of the serialization of any [`DataStream`], for example to size a
+/// buffer or to decide whether a command needs to be chained:
+///
+/// ```
+/// use iso7816::command::writer::CountingWriter;
+/// use iso7816::tlv::{Tag, Tlv};
+/// let aid = [0xA0, 0x00, 0x00, 0x03, 0x08];
+/// let fci = Tlv::new(Tag::from_u8(0x6F), Tlv::new(Tag::from_u8(0x84), aid));
+/// assert_eq!(CountingWriter::measure(&fci).unwrap(), 9);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CountingWriter {
+    count: usize,
+}
+
+impl CountingWriter {
+    pub const fn new() -> Self {
+        Self { count: 0 }
+    }
+
+    /// Number of bytes written so far
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Length of the serialization of `data`
+    pub fn measure<S: DataStream<Self> + ?Sized>(data: &S) -> Result<usize, SerializationError> {
+        let mut writer = Self::new();
+        data.to_writer(&mut writer)?;
+        Ok(writer.count)
+    }
+}
+
+impl Writer for CountingWriter {
+    type Error = SerializationError;
+    fn write(&mut self, data: &[u8]) -> Result<usize, SerializationError> {
+        self.count += data.len();
+        Ok(data.len())
+    }
+}
+
+/// [`Writer`] cutting the data into frames of `N` bytes, passed to a callback
+///
+/// Each frame is passed to `flush` as soon as it is full, and [`finish`](Self::finish) passes the
+/// last, shorter frame. This serializes directly into transport frames (CCID, ISO-DEP, I2C), with
+/// only one frame buffered:
+///
+/// ```
+/// use iso7816::command::{writer::FrameWriter, DataStream};
+/// use iso7816::tlv::{Tag, Tlv};
+/// let mut frames = Vec::new();
+/// let mut writer = FrameWriter::<_, 4>::new(|frame: &[u8]| {
+///     frames.push(frame.to_vec());
+///     Ok::<(), ()>(())
+/// });
+/// Tlv::new(Tag::from_u8(0x53), [0x01, 0x02, 0x03, 0x04]).to_writer(&mut writer).unwrap();
+/// writer.finish().unwrap();
+/// assert_eq!(frames, [&[0x53, 0x04, 0x01, 0x02][..], &[0x03, 0x04]]);
+/// ```
+#[derive(Debug)]
+pub struct FrameWriter<F, const N: usize> {
+    frame: heapless::Vec<u8, N>,
+    flush: F,
+}
+
+impl<F, E, const N: usize> FrameWriter<F, N>
+where
+    F: FnMut(&[u8]) -> Result<(), E>,
+    E: Debug,
+{
+    pub fn new(flush: F) -> Self {
+        assert!(N > 0, "Frames must not be empty");
+        Self {
+            frame: heapless::Vec::new(),
+            flush,
+        }
+    }
+
+    /// Flush the last frame, if it is not empty
+    pub fn finish(mut self) -> Result<(), FrameError<E>> {
+        if !self.frame.is_empty() {
+            (self.flush)(&self.frame).map_err(FrameError::Flush)?;
+        }
+        Ok(())
+    }
+}
+
+impl<F, E, const N: usize> Writer for FrameWriter<F, N>
+where
+    F: FnMut(&[u8]) -> Result<(), E>,
+    E: Debug,
+{
+    type Error = FrameError<E>;
+    fn write(&mut self, data: &[u8]) -> Result<usize, Self::Error> {
+        let amt = data.len().min(N - self.frame.len());
+        self.frame.extend_from_slice(&data[..amt]).unwrap();
+        if self.frame.is_full() {
+            (self.flush)(&self.frame).map_err(FrameError::Flush)?;
+            self.frame.clear();
+        }
+        Ok(amt)
+    }
+}
+
+/// Error of a [`FrameWriter`]
+#[derive(Debug)]
+pub enum FrameError<E> {
+    /// The callback failed
+    Flush(E),
+    Serialization(&'static str),
+}
+
+impl<E> Display for FrameError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Flush(_) => f.write_str("Failed to flush a frame"),
+            Self::Serialization(cause) => f.write_str(cause),
+        }
+    }
+}
+
+impl<E: Debug> Error for FrameError<E> {
+    fn failed_serialization(cause: &'static str) -> Self {
+        Self::Serialization(cause)
+    }
+}
+
 #[cfg(any(feature = "std", test))]
 impl Writer for Vec<u8> {
     type Error = SerializationError;
@@ -141,6 +751,16 @@ impl Writer for Vec<u8> {
     }
 }
 
+#[cfg(any(feature = "std", test))]
+impl Backpatch for Vec<u8> {
+    fn written_mut(&mut self) -> &mut [u8] {
+        self
+    }
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len)
+    }
+}
+
 #[cfg(any(feature = "std", test))]
 impl IntoWriter for Vec<u8> {
     type Writer = Self;
@@ -149,7 +769,545 @@ impl IntoWriter for Vec<u8> {
     }
 }
 
+/// [`Writer`] writing to a [`std::io::Write`]
+///
+/// ```
+/// use iso7816::command::{writer::IoWriter, CommandBuilder, DataStream};
+/// use iso7816::Instruction;
+/// let mut socket = std::io::Cursor::new(Vec::new());
+/// let class = 0x00.try_into().unwrap();
+/// let command = CommandBuilder::new(class, Instruction::Select, 0x04, 0x00, &[0xA0, 0x00], 256);
+/// command.to_writer(&mut IoWriter(&mut socket)).unwrap();
+/// assert_eq!(socket.get_ref(), &[0x00, 0xA4, 0x04, 0x00, 0x02, 0xA0, 0x00, 0x00]);
+/// ```
+#[cfg(any(feature = "std", test))]
+#[derive(Debug)]
+pub struct IoWriter<W>(pub W);
+
+#[cfg(any(feature = "std", test))]
+impl<W: std::io::Write> Writer for IoWriter<W> {
+    type Error = IoError;
+    fn write(&mut self, data: &[u8]) -> Result<usize, IoError> {
+        match self.0.write(data)? {
+            0 if !data.is_empty() => {
+                Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into())
+            }
+            written => Ok(written),
+        }
+    }
+    fn write_all(&mut self, data: &[u8]) -> Result<(), IoError> {
+        Ok(self.0.write_all(data)?)
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl<W: std::io::Write> IntoWriter for IoWriter<W> {
+    type Writer = Self;
+    fn into_writer(self, _to_write: usize) -> Result<Self, IoError> {
+        Ok(self)
+    }
+}
+
+/// Error of an [`IoWriter`]
+#[cfg(any(feature = "std", test))]
+#[derive(Debug)]
+pub enum IoError {
+    Io(std::io::Error),
+    Serialization(&'static str),
+}
+
+#[cfg(any(feature = "std", test))]
+impl From<std::io::Error> for IoError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl Display for IoError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io(error) => Display::fmt(error, f),
+            Self::Serialization(cause) => f.write_str(cause),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl std::error::Error for IoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Serialization(_) => None,
+        }
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl Error for IoError {
+    fn failed_serialization(cause: &'static str) -> Self {
+        Self::Serialization(cause)
+    }
+}
+
+/// [`Writer`] writing to an [`embedded_io::Write`]
+#[cfg(feature = "embedded-io")]
+#[derive(Debug)]
+pub struct EmbeddedIoWriter<W>(pub W);
+
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> Writer for EmbeddedIoWriter<W> {
+    type Error = EmbeddedIoError<W::Error>;
+    fn write(&mut self, data: &[u8]) -> Result<usize, Self::Error> {
+        self.0.write(data).map_err(EmbeddedIoError::Io)
+    }
+    fn write_all(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.0.write_all(data).map_err(EmbeddedIoError::Io)
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<W: embedded_io::Write> IntoWriter for EmbeddedIoWriter<W> {
+    type Writer = Self;
+    fn into_writer(self, _to_write: usize) -> Result<Self, EmbeddedIoError<W::Error>> {
+        Ok(self)
+    }
+}
+
+/// Error of an [`EmbeddedIoWriter`] or of [`write_async`]
+#[cfg(feature = "embedded-io")]
+#[derive(Debug)]
+pub enum EmbeddedIoError<E> {
+    Io(E),
+    Serialization(&'static str),
+}
+
+#[cfg(feature = "embedded-io")]
+impl<E: embedded_io::Error> Display for EmbeddedIoError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "I/O error: {:?}", error.kind()),
+            Self::Serialization(cause) => f.write_str(cause),
+        }
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<E: embedded_io::Error> Error for EmbeddedIoError<E> {
+    fn failed_serialization(cause: &'static str) -> Self {
+        Self::Serialization(cause)
+    }
+}
+
+/// Write `data` to an [`embedded_io_async::Write`]
+///
+/// [`DataStream`] is synchronous, so the data is serialized in chunks of the size of `scratch`,
+/// each being written before the next one is serialized. A `scratch` of at least
+/// [`data.len()`](super::DataSource::len) bytes serializes the data only once, a smaller one
+/// serializes it again for each chunk.
+#[cfg(feature = "embedded-io-async")]
+pub async fn write_async<W, S>(
+    writer: &mut W,
+    data: &S,
+    scratch: &mut [u8],
+) -> Result<(), EmbeddedIoError<W::Error>>
+where
+    W: embedded_io_async::Write,
+    S: for<'s> DataStream<Window<'s>> + ?Sized,
+{
+    assert!(!scratch.is_empty(), "The scratch buffer is empty");
+    let len = data.len();
+    let mut offset = 0;
+    while offset < len {
+        let mut window = Window {
+            skip: offset,
+            buf: &mut *scratch,
+            filled: 0,
+        };
+        match data.to_writer(&mut window) {
+            Ok(()) | Err(WindowError::Full) => {}
+            Err(WindowError::Serialization(cause)) => {
+                return Err(EmbeddedIoError::Serialization(cause))
+            }
+        }
+        let filled = window.filled;
+        if filled == 0 {
+            return Err(EmbeddedIoError::Serialization(
+                "Data is shorter than its length",
+            ));
+        }
+        writer
+            .write_all(&scratch[..filled])
+            .await
+            .map_err(EmbeddedIoError::Io)?;
+        offset += filled;
+    }
+    Ok(())
+}
+
+/// [`Writer`] keeping a window of the serialized data, used by [`write_async`]
+#[cfg(feature = "embedded-io-async")]
+#[derive(Debug)]
+pub struct Window<'s> {
+    skip: usize,
+    buf: &'s mut [u8],
+    filled: usize,
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl Writer for Window<'_> {
+    type Error = WindowError;
+    fn write(&mut self, data: &[u8]) -> Result<usize, WindowError> {
+        let skipped = self.skip.min(data.len());
+        self.skip -= skipped;
+        let available = self.buf.len() - self.filled;
+        if available == 0 {
+            return Err(WindowError::Full);
+        }
+        let amt = (data.len() - skipped).min(available);
+        self.buf[self.filled..][..amt].copy_from_slice(&data[skipped..][..amt]);
+        self.filled += amt;
+        Ok(skipped + amt)
+    }
+}
+
+/// Error of a [`Window`], `Full` meaning that the window is complete
+#[cfg(feature = "embedded-io-async")]
+#[derive(Debug)]
+pub enum WindowError {
+    Full,
+    Serialization(&'static str),
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl Display for WindowError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Full => f.write_str("Window is full"),
+            Self::Serialization(cause) => f.write_str(cause),
+        }
+    }
+}
+
+#[cfg(feature = "embedded-io-async")]
+impl Error for WindowError {
+    fn failed_serialization(cause: &'static str) -> Self {
+        Self::Serialization(cause)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Writer for bytes::BytesMut {
+    type Error = SerializationError;
+    fn write(&mut self, data: &[u8]) -> Result<usize, SerializationError> {
+        self.extend_from_slice(data);
+        Ok(data.len())
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Backpatch for bytes::BytesMut {
+    fn written_mut(&mut self) -> &mut [u8] {
+        self
+    }
+    fn truncate(&mut self, len: usize) {
+        bytes::BytesMut::truncate(self, len)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl IntoWriter for bytes::BytesMut {
+    type Writer = Self;
+    fn into_writer(mut self, to_write: usize) -> Result<Self, SerializationError> {
+        self.reserve(to_write);
+        Ok(self)
+    }
+}
+
 pub trait IntoWriter {
     type Writer: Writer;
     fn into_writer(self, to_write: usize) -> Result<Self::Writer, <Self::Writer as Writer>::Error>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Fill;
+    use crate::tlv::{Tag, Tlv};
+
+    struct Full;
+
+    impl std::io::Write for Full {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Ok(0)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn io_writer() {
+        let mut out = Vec::new();
+        let tlv = Tlv::new(Tag::from_u8(0x53), [0x01, 0x02]);
+        tlv.to_writer(&mut IoWriter(&mut out)).unwrap();
+        assert_eq!(out, [0x53, 0x02, 0x01, 0x02]);
+
+        let error = tlv.to_writer(&mut IoWriter(Full)).unwrap_err();
+        assert!(
+            matches!(&error, IoError::Io(error) if error.kind() == std::io::ErrorKind::WriteZero)
+        );
+        assert!(IoWriter(Full).write(&[]).is_ok());
+        assert!(IoWriter(Full).write(&[0]).is_err());
+
+        let error = Tlv::new(Tag::from_u8(0x53), [0; 0x10000])
+            .to_writer(&mut IoWriter(&mut out))
+            .unwrap_err();
+        assert!(matches!(error, IoError::Serialization(_)));
+    }
+
+    #[test]
+    fn counting_writer() {
+        use crate::command::{CommandBuilder, ExpectedLen};
+        use crate::Instruction;
+
+        let command = CommandBuilder::new(
+            0x00.try_into().unwrap(),
+            Instruction::PutData,
+            0x3F,
+            0xFF,
+            Tlv::new(Tag::from_u8(0x53), [0x55; 300]),
+            ExpectedLen::Max,
+        );
+        let len = CountingWriter::measure(&command).unwrap();
+        assert_eq!(len, command.required_len());
+        assert_eq!(len, 7 + 4 + 300 + 2);
+
+        let mut writer = CountingWriter::new();
+        writer.write_all(&[0; 3]).unwrap();
+        writer.write_all(&[]).unwrap();
+        assert_eq!(writer.count(), 3);
+
+        let too_long = Tlv::new(Tag::from_u8(0x53), [0; 0x10000]);
+        assert!(CountingWriter::measure(&too_long).is_err());
+    }
+
+    #[test]
+    fn vectored() {
+        use crate::command::{CommandBuilder, ExpectedLen};
+        use crate::Instruction;
+
+        /// Records the number of slices of each call
+        #[derive(Default)]
+        struct Calls(Vec<usize>);
+
+        impl Writer for Calls {
+            type Error = SerializationError;
+            fn write(&mut self, data: &[u8]) -> Result<usize, SerializationError> {
+                self.0.push(1);
+                Ok(data.len())
+            }
+            fn write_all_vectored(&mut self, data: &[&[u8]]) -> Result<(), SerializationError> {
+                self.0.push(data.len());
+                Ok(())
+            }
+        }
+
+        let command = CommandBuilder::new(
+            0x00.try_into().unwrap(),
+            Instruction::Verify,
+            0x00,
+            0x81,
+            [0x31; 6],
+            ExpectedLen::Ne(0),
+        );
+        let mut calls = Calls::default();
+        command.to_writer(&mut calls).unwrap();
+        assert_eq!(calls.0, [2, 1]);
+
+        let mut writer = MapErr::new(Vec::new(), |error| error);
+        writer
+            .write_all_vectored(&[&[0x01], &[], &[0x02, 0x03]])
+            .unwrap();
+        assert_eq!(writer.into_inner(), [0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn tee_writer() {
+        let mut seen = Vec::new();
+        let mut buf = [0; 3];
+        let mut writer = TeeWriter::new(&mut buf[..], |data: &[u8]| seen.extend_from_slice(data));
+        assert!(writer.write_all(&[0x01, 0x02, 0x03, 0x04]).is_err());
+        assert_eq!(seen, [0x01, 0x02, 0x03]);
+        assert_eq!(buf, [0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn into_writer_by_ref() {
+        fn send(buf: impl IntoWriter, len: usize) -> bool {
+            let Ok(mut writer) = buf.into_writer(len) else {
+                return false;
+            };
+            writer.write_all(&vec![0; len]).is_ok()
+        }
+
+        let mut buf = heapless::Vec::<u8, 4>::new();
+        assert!(send(&mut buf, 3));
+        assert!(!send(&mut buf, 3));
+        buf.clear();
+        assert!(send(&mut buf, 4));
+
+        let mut buf = Vec::new();
+        assert!(send(&mut buf, 300));
+        assert!(send(&mut buf, 300));
+        assert_eq!(buf.len(), 600);
+
+        let mut buf = [0; 4];
+        let mut cursor = Cursor::new(&mut buf);
+        assert!(send(&mut cursor, 2));
+        assert!(!send(&mut cursor, 3));
+        assert!(!send(&mut MapErr::new(&mut cursor, |e| e), 3));
+        assert!(send(&mut cursor, 2));
+    }
+
+    #[test]
+    fn dyn_writer() {
+        let mut buf = [0; 4];
+        let mut cursor = Cursor::new(&mut buf);
+        let mut writer = DynWriter::new(&mut cursor);
+        assert_eq!(writer.remaining_len(), 4);
+        writer.write_all_vectored(&[&[1, 2], &[3]]).unwrap();
+        assert_eq!(writer.remaining_len(), 1);
+        assert_eq!(writer.write_all(&[4, 5]), Err(DynError::Writer));
+        assert_eq!(
+            writer.write_do(Tag::from_u8(0x53), &Fill { byte: 0, len: 0 }),
+            Err(DynError::Writer)
+        );
+        assert_eq!(cursor.written(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn cursor() {
+        use crate::tlv::StreamingTlv;
+
+        let mut buf = [0; 6];
+        let mut cursor = Cursor::new(&mut buf);
+        let tlv = StreamingTlv::start(&mut cursor, Tag::from_u8(0x53)).unwrap();
+        cursor.write_all(&[0x01]).unwrap();
+        assert!(cursor.write_all(&[0x02; 2]).is_err());
+        assert_eq!(cursor.remaining(), 0);
+        tlv.finish(&mut cursor).unwrap();
+        assert_eq!(cursor.written(), &[0x53, 0x02, 0x01, 0x02]);
+        assert!(Cursor::new(&mut [0; 2]).into_writer(3).is_err());
+
+        cursor.rewind(2);
+        let written = cursor.into_written();
+        written[1] = 0x00;
+        assert_eq!(written, &[0x53, 0x00]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn cursor_rewind_forward() {
+        let mut buf = [0; 6];
+        let mut cursor = Cursor::new(&mut buf);
+        cursor.rewind(1);
+    }
+
+    #[test]
+    fn frame_writer() {
+        let data: Vec<u8> = (0..10).collect();
+        let mut frames = Vec::new();
+        let mut writer = FrameWriter::<_, 5>::new(|frame: &[u8]| {
+            frames.push(frame.to_vec());
+            Ok::<(), ()>(())
+        });
+        writer.write_all(&data[..2]).unwrap();
+        writer.write_all(&data[2..]).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(frames, [&data[..5], &data[5..]]);
+
+        let mut count = 0;
+        let writer = FrameWriter::<_, 5>::new(|_: &[u8]| {
+            count += 1;
+            Ok::<(), ()>(())
+        });
+        writer.finish().unwrap();
+        assert_eq!(count, 0);
+
+        let mut writer = FrameWriter::<_, 2>::new(|_: &[u8]| Err("disconnected"));
+        assert!(matches!(
+            writer.write_all(&data),
+            Err(FrameError::Flush("disconnected"))
+        ));
+    }
+
+    #[cfg(feature = "arrayvec")]
+    #[test]
+    fn arrayvec() {
+        use crate::tlv::StreamingTlv;
+
+        let mut buf = arrayvec::ArrayVec::<u8, 6>::new();
+        let tlv = StreamingTlv::start(&mut buf, Tag::from_u8(0x53)).unwrap();
+        buf.write_all(&[0x01, 0x02]).unwrap();
+        tlv.finish(&mut buf).unwrap();
+        assert_eq!(&buf[..], &[0x53, 0x02, 0x01, 0x02]);
+        assert!(buf.write_all(&[0; 3]).is_err());
+        assert_eq!(buf.len(), 6);
+        assert!(buf.clone().into_writer(1).is_err());
+        buf.clear();
+        assert!(buf.into_writer(6).is_ok());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn bytes_mut() {
+        let tlv = Tlv::new(Tag::from_u8(0x53), [0x01, 0x02]);
+        let mut buf = bytes::BytesMut::new().into_writer(4).unwrap();
+        assert!(buf.capacity() >= 4);
+        tlv.to_writer(&mut buf).unwrap();
+        tlv.to_writer(&mut buf).unwrap();
+        assert_eq!(&buf[..], &[0x53, 0x02, 0x01, 0x02, 0x53, 0x02, 0x01, 0x02]);
+        let frozen = buf.split().freeze();
+        assert_eq!(frozen.len(), 8);
+        assert!(buf.is_empty());
+    }
+
+    #[cfg(feature = "embedded-io")]
+    #[test]
+    fn embedded_io_writer() {
+        let mut buf = [0; 4];
+        let tlv = Tlv::new(Tag::from_u8(0x53), [0x01, 0x02]);
+        tlv.to_writer(&mut EmbeddedIoWriter(&mut buf[..])).unwrap();
+        assert_eq!(buf, [0x53, 0x02, 0x01, 0x02]);
+        let error = tlv
+            .to_writer(&mut EmbeddedIoWriter(&mut buf[..3]))
+            .unwrap_err();
+        assert!(matches!(error, EmbeddedIoError::Io(_)));
+    }
+
+    #[cfg(all(feature = "embedded-io-async", feature = "async"))]
+    #[test]
+    fn embedded_io_async() {
+        use crate::transport::asynch::tests::block_on;
+
+        let tlv = Tlv::new(
+            Tag::from_u8(0x53),
+            (Tlv::new(Tag::from_u8(0x80), [0xAA; 20]), [0xBB; 9]),
+        );
+        let mut expected = Vec::new();
+        tlv.to_writer(&mut expected).unwrap();
+        for scratch_len in [1, 7, 32, 64] {
+            let mut buf = [0; 33];
+            let mut out = &mut buf[..];
+            let mut scratch = vec![0; scratch_len];
+            block_on(write_async(&mut out, &tlv, &mut scratch)).unwrap();
+            assert_eq!(&buf[..], &expected[..]);
+        }
+
+        let mut buf = [0; 32];
+        let mut out = &mut buf[..];
+        let result = block_on(write_async(&mut out, &tlv, &mut [0; 8]));
+        assert!(matches!(result, Err(EmbeddedIoError::Io(_))));
+    }
+}
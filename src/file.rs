@@ -0,0 +1,339 @@
+//! File identifiers and paths (ISO 7816-4 7.2)
+//!
+//! - [`FileId`] is a two-byte file identifier. `3FFF` and `FFFF` are reserved and rejected, `3F00`
+//!   always refers to the master file.
+//! - [`ShortEf`] is a short EF identifier, from 1 to 30, as used in P1 of READ BINARY.
+//! - [`Path`] is a sequence of file identifiers, either from the MF or from the current DF.
+//!
+//! ```
+//! use iso7816::file::{FileId, Path, ShortEf};
+//! let path = Path::from_bytes(&[0x3F, 0x00, 0x7F, 0x10, 0x6F, 0x3A]).unwrap();
+//! assert!(path.is_absolute());
+//! assert_eq!(path.select_p1(), 0x08);
+//! assert_eq!(path.ids(), [FileId::new(0x7F10).unwrap(), FileId::new(0x6F3A).unwrap()]);
+//! assert_eq!(ShortEf::new(0x1E).unwrap().read_binary_p1(), 0x9E);
+//! assert!(FileId::new(0x3FFF).is_err());
+//! ```
+
+pub mod fci;
+
+use crate::command::{DataSource, DataStream, Writer};
+
+/// Largest number of file identifiers in a [`Path`], not counting the MF or current DF
+pub const MAX_PATH_LEN: usize = 8;
+
+const CURRENT_DF: u16 = 0x3FFF;
+const RFU: u16 = 0xFFFF;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileError {
+    /// `3FFF` and `FFFF` can't be used as file identifiers
+    ReservedFileId(u16),
+    /// Short EF identifiers range from 1 to 30
+    InvalidShortEf(u8),
+    /// The path has an odd length, is too long, or has the MF or current DF after its first element
+    InvalidPath,
+}
+
+/// File identifier
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u16);
+
+impl FileId {
+    /// Master file
+    pub const MF: Self = Self(0x3F00);
+
+    pub const fn new(id: u16) -> Result<Self, FileError> {
+        match id {
+            CURRENT_DF | RFU => Err(FileError::ReservedFileId(id)),
+            _ => Ok(Self(id)),
+        }
+    }
+
+    pub const fn get(self) -> u16 {
+        self.0
+    }
+
+    pub const fn to_bytes(self) -> [u8; 2] {
+        self.0.to_be_bytes()
+    }
+
+    pub const fn is_mf(self) -> bool {
+        self.0 == Self::MF.0
+    }
+}
+
+impl TryFrom<u16> for FileId {
+    type Error = FileError;
+
+    fn try_from(id: u16) -> Result<Self, Self::Error> {
+        Self::new(id)
+    }
+}
+
+impl TryFrom<[u8; 2]> for FileId {
+    type Error = FileError;
+
+    fn try_from(id: [u8; 2]) -> Result<Self, Self::Error> {
+        Self::new(u16::from_be_bytes(id))
+    }
+}
+
+impl From<FileId> for u16 {
+    fn from(id: FileId) -> Self {
+        id.0
+    }
+}
+
+impl DataSource for FileId {
+    fn len(&self) -> usize {
+        2
+    }
+
+    fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+impl<W: Writer> DataStream<W> for FileId {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        writer.write_all(&self.to_bytes())
+    }
+}
+
+impl DataSource for [FileId] {
+    fn len(&self) -> usize {
+        2 * <[FileId]>::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        <[FileId]>::is_empty(self)
+    }
+}
+
+impl<W: Writer> DataStream<W> for [FileId] {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        self.iter().try_for_each(|id| id.to_writer(writer))
+    }
+}
+
+/// Short EF identifier
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShortEf(u8);
+
+impl ShortEf {
+    pub const fn new(id: u8) -> Result<Self, FileError> {
+        match id {
+            1..=30 => Ok(Self(id)),
+            _ => Err(FileError::InvalidShortEf(id)),
+        }
+    }
+
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+
+    /// P1 of READ BINARY and UPDATE BINARY selecting the EF: `100` followed by the identifier
+    pub const fn read_binary_p1(self) -> u8 {
+        0x80 | self.0
+    }
+
+    /// Short EF identifier of P1 of READ BINARY and UPDATE BINARY, if P1 selects an EF
+    pub const fn from_read_binary_p1(p1: u8) -> Option<Self> {
+        if p1 & 0xE0 != 0x80 {
+            return None;
+        }
+        match Self::new(p1 & 0x1F) {
+            Ok(id) => Some(id),
+            Err(_) => None,
+        }
+    }
+}
+
+impl TryFrom<u8> for ShortEf {
+    type Error = FileError;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        Self::new(id)
+    }
+}
+
+impl From<ShortEf> for u8 {
+    fn from(id: ShortEf) -> Self {
+        id.0
+    }
+}
+
+/// Path to a file, from the MF or from the current DF
+///
+/// The MF and current DF identifiers are not stored in [`ids`](Self::ids).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Path {
+    absolute: bool,
+    ids: heapless::Vec<FileId, MAX_PATH_LEN>,
+}
+
+impl Path {
+    fn new(absolute: bool, ids: &[FileId]) -> Result<Self, FileError> {
+        if ids.iter().any(|id| id.is_mf()) {
+            return Err(FileError::InvalidPath);
+        }
+        let ids = heapless::Vec::from_slice(ids).map_err(|_| FileError::InvalidPath)?;
+        Ok(Self { absolute, ids })
+    }
+
+    /// Path from the MF, `ids` not including the MF
+    pub fn from_mf(ids: &[FileId]) -> Result<Self, FileError> {
+        Self::new(true, ids)
+    }
+
+    /// Path from the current DF, `ids` not including the current DF
+    pub fn from_current_df(ids: &[FileId]) -> Result<Self, FileError> {
+        Self::new(false, ids)
+    }
+
+    /// Parse a concatenation of file identifiers.
+    ///
+    /// The path is absolute if it starts with `3F00`. A leading `3FFF` is skipped.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FileError> {
+        if bytes.len() % 2 != 0 {
+            return Err(FileError::InvalidPath);
+        }
+        let mut chunks = bytes.chunks_exact(2).peekable();
+        let first = chunks.peek().map(|c| u16::from_be_bytes([c[0], c[1]]));
+        let absolute = first == Some(FileId::MF.0);
+        if absolute || first == Some(CURRENT_DF) {
+            chunks.next();
+        }
+        let mut path = Self {
+            absolute,
+            ids: heapless::Vec::new(),
+        };
+        for chunk in chunks {
+            let id = FileId::try_from([chunk[0], chunk[1]]).map_err(|_| FileError::InvalidPath)?;
+            if id.is_mf() {
+                return Err(FileError::InvalidPath);
+            }
+            path.ids.push(id).map_err(|_| FileError::InvalidPath)?;
+        }
+        Ok(path)
+    }
+
+    pub fn is_absolute(&self) -> bool {
+        self.absolute
+    }
+
+    pub fn ids(&self) -> &[FileId] {
+        &self.ids
+    }
+
+    /// P1 of SELECT by path: `08` from the MF, `09` from the current DF
+    pub fn select_p1(&self) -> u8 {
+        if self.absolute {
+            0x08
+        } else {
+            0x09
+        }
+    }
+
+    /// Data field of SELECT by path, without the MF or current DF identifier
+    pub fn select_data(&self) -> &[FileId] {
+        &self.ids
+    }
+}
+
+/// Full path, starting with `3F00` if it is absolute
+impl DataSource for Path {
+    fn len(&self) -> usize {
+        2 * (usize::from(self.absolute) + self.ids.len())
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.absolute && self.ids.is_empty()
+    }
+}
+
+impl<W: Writer> DataStream<W> for Path {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        if self.absolute {
+            FileId::MF.to_writer(writer)?;
+        }
+        self.ids().to_writer(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    fn id(id: u16) -> FileId {
+        FileId::new(id).unwrap()
+    }
+
+    #[test]
+    fn file_id() {
+        assert_eq!(FileId::try_from(0x3F00), Ok(FileId::MF));
+        assert_eq!(
+            FileId::try_from(0x3FFF),
+            Err(FileError::ReservedFileId(0x3FFF))
+        );
+        assert_eq!(
+            FileId::try_from([0xFF, 0xFF]),
+            Err(FileError::ReservedFileId(0xFFFF))
+        );
+        assert_eq!(u16::from(id(0x2F00)), 0x2F00);
+    }
+
+    #[test]
+    fn short_ef() {
+        assert_eq!(ShortEf::new(0), Err(FileError::InvalidShortEf(0)));
+        assert_eq!(ShortEf::new(31), Err(FileError::InvalidShortEf(31)));
+        let sfi = ShortEf::new(1).unwrap();
+        assert_eq!(sfi.read_binary_p1(), 0x81);
+        assert_eq!(ShortEf::from_read_binary_p1(0x81), Some(sfi));
+        assert_eq!(ShortEf::from_read_binary_p1(0x01), None);
+        assert_eq!(ShortEf::from_read_binary_p1(0xA1), None);
+        assert_eq!(ShortEf::from_read_binary_p1(0x9F), None);
+    }
+
+    #[test]
+    fn path() {
+        let path = Path::from_mf(&[id(0x7F10), id(0x6F3A)]).unwrap();
+        assert_eq!(Path::from_bytes(&hex!("3F00 7F10 6F3A")), Ok(path.clone()));
+        let mut buffer = Vec::new();
+        path.to_writer(&mut buffer).unwrap();
+        assert_eq!(buffer, hex!("3F00 7F10 6F3A"));
+        assert_eq!(path.len(), 6);
+        buffer.clear();
+        path.select_data().to_writer(&mut buffer).unwrap();
+        assert_eq!(buffer, hex!("7F10 6F3A"));
+
+        let path = Path::from_bytes(&hex!("3FFF 5015")).unwrap();
+        assert!(!path.is_absolute());
+        assert_eq!(path.select_p1(), 0x09);
+        assert_eq!(path, Path::from_bytes(&hex!("5015")).unwrap());
+        assert_eq!(path, Path::from_current_df(&[id(0x5015)]).unwrap());
+
+        assert_eq!(
+            Path::from_bytes(&hex!("3F00 7F")),
+            Err(FileError::InvalidPath)
+        );
+        assert_eq!(
+            Path::from_bytes(&hex!("7F10 3F00")),
+            Err(FileError::InvalidPath)
+        );
+        assert_eq!(
+            Path::from_bytes(&hex!("3F00 3FFF")),
+            Err(FileError::InvalidPath)
+        );
+        assert_eq!(
+            Path::from_bytes(&[0x50; 2 * MAX_PATH_LEN + 2]),
+            Err(FileError::InvalidPath)
+        );
+        assert_eq!(
+            Path::from_current_df(&[FileId::MF]),
+            Err(FileError::InvalidPath)
+        );
+    }
+}
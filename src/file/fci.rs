@@ -0,0 +1,753 @@
+//! File control information templates (ISO 7816-4 7.4)
+//!
+//! [`FciBuilder`] writes the `6F` (FCI), `62` (FCP) or `64` (FMD) template returned by SELECT,
+//! with the common file control parameters:
+//!
+//! | Tag  | Parameter                                      |
+//! |------|------------------------------------------------|
+//! | `80` | Number of data bytes in the file               |
+//! | `81` | Number of bytes in the file, including structural information |
+//! | `82` | [`FileDescriptor`]                             |
+//! | `83` | [`FileId`]                                     |
+//! | `84` | DF name                                        |
+//! | `88` | [`ShortEf`]                                    |
+//! | `8A` | [`LifeCycle`] status                           |
+//! | `A5` | Proprietary BER-TLV data objects               |
+//!
+//! [`Fci`] parses the same templates on the host side, the data objects it does not interpret
+//! being available through [`Fci::unknown`].
+//!
+//! ```
+//! use iso7816::command::DataStream;
+//! use iso7816::file::fci::{FciBuilder, FileDescriptor, LifeCycle, Template};
+//! use iso7816::file::FileId;
+//! let mut fcp = heapless::Vec::<u8, 32>::new();
+//! FciBuilder::new(Template::Fcp)
+//!     .file_size(0x100)
+//!     .descriptor(FileDescriptor::TRANSPARENT)
+//!     .file_id(FileId::new(0x2F00).unwrap())
+//!     .life_cycle(LifeCycle::Activated)
+//!     .to_writer(&mut fcp)
+//!     .unwrap();
+//! assert_eq!(
+//!     &*fcp,
+//!     &[0x62, 0x0E, 0x80, 0x02, 0x01, 0x00, 0x82, 0x01, 0x01, 0x83, 0x02, 0x2F, 0x00, 0x8A, 0x01, 0x05],
+//! );
+//! ```
+
+use super::{FileId, ShortEf};
+use crate::command::{DataSource, DataStream, Writer};
+use crate::tlv::{take_data_object, Tag, Tlv};
+
+/// Number of data bytes in the file
+pub const FILE_SIZE: u8 = 0x80;
+/// Number of bytes in the file, including structural information
+pub const TOTAL_FILE_SIZE: u8 = 0x81;
+pub const FILE_DESCRIPTOR: u8 = 0x82;
+pub const FILE_ID: u8 = 0x83;
+pub const DF_NAME: u8 = 0x84;
+pub const SHORT_EF: u8 = 0x88;
+pub const LIFE_CYCLE: u8 = 0x8A;
+/// Proprietary information encoded in BER-TLV
+pub const PROPRIETARY: u8 = 0xA5;
+/// Proprietary information not encoded in BER-TLV
+pub const PROPRIETARY_PRIMITIVE: u8 = 0x85;
+/// Security attributes in proprietary format
+pub const SECURITY_PROPRIETARY: u8 = 0x86;
+/// Security attributes referencing the expanded format
+pub const SECURITY_REFERENCED: u8 = 0x8B;
+/// Security attributes in compact format
+pub const SECURITY_COMPACT: u8 = 0x8C;
+/// Security attributes in expanded format
+pub const SECURITY_EXPANDED: u8 = 0xAB;
+
+/// Template returned by SELECT
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Template {
+    /// File control information (`6F`)
+    Fci,
+    /// File control parameters (`62`)
+    Fcp,
+    /// File management data (`64`)
+    Fmd,
+}
+
+impl Template {
+    pub const fn tag(self) -> u8 {
+        match self {
+            Self::Fci => 0x6F,
+            Self::Fcp => 0x62,
+            Self::Fmd => 0x64,
+        }
+    }
+
+    pub const fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0x6F => Some(Self::Fci),
+            0x62 => Some(Self::Fcp),
+            0x64 => Some(Self::Fmd),
+            _ => None,
+        }
+    }
+}
+
+/// File descriptor (`82`): file descriptor byte, data coding byte and record sizes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileDescriptor {
+    byte: u8,
+    data_coding: Option<u8>,
+    max_record_size: Option<u16>,
+    records: Option<u16>,
+}
+
+impl FileDescriptor {
+    /// Data coding byte used when record sizes are given without a data coding byte
+    pub const DEFAULT_DATA_CODING: u8 = 0x21;
+
+    pub const DF: Self = Self::new(0x38);
+    pub const TRANSPARENT: Self = Self::new(0x01);
+    pub const LINEAR_FIXED: Self = Self::new(0x02);
+    pub const LINEAR_VARIABLE: Self = Self::new(0x04);
+    pub const CYCLIC: Self = Self::new(0x06);
+
+    pub const fn new(byte: u8) -> Self {
+        Self {
+            byte,
+            data_coding: None,
+            max_record_size: None,
+            records: None,
+        }
+    }
+
+    /// Set the shareable bit of the file descriptor byte
+    pub const fn shareable(mut self) -> Self {
+        self.byte |= 0x40;
+        self
+    }
+
+    pub const fn with_data_coding(mut self, data_coding: u8) -> Self {
+        self.data_coding = Some(data_coding);
+        self
+    }
+
+    /// Maximum record size and number of records of a record file
+    pub const fn with_records(mut self, max_record_size: u16, records: u16) -> Self {
+        self.max_record_size = Some(max_record_size);
+        self.records = Some(records);
+        self
+    }
+
+    pub const fn byte(&self) -> u8 {
+        self.byte
+    }
+
+    pub const fn data_coding(&self) -> Option<u8> {
+        self.data_coding
+    }
+
+    pub const fn max_record_size(&self) -> Option<u16> {
+        self.max_record_size
+    }
+
+    pub const fn records(&self) -> Option<u16> {
+        self.records
+    }
+
+    pub const fn is_df(&self) -> bool {
+        self.byte & 0xBF == 0x38
+    }
+
+    pub const fn is_shareable(&self) -> bool {
+        self.byte & 0x40 != 0
+    }
+
+    /// Parse the value of the `82` data object
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&byte, rest) = bytes.split_first()?;
+        let mut descriptor = Self::new(byte);
+        let Some((&data_coding, rest)) = rest.split_first() else {
+            return Some(descriptor);
+        };
+        descriptor.data_coding = Some(data_coding);
+        let (size, records) = match *rest {
+            [] => (None, None),
+            [size] => (Some(size.into()), None),
+            [s1, s2] => (Some(u16::from_be_bytes([s1, s2])), None),
+            [s1, s2, r] => (Some(u16::from_be_bytes([s1, s2])), Some(r.into())),
+            [s1, s2, r1, r2] => (
+                Some(u16::from_be_bytes([s1, s2])),
+                Some(u16::from_be_bytes([r1, r2])),
+            ),
+            _ => return None,
+        };
+        descriptor.max_record_size = size;
+        descriptor.records = records;
+        Some(descriptor)
+    }
+
+    fn to_bytes(self) -> heapless::Vec<u8, 6> {
+        let mut bytes = heapless::Vec::new();
+        bytes.push(self.byte).ok();
+        if self.data_coding.is_none() && self.max_record_size.is_none() {
+            return bytes;
+        }
+        bytes
+            .push(self.data_coding.unwrap_or(Self::DEFAULT_DATA_CODING))
+            .ok();
+        if let Some(size) = self.max_record_size {
+            bytes.extend_from_slice(&size.to_be_bytes()).ok();
+        }
+        if let Some(records) = self.records {
+            match u8::try_from(records) {
+                Ok(records) => bytes.push(records).ok(),
+                Err(_) => bytes.extend_from_slice(&records.to_be_bytes()).ok(),
+            };
+        }
+        bytes
+    }
+}
+
+impl From<u8> for FileDescriptor {
+    fn from(byte: u8) -> Self {
+        Self::new(byte)
+    }
+}
+
+impl DataSource for FileDescriptor {
+    fn len(&self) -> usize {
+        self.to_bytes().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+impl<W: Writer> DataStream<W> for FileDescriptor {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        writer.write_all(&self.to_bytes())
+    }
+}
+
+/// Life cycle status (`8A`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LifeCycle {
+    NoInformation,
+    Creation,
+    Initialisation,
+    /// Operational state, activated
+    Activated,
+    /// Operational state, deactivated
+    Deactivated,
+    Termination,
+    Proprietary(u8),
+}
+
+impl From<u8> for LifeCycle {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => Self::NoInformation,
+            0x01 => Self::Creation,
+            0x03 => Self::Initialisation,
+            0x05 | 0x07 => Self::Activated,
+            0x04 | 0x06 => Self::Deactivated,
+            0x0C..=0x0F => Self::Termination,
+            _ => Self::Proprietary(value),
+        }
+    }
+}
+
+impl From<LifeCycle> for u8 {
+    fn from(value: LifeCycle) -> Self {
+        match value {
+            LifeCycle::NoInformation => 0x00,
+            LifeCycle::Creation => 0x01,
+            LifeCycle::Initialisation => 0x03,
+            LifeCycle::Activated => 0x05,
+            LifeCycle::Deactivated => 0x04,
+            LifeCycle::Termination => 0x0C,
+            LifeCycle::Proprietary(value) => value,
+        }
+    }
+}
+
+/// Builder for the FCI, FCP and FMD templates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FciBuilder<'a> {
+    template: Template,
+    file_size: Option<u16>,
+    total_file_size: Option<u16>,
+    descriptor: Option<FileDescriptor>,
+    file_id: Option<FileId>,
+    df_name: Option<&'a [u8]>,
+    short_ef: Option<Option<ShortEf>>,
+    life_cycle: Option<LifeCycle>,
+    proprietary: Option<&'a [u8]>,
+}
+
+impl<'a> FciBuilder<'a> {
+    pub fn new(template: Template) -> Self {
+        Self {
+            template,
+            file_size: None,
+            total_file_size: None,
+            descriptor: None,
+            file_id: None,
+            df_name: None,
+            short_ef: None,
+            life_cycle: None,
+            proprietary: None,
+        }
+    }
+
+    /// Number of data bytes in the file (`80`)
+    pub fn file_size(mut self, size: u16) -> Self {
+        self.file_size = Some(size);
+        self
+    }
+
+    /// Number of bytes in the file, including structural information (`81`)
+    pub fn total_file_size(mut self, size: u16) -> Self {
+        self.total_file_size = Some(size);
+        self
+    }
+
+    pub fn descriptor(mut self, descriptor: FileDescriptor) -> Self {
+        self.descriptor = Some(descriptor);
+        self
+    }
+
+    pub fn file_id(mut self, file_id: FileId) -> Self {
+        self.file_id = Some(file_id);
+        self
+    }
+
+    pub fn df_name(mut self, df_name: &'a [u8]) -> Self {
+        self.df_name = Some(df_name);
+        self
+    }
+
+    /// Short EF identifier (`88`), `None` indicating that the EF has no short identifier
+    pub fn short_ef(mut self, short_ef: Option<ShortEf>) -> Self {
+        self.short_ef = Some(short_ef);
+        self
+    }
+
+    pub fn life_cycle(mut self, life_cycle: LifeCycle) -> Self {
+        self.life_cycle = Some(life_cycle);
+        self
+    }
+
+    /// Value of the proprietary template (`A5`), already encoded in BER-TLV
+    pub fn proprietary(mut self, data: &'a [u8]) -> Self {
+        self.proprietary = Some(data);
+        self
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn template(
+        &self,
+    ) -> Tlv<(
+        Option<Tlv<[u8; 2]>>,
+        Option<Tlv<[u8; 2]>>,
+        Option<Tlv<FileDescriptor>>,
+        Option<Tlv<FileId>>,
+        Option<Tlv<&'a [u8]>>,
+        Option<Tlv<Option<[u8; 1]>>>,
+        Option<Tlv<[u8; 1]>>,
+        Option<Tlv<&'a [u8]>>,
+    )> {
+        fn tlv<T: DataSource>(tag: u8, value: Option<T>) -> Option<Tlv<T>> {
+            value.map(|value| Tlv::new(Tag::from_u8(tag), value))
+        }
+        // The short EF identifier is in bits 8 to 4, an empty value meaning there is none
+        let short_ef = self
+            .short_ef
+            .map(|short_ef| short_ef.map(|short_ef| [short_ef.get() << 3]));
+        Tlv::new(
+            Tag::from_u8(self.template.tag()),
+            (
+                tlv(FILE_SIZE, self.file_size.map(u16::to_be_bytes)),
+                tlv(TOTAL_FILE_SIZE, self.total_file_size.map(u16::to_be_bytes)),
+                tlv(FILE_DESCRIPTOR, self.descriptor),
+                tlv(FILE_ID, self.file_id),
+                tlv(DF_NAME, self.df_name),
+                tlv(SHORT_EF, short_ef),
+                tlv(LIFE_CYCLE, self.life_cycle.map(|l| [l.into()])),
+                tlv(PROPRIETARY, self.proprietary),
+            ),
+        )
+    }
+}
+
+impl DataSource for FciBuilder<'_> {
+    fn len(&self) -> usize {
+        self.template().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+impl<W: Writer> DataStream<W> for FciBuilder<'_> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        self.template().to_writer(writer)
+    }
+}
+
+/// Security attributes of a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecurityAttributes<'a> {
+    /// `86`
+    Proprietary(&'a [u8]),
+    /// `8B`: identifier of an EF.ARR and record numbers
+    Referenced(&'a [u8]),
+    /// `8C`: access mode byte followed by security condition bytes
+    Compact(&'a [u8]),
+    /// `AB`: access mode and security condition data objects
+    Expanded(&'a [u8]),
+}
+
+impl<'a> SecurityAttributes<'a> {
+    fn from_do(tag: Tag, value: &'a [u8]) -> Option<Self> {
+        match Fci::known(tag)? {
+            SECURITY_PROPRIETARY => Some(Self::Proprietary(value)),
+            SECURITY_REFERENCED => Some(Self::Referenced(value)),
+            SECURITY_COMPACT => Some(Self::Compact(value)),
+            SECURITY_EXPANDED => Some(Self::Expanded(value)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FciError {
+    /// The response is not a single `6F`, `62` or `64` template
+    InvalidTemplate,
+    /// A data object of the template is not valid BER-TLV or has an invalid value
+    InvalidData,
+}
+
+/// Iterator over the data objects of a template
+#[derive(Clone)]
+struct DataObjects<'a>(&'a [u8]);
+
+impl<'a> Iterator for DataObjects<'a> {
+    type Item = (Tag, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (tag, value, rest) = take_data_object(self.0)?;
+        self.0 = rest;
+        Some((tag, value))
+    }
+}
+
+/// Big endian integer of at most 4 bytes
+fn integer(value: &[u8]) -> Result<u32, FciError> {
+    if value.is_empty() || value.len() > 4 {
+        return Err(FciError::InvalidData);
+    }
+    Ok(value.iter().fold(0, |acc, &b| acc << 8 | u32::from(b)))
+}
+
+/// Parsed FCI, FCP or FMD template
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fci<'a> {
+    template: Template,
+    data: &'a [u8],
+    file_size: Option<u32>,
+    total_file_size: Option<u32>,
+    descriptor: Option<FileDescriptor>,
+    file_id: Option<FileId>,
+    df_name: Option<&'a [u8]>,
+    short_ef: Option<Option<ShortEf>>,
+    life_cycle: Option<LifeCycle>,
+    proprietary: Option<&'a [u8]>,
+}
+
+impl<'a> Fci<'a> {
+    const KNOWN: [u8; 13] = [
+        FILE_SIZE,
+        TOTAL_FILE_SIZE,
+        FILE_DESCRIPTOR,
+        FILE_ID,
+        DF_NAME,
+        PROPRIETARY_PRIMITIVE,
+        SHORT_EF,
+        LIFE_CYCLE,
+        PROPRIETARY,
+        SECURITY_PROPRIETARY,
+        SECURITY_REFERENCED,
+        SECURITY_COMPACT,
+        SECURITY_EXPANDED,
+    ];
+
+    /// Parse the response data of SELECT
+    pub fn parse(response: &'a [u8]) -> Result<Self, FciError> {
+        let (tag, data, rest) = take_data_object(response).ok_or(FciError::InvalidTemplate)?;
+        let template = [Template::Fci, Template::Fcp, Template::Fmd]
+            .into_iter()
+            .find(|template| Tag::from_u8(template.tag()) == tag)
+            .filter(|_| rest.is_empty())
+            .ok_or(FciError::InvalidTemplate)?;
+
+        let mut fci = Self {
+            template,
+            data,
+            file_size: None,
+            total_file_size: None,
+            descriptor: None,
+            file_id: None,
+            df_name: None,
+            short_ef: None,
+            life_cycle: None,
+            proprietary: None,
+        };
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (tag, value, next) = take_data_object(rest).ok_or(FciError::InvalidData)?;
+            rest = next;
+            let Some(tag) = Self::known(tag) else {
+                continue;
+            };
+            match tag {
+                FILE_SIZE => fci.file_size = Some(integer(value)?),
+                TOTAL_FILE_SIZE => fci.total_file_size = Some(integer(value)?),
+                FILE_DESCRIPTOR => {
+                    fci.descriptor =
+                        Some(FileDescriptor::from_bytes(value).ok_or(FciError::InvalidData)?)
+                }
+                FILE_ID => {
+                    let id: [u8; 2] = value.try_into().map_err(|_| FciError::InvalidData)?;
+                    fci.file_id = Some(FileId::try_from(id).map_err(|_| FciError::InvalidData)?);
+                }
+                DF_NAME => fci.df_name = Some(value),
+                SHORT_EF => {
+                    fci.short_ef = Some(match *value {
+                        [] => None,
+                        [id] => Some(ShortEf::new(id >> 3).map_err(|_| FciError::InvalidData)?),
+                        _ => return Err(FciError::InvalidData),
+                    })
+                }
+                LIFE_CYCLE => match *value {
+                    [status] => fci.life_cycle = Some(status.into()),
+                    _ => return Err(FciError::InvalidData),
+                },
+                PROPRIETARY => fci.proprietary = Some(value),
+                _ => {}
+            }
+        }
+        Ok(fci)
+    }
+
+    /// One-byte tag interpreted by this parser
+    fn known(tag: Tag) -> Option<u8> {
+        Self::KNOWN
+            .into_iter()
+            .find(|&known| Tag::from_u8(known) == tag)
+    }
+
+    pub fn template(&self) -> Template {
+        self.template
+    }
+
+    /// Content of the template
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Number of data bytes in the file (`80`)
+    pub fn file_size(&self) -> Option<u32> {
+        self.file_size
+    }
+
+    /// Number of bytes in the file, including structural information (`81`)
+    pub fn total_file_size(&self) -> Option<u32> {
+        self.total_file_size
+    }
+
+    pub fn descriptor(&self) -> Option<FileDescriptor> {
+        self.descriptor
+    }
+
+    pub fn file_id(&self) -> Option<FileId> {
+        self.file_id
+    }
+
+    pub fn df_name(&self) -> Option<&'a [u8]> {
+        self.df_name
+    }
+
+    /// Short EF identifier (`88`)
+    ///
+    /// `Some(None)` indicates that the EF has no short identifier. If the data object is absent,
+    /// the short identifier is usually given by the five low bits of the file identifier.
+    pub fn short_ef(&self) -> Option<Option<ShortEf>> {
+        self.short_ef
+    }
+
+    pub fn life_cycle(&self) -> Option<LifeCycle> {
+        self.life_cycle
+    }
+
+    /// Content of the proprietary template (`A5`)
+    pub fn proprietary(&self) -> Option<&'a [u8]> {
+        self.proprietary
+    }
+
+    /// Security attributes, in the order of the template
+    pub fn security_attributes(&self) -> impl Iterator<Item = SecurityAttributes<'a>> + 'a {
+        DataObjects(self.data).filter_map(|(tag, value)| SecurityAttributes::from_do(tag, value))
+    }
+
+    /// Data objects not interpreted by this parser
+    pub fn unknown(&self) -> impl Iterator<Item = (Tag, &'a [u8])> + 'a {
+        DataObjects(self.data).filter(|&(tag, _)| Self::known(tag).is_none())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    fn build(builder: FciBuilder<'_>) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        builder.to_writer(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), builder.len());
+        buffer
+    }
+
+    #[test]
+    fn fci() {
+        assert_eq!(
+            build(
+                FciBuilder::new(Template::Fci)
+                    .df_name(&hex!("A000000308000010000100"))
+                    .proprietary(&hex!("4F06000010000100"))
+            ),
+            hex!("6F 17 84 0B A000000308000010000100 A5 08 4F06000010000100")
+        );
+        assert_eq!(build(FciBuilder::new(Template::Fmd)), hex!("64 00"));
+    }
+
+    #[test]
+    fn fcp() {
+        assert_eq!(
+            build(
+                FciBuilder::new(Template::Fcp)
+                    .descriptor(FileDescriptor::LINEAR_FIXED.with_records(0x1C, 10))
+                    .file_id(FileId::new(0x6F3A).unwrap())
+                    .total_file_size(280)
+                    .short_ef(Some(ShortEf::new(0x1E).unwrap()))
+                    .life_cycle(LifeCycle::Deactivated)
+            ),
+            hex!("62 15 81 02 0118 82 05 0221001C0A 83 02 6F3A 88 01 F0 8A 01 04")
+        );
+        assert_eq!(
+            build(
+                FciBuilder::new(Template::Fcp)
+                    .descriptor(FileDescriptor::DF.shareable())
+                    .short_ef(None)
+            ),
+            hex!("62 05 82 01 78 88 00")
+        );
+    }
+
+    #[test]
+    fn descriptor() {
+        let descriptor = FileDescriptor::CYCLIC
+            .with_data_coding(0x41)
+            .with_records(0x100, 0x200);
+        assert_eq!(&*descriptor.to_bytes(), &hex!("06 41 0100 0200"));
+        assert_eq!(
+            FileDescriptor::from_bytes(&hex!("06 41 0100 0200")),
+            Some(descriptor)
+        );
+        assert_eq!(
+            FileDescriptor::from_bytes(&hex!("02 21 1C")),
+            Some(FileDescriptor {
+                byte: 0x02,
+                data_coding: Some(0x21),
+                max_record_size: Some(0x1C),
+                records: None,
+            })
+        );
+        assert!(FileDescriptor::from_bytes(&hex!("78")).unwrap().is_df());
+        assert_eq!(FileDescriptor::from_bytes(&[]), None);
+        assert_eq!(
+            FileDescriptor::from_bytes(&hex!("02 21 0001 0002 03")),
+            None
+        );
+        assert_eq!(LifeCycle::from(0x07), LifeCycle::Activated);
+        assert_eq!(u8::from(LifeCycle::Termination), 0x0C);
+    }
+
+    #[test]
+    fn parse() {
+        let response = hex!(
+            "62 23 82 01 01 83 02 2F00 80 02 0100 88 01 F0 8A 01 05 8C 03 03 00 FF
+             A5 03 C0 01 01 C2 02 AABB 9F 65 01 FF"
+        );
+        let fci = Fci::parse(&response).unwrap();
+        assert_eq!(fci.template(), Template::Fcp);
+        assert_eq!(fci.descriptor(), Some(FileDescriptor::TRANSPARENT));
+        assert_eq!(fci.file_id(), Some(FileId::new(0x2F00).unwrap()));
+        assert_eq!(fci.file_size(), Some(0x100));
+        assert_eq!(fci.total_file_size(), None);
+        assert_eq!(fci.short_ef(), Some(Some(ShortEf::new(0x1E).unwrap())));
+        assert_eq!(fci.life_cycle(), Some(LifeCycle::Activated));
+        assert_eq!(fci.proprietary(), Some(&hex!("C00101")[..]));
+        assert_eq!(fci.df_name(), None);
+        assert_eq!(
+            fci.security_attributes().collect::<Vec<_>>(),
+            [SecurityAttributes::Compact(&hex!("0300FF"))]
+        );
+        assert_eq!(
+            fci.unknown().collect::<Vec<_>>(),
+            [
+                (Tag::from_u8(0xC2), &hex!("AABB")[..]),
+                (Tag::from_u16(0x9F65), &hex!("FF")[..])
+            ]
+        );
+
+        // Round trip with the builder
+        let mut buffer = Vec::new();
+        FciBuilder::new(Template::Fci)
+            .df_name(&hex!("A0000005271002"))
+            .short_ef(None)
+            .to_writer(&mut buffer)
+            .unwrap();
+        let fci = Fci::parse(&buffer).unwrap();
+        assert_eq!(fci.template(), Template::Fci);
+        assert_eq!(fci.df_name(), Some(&hex!("A0000005271002")[..]));
+        assert_eq!(fci.short_ef(), Some(None));
+        assert_eq!(fci.unknown().count(), 0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Fci::parse(&[]), Err(FciError::InvalidTemplate));
+        assert_eq!(
+            Fci::parse(&hex!("70 03 83 01 00")),
+            Err(FciError::InvalidTemplate)
+        );
+        assert_eq!(
+            Fci::parse(&hex!("62 00 9000")),
+            Err(FciError::InvalidTemplate)
+        );
+        assert_eq!(
+            Fci::parse(&hex!("62 03 83 01 00")),
+            Err(FciError::InvalidData)
+        );
+        assert_eq!(
+            Fci::parse(&hex!("62 03 80 02 01")),
+            Err(FciError::InvalidData)
+        );
+        assert_eq!(
+            Fci::parse(&hex!("62 07 80 05 0102030405")),
+            Err(FciError::InvalidData)
+        );
+    }
+}
@@ -0,0 +1,882 @@
+//! Card-side virtual file system
+//!
+//! [`FileSystem`] processes SELECT, READ BINARY, UPDATE BINARY, READ RECORD, UPDATE RECORD and
+//! APPEND RECORD over a tree of [`File`]s, the content of the EFs being kept by a [`Storage`].
+//!
+//! The tree is a slice of [`File`]s, each referencing its parent DF by its index in the slice, the
+//! first file being the MF. The [`Storage`] identifies the EFs by the same index.
+//!
+//! Errors are reported with the status words of ISO 7816-4, among which:
+//!
+//! | Status | Meaning                                                              |
+//! |--------|----------------------------------------------------------------------|
+//! | `6A82` | File not found                                                       |
+//! | `6982` | The access condition of the file is not satisfied                    |
+//! | `6B00` | Offset beyond the end of the file                                    |
+//! | `6282` | End of file reached before reading Le bytes, the data read is kept   |
+//! | `6981` | The command is incompatible with the file structure                  |
+//! | `6986` | No EF is selected                                                    |
+//! | `6A83` | Record not found                                                     |
+//! | `6A84` | The file is full                                                     |
+//!
+//! ```
+//! use iso7816::command::CommandView;
+//! use iso7816::file::{FileId, ShortEf};
+//! use iso7816::filesystem::{File, FileSystem, Storage};
+//! use iso7816::Data;
+//!
+//! struct Version;
+//!
+//! impl Storage for Version {
+//!     fn len(&self, _file: usize) -> usize {
+//!         2
+//!     }
+//!     fn read(&self, _file: usize, offset: usize, buffer: &mut [u8]) -> iso7816::Result {
+//!         buffer.copy_from_slice(&[0x01, 0x02][offset..][..buffer.len()]);
+//!         Ok(())
+//!     }
+//!     // Read-only file system
+//! #   fn write(&mut self, _: usize, _: usize, _: &[u8]) -> iso7816::Result { unimplemented!() }
+//! #   fn record_count(&self, _: usize) -> usize { unimplemented!() }
+//! #   fn record_len(&self, _: usize, _: usize) -> usize { unimplemented!() }
+//! #   fn read_record(&self, _: usize, _: usize, _: &mut [u8]) -> iso7816::Result { unimplemented!() }
+//! #   fn update_record(&mut self, _: usize, _: usize, _: &[u8]) -> iso7816::Result { unimplemented!() }
+//! #   fn append_record(&mut self, _: usize, _: &[u8]) -> iso7816::Result { unimplemented!() }
+//! }
+//!
+//! let files = [
+//!     File::mf(),
+//!     File::transparent(FileId::new(0x2F01).unwrap(), 0, 2)
+//!         .with_short_ef(ShortEf::new(1).unwrap()),
+//! ];
+//! let mut fs = FileSystem::new(&files, Version);
+//! let mut response = Data::<16>::new();
+//! // READ BINARY with the short EF identifier 1
+//! let command = CommandView::try_from(&[0x00, 0xB0, 0x81, 0x00, 0x02][..]).unwrap();
+//! fs.call(&command, &mut response).unwrap();
+//! assert_eq!(&*response, &[0x01, 0x02]);
+//! assert_eq!(fs.current_ef(), Some(1));
+//! ```
+//!
+//! [`data_objects`] adds GET DATA and PUT DATA, standalone or in a DF of the file system.
+
+pub mod data_objects;
+
+use crate::command::binary::{self, Binary, BinaryError, Ef, Operation as BinaryOperation};
+use crate::command::record::{Operation as RecordOperation, Record, RecordError, Records};
+use crate::command::select::{Occurrence, Select, SelectError, SelectResponse, SelectTarget};
+use crate::command::{CommandView, DataStream};
+use crate::file::fci::{FciBuilder, FileDescriptor, Template};
+use crate::file::{FileId, ShortEf};
+use crate::tlv::serialize_len;
+use crate::{Data, Status};
+
+/// Access condition of a file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Access {
+    #[default]
+    Always,
+    Never,
+    /// Security conditions granted with [`FileSystem::grant`], all the bits of the mask being
+    /// required
+    Conditions(u8),
+}
+
+impl Access {
+    fn check(self, granted: u8) -> crate::Result {
+        match self {
+            Self::Always => Ok(()),
+            Self::Conditions(mask) if granted & mask == mask => Ok(()),
+            _ => Err(Status::SecurityStatusNotSatisfied),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind<'a> {
+    /// Dedicated file, with an optional DF name
+    Df(Option<&'a [u8]>),
+    /// Transparent EF of at most `max_size` bytes
+    Transparent { max_size: usize },
+    /// Linear EF of at most `max_records` records of at most `max_record_size` bytes
+    Records {
+        max_record_size: u16,
+        max_records: u16,
+    },
+}
+
+/// File of the tree of a [`FileSystem`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct File<'a> {
+    id: FileId,
+    parent: usize,
+    kind: FileKind<'a>,
+    short_ef: Option<ShortEf>,
+    read: Access,
+    update: Access,
+}
+
+impl<'a> File<'a> {
+    const fn new(id: FileId, parent: usize, kind: FileKind<'a>) -> Self {
+        Self {
+            id,
+            parent,
+            kind,
+            short_ef: None,
+            read: Access::Always,
+            update: Access::Always,
+        }
+    }
+
+    /// Master file, which must be the first file of the tree
+    pub const fn mf() -> Self {
+        Self::new(FileId::MF, 0, FileKind::Df(None))
+    }
+
+    /// DF under the DF at index `parent`
+    pub const fn df(id: FileId, parent: usize) -> Self {
+        Self::new(id, parent, FileKind::Df(None))
+    }
+
+    /// Transparent EF under the DF at index `parent`
+    pub const fn transparent(id: FileId, parent: usize, max_size: usize) -> Self {
+        Self::new(id, parent, FileKind::Transparent { max_size })
+    }
+
+    /// Linear EF with records of variable size under the DF at index `parent`
+    pub const fn records(
+        id: FileId,
+        parent: usize,
+        max_record_size: u16,
+        max_records: u16,
+    ) -> Self {
+        Self::new(
+            id,
+            parent,
+            FileKind::Records {
+                max_record_size,
+                max_records,
+            },
+        )
+    }
+
+    /// DF name, ignored for EFs
+    pub const fn with_name(mut self, name: &'a [u8]) -> Self {
+        if let FileKind::Df(_) = self.kind {
+            self.kind = FileKind::Df(Some(name));
+        }
+        self
+    }
+
+    pub const fn with_short_ef(mut self, short_ef: ShortEf) -> Self {
+        self.short_ef = Some(short_ef);
+        self
+    }
+
+    /// Access condition of READ BINARY and READ RECORD
+    pub const fn with_read_access(mut self, access: Access) -> Self {
+        self.read = access;
+        self
+    }
+
+    /// Access condition of UPDATE BINARY, UPDATE RECORD and APPEND RECORD
+    pub const fn with_update_access(mut self, access: Access) -> Self {
+        self.update = access;
+        self
+    }
+
+    pub const fn id(&self) -> FileId {
+        self.id
+    }
+
+    /// Index of the parent DF, the MF being its own parent
+    pub const fn parent(&self) -> usize {
+        self.parent
+    }
+
+    pub const fn kind(&self) -> FileKind<'a> {
+        self.kind
+    }
+
+    pub const fn short_ef(&self) -> Option<ShortEf> {
+        self.short_ef
+    }
+
+    pub const fn read_access(&self) -> Access {
+        self.read
+    }
+
+    pub const fn update_access(&self) -> Access {
+        self.update
+    }
+
+    pub const fn is_df(&self) -> bool {
+        matches!(self.kind, FileKind::Df(_))
+    }
+
+    fn name(&self) -> Option<&'a [u8]> {
+        match self.kind {
+            FileKind::Df(name) => name,
+            _ => None,
+        }
+    }
+}
+
+/// Content of the EFs of a [`FileSystem`]
+///
+/// `file` is the index of the EF in the tree. The [`FileSystem`] checks the file structure, the
+/// access conditions and the bounds before calling the storage, so errors returned by the
+/// storage are those of the underlying memory, such as [`Status::MemoryFailure`].
+pub trait Storage {
+    /// Length of the content of a transparent EF
+    fn len(&self, file: usize) -> usize;
+
+    /// Fill `buffer` from `offset` in a transparent EF, `offset + buffer.len()` being at most
+    /// [`len`](Self::len)
+    fn read(&self, file: usize, offset: usize, buffer: &mut [u8]) -> crate::Result;
+
+    /// Write `data` at `offset` in a transparent EF, growing it if needed
+    ///
+    /// `offset` is at most [`len`](Self::len), and the new length at most the maximum size of the
+    /// file.
+    fn write(&mut self, file: usize, offset: usize, data: &[u8]) -> crate::Result;
+
+    /// Number of records of a record EF
+    fn record_count(&self, file: usize) -> usize;
+
+    /// Length of the record `index`, counting from 0, `index` being less than
+    /// [`record_count`](Self::record_count)
+    fn record_len(&self, file: usize, index: usize) -> usize;
+
+    /// Read the record `index`, `buffer` being [`record_len`](Self::record_len) bytes long
+    fn read_record(&self, file: usize, index: usize, buffer: &mut [u8]) -> crate::Result;
+
+    /// Replace the record `index`
+    fn update_record(&mut self, file: usize, index: usize, data: &[u8]) -> crate::Result;
+
+    /// Add a record after the last one
+    fn append_record(&mut self, file: usize, data: &[u8]) -> crate::Result;
+}
+
+impl<S: Storage + ?Sized> Storage for &mut S {
+    fn len(&self, file: usize) -> usize {
+        S::len(self, file)
+    }
+
+    fn read(&self, file: usize, offset: usize, buffer: &mut [u8]) -> crate::Result {
+        S::read(self, file, offset, buffer)
+    }
+
+    fn write(&mut self, file: usize, offset: usize, data: &[u8]) -> crate::Result {
+        S::write(self, file, offset, data)
+    }
+
+    fn record_count(&self, file: usize) -> usize {
+        S::record_count(self, file)
+    }
+
+    fn record_len(&self, file: usize, index: usize) -> usize {
+        S::record_len(self, file, index)
+    }
+
+    fn read_record(&self, file: usize, index: usize, buffer: &mut [u8]) -> crate::Result {
+        S::read_record(self, file, index, buffer)
+    }
+
+    fn update_record(&mut self, file: usize, index: usize, data: &[u8]) -> crate::Result {
+        S::update_record(self, file, index, data)
+    }
+
+    fn append_record(&mut self, file: usize, data: &[u8]) -> crate::Result {
+        S::append_record(self, file, data)
+    }
+}
+
+/// Card-side file system, see the [module documentation](self)
+pub struct FileSystem<'a, S> {
+    files: &'a [File<'a>],
+    storage: S,
+    current_df: usize,
+    current_ef: Option<usize>,
+    current_record: Option<usize>,
+    granted: u8,
+}
+
+impl<'a, S: Storage> FileSystem<'a, S> {
+    /// File system with the MF selected
+    ///
+    /// # Panics
+    ///
+    /// Panics if the first file is not the MF, or if a parent index is not a DF.
+    pub fn new(files: &'a [File<'a>], storage: S) -> Self {
+        assert!(
+            files.first().map(|f| f.id) == Some(FileId::MF),
+            "The first file must be the MF"
+        );
+        assert!(
+            files
+                .iter()
+                .all(|f| files.get(f.parent).is_some_and(File::is_df)),
+            "Parents must be DFs"
+        );
+        Self {
+            files,
+            storage,
+            current_df: 0,
+            current_ef: None,
+            current_record: None,
+            granted: 0,
+        }
+    }
+
+    pub fn files(&self) -> &'a [File<'a>] {
+        self.files
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+
+    /// Index of the current DF
+    pub fn current_df(&self) -> usize {
+        self.current_df
+    }
+
+    /// Index of the current EF
+    pub fn current_ef(&self) -> Option<usize> {
+        self.current_ef
+    }
+
+    /// Grant security conditions, for example after a successful VERIFY
+    pub fn grant(&mut self, conditions: u8) {
+        self.granted |= conditions;
+    }
+
+    pub fn revoke(&mut self, conditions: u8) {
+        self.granted &= !conditions;
+    }
+
+    /// Security conditions currently granted
+    pub fn granted(&self) -> u8 {
+        self.granted
+    }
+
+    /// Select the MF and revoke all security conditions, for example on reset
+    pub fn reset(&mut self) {
+        self.current_df = 0;
+        self.current_ef = None;
+        self.current_record = None;
+        self.granted = 0;
+    }
+
+    /// Process a command, writing the response data to `response`
+    ///
+    /// With [`Status::UnexpectedEof`], `response` contains the data read until the end of the
+    /// file. Other instructions are rejected with [`Status::InstructionNotSupportedOrInvalid`].
+    pub fn call<const R: usize>(
+        &mut self,
+        command: &CommandView<'_>,
+        response: &mut Data<R>,
+    ) -> crate::Result {
+        response.clear();
+        match Select::try_from(command) {
+            Err(SelectError::WrongInstruction) => {}
+            select => return self.select(select?, response),
+        }
+        match Binary::try_from(command) {
+            Err(BinaryError::WrongInstruction) => {}
+            binary => {
+                // The response to the odd READ BINARY is wrapped in a data object
+                let odd = u8::from(command.instruction()) & 1 == 1;
+                return self.binary(binary?, odd, response);
+            }
+        }
+        match Record::try_from(command) {
+            Err(RecordError::WrongInstruction) => {}
+            record => return self.record(record?, response),
+        }
+        Err(Status::InstructionNotSupportedOrInvalid)
+    }
+
+    fn children(&self, df: usize) -> impl Iterator<Item = (usize, &'a File<'a>)> {
+        self.files
+            .iter()
+            .enumerate()
+            .filter(move |&(i, f)| f.parent == df && i != df)
+    }
+
+    fn child(&self, df: usize, id: FileId) -> Option<usize> {
+        self.children(df).find(|(_, f)| f.id == id).map(|(i, _)| i)
+    }
+
+    fn find(&self, target: &SelectTarget<'_>, occurrence: Occurrence) -> Option<usize> {
+        let df = self.current_df;
+        let parent = self.files[df].parent;
+        let is_df = |i: &usize| self.files[*i].is_df();
+        match target {
+            SelectTarget::Mf => Some(0),
+            SelectTarget::FileId(id) => [df, parent]
+                .into_iter()
+                .find(|&i| self.files[i].id == *id)
+                .or_else(|| self.child(df, *id))
+                .or_else(|| self.child(parent, *id)),
+            SelectTarget::ChildDf(id) => self.child(df, *id).filter(is_df),
+            SelectTarget::Ef(id) => self.child(df, *id).filter(|i| !is_df(i)),
+            SelectTarget::Parent => Some(parent).filter(|_| df != 0),
+            SelectTarget::DfName(name) => {
+                let mut matches = (0..self.files.len())
+                    .filter(|&i| self.files[i].name().is_some_and(|n| n.starts_with(name)));
+                match occurrence {
+                    Occurrence::First => matches.next(),
+                    Occurrence::Last => matches.next_back(),
+                    Occurrence::Next => matches.find(|&i| i > df),
+                    Occurrence::Previous => matches.rev().find(|&i| i < df),
+                }
+            }
+            SelectTarget::Path(path) => {
+                let start = if path.is_absolute() { 0 } else { df };
+                path.ids().iter().try_fold(start, |df, id| {
+                    Some(df).filter(is_df).and_then(|df| self.child(df, *id))
+                })
+            }
+        }
+    }
+
+    fn select<const R: usize>(
+        &mut self,
+        select: Select<'_>,
+        response: &mut Data<R>,
+    ) -> crate::Result {
+        let index = self
+            .find(select.target(), select.file_occurrence())
+            .ok_or(Status::NotFound)?;
+        let file = &self.files[index];
+        if file.is_df() {
+            self.current_df = index;
+            self.current_ef = None;
+        } else {
+            self.current_df = file.parent;
+            self.current_ef = Some(index);
+        }
+        self.current_record = None;
+
+        let template = match select.response_template() {
+            SelectResponse::Fci => Template::Fci,
+            SelectResponse::Fcp => Template::Fcp,
+            SelectResponse::Fmd => Template::Fmd,
+            SelectResponse::None => return Ok(()),
+        };
+        let mut fci = FciBuilder::new(template);
+        if template != Template::Fmd {
+            fci = fci.file_id(file.id);
+            match file.kind {
+                FileKind::Df(name) => {
+                    fci = fci.descriptor(FileDescriptor::DF);
+                    if let Some(name) = name {
+                        fci = fci.df_name(name);
+                    }
+                }
+                FileKind::Transparent { .. } => {
+                    let size = self.storage.len(index);
+                    fci = fci
+                        .descriptor(FileDescriptor::TRANSPARENT)
+                        .file_size(u16::try_from(size).unwrap_or(u16::MAX));
+                }
+                FileKind::Records {
+                    max_record_size, ..
+                } => {
+                    let count = self.storage.record_count(index);
+                    fci =
+                        fci.descriptor(FileDescriptor::LINEAR_VARIABLE.with_records(
+                            max_record_size,
+                            u16::try_from(count).unwrap_or(u16::MAX),
+                        ));
+                }
+            }
+            if let Some(short_ef) = file.short_ef {
+                fci = fci.short_ef(Some(short_ef));
+            }
+        }
+        fci.to_writer(response).map_err(|_| Status::NotEnoughMemory)
+    }
+
+    /// Resolve and select the EF of a binary or record command
+    fn ef(&mut self, ef: Ef) -> crate::Result<usize> {
+        let index = match ef {
+            Ef::Current => self.current_ef.ok_or(Status::CommandNotAllowedNoEf)?,
+            Ef::Short(short_ef) => self
+                .children(self.current_df)
+                .find(|(_, f)| f.short_ef == Some(short_ef))
+                .map(|(i, _)| i)
+                .ok_or(Status::NotFound)?,
+            Ef::Id(id) => self
+                .child(self.current_df, id)
+                .filter(|&i| !self.files[i].is_df())
+                .ok_or(Status::NotFound)?,
+        };
+        if self.current_ef != Some(index) {
+            self.current_ef = Some(index);
+            self.current_record = None;
+        }
+        Ok(index)
+    }
+
+    fn binary<const R: usize>(
+        &mut self,
+        binary: Binary<'_>,
+        odd: bool,
+        response: &mut Data<R>,
+    ) -> crate::Result {
+        if binary.operation() == BinaryOperation::Write {
+            return Err(Status::InstructionNotSupportedOrInvalid);
+        }
+        let index = self.ef(binary.target())?;
+        let file = self.files[index];
+        let FileKind::Transparent { max_size } = file.kind else {
+            return Err(Status::CommandIncompatibleFileStructure);
+        };
+        match binary.operation() {
+            BinaryOperation::Read => file.read.check(self.granted)?,
+            _ => file.update.check(self.granted)?,
+        }
+        let len = self.storage.len(index);
+        let offset = usize::try_from(binary.offset()).map_err(|_| Status::WrongParameters)?;
+        if offset > len {
+            return Err(Status::WrongParameters);
+        }
+
+        if binary.operation() == BinaryOperation::Update {
+            let data = binary.data();
+            if data.len() > max_size.saturating_sub(offset) {
+                return Err(Status::NotEnoughMemory);
+            }
+            return self.storage.write(index, offset, data);
+        }
+
+        let le = binary.expected();
+        let mut budget = le.min(R);
+        if odd {
+            let header = 1 + serialize_len(budget).map_or(3, |l| l.len());
+            budget = budget.saturating_sub(header);
+        }
+        let n = budget.min(len - offset);
+        if odd {
+            response.push(binary::DATA).ok();
+            response
+                .extend_from_slice(&serialize_len(n).unwrap_or_default())
+                .ok();
+        }
+        let start = response.len();
+        response
+            .resize_default(start + n)
+            .map_err(|_| Status::NotEnoughMemory)?;
+        self.storage.read(index, offset, &mut response[start..])?;
+        if offset + n == len && response.len() < le {
+            return Err(Status::UnexpectedEof);
+        }
+        Ok(())
+    }
+
+    fn record<const R: usize>(
+        &mut self,
+        record: Record<'_>,
+        response: &mut Data<R>,
+    ) -> crate::Result {
+        if record.operation() == RecordOperation::Search {
+            return Err(Status::InstructionNotSupportedOrInvalid);
+        }
+        let ef = record.short_ef().map_or(Ef::Current, Ef::Short);
+        let index = self.ef(ef)?;
+        let file = self.files[index];
+        let FileKind::Records {
+            max_record_size,
+            max_records,
+        } = file.kind
+        else {
+            return Err(Status::CommandIncompatibleFileStructure);
+        };
+        match record.operation() {
+            RecordOperation::Read => file.read.check(self.granted)?,
+            _ => file.update.check(self.granted)?,
+        }
+        let count = self.storage.record_count(index);
+        let data = record.data();
+        if data.len() > usize::from(max_record_size) {
+            return Err(Status::WrongLength);
+        }
+
+        let record_index = match record.records() {
+            Some(Records::Number(0)) => self.current_record.ok_or(Status::RecordNotFound)?,
+            Some(Records::Number(n)) => usize::from(n - 1),
+            Some(_) => return Err(Status::FunctionNotSupported),
+            None => count,
+        };
+        if record.operation() == RecordOperation::Append {
+            if count >= usize::from(max_records) {
+                return Err(Status::NotEnoughMemory);
+            }
+            self.storage.append_record(index, data)?;
+            self.current_record = Some(count);
+            return Ok(());
+        }
+        if record_index >= count {
+            return Err(Status::RecordNotFound);
+        }
+
+        match record.operation() {
+            RecordOperation::Read => {
+                let len = self.storage.record_len(index, record_index);
+                if len > R {
+                    return Err(Status::NotEnoughMemory);
+                }
+                response.resize_default(len).ok();
+                self.storage.read_record(index, record_index, response)?;
+            }
+            _ => self.storage.update_record(index, record_index, data)?,
+        }
+        self.current_record = Some(record_index);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[derive(Default)]
+    struct Memory {
+        transparent: [Vec<u8>; 6],
+        records: [Vec<Vec<u8>>; 6],
+    }
+
+    impl Storage for Memory {
+        fn len(&self, file: usize) -> usize {
+            self.transparent[file].len()
+        }
+
+        fn read(&self, file: usize, offset: usize, buffer: &mut [u8]) -> crate::Result {
+            buffer.copy_from_slice(&self.transparent[file][offset..][..buffer.len()]);
+            Ok(())
+        }
+
+        fn write(&mut self, file: usize, offset: usize, data: &[u8]) -> crate::Result {
+            let content = &mut self.transparent[file];
+            content.resize(content.len().max(offset + data.len()), 0);
+            content[offset..][..data.len()].copy_from_slice(data);
+            Ok(())
+        }
+
+        fn record_count(&self, file: usize) -> usize {
+            self.records[file].len()
+        }
+
+        fn record_len(&self, file: usize, index: usize) -> usize {
+            self.records[file][index].len()
+        }
+
+        fn read_record(&self, file: usize, index: usize, buffer: &mut [u8]) -> crate::Result {
+            buffer.copy_from_slice(&self.records[file][index]);
+            Ok(())
+        }
+
+        fn update_record(&mut self, file: usize, index: usize, data: &[u8]) -> crate::Result {
+            self.records[file][index] = data.into();
+            Ok(())
+        }
+
+        fn append_record(&mut self, file: usize, data: &[u8]) -> crate::Result {
+            self.records[file].push(data.into());
+            Ok(())
+        }
+    }
+
+    fn id(id: u16) -> FileId {
+        FileId::new(id).unwrap()
+    }
+
+    fn sfi(id: u8) -> ShortEf {
+        ShortEf::new(id).unwrap()
+    }
+
+    const AID: &[u8] = &hex!("A000000001 0101");
+
+    fn files() -> [File<'static>; 6] {
+        [
+            File::mf(),
+            File::transparent(id(0x2F00), 0, 16).with_short_ef(sfi(1)),
+            File::df(id(0x7F10), 0).with_name(AID),
+            File::records(id(0x6F3A), 2, 4, 2)
+                .with_short_ef(sfi(2))
+                .with_update_access(Access::Conditions(0b01)),
+            File::transparent(id(0x6F3B), 2, 4).with_read_access(Access::Never),
+            File::df(id(0x7F20), 2),
+        ]
+    }
+
+    struct Card<'a> {
+        fs: FileSystem<'a, Memory>,
+        response: Data<32>,
+    }
+
+    impl Card<'_> {
+        fn call(&mut self, apdu: &[u8]) -> crate::Result<&[u8]> {
+            let command = CommandView::try_from(apdu).unwrap();
+            self.fs.call(&command, &mut self.response)?;
+            Ok(&self.response)
+        }
+    }
+
+    #[test]
+    fn select() {
+        let files = files();
+        let mut card = Card {
+            fs: FileSystem::new(&files, Memory::default()),
+            response: Data::new(),
+        };
+        card.fs.storage_mut().transparent[1] = hex!("0102").into();
+        assert_eq!(
+            card.call(&hex!("00A40004 02 2F00 00")),
+            Ok(&hex!("620E 80020002 820101 83022F00 880108")[..])
+        );
+        assert_eq!(card.fs.current_ef(), Some(1));
+        assert_eq!(
+            card.call(&hex!("00A40400 05 A000000001 00")),
+            Ok(&hex!("6F10 820138 83027F10 8407A0000000010101")[..])
+        );
+        assert_eq!(card.fs.current_df(), 2);
+        assert_eq!(card.fs.current_ef(), None);
+        assert_eq!(card.call(&hex!("00A4020C 02 6F3A")), Ok(&[][..]));
+        assert_eq!(card.fs.current_ef(), Some(3));
+        assert_eq!(card.call(&hex!("00A4010C 02 6F3B")), Err(Status::NotFound));
+        assert_eq!(card.call(&hex!("00A4010C 02 7F20")), Ok(&[][..]));
+        assert_eq!(card.fs.current_df(), 5);
+        // Siblings of the current DF can be selected by file identifier
+        assert_eq!(card.call(&hex!("00A4000C 02 6F3B")), Ok(&[][..]));
+        assert_eq!(card.fs.current_df(), 2);
+        assert_eq!(card.call(&hex!("00A4030C")), Ok(&[][..]));
+        assert_eq!(card.fs.current_df(), 0);
+        assert_eq!(card.call(&hex!("00A4030C")), Err(Status::NotFound));
+        assert_eq!(card.call(&hex!("00A4080C 04 7F106F3A")), Ok(&[][..]));
+        assert_eq!(card.fs.current_ef(), Some(3));
+        assert_eq!(
+            card.call(&hex!("00A4080C 04 2F006F3A")),
+            Err(Status::NotFound)
+        );
+        assert_eq!(
+            card.call(&hex!("00A4040C 03 A00001")),
+            Err(Status::NotFound)
+        );
+        assert_eq!(
+            card.call(&hex!("00A40500")),
+            Err(Status::IncorrectP1OrP2Parameter)
+        );
+        assert_eq!(
+            card.call(&hex!("00CA006E 00")),
+            Err(Status::InstructionNotSupportedOrInvalid)
+        );
+    }
+
+    #[test]
+    fn binary() {
+        let files = files();
+        let mut card = Card {
+            fs: FileSystem::new(&files, Memory::default()),
+            response: Data::new(),
+        };
+        assert_eq!(
+            card.call(&hex!("00B00000 00")),
+            Err(Status::CommandNotAllowedNoEf)
+        );
+        assert_eq!(card.call(&hex!("00D68100 03 010203")), Ok(&[][..]));
+        assert_eq!(card.fs.current_ef(), Some(1));
+        assert_eq!(card.call(&hex!("00D60003 02 0405")), Ok(&[][..]));
+        assert_eq!(card.call(&hex!("00B00001 03")), Ok(&hex!("020304")[..]));
+        assert_eq!(card.call(&hex!("00B00003 00")), Err(Status::UnexpectedEof));
+        assert_eq!(&*card.response, &hex!("0405"));
+        assert_eq!(
+            card.call(&hex!("00B10000 03 540101 00")),
+            Err(Status::UnexpectedEof)
+        );
+        assert_eq!(&*card.response, &hex!("5304 02030405"));
+        assert_eq!(
+            card.call(&hex!("00B00006 00")),
+            Err(Status::WrongParameters)
+        );
+        assert_eq!(
+            card.call(&hex!("00D60004 0D 0102030405060708090A0B0C0D")),
+            Err(Status::NotEnoughMemory)
+        );
+
+        card.call(&hex!("00A4040C 07 A0000000010101")).unwrap();
+        assert_eq!(card.fs.current_ef(), None);
+        assert_eq!(card.call(&hex!("00D68100 01 01")), Err(Status::NotFound));
+        assert_eq!(
+            card.call(&hex!("00B08200 00")),
+            Err(Status::CommandIncompatibleFileStructure)
+        );
+        assert_eq!(
+            card.call(&hex!("00B16F3B 03 540100 00")),
+            Err(Status::SecurityStatusNotSatisfied)
+        );
+        assert_eq!(card.fs.current_ef(), Some(4));
+        assert_eq!(
+            card.call(&hex!("00D00000 01 01")),
+            Err(Status::InstructionNotSupportedOrInvalid)
+        );
+    }
+
+    #[test]
+    fn records() {
+        let files = files();
+        let mut card = Card {
+            fs: FileSystem::new(&files, Memory::default()),
+            response: Data::new(),
+        };
+        card.call(&hex!("00A4040C 07 A0000000010101")).unwrap();
+        assert_eq!(
+            card.call(&hex!("00E20010 01 01")),
+            Err(Status::SecurityStatusNotSatisfied)
+        );
+        card.fs.grant(0b11);
+        assert_eq!(card.call(&hex!("00E20010 01 01")), Ok(&[][..]));
+        assert_eq!(card.call(&hex!("00E20000 02 0203")), Ok(&[][..]));
+        assert_eq!(
+            card.call(&hex!("00E20000 01 04")),
+            Err(Status::NotEnoughMemory)
+        );
+        assert_eq!(card.call(&hex!("00B20004 00")), Ok(&hex!("0203")[..]));
+        assert_eq!(card.call(&hex!("00B20114 00")), Ok(&hex!("01")[..]));
+        assert_eq!(card.call(&hex!("00DC0004 02 0A0B")), Ok(&[][..]));
+        assert_eq!(card.call(&hex!("00B20114 00")), Ok(&hex!("0A0B")[..]));
+        assert_eq!(
+            card.call(&hex!("00DC0104 05 0102030405")),
+            Err(Status::WrongLength)
+        );
+        assert_eq!(card.call(&hex!("00B20314 00")), Err(Status::RecordNotFound));
+        assert_eq!(
+            card.call(&hex!("00B20115 00")),
+            Err(Status::FunctionNotSupported)
+        );
+
+        card.fs.reset();
+        assert_eq!(card.fs.current_df(), 0);
+        assert_eq!(
+            card.call(&hex!("00B2010C 00")),
+            Err(Status::CommandIncompatibleFileStructure)
+        );
+        card.call(&hex!("00A4080C 04 7F106F3A")).unwrap();
+        assert_eq!(
+            card.call(&hex!("00DC0104 01 01")),
+            Err(Status::SecurityStatusNotSatisfied)
+        );
+    }
+}
@@ -0,0 +1,508 @@
+//! Card-side data object store
+//!
+//! [`DataObjectStore`] processes GET DATA and PUT DATA over a list of [`DataObject`]s, the values
+//! being kept by an [`ObjectStorage`]. Each data object has a maximum length and its own
+//! [`Access`] conditions.
+//!
+//! - GET DATA with a tag in P1-P2 returns the value of the data object.
+//! - GET DATA with a tag list returns the requested data objects, tags and lengths included.
+//! - PUT DATA with a tag in P1-P2 replaces the value of the data object, an empty value deleting
+//!   it.
+//! - PUT DATA with the odd instruction stores each data object of the data field.
+//!
+//! Unknown and deleted data objects are reported with `6A88`.
+//!
+//! The store can be used on its own with [`call`](DataObjectStore::call), or
+//! [mounted](DataObjectStore::mount) in a DF of a [`FileSystem`] with
+//! [`call_in`](DataObjectStore::call_in), sharing the security conditions granted to the file
+//! system.
+
+use super::{Access, FileSystem, Storage};
+use crate::command::data_objects::{DataObjectError, GetData, PutData, Query};
+use crate::command::CommandView;
+use crate::tlv::{serialize_len, Tag};
+use crate::{Data, Status};
+
+/// Data object of a [`DataObjectStore`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DataObject {
+    tag: Tag,
+    max_len: usize,
+    read: Access,
+    update: Access,
+}
+
+impl DataObject {
+    pub const fn new(tag: Tag, max_len: usize) -> Self {
+        Self {
+            tag,
+            max_len,
+            read: Access::Always,
+            update: Access::Always,
+        }
+    }
+
+    /// Access condition of GET DATA
+    pub const fn with_read_access(mut self, access: Access) -> Self {
+        self.read = access;
+        self
+    }
+
+    /// Access condition of PUT DATA
+    pub const fn with_update_access(mut self, access: Access) -> Self {
+        self.update = access;
+        self
+    }
+
+    pub const fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    pub const fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    pub const fn read_access(&self) -> Access {
+        self.read
+    }
+
+    pub const fn update_access(&self) -> Access {
+        self.update
+    }
+}
+
+/// Values of the data objects of a [`DataObjectStore`]
+///
+/// `object` is the index of the data object in the list given to the store, which checks the
+/// access conditions and lengths before calling the storage.
+pub trait ObjectStorage {
+    /// Length of the value, `None` if the data object is absent
+    fn len(&self, object: usize) -> Option<usize>;
+
+    /// Read the value, `buffer` being [`len`](Self::len) bytes long
+    fn read(&self, object: usize, buffer: &mut [u8]) -> crate::Result;
+
+    /// Replace the value
+    fn write(&mut self, object: usize, value: &[u8]) -> crate::Result;
+
+    fn remove(&mut self, object: usize) -> crate::Result;
+}
+
+impl<S: ObjectStorage + ?Sized> ObjectStorage for &mut S {
+    fn len(&self, object: usize) -> Option<usize> {
+        S::len(self, object)
+    }
+
+    fn read(&self, object: usize, buffer: &mut [u8]) -> crate::Result {
+        S::read(self, object, buffer)
+    }
+
+    fn write(&mut self, object: usize, value: &[u8]) -> crate::Result {
+        S::write(self, object, value)
+    }
+
+    fn remove(&mut self, object: usize) -> crate::Result {
+        S::remove(self, object)
+    }
+}
+
+/// Card-side data object store, see the [module documentation](self)
+pub struct DataObjectStore<'a, S> {
+    objects: &'a [DataObject],
+    storage: S,
+    df: Option<usize>,
+    granted: u8,
+}
+
+impl<'a, S: ObjectStorage> DataObjectStore<'a, S> {
+    pub fn new(objects: &'a [DataObject], storage: S) -> Self {
+        Self {
+            objects,
+            storage,
+            df: None,
+            granted: 0,
+        }
+    }
+
+    /// Only process commands when the DF at index `df` is the current DF of the file system, see
+    /// [`call_in`](Self::call_in)
+    pub fn mount(mut self, df: usize) -> Self {
+        self.df = Some(df);
+        self
+    }
+
+    pub fn objects(&self) -> &'a [DataObject] {
+        self.objects
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+
+    /// Grant security conditions, for example after a successful VERIFY
+    pub fn grant(&mut self, conditions: u8) {
+        self.granted |= conditions;
+    }
+
+    pub fn revoke(&mut self, conditions: u8) {
+        self.granted &= !conditions;
+    }
+
+    /// Revoke all security conditions, for example on reset
+    pub fn reset(&mut self) {
+        self.granted = 0;
+    }
+
+    /// Index of the data object with this tag
+    pub fn find(&self, tag: Tag) -> Option<usize> {
+        self.objects.iter().position(|object| object.tag == tag)
+    }
+
+    /// Process a GET DATA or PUT DATA command, writing the response data to `response`
+    ///
+    /// Other instructions are rejected with [`Status::InstructionNotSupportedOrInvalid`].
+    pub fn call<const R: usize>(
+        &mut self,
+        command: &CommandView<'_>,
+        response: &mut Data<R>,
+    ) -> crate::Result {
+        self.process(command, response, self.granted)
+    }
+
+    /// Process a GET DATA or PUT DATA command with the security conditions granted to the file
+    /// system
+    ///
+    /// If the store is [mounted](Self::mount) in another DF than the current DF, the command is
+    /// rejected with [`Status::InstructionNotSupportedOrInvalid`] so that it can be passed on.
+    pub fn call_in<T: Storage, const R: usize>(
+        &mut self,
+        filesystem: &FileSystem<'_, T>,
+        command: &CommandView<'_>,
+        response: &mut Data<R>,
+    ) -> crate::Result {
+        if self.df.is_some_and(|df| df != filesystem.current_df()) {
+            response.clear();
+            return Err(Status::InstructionNotSupportedOrInvalid);
+        }
+        self.process(command, response, filesystem.granted())
+    }
+
+    fn process<const R: usize>(
+        &mut self,
+        command: &CommandView<'_>,
+        response: &mut Data<R>,
+        granted: u8,
+    ) -> crate::Result {
+        response.clear();
+        match GetData::try_from(command) {
+            Err(DataObjectError::WrongInstruction) => {}
+            get_data => return self.get_data(get_data?, response, granted),
+        }
+        match PutData::try_from(command) {
+            Err(DataObjectError::WrongInstruction) => {}
+            put_data => return self.put_data(put_data?, granted),
+        }
+        Err(Status::InstructionNotSupportedOrInvalid)
+    }
+
+    /// Index and length of a data object that can be read
+    fn readable(&self, tag: Tag, granted: u8) -> crate::Result<(usize, usize)> {
+        let index = self.find(tag).ok_or(Status::KeyReferenceNotFound)?;
+        self.objects[index].read.check(granted)?;
+        let len = self
+            .storage
+            .len(index)
+            .ok_or(Status::KeyReferenceNotFound)?;
+        Ok((index, len))
+    }
+
+    fn read<const R: usize>(
+        &self,
+        index: usize,
+        len: usize,
+        response: &mut Data<R>,
+    ) -> crate::Result {
+        let start = response.len();
+        response
+            .resize_default(start + len)
+            .map_err(|_| Status::NotEnoughMemory)?;
+        self.storage.read(index, &mut response[start..])
+    }
+
+    fn get_data<const R: usize>(
+        &self,
+        get_data: GetData<'_>,
+        response: &mut Data<R>,
+        granted: u8,
+    ) -> crate::Result {
+        match get_data.query() {
+            Query::Tag(tag) => {
+                let tag = Tag::try_from_u16(tag).map_err(|_| Status::KeyReferenceNotFound)?;
+                let (index, len) = self.readable(tag, granted)?;
+                self.read(index, len, response)
+            }
+            Query::TagList(_) => {
+                for tag in get_data.tags() {
+                    let (index, len) = self.readable(tag, granted)?;
+                    let header = tag.serialize();
+                    let len_bytes = serialize_len(len).ok_or(Status::NotEnoughMemory)?;
+                    response
+                        .extend_from_slice(&header)
+                        .and_then(|()| response.extend_from_slice(&len_bytes))
+                        .map_err(|_| Status::NotEnoughMemory)?;
+                    self.read(index, len, response)?;
+                }
+                Ok(())
+            }
+            Query::HeaderList(_) => Err(Status::FunctionNotSupported),
+        }
+    }
+
+    /// Index of a data object that can be replaced by `value`
+    fn writable(&self, tag: Tag, value: &[u8], granted: u8) -> crate::Result<usize> {
+        let index = self.find(tag).ok_or(Status::KeyReferenceNotFound)?;
+        let object = &self.objects[index];
+        object.update.check(granted)?;
+        if value.len() > object.max_len {
+            return Err(Status::NotEnoughMemory);
+        }
+        Ok(index)
+    }
+
+    fn write(&mut self, index: usize, value: &[u8]) -> crate::Result {
+        if value.is_empty() {
+            self.storage.remove(index)
+        } else {
+            self.storage.write(index, value)
+        }
+    }
+
+    fn put_data(&mut self, put_data: PutData<'_>, granted: u8) -> crate::Result {
+        if let Some(tag) = put_data.data_tag() {
+            let tag = Tag::try_from_u16(tag).map_err(|_| Status::KeyReferenceNotFound)?;
+            let index = self.writable(tag, put_data.data(), granted)?;
+            return self.write(index, put_data.data());
+        }
+        // Check all the data objects before storing any
+        for object in put_data.objects_iter() {
+            let (tag, value) = object.map_err(|_| Status::IncorrectDataParameter)?;
+            self.writable(tag, value, granted)?;
+        }
+        for object in put_data.objects_iter() {
+            let (tag, value) = object.map_err(|_| Status::IncorrectDataParameter)?;
+            let index = self.writable(tag, value, granted)?;
+            self.write(index, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::FileId;
+    use crate::filesystem::File;
+    use hex_literal::hex;
+
+    #[derive(Default)]
+    struct Memory([Option<Vec<u8>>; 3]);
+
+    impl ObjectStorage for Memory {
+        fn len(&self, object: usize) -> Option<usize> {
+            self.0[object].as_ref().map(Vec::len)
+        }
+
+        fn read(&self, object: usize, buffer: &mut [u8]) -> crate::Result {
+            buffer.copy_from_slice(self.0[object].as_ref().unwrap());
+            Ok(())
+        }
+
+        fn write(&mut self, object: usize, value: &[u8]) -> crate::Result {
+            self.0[object] = Some(value.into());
+            Ok(())
+        }
+
+        fn remove(&mut self, object: usize) -> crate::Result {
+            self.0[object] = None;
+            Ok(())
+        }
+    }
+
+    const OBJECTS: [DataObject; 3] = [
+        DataObject::new(Tag::from_u8(0x5B), 8),
+        DataObject::new(Tag::from_3([0x5F, 0xC1, 0x02]), 4)
+            .with_update_access(Access::Conditions(0b01)),
+        DataObject::new(Tag::from_u16(0x7F21), 4).with_read_access(Access::Never),
+    ];
+
+    fn call<'r>(
+        store: &mut DataObjectStore<'_, Memory>,
+        apdu: &[u8],
+        response: &'r mut Data<16>,
+    ) -> crate::Result<&'r [u8]> {
+        let command = CommandView::try_from(apdu).unwrap();
+        store.call(&command, response)?;
+        Ok(response)
+    }
+
+    #[test]
+    fn get_put_data() {
+        let mut store = DataObjectStore::new(&OBJECTS, Memory::default());
+        let response = &mut Data::new();
+        assert_eq!(
+            call(&mut store, &hex!("00CA005B 00"), response),
+            Err(Status::KeyReferenceNotFound)
+        );
+        assert_eq!(
+            call(&mut store, &hex!("00DA005B 03 446F65"), response),
+            Ok(&[][..])
+        );
+        assert_eq!(
+            call(&mut store, &hex!("00CA005B 00"), response),
+            Ok(&hex!("446F65")[..])
+        );
+        assert_eq!(
+            call(&mut store, &hex!("00DB3FFF 06 5FC102 02 0102"), response),
+            Err(Status::SecurityStatusNotSatisfied)
+        );
+        store.grant(0b01);
+        assert_eq!(
+            call(
+                &mut store,
+                &hex!("00DB3FFF 09 5FC102 02 0102 5B 01 41"),
+                response
+            ),
+            Ok(&[][..])
+        );
+        assert_eq!(
+            call(&mut store, &hex!("00CB3FFF 06 5C04 5FC1025B 00"), response),
+            Ok(&hex!("5FC102 02 0102 5B 01 41")[..])
+        );
+
+        // Nothing is stored if a data object is rejected
+        assert_eq!(
+            call(
+                &mut store,
+                &hex!("00DB3FFF 08 5B 01 42 5FC102 02 01"),
+                response
+            ),
+            Err(Status::IncorrectDataParameter)
+        );
+        assert_eq!(
+            call(&mut store, &hex!("00CA005B 00"), response),
+            Ok(&hex!("41")[..])
+        );
+        assert_eq!(
+            call(
+                &mut store,
+                &hex!("00DB3FFF 0A 5B 01 42 5FC102 03 010203"),
+                response
+            ),
+            Ok(&[][..])
+        );
+        assert_eq!(
+            call(
+                &mut store,
+                &hex!("00DB3FFF 0C 5B 01 43 5FC102 05 0102030405"),
+                response
+            ),
+            Err(Status::NotEnoughMemory)
+        );
+        assert_eq!(
+            call(&mut store, &hex!("00CA005B 00"), response),
+            Ok(&hex!("42")[..])
+        );
+
+        assert_eq!(
+            call(&mut store, &hex!("00DA7F21 01 01"), response),
+            Ok(&[][..])
+        );
+        assert_eq!(
+            call(&mut store, &hex!("00CA7F21 00"), response),
+            Err(Status::SecurityStatusNotSatisfied)
+        );
+        assert_eq!(call(&mut store, &hex!("00DA005B"), response), Ok(&[][..]));
+        assert_eq!(
+            call(&mut store, &hex!("00CA005B 00"), response),
+            Err(Status::KeyReferenceNotFound)
+        );
+        assert_eq!(
+            call(&mut store, &hex!("00CA0042 00"), response),
+            Err(Status::KeyReferenceNotFound)
+        );
+        assert_eq!(
+            call(&mut store, &hex!("00CB3FFF 04 4D02 5B00 00"), response),
+            Err(Status::FunctionNotSupported)
+        );
+        assert_eq!(
+            call(&mut store, &hex!("00A40000"), response),
+            Err(Status::InstructionNotSupportedOrInvalid)
+        );
+    }
+
+    #[test]
+    fn mounted() {
+        struct NoContent;
+
+        impl Storage for NoContent {
+            fn len(&self, _file: usize) -> usize {
+                0
+            }
+            fn read(&self, _: usize, _: usize, _: &mut [u8]) -> crate::Result {
+                Ok(())
+            }
+            fn write(&mut self, _: usize, _: usize, _: &[u8]) -> crate::Result {
+                Err(Status::MemoryFailure)
+            }
+            fn record_count(&self, _file: usize) -> usize {
+                0
+            }
+            fn record_len(&self, _: usize, _: usize) -> usize {
+                0
+            }
+            fn read_record(&self, _: usize, _: usize, _: &mut [u8]) -> crate::Result {
+                Ok(())
+            }
+            fn update_record(&mut self, _: usize, _: usize, _: &[u8]) -> crate::Result {
+                Err(Status::MemoryFailure)
+            }
+            fn append_record(&mut self, _: usize, _: &[u8]) -> crate::Result {
+                Err(Status::MemoryFailure)
+            }
+        }
+
+        let files = [File::mf(), File::df(FileId::new(0x7F10).unwrap(), 0)];
+        let mut fs = FileSystem::new(&files, NoContent);
+        let mut store = DataObjectStore::new(&OBJECTS, Memory::default()).mount(1);
+        let mut response = Data::<16>::new();
+        let put_data = CommandView::try_from(&hex!("00DB3FFF 05 5FC102 01 01")[..]).unwrap();
+        assert_eq!(
+            store.call_in(&fs, &put_data, &mut response),
+            Err(Status::InstructionNotSupportedOrInvalid)
+        );
+
+        let select = CommandView::try_from(&hex!("00A4000C 02 7F10")[..]).unwrap();
+        fs.call(&select, &mut response).unwrap();
+        assert_eq!(
+            store.call_in(&fs, &put_data, &mut response),
+            Err(Status::SecurityStatusNotSatisfied)
+        );
+        // The conditions granted to the store itself are ignored
+        store.grant(0b01);
+        assert_eq!(
+            store.call_in(&fs, &put_data, &mut response),
+            Err(Status::SecurityStatusNotSatisfied)
+        );
+        fs.grant(0b01);
+        assert_eq!(store.call_in(&fs, &put_data, &mut response), Ok(()));
+        assert_eq!(store.storage().0[1].as_deref(), Some(&hex!("01")[..]));
+    }
+}
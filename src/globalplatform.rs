@@ -0,0 +1,7 @@
+//! GlobalPlatform card management (GlobalPlatform Card Specification)
+//!
+//! Commands are sent with the class [`NO_SM_CLA`](crate::command::class::NO_SM_CLA), or
+//! [`SM_CLA`](crate::command::class::SM_CLA) within a secure channel.
+
+pub mod get_status;
+pub mod isd;
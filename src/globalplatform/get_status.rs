@@ -0,0 +1,397 @@
+//! GET STATUS (GlobalPlatform Card Specification 11.4)
+//!
+//! The response lists the registry entries of the card content selected by P1, each in an `E3`
+//! template:
+//!
+//! | Tag    | Data object                                  |
+//! |--------|----------------------------------------------|
+//! | `4F`   | AID                                          |
+//! | `9F70` | Life cycle state                             |
+//! | `C5`   | [`Privileges`]                               |
+//! | `C4`   | AID of the executable load file              |
+//! | `CE`   | Version of the executable load file          |
+//! | `84`   | AID of an executable module, may be repeated |
+//! | `CC`   | AID of the associated security domain        |
+//!
+//! When the entries do not fit in one response, the card returns [`MORE_DATA`] and the next
+//! entries are requested with [`GetStatus::next`]. [`get_status`] performs these exchanges.
+//!
+//! ```
+//! use iso7816::command::class::NO_SM_CLA;
+//! use iso7816::globalplatform::get_status::{entries, GetStatus, Subset};
+//! let mut command = heapless::Vec::<u8, 16>::new();
+//! GetStatus::new(Subset::Applications)
+//!     .command(NO_SM_CLA)
+//!     .serialize_into(&mut command)
+//!     .unwrap();
+//! assert_eq!(&*command, &[0x80, 0xF2, 0x40, 0x02, 0x02, 0x4F, 0x00, 0x00]);
+//!
+//! let response = [
+//!     0xE3, 0x10, 0x4F, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08, 0x9F, 0x70, 0x01, 0x07, 0xC5, 0x03,
+//!     0x00, 0x00, 0x00,
+//! ];
+//! let entry = entries(&response).next().unwrap().unwrap();
+//! assert_eq!(entry.aid(), [0xA0, 0x00, 0x00, 0x03, 0x08]);
+//! assert_eq!(entry.life_cycle(), 0x07);
+//! ```
+
+use crate::command::class::Class;
+use crate::command::{CommandBuilder, Instruction};
+use crate::tlv::{self, Tag};
+use crate::transport::{self, Channel, Transport};
+use crate::{Data, Status};
+
+/// `63 10`: more entries are available with [`GetStatus::next`]
+pub const MORE_DATA: Status = Status::from_u16(0x6310);
+
+/// Search criteria matching all the entries of the subset (`4F 00`)
+const ALL: &[u8] = &[0x4F, 0x00];
+
+const REGISTRY_ENTRY: u8 = 0xE3;
+const AID: u8 = 0x4F;
+const LIFE_CYCLE: u16 = 0x9F70;
+const PRIVILEGES: u8 = 0xC5;
+const LOAD_FILE: u8 = 0xC4;
+const VERSION: u8 = 0xCE;
+const MODULE: u8 = 0x84;
+const SECURITY_DOMAIN: u8 = 0xCC;
+
+/// Card content to list, encoded in P1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Subset {
+    /// Issuer security domain (`80`)
+    IssuerSecurityDomain,
+    /// Applications and supplementary security domains (`40`)
+    Applications,
+    /// Executable load files (`20`)
+    LoadFiles,
+    /// Executable load files and their executable modules (`10`)
+    LoadFilesAndModules,
+}
+
+impl Subset {
+    pub const fn p1(self) -> u8 {
+        match self {
+            Self::IssuerSecurityDomain => 0x80,
+            Self::Applications => 0x40,
+            Self::LoadFiles => 0x20,
+            Self::LoadFilesAndModules => 0x10,
+        }
+    }
+}
+
+/// GET STATUS command, requesting the entries in the TLV format (P2 bit 2)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GetStatus<'a> {
+    subset: Subset,
+    criteria: &'a [u8],
+    next: bool,
+}
+
+impl<'a> GetStatus<'a> {
+    /// First entries of the subset, without filtering
+    pub fn new(subset: Subset) -> Self {
+        Self {
+            subset,
+            criteria: ALL,
+            next: false,
+        }
+    }
+
+    /// Only list the entries matching the search criteria, for example `4F` with an AID prefix
+    pub fn search(mut self, criteria: &'a [u8]) -> Self {
+        self.criteria = criteria;
+        self
+    }
+
+    /// Request the entries following those of a response with [`MORE_DATA`]
+    pub fn next(mut self) -> Self {
+        self.next = true;
+        self
+    }
+
+    pub fn subset(&self) -> Subset {
+        self.subset
+    }
+
+    pub fn p2(&self) -> u8 {
+        0x02 | u8::from(self.next)
+    }
+
+    pub fn command(self, class: Class) -> CommandBuilder<&'a [u8]> {
+        CommandBuilder::new(
+            class,
+            Instruction::Unknown(0xF2),
+            self.subset.p1(),
+            self.p2(),
+            self.criteria,
+            256,
+        )
+    }
+}
+
+/// Privileges of an application or security domain (`C5`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Privileges(pub [u8; 3]);
+
+impl Privileges {
+    pub const SECURITY_DOMAIN: Self = Self([0x80, 0, 0]);
+    pub const DAP_VERIFICATION: Self = Self([0xC0, 0, 0]);
+    pub const DELEGATED_MANAGEMENT: Self = Self([0xA0, 0, 0]);
+    pub const CARD_LOCK: Self = Self([0x10, 0, 0]);
+    pub const CARD_TERMINATE: Self = Self([0x08, 0, 0]);
+    pub const CARD_RESET: Self = Self([0x04, 0, 0]);
+    pub const CVM_MANAGEMENT: Self = Self([0x02, 0, 0]);
+    pub const MANDATED_DAP_VERIFICATION: Self = Self([0xC1, 0, 0]);
+    pub const TRUSTED_PATH: Self = Self([0, 0x80, 0]);
+    pub const AUTHORIZED_MANAGEMENT: Self = Self([0, 0x40, 0]);
+    pub const TOKEN_VERIFICATION: Self = Self([0, 0x20, 0]);
+    pub const GLOBAL_DELETE: Self = Self([0, 0x10, 0]);
+    pub const GLOBAL_LOCK: Self = Self([0, 0x08, 0]);
+    pub const GLOBAL_REGISTRY: Self = Self([0, 0x04, 0]);
+    pub const FINAL_APPLICATION: Self = Self([0, 0x02, 0]);
+    pub const GLOBAL_SERVICE: Self = Self([0, 0x01, 0]);
+    pub const RECEIPT_GENERATION: Self = Self([0, 0, 0x80]);
+    pub const CIPHERED_LOAD_FILE_DATA_BLOCK: Self = Self([0, 0, 0x40]);
+    pub const CONTACTLESS_ACTIVATION: Self = Self([0, 0, 0x20]);
+    pub const CONTACTLESS_SELF_ACTIVATION: Self = Self([0, 0, 0x10]);
+
+    /// Parse the value of `C5`, of one byte before GlobalPlatform 2.2 and three bytes since
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match *bytes {
+            [b1] => Some(Self([b1, 0, 0])),
+            [b1, b2, b3] => Some(Self([b1, b2, b3])),
+            _ => None,
+        }
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        let (a, b) = (self.0, other.0);
+        a[0] & b[0] == b[0] && a[1] & b[1] == b[1] && a[2] & b[2] == b[2]
+    }
+}
+
+/// Registry entry of a GET STATUS response (`E3`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegistryEntry<'a> {
+    aid: &'a [u8],
+    life_cycle: u8,
+    privileges: Option<Privileges>,
+    load_file: Option<&'a [u8]>,
+    version: Option<&'a [u8]>,
+    security_domain: Option<&'a [u8]>,
+    /// Value of the template, for the repeated `84` data objects
+    value: &'a [u8],
+}
+
+impl<'a> RegistryEntry<'a> {
+    /// Parse the value of an `E3` template
+    pub fn from_bytes(value: &'a [u8]) -> Result<Self, GetStatusError> {
+        let mut aid = None;
+        let mut life_cycle = None;
+        let mut entry = Self {
+            aid: &[],
+            life_cycle: 0,
+            privileges: None,
+            load_file: None,
+            version: None,
+            security_domain: None,
+            value,
+        };
+        for object in tlv::iter(value) {
+            let (tag, value) = object.map_err(|_| GetStatusError::InvalidData)?;
+            if tag == Tag::from_u16(LIFE_CYCLE) {
+                let &[state] = value else {
+                    return Err(GetStatusError::InvalidData);
+                };
+                life_cycle = Some(state);
+                continue;
+            }
+            let [tag] = tag.as_bytes() else {
+                continue;
+            };
+            match *tag {
+                AID => aid = Some(value),
+                PRIVILEGES => {
+                    entry.privileges =
+                        Some(Privileges::from_bytes(value).ok_or(GetStatusError::InvalidData)?)
+                }
+                LOAD_FILE => entry.load_file = Some(value),
+                VERSION => entry.version = Some(value),
+                SECURITY_DOMAIN => entry.security_domain = Some(value),
+                _ => {}
+            }
+        }
+        entry.aid = aid.ok_or(GetStatusError::InvalidData)?;
+        entry.life_cycle = life_cycle.ok_or(GetStatusError::InvalidData)?;
+        Ok(entry)
+    }
+
+    pub fn aid(&self) -> &'a [u8] {
+        self.aid
+    }
+
+    /// Life cycle state (`9F70`), whose coding depends on the kind of card content
+    pub fn life_cycle(&self) -> u8 {
+        self.life_cycle
+    }
+
+    /// Privileges, absent for executable load files
+    pub fn privileges(&self) -> Option<Privileges> {
+        self.privileges
+    }
+
+    /// AID of the executable load file the application was installed from
+    pub fn load_file(&self) -> Option<&'a [u8]> {
+        self.load_file
+    }
+
+    /// Version of the executable load file
+    pub fn version(&self) -> Option<&'a [u8]> {
+        self.version
+    }
+
+    /// AID of the associated security domain
+    pub fn security_domain(&self) -> Option<&'a [u8]> {
+        self.security_domain
+    }
+
+    /// AIDs of the executable modules of an executable load file
+    pub fn modules(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        tlv::iter(self.value)
+            .map_while(Result::ok)
+            .filter(|(tag, _)| *tag == Tag::from_u8(MODULE))
+            .map(|(_, value)| value)
+    }
+}
+
+/// Parse the registry entries of a GET STATUS response
+pub fn entries(data: &[u8]) -> impl Iterator<Item = Result<RegistryEntry<'_>, GetStatusError>> {
+    tlv::iter(data).map(|object| {
+        let (tag, value) = object.map_err(|_| GetStatusError::InvalidData)?;
+        if tag != Tag::from_u8(REGISTRY_ENTRY) {
+            return Err(GetStatusError::InvalidData);
+        }
+        RegistryEntry::from_bytes(value)
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GetStatusError<E = ()> {
+    /// The response is not a list of valid registry entries
+    InvalidData,
+    /// The card returned an error
+    Status(Status),
+    /// The exchange failed
+    Transport(transport::Error<E>),
+}
+
+impl<E> From<transport::Error<E>> for GetStatusError<E> {
+    fn from(error: transport::Error<E>) -> Self {
+        Self::Transport(error)
+    }
+}
+
+/// Send `request` and the following GET STATUS commands until all the entries are received
+///
+/// Each response is received into `page` and its entries are passed to `f`, so `page` only needs
+/// to hold one response.
+pub fn get_status<T: Transport, const N: usize, const R: usize>(
+    channel: &mut Channel<T, N>,
+    class: Class,
+    mut request: GetStatus<'_>,
+    page: &mut Data<R>,
+    mut f: impl FnMut(RegistryEntry<'_>),
+) -> Result<(), GetStatusError<T::Error>> {
+    loop {
+        let status = channel.exchange(request.command(class), page)?;
+        if status != Status::Success && status != MORE_DATA {
+            return Err(GetStatusError::Status(status));
+        }
+        for entry in entries(page) {
+            f(entry.map_err(|_| GetStatusError::InvalidData)?);
+        }
+        if status == Status::Success {
+            return Ok(());
+        }
+        request = request.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::class::NO_SM_CLA;
+    use crate::transport::tests::Script;
+    use hex_literal::hex;
+
+    #[test]
+    fn registry_entries() {
+        let data = hex!(
+            "E3 14 4F 07 A0000001510000 9F70 01 0F C5 03 9EFE80 CC 00"
+            "E3 1F 4F 07 A0000000620001 9F70 01 01 CE 02 0100 84 05 A000000062 84 05 A000000063"
+        );
+        let parsed: Vec<_> = entries(&data).collect::<Result<_, _>>().unwrap();
+        assert_eq!(parsed.len(), 2);
+        let isd = parsed[0];
+        assert_eq!(isd.aid(), hex!("A0000001510000"));
+        assert_eq!(isd.life_cycle(), 0x0F);
+        let privileges = isd.privileges().unwrap();
+        assert!(privileges.contains(Privileges::SECURITY_DOMAIN));
+        assert!(privileges.contains(Privileges::CARD_RESET));
+        assert!(!privileges.contains(Privileges::MANDATED_DAP_VERIFICATION));
+        assert_eq!(isd.security_domain(), Some(&[][..]));
+        assert_eq!(isd.modules().count(), 0);
+        let load_file = parsed[1];
+        assert_eq!(load_file.privileges(), None);
+        assert_eq!(load_file.version(), Some(&hex!("0100")[..]));
+        assert_eq!(
+            load_file.modules().collect::<Vec<_>>(),
+            [hex!("A000000062"), hex!("A000000063")]
+        );
+
+        assert_eq!(
+            RegistryEntry::from_bytes(&hex!("9F70 01 07")),
+            Err(GetStatusError::InvalidData)
+        );
+        assert_eq!(
+            entries(&hex!("E4 03 4F 01 A0")).next(),
+            Some(Err(GetStatusError::InvalidData))
+        );
+    }
+
+    #[test]
+    fn continuation() {
+        let mut channel = Channel::<_, 300>::new(Script::new(&[
+            &hex!("E3 0A 4F 01 A1 9F70 01 07 C5 01 00 6310")[..],
+            &hex!("E3 07 4F 01 A2 9F70 01 07 9000"),
+            &hex!("6A88"),
+        ]));
+        let mut page = Data::<32>::new();
+        let mut aids = Vec::new();
+        get_status(
+            &mut channel,
+            NO_SM_CLA,
+            GetStatus::new(Subset::Applications),
+            &mut page,
+            |entry| aids.push(entry.aid().to_vec()),
+        )
+        .unwrap();
+        assert_eq!(aids, [[0xA1], [0xA2]]);
+        assert_eq!(
+            channel.transport().commands,
+            [hex!("80F24002 02 4F00 00"), hex!("80F24003 02 4F00 00")]
+        );
+
+        assert_eq!(
+            get_status(
+                &mut channel,
+                NO_SM_CLA,
+                GetStatus::new(Subset::LoadFiles),
+                &mut page,
+                |_| {}
+            ),
+            Err(GetStatusError::Status(Status::KeyReferenceNotFound))
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! SELECT response of the issuer security domain (GlobalPlatform Card Specification 11.1.6 and H.2)
+//!
+//! The FCI of a security domain carries its security domain management data in the proprietary
+//! template, in the same format as the card recognition data returned by GET DATA `66`:
+//!
+//! ```text
+//! 6F
+//!   84    AID of the security domain
+//!   A5
+//!     73  Security domain management data
+//!       06  {globalPlatform 1}
+//!       60  06 {globalPlatform 2 version}: card management type and version
+//!       63  06 {globalPlatform 3}: card identification scheme
+//!       64  06 {globalPlatform 4 scp i}: secure channel protocol, may be repeated
+//!       65, 66, 67, 68: configuration, chip and security domain details
+//!     9F6E  Application production life cycle data
+//!     9F65  Maximum length of the command data field
+//! ```
+//!
+//! [`Scp`] gives the secure channel protocols supported by the security domain, so that hosts can
+//! choose between SCP02 and SCP03:
+//!
+//! ```
+//! use iso7816::globalplatform::isd::{IsdFci, Scp};
+//! let response = [
+//!     0x6F, 0x24, 0x84, 0x08, 0xA0, 0x00, 0x00, 0x01, 0x51, 0x00, 0x00, 0x00, 0xA5, 0x18, 0x73,
+//!     0x16, 0x06, 0x07, 0x2A, 0x86, 0x48, 0x86, 0xFC, 0x6B, 0x01, 0x64, 0x0B, 0x06, 0x09, 0x2A,
+//!     0x86, 0x48, 0x86, 0xFC, 0x6B, 0x04, 0x03, 0x70,
+//! ];
+//! let fci = IsdFci::parse(&response).unwrap();
+//! assert_eq!(fci.aid(), Some(&[0xA0, 0x00, 0x00, 0x01, 0x51, 0x00, 0x00, 0x00][..]));
+//! assert_eq!(fci.scp(), Some(Scp { version: 0x03, i: 0x70 }));
+//! ```
+
+use crate::file::fci::{Fci, FciError};
+use crate::tlv::{self, take_data_object, Tag};
+
+/// Object identifier {iso(1) member-body(2) us(840) globalPlatform(114283)}
+const GLOBAL_PLATFORM: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xFC, 0x6B];
+
+const OID: u8 = 0x06;
+const CARD_RECOGNITION_DATA: u8 = 0x66;
+const SD_MANAGEMENT_DATA: u8 = 0x73;
+const CARD_MANAGEMENT_TYPE: u8 = 0x60;
+const SECURE_CHANNEL_PROTOCOL: u8 = 0x64;
+const LIFE_CYCLE_DATA: u16 = 0x9F6E;
+const MAX_COMMAND_DATA_LEN: u16 = 0x9F65;
+
+/// Secure channel protocol supported by a security domain, `{globalPlatform 4 version i}`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scp {
+    /// Protocol number, `02` for SCP02 and `03` for SCP03
+    pub version: u8,
+    /// Implementation options of the protocol
+    pub i: u8,
+}
+
+impl Scp {
+    pub const fn is_scp02(&self) -> bool {
+        self.version == 0x02
+    }
+
+    pub const fn is_scp03(&self) -> bool {
+        self.version == 0x03
+    }
+}
+
+/// Arcs of a GlobalPlatform object identifier following `{globalPlatform kind}`
+fn global_platform_oid(oid: &[u8], kind: u8) -> Option<&[u8]> {
+    match oid.strip_prefix(GLOBAL_PLATFORM)?.split_first()? {
+        (&k, arcs) if k == kind => Some(arcs),
+        _ => None,
+    }
+}
+
+/// Object identifier in the `06` data object of a template
+fn oid(template: &[u8]) -> Option<&[u8]> {
+    let (tag, value, _) = take_data_object(template)?;
+    (tag == Tag::from_u8(OID)).then_some(value)
+}
+
+/// Card recognition data, or security domain management data (content of `73`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CardRecognition<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> CardRecognition<'a> {
+    /// Parse the content of the `73` template
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, FciError> {
+        if tlv::iter(data).any(|object| object.is_err()) {
+            return Err(FciError::InvalidData);
+        }
+        match oid(data).and_then(|oid| global_platform_oid(oid, 1)) {
+            Some([]) => Ok(Self { data }),
+            _ => Err(FciError::InvalidData),
+        }
+    }
+
+    /// Parse the response of GET DATA `66`
+    pub fn parse(response: &'a [u8]) -> Result<Self, FciError> {
+        let (tag, value, rest) = take_data_object(response).ok_or(FciError::InvalidTemplate)?;
+        if tag != Tag::from_u8(CARD_RECOGNITION_DATA) || !rest.is_empty() {
+            return Err(FciError::InvalidTemplate);
+        }
+        let (tag, value, rest) = take_data_object(value).ok_or(FciError::InvalidData)?;
+        if tag != Tag::from_u8(SD_MANAGEMENT_DATA) || !rest.is_empty() {
+            return Err(FciError::InvalidData);
+        }
+        Self::from_bytes(value)
+    }
+
+    /// Templates of the data, each containing an object identifier
+    fn templates(&self) -> impl Iterator<Item = (Tag, &'a [u8])> + 'a {
+        tlv::iter(self.data).map_while(Result::ok)
+    }
+
+    /// Content of the data
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Version of the card management, such as `[2, 3, 1]` for GlobalPlatform 2.3.1 (`60`)
+    pub fn version(&self) -> Option<&'a [u8]> {
+        self.templates()
+            .find(|(tag, _)| *tag == Tag::from_u8(CARD_MANAGEMENT_TYPE))
+            .and_then(|(_, template)| global_platform_oid(oid(template)?, 2))
+    }
+
+    /// Secure channel protocols, in the order of the data (`64`)
+    pub fn scps(&self) -> impl Iterator<Item = Scp> + 'a {
+        self.templates()
+            .filter(|(tag, _)| *tag == Tag::from_u8(SECURE_CHANNEL_PROTOCOL))
+            .filter_map(
+                |(_, template)| match global_platform_oid(oid(template)?, 4)? {
+                    &[version, i] => Some(Scp { version, i }),
+                    _ => None,
+                },
+            )
+    }
+
+    /// Most recent secure channel protocol supported
+    pub fn scp(&self) -> Option<Scp> {
+        self.scps().max_by_key(|scp| scp.version)
+    }
+}
+
+/// Parsed SELECT response of a security domain
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IsdFci<'a> {
+    fci: Fci<'a>,
+    card_recognition: Option<CardRecognition<'a>>,
+    life_cycle_data: Option<&'a [u8]>,
+    max_command_data_len: Option<usize>,
+}
+
+impl<'a> IsdFci<'a> {
+    /// Parse the response data of SELECT
+    pub fn parse(response: &'a [u8]) -> Result<Self, FciError> {
+        let fci = Fci::parse(response)?;
+        let mut isd = Self {
+            fci,
+            card_recognition: None,
+            life_cycle_data: None,
+            max_command_data_len: None,
+        };
+        for object in tlv::iter(fci.proprietary().unwrap_or_default()) {
+            let (tag, value) = object.map_err(|_| FciError::InvalidData)?;
+            if tag == Tag::from_u8(SD_MANAGEMENT_DATA) {
+                isd.card_recognition = Some(CardRecognition::from_bytes(value)?);
+            } else if tag == Tag::from_u16(LIFE_CYCLE_DATA) {
+                isd.life_cycle_data = Some(value);
+            } else if tag == Tag::from_u16(MAX_COMMAND_DATA_LEN) {
+                if value.is_empty() || value.len() > 2 {
+                    return Err(FciError::InvalidData);
+                }
+                isd.max_command_data_len =
+                    Some(value.iter().fold(0, |acc, &b| acc << 8 | usize::from(b)));
+            }
+        }
+        Ok(isd)
+    }
+
+    /// Generic FCI
+    pub fn fci(&self) -> Fci<'a> {
+        self.fci
+    }
+
+    /// AID of the security domain (`84`)
+    pub fn aid(&self) -> Option<&'a [u8]> {
+        self.fci.df_name()
+    }
+
+    /// Security domain management data (`73`)
+    pub fn card_recognition(&self) -> Option<CardRecognition<'a>> {
+        self.card_recognition
+    }
+
+    /// Application production life cycle data (`9F6E`)
+    pub fn life_cycle_data(&self) -> Option<&'a [u8]> {
+        self.life_cycle_data
+    }
+
+    /// Maximum length of the data field of command messages (`9F65`)
+    pub fn max_command_data_len(&self) -> Option<usize> {
+        self.max_command_data_len
+    }
+
+    /// Most recent secure channel protocol supported, see [`CardRecognition::scp`]
+    pub fn scp(&self) -> Option<Scp> {
+        self.card_recognition?.scp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn isd_fci() {
+        let response = hex!(
+            "6F72 8408A000000151000000 A566"
+            "7357 06072A864886FC6B01 600C060A2A864886FC6B02020301 630906072A864886FC6B03"
+            "640B06092A864886FC6B040255 640B06092A864886FC6B040370 650B06092A864886FC6B050101"
+            "660C060A2B060104012A026E0102"
+            "9F6E06479100781300 9F6501FF"
+        );
+        let fci = IsdFci::parse(&response).unwrap();
+        assert_eq!(fci.aid(), Some(&hex!("A000000151000000")[..]));
+        assert_eq!(fci.max_command_data_len(), Some(255));
+        assert_eq!(fci.life_cycle_data(), Some(&hex!("479100781300")[..]));
+        let recognition = fci.card_recognition().unwrap();
+        assert_eq!(recognition.version(), Some(&hex!("020301")[..]));
+        assert_eq!(
+            recognition.scps().collect::<Vec<_>>(),
+            [
+                Scp {
+                    version: 0x02,
+                    i: 0x55
+                },
+                Scp {
+                    version: 0x03,
+                    i: 0x70
+                }
+            ]
+        );
+        assert!(fci.scp().unwrap().is_scp03());
+
+        // The same data is returned by GET DATA
+        let mut card_recognition = hex!("6659").to_vec();
+        card_recognition.extend_from_slice(&response[14..14 + 0x59]);
+        assert_eq!(CardRecognition::parse(&card_recognition), Ok(recognition));
+
+        // Cards without security domain management data
+        let fci = IsdFci::parse(&hex!("6F0A 8408A000000151000000")).unwrap();
+        assert_eq!(fci.card_recognition(), None);
+        assert_eq!(fci.scp(), None);
+
+        assert_eq!(
+            IsdFci::parse(&hex!("6F0F 8408A000000151000000 A503 7301 05")),
+            Err(FciError::InvalidData)
+        );
+        assert_eq!(
+            CardRecognition::from_bytes(&hex!("06072A864886FC6B02")),
+            Err(FciError::InvalidData)
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Logical interfaces over which APDUs are exchanged
+//!
+//! The [`Capabilities`] of an interface describe how commands and responses must be sized and
+//! split over it. The default capabilities are conservative, applications knowing the actual
+//! reader or card can adjust them:
+//!
+//! ```
+//! use iso7816::Interface;
+//! let mut capabilities = Interface::Contactless.capabilities();
+//! assert!(!capabilities.extended_length);
+//! // The reader was checked to support extended length APDUs
+//! capabilities.extended_length = true;
+//! assert!(capabilities.max_command_len >= 261);
+//! ```
+
+/// Logical transport carrying APDUs
+///
+/// New transports may be added in minor releases.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Interface {
+    /// ISO 7816-3 contact interface
+    Contact,
+    /// ISO 14443-4 contactless interface
+    Contactless,
+    /// USB CCID, the smart card class
+    Ccid,
+    /// APDUs wrapped in CTAPHID messages (`CTAPHID_MSG`)
+    CtapHid,
+    /// Virtual serial port
+    Vcp,
+    /// Proprietary host channel, identified by the application
+    Proprietary(u8),
+}
+
+/// Limits and features of an [`Interface`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Length of the largest command APDU, before chaining
+    pub max_command_len: usize,
+    /// Length of the largest response APDU, status included, before GET RESPONSE is needed
+    pub max_response_len: usize,
+    /// Whether commands and responses can use extended length fields
+    pub extended_length: bool,
+    /// Whether command chaining (ISO 7816-4 5.3.3) can be used for commands longer than [`max_command_len`](Self::max_command_len)
+    pub chaining: bool,
+    /// Whether long operations must request more time from the reader (T=1 or ISO-DEP S(WTX))
+    pub requires_wtx: bool,
+}
+
+impl Capabilities {
+    /// Short APDUs only: 255 bytes of data and 256 bytes expected
+    pub const SHORT: Self = Self {
+        max_command_len: 4 + 1 + 255 + 1,
+        max_response_len: 256 + 2,
+        extended_length: false,
+        chaining: true,
+        requires_wtx: false,
+    };
+
+    /// Extended APDUs: 65535 bytes of data and 65536 bytes expected
+    pub const EXTENDED: Self = Self {
+        max_command_len: 4 + 3 + 65535 + 2,
+        max_response_len: 65536 + 2,
+        extended_length: true,
+        chaining: true,
+        requires_wtx: false,
+    };
+
+    /// Whether a command of `len` bytes can be sent, possibly with chaining
+    pub const fn fits_command(&self, len: usize) -> bool {
+        self.chaining || len <= self.max_command_len
+    }
+}
+
+/// Largest CTAPHID message: one initialization packet and 128 continuation packets of 64 bytes
+const CTAPHID_MAX_MESSAGE_LEN: usize = 64 - 7 + 128 * (64 - 5);
+
+impl Interface {
+    /// Default capabilities of the interface
+    ///
+    /// Contact cards are assumed to support extended length, over T=1.
+    /// Contactless readers commonly limit APDUs to short lengths.
+    /// USB and proprietary channels carry extended APDUs.
+    pub const fn capabilities(&self) -> Capabilities {
+        match self {
+            Self::Contact => Capabilities {
+                requires_wtx: true,
+                ..Capabilities::EXTENDED
+            },
+            Self::Contactless => Capabilities {
+                requires_wtx: true,
+                ..Capabilities::SHORT
+            },
+            // Time extensions are requested with the CCID bStatus field
+            Self::Ccid => Capabilities {
+                requires_wtx: true,
+                ..Capabilities::EXTENDED
+            },
+            // Messages are limited by the 128 continuation packets of CTAPHID, and keepalives
+            // are sent by the CTAPHID layer itself
+            Self::CtapHid => Capabilities {
+                max_command_len: CTAPHID_MAX_MESSAGE_LEN,
+                max_response_len: CTAPHID_MAX_MESSAGE_LEN,
+                ..Capabilities::EXTENDED
+            },
+            Self::Vcp | Self::Proprietary(_) => Capabilities::EXTENDED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities() {
+        let contact = Interface::Contact.capabilities();
+        assert!(contact.extended_length);
+        assert_eq!(contact.max_command_len, 65544);
+        let contactless = Interface::Contactless.capabilities();
+        assert!(!contactless.extended_length);
+        assert_eq!(contactless.max_response_len, 258);
+        assert!(contactless.fits_command(1000));
+        let unchained = Capabilities {
+            chaining: false,
+            ..contactless
+        };
+        assert!(unchained.fits_command(261));
+        assert!(!unchained.fits_command(262));
+
+        assert_eq!(Interface::CtapHid.capabilities().max_command_len, 7609);
+        assert!(!Interface::Vcp.capabilities().requires_wtx);
+        assert_eq!(
+            Interface::Proprietary(1).capabilities(),
+            Capabilities::EXTENDED
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Conversions with the [`apdu_core`] types
+//!
+//! The encoding of `Le` differs between the two crates: `apdu_core` uses an optional `u16`,
+//! while this crate uses the expected number of bytes (`0` meaning no response data).
+//! `Some(0)` is therefore not accepted when converting from `apdu_core`, and an expected length of
+//! 65536 bytes can't be converted to `apdu_core`.
+//!
+//! [`Response`] can only carry either data or a status, so a response with both data and a
+//! status other than [`Status::Success`] is converted to [`Response::Status`].
+
+use crate::command::class::Class;
+use crate::command::{CommandBuilder, CommandView, ExpectedLen};
+use crate::{Command, Data, Response, Status};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InteropError {
+    InvalidClass,
+    /// `Le` can't be represented in the target type
+    InvalidLe,
+    /// The data does not fit in the target buffer
+    TooLong,
+}
+
+impl<'a> TryFrom<apdu_core::Command<'a>> for CommandBuilder<&'a [u8]> {
+    type Error = InteropError;
+
+    fn try_from(command: apdu_core::Command<'a>) -> Result<Self, InteropError> {
+        let class = Class::try_from(command.cla).map_err(|_| InteropError::InvalidClass)?;
+        let data = command.payload.unwrap_or_default();
+        if data.len() > u16::MAX as usize {
+            return Err(InteropError::TooLong);
+        }
+        let le = match command.le {
+            None => ExpectedLen::Ne(0),
+            Some(0) => return Err(InteropError::InvalidLe),
+            Some(le) => ExpectedLen::Ne(le),
+        };
+        Ok(CommandBuilder::new(
+            class,
+            command.ins.into(),
+            command.p1,
+            command.p2,
+            data,
+            le,
+        ))
+    }
+}
+
+impl<'a> TryFrom<CommandView<'a>> for apdu_core::Command<'a> {
+    type Error = InteropError;
+
+    fn try_from(command: CommandView<'a>) -> Result<Self, InteropError> {
+        let le = match command.expected() {
+            0 => None,
+            le => Some(u16::try_from(le).map_err(|_| InteropError::InvalidLe)?),
+        };
+        let data = command.data();
+        Ok(apdu_core::Command {
+            cla: command.class().into_inner(),
+            ins: command.instruction().into(),
+            p1: command.p1,
+            p2: command.p2,
+            le,
+            payload: (!data.is_empty()).then_some(data),
+        })
+    }
+}
+
+impl<'a, const S: usize> TryFrom<&'a Command<S>> for apdu_core::Command<'a> {
+    type Error = InteropError;
+
+    fn try_from(command: &'a Command<S>) -> Result<Self, InteropError> {
+        command.as_view().try_into()
+    }
+}
+
+impl<'a> From<apdu_core::Response<'a>> for Status {
+    fn from(response: apdu_core::Response<'a>) -> Self {
+        response.trailer.into()
+    }
+}
+
+impl<'a, const S: usize> TryFrom<apdu_core::Response<'a>> for Response<S> {
+    type Error = InteropError;
+
+    fn try_from(response: apdu_core::Response<'a>) -> Result<Self, InteropError> {
+        let status = Status::from(response.trailer);
+        if status != Status::Success {
+            return Ok(Response::Status(status));
+        }
+        Data::from_slice(response.payload)
+            .map(Response::Data)
+            .map_err(|_| InteropError::TooLong)
+    }
+}
+
+impl<'a, const S: usize> From<&'a Response<S>> for apdu_core::Response<'a> {
+    fn from(response: &'a Response<S>) -> Self {
+        let (payload, status) = match response {
+            Response::Data(data) => (data.as_slice(), Status::Success),
+            Response::Status(status) => (&[][..], *status),
+        };
+        let [sw1, sw2]: [u8; 2] = status.into();
+        apdu_core::Response {
+            payload,
+            trailer: (sw1, sw2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn command_roundtrip() {
+        let view = CommandView::try_from(&hex!("00A40400 02 A000 00")[..]).unwrap();
+        let command = apdu_core::Command::try_from(view).unwrap();
+        assert_eq!(command.cla, 0x00);
+        assert_eq!(command.ins, 0xA4);
+        assert_eq!(command.le, Some(256));
+        assert_eq!(command.payload, Some(&hex!("A000")[..]));
+        let builder = CommandBuilder::try_from(command).unwrap();
+        assert_eq!(builder, view);
+    }
+
+    #[test]
+    fn response() {
+        let response = apdu_core::Response {
+            payload: &hex!("0102"),
+            trailer: (0x90, 0x00),
+        };
+        assert_eq!(
+            Response::<4>::try_from(response),
+            Ok(Response::Data(Data::from_slice(&hex!("0102")).unwrap()))
+        );
+        let response = apdu_core::Response {
+            payload: &[],
+            trailer: (0x69, 0x82),
+        };
+        assert_eq!(
+            Response::<4>::try_from(response),
+            Ok(Response::Status(Status::SecurityStatusNotSatisfied))
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! ISO/IEC 14443-4 (ISO-DEP) activation parameters
+//!
+//! The reader sends RATS with its maximum frame size (FSDI) and the card answers with the ATS,
+//! indicating its own maximum frame size (FSCI), its timings and its historical bytes.
+//!
+//! APDUs are carried in the information field of I-blocks, framed by the PCB, the optional CID
+//! and NAD, and the two CRC bytes. [`Link::max_inf_len`] gives the space left for the APDU in a
+//! single frame, suitable for [`CommandBuilder::should_split`](crate::command::CommandBuilder::should_split)
+//! and [`CommandBuilder::new_non_extended`](crate::command::CommandBuilder::new_non_extended).
+//!
+//! ```
+//! use iso7816::iso_dep::{Ats, Link};
+//! let ats = Ats::parse(&[0x05, 0x78, 0x77, 0x71, 0x02]).unwrap();
+//! assert_eq!(ats.fsc(), 256);
+//! let link = Link::new(ats.fsc(), 256);
+//! assert_eq!(link.max_inf_len(), 253);
+//! ```
+
+use crate::atr::historical::HistoricalBytes;
+use crate::atr::AtrError;
+
+/// Frame sizes indexed by FSCI or FSDI. Values from `D` are RFU and interpreted as 256.
+pub const FRAME_SIZES: [u16; 13] = [16, 24, 32, 40, 48, 64, 96, 128, 256, 512, 1024, 2048, 4096];
+
+/// RATS command start byte
+pub const RATS: u8 = 0xE0;
+
+/// Frame size for a FSCI or FSDI value
+pub fn frame_size(index: u8) -> u16 {
+    FRAME_SIZES.get(usize::from(index)).copied().unwrap_or(256)
+}
+
+/// Largest FSCI or FSDI whose frame size is at most `size`, `None` if `size` is smaller than 16
+pub fn frame_size_index(size: usize) -> Option<u8> {
+    FRAME_SIZES
+        .iter()
+        .rposition(|&s| usize::from(s) <= size)
+        .map(|i| i as u8)
+}
+
+/// RATS command for the given FSDI and CID
+pub fn rats(fsdi: u8, cid: u8) -> [u8; 2] {
+    [RATS, (fsdi & 0x0F) << 4 | (cid & 0x0F)]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AtsError {
+    /// The ATS is shorter than indicated by TL or T0
+    Truncated,
+    /// The ATS is longer than indicated by TL
+    TooLong,
+    /// More historical bytes than fit in the ATS
+    TooManyHistoricalBytes,
+}
+
+/// Answer to select
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ats<'a> {
+    /// Frame size for the card integer
+    pub fsci: u8,
+    /// Supported bit rates
+    pub ta: Option<u8>,
+    /// FWI (high nibble) and SFGI (low nibble)
+    pub tb: Option<u8>,
+    /// Supported options: NAD (b1) and CID (b2)
+    pub tc: Option<u8>,
+    pub historical_bytes: &'a [u8],
+}
+
+impl<'a> Ats<'a> {
+    /// Parse an ATS, without its CRC
+    pub fn parse(ats: &'a [u8]) -> Result<Self, AtsError> {
+        let (&tl, rest) = ats.split_first().ok_or(AtsError::Truncated)?;
+        let tl = usize::from(tl);
+        match ats.len() {
+            len if len < tl => return Err(AtsError::Truncated),
+            len if len > tl => return Err(AtsError::TooLong),
+            _ => {}
+        }
+        let Some((&t0, mut rest)) = rest.split_first() else {
+            // TL only: all the defaults
+            return Ok(Self::new(&[]));
+        };
+        let mut interface = [None; 3];
+        for (bit, byte) in interface.iter_mut().enumerate() {
+            if t0 & (0x10 << bit) != 0 {
+                let (&value, next) = rest.split_first().ok_or(AtsError::Truncated)?;
+                *byte = Some(value);
+                rest = next;
+            }
+        }
+        let [ta, tb, tc] = interface;
+        Ok(Self {
+            fsci: t0 & 0x0F,
+            ta,
+            tb,
+            tc,
+            historical_bytes: rest,
+        })
+    }
+
+    /// ATS with the default parameters and the given historical bytes
+    pub fn new(historical_bytes: &'a [u8]) -> Self {
+        Self {
+            fsci: 2,
+            ta: None,
+            tb: None,
+            tc: None,
+            historical_bytes,
+        }
+    }
+
+    /// Serialize the ATS, without its CRC
+    pub fn to_bytes(&self) -> Result<heapless::Vec<u8, 255>, AtsError> {
+        let t0 = (self.ta.is_some() as u8) << 4
+            | (self.tb.is_some() as u8) << 5
+            | (self.tc.is_some() as u8) << 6
+            | (self.fsci & 0x0F);
+        let mut ats = heapless::Vec::new();
+        ats.push(0).ok();
+        ats.push(t0).ok();
+        for byte in [self.ta, self.tb, self.tc].into_iter().flatten() {
+            ats.push(byte).ok();
+        }
+        ats.extend_from_slice(self.historical_bytes)
+            .map_err(|_| AtsError::TooManyHistoricalBytes)?;
+        ats[0] = ats.len() as u8;
+        Ok(ats)
+    }
+
+    /// Maximum frame size accepted by the card
+    pub fn fsc(&self) -> u16 {
+        frame_size(self.fsci)
+    }
+
+    /// Frame waiting time integer
+    pub fn fwi(&self) -> u8 {
+        self.tb.map(|tb| tb >> 4).unwrap_or(4)
+    }
+
+    /// Start-up frame guard time integer
+    pub fn sfgi(&self) -> u8 {
+        self.tb.map(|tb| tb & 0x0F).unwrap_or(0)
+    }
+
+    pub fn cid_supported(&self) -> bool {
+        self.tc.map(|tc| tc & 0x02 != 0).unwrap_or(true)
+    }
+
+    pub fn nad_supported(&self) -> bool {
+        self.tc.map(|tc| tc & 0x01 != 0).unwrap_or(false)
+    }
+
+    /// Parse the historical bytes, which use the same format as in the ATR
+    pub fn historical(&self) -> Result<HistoricalBytes<'a>, AtrError> {
+        HistoricalBytes::parse(self.historical_bytes)
+    }
+}
+
+/// Frame sizes and options in use on an ISO-DEP link
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Link {
+    /// Maximum frame size of the card
+    pub fsc: u16,
+    /// Maximum frame size of the reader
+    pub fsd: u16,
+    /// A CID byte is included in each block
+    pub cid: bool,
+    /// A NAD byte is included in each block
+    pub nad: bool,
+}
+
+impl Link {
+    pub fn new(fsc: u16, fsd: u16) -> Self {
+        Self {
+            fsc,
+            fsd,
+            cid: false,
+            nad: false,
+        }
+    }
+
+    fn overhead(&self) -> usize {
+        // PCB and CRC
+        3 + self.cid as usize + self.nad as usize
+    }
+
+    /// Maximum length of the command APDU fragment in a single I-block
+    pub fn max_inf_len(&self) -> usize {
+        usize::from(self.fsc).saturating_sub(self.overhead())
+    }
+
+    /// Maximum length of the response APDU fragment in a single I-block
+    pub fn max_response_inf_len(&self) -> usize {
+        usize::from(self.fsd).saturating_sub(self.overhead())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn ats() {
+        // Yubikey 5 NFC
+        let bytes = hex!("12 78 80 70 02 80 73 C0 21 C0 57 59 75 62 69 4B 65 79");
+        let ats = Ats::parse(&bytes).unwrap();
+        assert_eq!(ats.fsc(), 256);
+        assert_eq!(ats.fwi(), 7);
+        assert_eq!(ats.sfgi(), 0);
+        assert!(ats.cid_supported());
+        assert!(!ats.nad_supported());
+        assert!(
+            ats.historical()
+                .unwrap()
+                .card_capabilities()
+                .extended_length
+        );
+        assert_eq!(&*ats.to_bytes().unwrap(), &bytes);
+
+        let ats = Ats::parse(&hex!("01")).unwrap();
+        assert_eq!(ats, Ats::new(&[]));
+        assert_eq!(ats.fsc(), 32);
+        assert_eq!(Ats::parse(&hex!("02 78 00")), Err(AtsError::TooLong));
+        assert_eq!(Ats::parse(&hex!("02 78")), Err(AtsError::Truncated));
+        assert_eq!(
+            Ats::parse(&hex!("15 78 80 70 02")),
+            Err(AtsError::Truncated)
+        );
+    }
+
+    #[test]
+    fn frame_sizes() {
+        assert_eq!(frame_size(0), 16);
+        assert_eq!(frame_size(8), 256);
+        assert_eq!(frame_size(0xF), 256);
+        assert_eq!(frame_size_index(256), Some(8));
+        assert_eq!(frame_size_index(300), Some(8));
+        assert_eq!(frame_size_index(15), None);
+        assert_eq!(rats(8, 0), hex!("E0 80"));
+
+        let link = Link {
+            cid: true,
+            ..Link::new(64, 256)
+        };
+        assert_eq!(link.max_inf_len(), 60);
+        assert_eq!(link.max_response_inf_len(), 252);
+    }
+}
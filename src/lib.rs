@@ -4,23 +4,41 @@
 extern crate delog;
 // generate_macros!();
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
-pub enum Interface {
-    Contact,
-    Contactless,
-}
-
 pub type Data<const S: usize> = heapless::Vec<u8, S>;
 pub type Result<T = ()> = core::result::Result<T, Status>;
 
 pub mod aid;
+pub mod atr;
+pub mod ccid;
 pub mod command;
+pub mod file;
+pub mod filesystem;
+pub mod globalplatform;
+pub mod interface;
+pub mod iso_dep;
+pub mod ndef;
+pub mod pps;
 pub mod response;
+pub mod segments;
+pub mod sm;
+pub mod t0;
+pub mod t1;
 
 pub use aid::{Aid, App};
 pub use command::{Command, Instruction};
+pub use interface::{Capabilities, Interface};
 pub use response::{Response, Status};
 pub mod tlv;
+pub mod trace;
+pub mod transport;
+
+#[cfg(feature = "apdu-core")]
+pub mod interop;
+
+#[cfg(feature = "proptest")]
+pub mod strategy;
+#[cfg(feature = "test-utils")]
+pub mod testing;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,389 @@
+//! NFC Forum Type 4 Tag application
+//!
+//! [`NdefApp`] serves an NDEF message to NFC readers: it answers the SELECT of the application
+//! and of its two files, the Capability Container (CC) and the NDEF file, and the READ BINARY and
+//! UPDATE BINARY commands on them.
+//!
+//! The NDEF file contains the length of the NDEF message on two bytes (NLEN), followed by the message.
+//!
+//! ```
+//! use iso7816::command::CommandView;
+//! use iso7816::ndef::NdefApp;
+//! use iso7816::{Data, Status};
+//!
+//! let mut app = NdefApp::<64>::new(&[0xD1, 0x01, 0x00, 0x54]).unwrap();
+//! let mut response = Data::<64>::new();
+//! for command in [
+//!     &[0x00, 0xA4, 0x04, 0x00, 0x07, 0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01, 0x00][..],
+//!     &[0x00, 0xA4, 0x00, 0x0C, 0x02, 0xE1, 0x04],
+//! ] {
+//!     app.call(CommandView::try_from(command).unwrap(), &mut response).unwrap();
+//! }
+//! let read = [0x00, 0xB0, 0x00, 0x00, 0x06];
+//! app.call(CommandView::try_from(&read[..]).unwrap(), &mut response).unwrap();
+//! assert_eq!(&*response, &[0x00, 0x04, 0xD1, 0x01, 0x00, 0x54]);
+//! ```
+
+use crate::command::CommandView;
+use crate::{Aid, App, Data, Status};
+
+/// AID of the NDEF Tag Application
+pub const AID: Aid = Aid::new(&[0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01]);
+
+/// File identifier of the Capability Container
+pub const CC_FILE_ID: u16 = 0xE103;
+
+/// Default file identifier of the NDEF file
+pub const NDEF_FILE_ID: u16 = 0xE104;
+
+/// Mapping version 2.0
+pub const MAPPING_VERSION: u8 = 0x20;
+
+/// Length of the Capability Container with a single NDEF File Control TLV
+pub const CC_LEN: usize = 15;
+
+/// Access condition granting access without any security
+pub const ACCESS_GRANTED: u8 = 0x00;
+
+/// Access condition denying access
+pub const ACCESS_DENIED: u8 = 0xFF;
+
+/// Capability Container file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CapabilityContainer {
+    pub mapping_version: u8,
+    /// Maximum data size that can be read with a single READ BINARY
+    pub mle: u16,
+    /// Maximum data size that can be written with a single UPDATE BINARY
+    pub mlc: u16,
+    pub file_id: u16,
+    /// Maximum size of the NDEF file, including NLEN
+    pub max_size: u16,
+    pub read_access: u8,
+    pub write_access: u8,
+}
+
+impl CapabilityContainer {
+    pub fn to_bytes(&self) -> [u8; CC_LEN] {
+        let [mle0, mle1] = self.mle.to_be_bytes();
+        let [mlc0, mlc1] = self.mlc.to_be_bytes();
+        let [id0, id1] = self.file_id.to_be_bytes();
+        let [size0, size1] = self.max_size.to_be_bytes();
+        [
+            0x00,
+            CC_LEN as u8,
+            self.mapping_version,
+            mle0,
+            mle1,
+            mlc0,
+            mlc1,
+            // NDEF File Control TLV
+            0x04,
+            0x06,
+            id0,
+            id1,
+            size0,
+            size1,
+            self.read_access,
+            self.write_access,
+        ]
+    }
+
+    /// Parse a Capability Container, ignoring the TLV blocks following the NDEF File Control TLV
+    pub fn parse(cc: &[u8]) -> Option<Self> {
+        let cc = cc.get(..CC_LEN)?;
+        let u16_at = |i: usize| u16::from_be_bytes([cc[i], cc[i + 1]]);
+        if usize::from(u16_at(0)) < CC_LEN || cc[7..9] != [0x04, 0x06] {
+            return None;
+        }
+        Some(Self {
+            mapping_version: cc[2],
+            mle: u16_at(3),
+            mlc: u16_at(5),
+            file_id: u16_at(9),
+            max_size: u16_at(11),
+            read_access: cc[13],
+            write_access: cc[14],
+        })
+    }
+}
+
+/// Currently selected application or file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selected {
+    None,
+    Application,
+    CapabilityContainer,
+    NdefFile,
+}
+
+/// NDEF Tag Application serving the NDEF file from a `N` bytes buffer
+#[derive(Clone, Debug)]
+pub struct NdefApp<const N: usize> {
+    /// NLEN followed by the NDEF message
+    file: Data<N>,
+    selected: Selected,
+    writable: bool,
+    mle: u16,
+    mlc: u16,
+}
+
+impl<const N: usize> NdefApp<N> {
+    /// Writable NDEF file initially containing `message`.
+    ///
+    /// Returns `None` if the message and NLEN do not fit in `N` bytes.
+    pub fn new(message: &[u8]) -> Option<Self> {
+        let mut app = Self {
+            file: Data::new(),
+            selected: Selected::None,
+            writable: true,
+            mle: 0xFF,
+            mlc: 0xFF,
+        };
+        app.set_message(message).ok()?;
+        Some(app)
+    }
+
+    /// Deny UPDATE BINARY on the NDEF file
+    pub fn read_only(mut self) -> Self {
+        self.writable = false;
+        self
+    }
+
+    /// Maximum length of the READ BINARY responses and UPDATE BINARY data fields, at least 15
+    pub fn max_lengths(mut self, mle: u16, mlc: u16) -> Self {
+        self.mle = mle.max(CC_LEN as u16);
+        self.mlc = mlc.max(1);
+        self
+    }
+
+    pub fn capability_container(&self) -> CapabilityContainer {
+        CapabilityContainer {
+            mapping_version: MAPPING_VERSION,
+            mle: self.mle,
+            mlc: self.mlc,
+            file_id: NDEF_FILE_ID,
+            max_size: N.min(u16::MAX as usize) as u16,
+            read_access: ACCESS_GRANTED,
+            write_access: if self.writable {
+                ACCESS_GRANTED
+            } else {
+                ACCESS_DENIED
+            },
+        }
+    }
+
+    /// Current NDEF message, as indicated by NLEN
+    pub fn message(&self) -> &[u8] {
+        let nlen = u16::from_be_bytes([self.file[0], self.file[1]]);
+        self.file[2..]
+            .get(..usize::from(nlen))
+            .unwrap_or(&self.file[2..])
+    }
+
+    pub fn set_message(&mut self, message: &[u8]) -> Result<(), Status> {
+        let nlen = u16::try_from(message.len()).map_err(|_| Status::NotEnoughMemory)?;
+        self.file.clear();
+        self.file
+            .extend_from_slice(&nlen.to_be_bytes())
+            .and_then(|_| self.file.extend_from_slice(message))
+            .map_err(|_| Status::NotEnoughMemory)
+    }
+
+    pub fn selected(&self) -> Selected {
+        self.selected
+    }
+
+    pub fn deselect(&mut self) {
+        self.selected = Selected::None;
+    }
+
+    fn select(&mut self, command: &CommandView<'_>) -> Result<(), Status> {
+        match (command.p1, command.p2 & !0x0C) {
+            // By DF name
+            (0x04, 0x00) => {
+                if !AID.matches(command.data()) {
+                    return Err(Status::NotFound);
+                }
+                self.selected = Selected::Application;
+            }
+            // By file identifier
+            (0x00, 0x00) => {
+                if self.selected == Selected::None {
+                    return Err(Status::NotFound);
+                }
+                let file_id = match *command.data() {
+                    [id0, id1] => u16::from_be_bytes([id0, id1]),
+                    _ => return Err(Status::WrongLength),
+                };
+                self.selected = match file_id {
+                    CC_FILE_ID => Selected::CapabilityContainer,
+                    NDEF_FILE_ID => Selected::NdefFile,
+                    _ => return Err(Status::NotFound),
+                };
+            }
+            _ => return Err(Status::IncorrectP1OrP2Parameter),
+        }
+        Ok(())
+    }
+
+    fn offset(command: &CommandView<'_>) -> Result<usize, Status> {
+        if command.p1 & 0x80 != 0 {
+            // Short EF identifiers are not supported
+            return Err(Status::IncorrectP1OrP2Parameter);
+        }
+        Ok(usize::from(u16::from_be_bytes([command.p1, command.p2])))
+    }
+
+    fn read_binary<const R: usize>(
+        &self,
+        command: &CommandView<'_>,
+        response: &mut Data<R>,
+    ) -> Result<(), Status> {
+        let cc;
+        let file = match self.selected {
+            Selected::CapabilityContainer => {
+                cc = self.capability_container().to_bytes();
+                &cc[..]
+            }
+            Selected::NdefFile => &self.file[..],
+            _ => return Err(Status::CommandNotAllowedNoEf),
+        };
+        let offset = Self::offset(command)?;
+        let remaining = file.get(offset..).ok_or(Status::WrongParameters)?;
+        let len = remaining
+            .len()
+            .min(command.expected())
+            .min(usize::from(self.mle));
+        response
+            .extend_from_slice(&remaining[..len])
+            .map_err(|_| Status::NotEnoughMemory)
+    }
+
+    fn update_binary(&mut self, command: &CommandView<'_>) -> Result<(), Status> {
+        match self.selected {
+            Selected::NdefFile => {}
+            Selected::CapabilityContainer => return Err(Status::SecurityStatusNotSatisfied),
+            _ => return Err(Status::CommandNotAllowedNoEf),
+        }
+        if !self.writable {
+            return Err(Status::SecurityStatusNotSatisfied);
+        }
+        let data = command.data();
+        if data.len() > usize::from(self.mlc) {
+            return Err(Status::WrongLength);
+        }
+        let offset = Self::offset(command)?;
+        let end = offset + data.len();
+        if offset > self.file.len() || end > N {
+            return Err(Status::WrongParameters);
+        }
+        if end > self.file.len() {
+            self.file.resize_default(end).ok();
+        }
+        self.file[offset..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Process a command, writing the response data to `response`
+    pub fn call<const R: usize>(
+        &mut self,
+        command: CommandView<'_>,
+        response: &mut Data<R>,
+    ) -> crate::Result {
+        response.clear();
+        if command.class().into_inner() != 0x00 {
+            return Err(Status::ClassNotSupported);
+        }
+        let ins: u8 = command.instruction().into();
+        match ins {
+            0xA4 => self.select(&command),
+            0xB0 => self.read_binary(&command, response),
+            0xD6 => self.update_binary(&command),
+            _ => Err(Status::InstructionNotSupportedOrInvalid),
+        }
+    }
+}
+
+impl<const N: usize> App for NdefApp<N> {
+    fn aid(&self) -> Aid {
+        AID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    fn call(app: &mut NdefApp<64>, apdu: &[u8]) -> (crate::Result, Vec<u8>) {
+        let mut response = Data::<64>::new();
+        let res = app.call(CommandView::try_from(apdu).unwrap(), &mut response);
+        (res, response.to_vec())
+    }
+
+    #[test]
+    fn read() {
+        let mut app = NdefApp::<64>::new(&hex!("D1010454027465")).unwrap();
+        assert_eq!(
+            call(&mut app, &hex!("00A4000C 02 E103")).0,
+            Err(Status::NotFound)
+        );
+        assert_eq!(
+            call(&mut app, &hex!("00A40400 07 D2760000850101 00")).0,
+            Ok(())
+        );
+        assert_eq!(call(&mut app, &hex!("00A4000C 02 E103")).0, Ok(()));
+        let (res, cc) = call(&mut app, &hex!("00B00000 0F"));
+        assert_eq!(res, Ok(()));
+        assert_eq!(cc, hex!("000F 20 00FF 00FF 0406 E104 0040 00 00"));
+        assert_eq!(
+            CapabilityContainer::parse(&cc),
+            Some(app.capability_container())
+        );
+
+        assert_eq!(call(&mut app, &hex!("00A4000C 02 E104")).0, Ok(()));
+        assert_eq!(
+            call(&mut app, &hex!("00B00000 02")),
+            (Ok(()), hex!("0007").to_vec())
+        );
+        assert_eq!(
+            call(&mut app, &hex!("00B00002 00")),
+            (Ok(()), hex!("D1010454027465").to_vec())
+        );
+        assert_eq!(
+            call(&mut app, &hex!("00B00100 01")).0,
+            Err(Status::WrongParameters)
+        );
+    }
+
+    #[test]
+    fn update() {
+        let mut app = NdefApp::<64>::new(&[]).unwrap();
+        assert_eq!(
+            call(&mut app, &hex!("00A40400 07 D2760000850101")).0,
+            Ok(())
+        );
+        assert_eq!(
+            call(&mut app, &hex!("00D60000 02 0000")).0,
+            Err(Status::CommandNotAllowedNoEf)
+        );
+        assert_eq!(call(&mut app, &hex!("00A4000C 02 E104")).0, Ok(()));
+        // Recommended sequence: reset NLEN, write the message, write NLEN
+        assert_eq!(call(&mut app, &hex!("00D60000 02 0000")).0, Ok(()));
+        assert_eq!(call(&mut app, &hex!("00D60002 03 D00000")).0, Ok(()));
+        assert_eq!(app.message(), &[]);
+        assert_eq!(call(&mut app, &hex!("00D60000 02 0003")).0, Ok(()));
+        assert_eq!(app.message(), &hex!("D00000"));
+        assert_eq!(
+            call(&mut app, &hex!("00D6003F 02 0000")).0,
+            Err(Status::WrongParameters)
+        );
+
+        let mut app = app.read_only();
+        assert_eq!(
+            call(&mut app, &hex!("00D60000 02 0000")).0,
+            Err(Status::SecurityStatusNotSatisfied)
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Protocol and parameters selection (ISO 7816-3 9)
+//!
+//! After the ATR, the interface device may send a PPS request to select the protocol and the
+//! transmission parameters. The card echoes the request if it accepts it.
+//!
+//! ```
+//! use iso7816::{atr::Atr, pps::Pps};
+//! let atr = Atr::parse(&[0x3B, 0x90, 0x13, 0x01, 0x82]).unwrap();
+//! let request = Pps::negotiate(&atr, &[1, 0], 8).unwrap();
+//! assert_eq!(&*request.to_bytes(), &[0xFF, 0x11, 0x13, 0xFD]);
+//! ```
+
+use crate::atr::{Atr, DEFAULT_TA1, DI};
+
+/// Initial character of PPS requests and responses
+pub const PPSS: u8 = 0xFF;
+
+/// Maximum length of a PPS request or response
+pub const MAX_LEN: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PpsError {
+    /// The message is shorter than indicated by PPS0
+    Truncated,
+    /// The message is longer than indicated by PPS0
+    TooLong,
+    /// The first byte is not PPSS
+    InvalidPpss,
+    /// The check byte does not match
+    InvalidPck,
+    /// The response does not match the request
+    Rejected,
+    /// The card is in specific mode (TA2 is present), PPS is not allowed
+    SpecificMode,
+    /// The card does not offer any of the protocols supported by the interface device
+    NoCommonProtocol,
+}
+
+/// PPS request or response
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pps {
+    /// Protocol (low nibble of PPS0)
+    pub protocol: u8,
+    /// Fi and Di indices, in the format of TA1
+    pub pps1: Option<u8>,
+    /// SPU usage
+    pub pps2: Option<u8>,
+    /// Reserved for future use
+    pub pps3: Option<u8>,
+}
+
+impl Pps {
+    /// Request for `protocol` with the default transmission parameters
+    pub fn new(protocol: u8) -> Self {
+        Self {
+            protocol: protocol & 0x0F,
+            pps1: None,
+            pps2: None,
+            pps3: None,
+        }
+    }
+
+    pub fn with_ta1(mut self, ta1: u8) -> Self {
+        self.pps1 = Some(ta1);
+        self
+    }
+
+    pub fn parse(message: &[u8]) -> Result<Self, PpsError> {
+        let (&ppss, rest) = message.split_first().ok_or(PpsError::Truncated)?;
+        if ppss != PPSS {
+            return Err(PpsError::InvalidPpss);
+        }
+        let (&pps0, mut rest) = rest.split_first().ok_or(PpsError::Truncated)?;
+        let mut pps = Self::new(pps0);
+        for (bit, byte) in [&mut pps.pps1, &mut pps.pps2, &mut pps.pps3]
+            .into_iter()
+            .enumerate()
+        {
+            if pps0 & (0x10 << bit) != 0 {
+                let (&value, next) = rest.split_first().ok_or(PpsError::Truncated)?;
+                *byte = Some(value);
+                rest = next;
+            }
+        }
+        match rest {
+            [] => Err(PpsError::Truncated),
+            [_] => {
+                if message.iter().fold(0, |acc, b| acc ^ b) != 0 {
+                    return Err(PpsError::InvalidPck);
+                }
+                Ok(pps)
+            }
+            _ => Err(PpsError::TooLong),
+        }
+    }
+
+    pub fn to_bytes(&self) -> heapless::Vec<u8, MAX_LEN> {
+        let pps0 = (self.pps1.is_some() as u8) << 4
+            | (self.pps2.is_some() as u8) << 5
+            | (self.pps3.is_some() as u8) << 6
+            | (self.protocol & 0x0F);
+        let mut message = heapless::Vec::new();
+        message.push(PPSS).ok();
+        message.push(pps0).ok();
+        for byte in [self.pps1, self.pps2, self.pps3].into_iter().flatten() {
+            message.push(byte).ok();
+        }
+        let pck = message.iter().fold(0, |acc, b| acc ^ b);
+        message.push(pck).ok();
+        message
+    }
+
+    /// TA1 value in effect after the exchange: PPS1, or the default if absent
+    pub fn ta1(&self) -> u8 {
+        self.pps1.unwrap_or(DEFAULT_TA1)
+    }
+
+    /// Check the response of the card to this request, returning the parameters in effect
+    ///
+    /// The response must use the same protocol. PPS1 to PPS3 must be either echoed, or absent to
+    /// fall back to the default values.
+    pub fn check_response(&self, response: &Pps) -> Result<Pps, PpsError> {
+        let echoed = |requested: Option<u8>, answered: Option<u8>| {
+            answered.is_none() || answered == requested
+        };
+        if response.protocol != self.protocol
+            || !echoed(self.pps1, response.pps1)
+            || !echoed(self.pps2, response.pps2)
+            || !echoed(self.pps3, response.pps3)
+        {
+            return Err(PpsError::Rejected);
+        }
+        Ok(*response)
+    }
+
+    /// Build the request selecting the parameters compatible with the ATR and the interface device.
+    ///
+    /// `protocols` are the protocols supported by the interface device, by order of preference.
+    /// `max_di` is the largest baud rate adjustment factor supported by the interface device;
+    /// the Fi indicated by the card is always kept.
+    pub fn negotiate(atr: &Atr<'_>, protocols: &[u8], max_di: u8) -> Result<Self, PpsError> {
+        if atr.specific_mode().is_some() {
+            return Err(PpsError::SpecificMode);
+        }
+        let protocol = protocols
+            .iter()
+            .copied()
+            .find(|&t| atr.protocols().any(|offered| offered == t))
+            .ok_or(PpsError::NoCommonProtocol)?;
+        let ta1 = atr.ta1();
+        let card_di = atr.di().unwrap_or(1);
+        let limit = card_di.min(max_di);
+        // DI is not sorted: pick the largest value within the limits
+        let di = DI
+            .iter()
+            .enumerate()
+            .filter(|&(_, &di)| di != 0 && di <= limit)
+            .max_by_key(|&(_, &di)| di)
+            .map(|(i, _)| i as u8)
+            .unwrap_or(1);
+        let fi = if atr.fi().is_some() { ta1 & 0xF0 } else { 0x10 };
+        let mut pps = Self::new(protocol);
+        if fi | di != DEFAULT_TA1 {
+            pps.pps1 = Some(fi | di);
+        }
+        Ok(pps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn encoding() {
+        let pps = Pps::new(1).with_ta1(0x96);
+        let bytes = pps.to_bytes();
+        assert_eq!(&*bytes, &hex!("FF 11 96 78"));
+        assert_eq!(Pps::parse(&bytes), Ok(pps));
+        assert_eq!(Pps::parse(&hex!("FF 00 FF")), Ok(Pps::new(0)));
+        assert_eq!(Pps::parse(&hex!("FF 11 96")), Err(PpsError::Truncated));
+        assert_eq!(Pps::parse(&hex!("FF 00 FF 00")), Err(PpsError::TooLong));
+        assert_eq!(Pps::parse(&hex!("FF 00 00")), Err(PpsError::InvalidPck));
+        assert_eq!(Pps::parse(&hex!("3B 00 C4")), Err(PpsError::InvalidPpss));
+    }
+
+    #[test]
+    fn response() {
+        let request = Pps::new(1).with_ta1(0x96);
+        assert_eq!(request.check_response(&request), Ok(request));
+        // PPS1 absent: default Fi and Di
+        let fallback = request.check_response(&Pps::new(1)).unwrap();
+        assert_eq!(fallback.ta1(), DEFAULT_TA1);
+        assert_eq!(
+            request.check_response(&Pps::new(0)),
+            Err(PpsError::Rejected)
+        );
+        assert_eq!(
+            request.check_response(&Pps::new(1).with_ta1(0x11)),
+            Err(PpsError::Rejected)
+        );
+    }
+
+    #[test]
+    fn negotiate() {
+        // TA1 = 96 (Fi = 512, Di = 32), T=0 and T=1
+        let atr = Atr::parse(&hex!("3B 90 96 80 01 87")).unwrap();
+        assert_eq!(
+            Pps::negotiate(&atr, &[1, 0], 64),
+            Ok(Pps::new(1).with_ta1(0x96))
+        );
+        assert_eq!(
+            Pps::negotiate(&atr, &[0], 8),
+            Ok(Pps::new(0).with_ta1(0x94))
+        );
+        assert_eq!(
+            Pps::negotiate(&atr, &[15], 8),
+            Err(PpsError::NoCommonProtocol)
+        );
+
+        let atr = Atr::parse(&hex!("3B 00")).unwrap();
+        assert_eq!(Pps::negotiate(&atr, &[1, 0], 8), Ok(Pps::new(0)));
+
+        let atr = Atr::parse(&hex!("3B 80 11 01 90")).unwrap();
+        assert_eq!(Pps::negotiate(&atr, &[1], 8), Err(PpsError::SpecificMode));
+    }
+}
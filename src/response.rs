@@ -14,3 +14,84 @@ impl<const S: usize> Default for Response<S> {
         Self::Status(Default::default())
     }
 }
+
+impl<const S: usize> Response<S> {
+    /// Response data, if any
+    pub fn data(&self) -> Option<&Data<S>> {
+        match self {
+            Self::Data(data) => Some(data),
+            Self::Status(_) => None,
+        }
+    }
+
+    /// Status of the response, [`Status::Success`] if it carries data
+    pub fn status(&self) -> Status {
+        match self {
+            Self::Data(_) => Status::Success,
+            Self::Status(status) => *status,
+        }
+    }
+
+    /// Response data on success, or the error status
+    ///
+    /// A [`Status::Success`] response without data gives empty data.
+    ///
+    /// ```
+    /// use iso7816::{Data, Response, Status};
+    /// fn read(response: Response<16>) -> Result<usize, Status> {
+    ///     let data = response.into_result()?;
+    ///     Ok(data.len())
+    /// }
+    /// assert_eq!(read(Response::Data(Data::from_slice(&[1, 2]).unwrap())), Ok(2));
+    /// assert_eq!(read(Response::Status(Status::Success)), Ok(0));
+    /// assert_eq!(read(Response::Status(Status::NotFound)), Err(Status::NotFound));
+    /// ```
+    pub fn into_result(self) -> Result<Data<S>, Status> {
+        match self {
+            Self::Data(data) => Ok(data),
+            Self::Status(Status::Success) => Ok(Data::new()),
+            Self::Status(status) => Err(status),
+        }
+    }
+}
+
+/// Parse a response APDU, the data being dropped if the status is not [`Status::Success`]
+#[cfg(feature = "heapless-bytes")]
+impl<const S: usize, const N: usize> TryFrom<&heapless_bytes::Bytes<N>> for Response<S> {
+    type Error = crate::command::FromSliceError;
+
+    fn try_from(apdu: &heapless_bytes::Bytes<N>) -> core::result::Result<Self, Self::Error> {
+        use crate::command::FromSliceError;
+
+        let apdu: &[u8] = apdu;
+        if apdu.len() < 2 {
+            return Err(FromSliceError::TooShort);
+        }
+        let (data, sw) = apdu.split_at(apdu.len() - 2);
+        let status = Status::from((sw[0], sw[1]));
+        if status != Status::Success {
+            return Ok(Self::Status(status));
+        }
+        Data::from_slice(data)
+            .map(Self::Data)
+            .map_err(|_| FromSliceError::TooLong)
+    }
+}
+
+/// Serialize the response APDU, data followed by the status
+#[cfg(feature = "heapless-bytes")]
+impl<const S: usize, const N: usize> TryFrom<&Response<S>> for heapless_bytes::Bytes<N> {
+    type Error = crate::command::BufferFull;
+
+    fn try_from(response: &Response<S>) -> core::result::Result<Self, Self::Error> {
+        use crate::command::Writer;
+
+        let mut bytes = Self::new();
+        if let Some(data) = response.data() {
+            bytes.write_all(data)?;
+        }
+        let sw: [u8; 2] = response.status().into();
+        bytes.write_all(&sw)?;
+        Ok(bytes)
+    }
+}
@@ -474,6 +474,17 @@ impl From<Status> for [u8; 2] {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Status {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u16::arbitrary(u).map(Self::from)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        u16::size_hint(depth)
+    }
+}
+
 impl<const S: usize> From<Status> for Data<S> {
     #[inline]
     fn from(status: Status) -> Data<S> {
@@ -0,0 +1,213 @@
+//! Data reassembled from several buffers without copying them into one
+//!
+//! Responses collected with GET RESPONSE and commands received with chaining are split over
+//! several APDUs. Copying them into one contiguous buffer requires a buffer for the largest
+//! payload in addition to the APDU buffers. [`Segments`] instead keeps the APDU buffers and
+//! exposes the payload they carry as one logical piece of data, for example as a [`DataStream`].
+//!
+//! ```
+//! use iso7816::command::{CommandBuf, DataStream};
+//! use iso7816::segments::Segments;
+//!
+//! let mut segments = Segments::<_, 4>::new();
+//! let first = CommandBuf::new([0x10, 0xDB, 0x3F, 0xFF, 0x02, 0x01, 0x02]).unwrap();
+//! assert_eq!(segments.push_command(first), Ok(false));
+//! let last = CommandBuf::new([0x00, 0xDB, 0x3F, 0xFF, 0x02, 0x03, 0x04]).unwrap();
+//! assert_eq!(segments.push_command(last), Ok(true));
+//!
+//! assert_eq!(segments.len(), 4);
+//! let mut payload = heapless::Vec::<u8, 4>::new();
+//! segments.to_writer(&mut payload).unwrap();
+//! assert_eq!(payload, [0x01, 0x02, 0x03, 0x04]);
+//! ```
+
+use core::ops::Range;
+
+use crate::command::class::Chain;
+use crate::command::{CommandBuf, DataSource, DataStream, Writer};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Segment<B> {
+    buffer: B,
+    range: Range<usize>,
+}
+
+/// Data made of ranges of up to `M` buffers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segments<B, const M: usize> {
+    segments: heapless::Vec<Segment<B>, M>,
+    len: usize,
+    /// The last segment is a buffer lent to a transport, whose data is not part of the payload yet
+    reserved: bool,
+}
+
+impl<B, const M: usize> Default for Segments<B, M> {
+    fn default() -> Self {
+        Self {
+            segments: heapless::Vec::new(),
+            len: 0,
+            reserved: false,
+        }
+    }
+}
+
+impl<B: AsRef<[u8]>, const M: usize> Segments<B, M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total length of the data
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of buffers holding the data
+    pub fn segment_count(&self) -> usize {
+        self.segments.len() - usize::from(self.reserved)
+    }
+
+    /// Append the bytes of `buffer` within `range`
+    ///
+    /// Returns the buffer if all the segments are used.
+    /// Panics if `range` is not within the buffer.
+    pub fn push(&mut self, buffer: B, range: Range<usize>) -> Result<(), B> {
+        assert!(
+            buffer.as_ref().get(range.clone()).is_some(),
+            "range out of the buffer"
+        );
+        self.release();
+        let len = range.len();
+        self.segments
+            .push(Segment { buffer, range })
+            .map_err(|segment| segment.buffer)?;
+        self.len += len;
+        Ok(())
+    }
+
+    /// Append the data field of a command received with chaining, keeping its buffer
+    ///
+    /// Returns whether it is the last command of the chain, or the command if all the segments are used.
+    pub fn push_command(&mut self, command: CommandBuf<B>) -> Result<bool, CommandBuf<B>> {
+        if self.segment_count() == M {
+            return Err(command);
+        }
+        let last = command.class().chain() != Chain::NotTheLast;
+        let range = command.data_range();
+        let Ok(()) = self.push(command.into_inner(), range) else {
+            unreachable!("capacity checked above");
+        };
+        Ok(last)
+    }
+
+    /// Slices making up the data, in order
+    pub fn segments(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.segments[..self.segment_count()]
+            .iter()
+            .map(|segment| &segment.buffer.as_ref()[segment.range.clone()])
+    }
+
+    /// Bytes of the data, in order
+    pub fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.segments().flatten().copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.segments.clear();
+        self.len = 0;
+        self.reserved = false;
+    }
+
+    /// Drop the reserved buffer, if any
+    pub(crate) fn release(&mut self) {
+        if self.reserved {
+            self.segments.pop();
+            self.reserved = false;
+        }
+    }
+}
+
+impl<const S: usize, const M: usize> Segments<[u8; S], M> {
+    /// Buffer to receive the next segment into, reused until it is [committed](Self::commit)
+    pub(crate) fn reserve(&mut self) -> Option<&mut [u8]> {
+        if !self.reserved {
+            self.segments
+                .push(Segment {
+                    buffer: [0; S],
+                    range: 0..0,
+                })
+                .ok()?;
+            self.reserved = true;
+        }
+        self.segments
+            .last_mut()
+            .map(|segment| &mut segment.buffer[..])
+    }
+
+    /// Add the first `len` bytes of the reserved buffer to the data
+    pub(crate) fn commit(&mut self, len: usize) {
+        debug_assert!(self.reserved);
+        if let Some(segment) = self.segments.last_mut() {
+            segment.range = 0..len;
+            self.len += len;
+            self.reserved = false;
+        }
+    }
+}
+
+impl<B: AsRef<[u8]>, const M: usize> DataSource for Segments<B, M> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<W: Writer, B: AsRef<[u8]>, const M: usize> DataStream<W> for Segments<B, M> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        self.segments()
+            .try_for_each(|segment| writer.write_all(segment))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn segments() {
+        let mut segments = Segments::<&[u8], 2>::new();
+        assert!(segments.is_empty());
+        segments.push(&hex!("01020304"), 1..3).unwrap();
+        segments.push(&hex!("0506"), 0..2).unwrap();
+        assert_eq!(segments.push(&hex!("07"), 0..1), Err(&hex!("07")[..]));
+        assert_eq!(segments.len(), 4);
+        assert_eq!(segments.segment_count(), 2);
+        assert_eq!(segments.bytes().collect::<Vec<_>>(), hex!("02030506"));
+        segments.clear();
+        assert_eq!(segments.segments().count(), 0);
+
+        let mut segments = Segments::<[u8; 4], 2>::new();
+        segments.reserve().unwrap()[..3].copy_from_slice(&hex!("010203"));
+        segments.commit(2);
+        segments.reserve().unwrap();
+        assert_eq!(segments.segment_count(), 1);
+        assert!(segments.reserve().is_some());
+        segments.release();
+        assert_eq!(segments.segments().collect::<Vec<_>>(), [&hex!("0102")]);
+    }
+
+    #[test]
+    fn chained_commands() {
+        let mut segments = Segments::<Vec<u8>, 2>::new();
+        let first = CommandBuf::new(hex!("10DB3FFF 02 0102").to_vec()).unwrap();
+        assert_eq!(segments.push_command(first), Ok(false));
+        let last = CommandBuf::new(hex!("00DB3FFF 01 03 00").to_vec()).unwrap();
+        assert_eq!(segments.push_command(last), Ok(true));
+        let extra = CommandBuf::new(hex!("00DB3FFF 01 04").to_vec()).unwrap();
+        assert_eq!(segments.push_command(extra.clone()), Err(extra));
+        assert_eq!(segments.bytes().collect::<Vec<_>>(), hex!("010203"));
+    }
+}
@@ -0,0 +1,823 @@
+//! Secure messaging data objects (ISO 7816-4 10)
+//!
+//! [`wrap`] replaces the data field of a command with secure messaging data objects:
+//!
+//! - `87` (padding-content indicator followed by the cryptogram) for even instructions, or `85`
+//!   (cryptogram of BER-TLV encoded data) for odd instructions,
+//! - `97` with the `Le` of the plain command,
+//! - `8E` with the MAC computed over the padded header and the preceding data objects.
+//!
+//! [`unwrap`] verifies the `8E` MAC of a protected response, decrypts its `87`, `85` or `81` data
+//! object and checks the status of the `99` data object against the plain status, as configured
+//! by [`StatusPolicy`].
+//!
+//! The cryptographic primitives are supplied by the caller through [`SmCipher`] and [`SmMac`], so
+//! that they can be backed by RustCrypto, trussed or a hardware accelerator. The data is padded
+//! with ISO/IEC 9797-1 method 2 before encryption. The MAC input is streamed to the [`SmMac`]
+//! writer unpadded after the header, which is padded on the fly with [`Padded`]: the MAC algorithm
+//! is expected to apply its own padding.
+//! [`Session`] plugs these functions into [`SmTransport`](crate::transport::secure_messaging::SmTransport).
+
+pub mod channel;
+pub mod padding;
+pub mod scp03;
+
+use core::fmt::Debug;
+
+use crate::command::{CommandView, DataStream, Instruction, Writer};
+use crate::tlv::{serialize_len, take_len};
+use crate::transport::secure_messaging::SmSession;
+use crate::Status;
+use padding::{Method, Padded};
+
+/// Plain value not encoded in BER-TLV
+pub const PLAIN_VALUE: u8 = 0x81;
+/// Cryptogram, the plain value being encoded in BER-TLV
+pub const CRYPTOGRAM: u8 = 0x85;
+/// Padding-content indicator byte followed by a cryptogram
+pub const PADDED_CRYPTOGRAM: u8 = 0x87;
+/// Expected length of the plain response
+pub const LE: u8 = 0x97;
+/// Processing status of the plain response
+pub const STATUS: u8 = 0x99;
+/// Cryptographic checksum
+pub const MAC: u8 = 0x8E;
+
+/// Largest block size supported for padding
+pub const MAX_BLOCK_SIZE: usize = 16;
+/// Largest MAC supported
+pub const MAX_MAC_LEN: usize = 16;
+
+/// First byte of the value of the `87` data object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaddingIndicator {
+    NoFurtherIndication,
+    /// ISO/IEC 9797-1 padding method 2: `80` followed by zeroes up to the block size
+    Iso9797Method2,
+    NoPadding,
+    Other(u8),
+}
+
+impl From<u8> for PaddingIndicator {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => Self::NoFurtherIndication,
+            0x01 => Self::Iso9797Method2,
+            0x02 => Self::NoPadding,
+            _ => Self::Other(value),
+        }
+    }
+}
+
+impl From<PaddingIndicator> for u8 {
+    fn from(value: PaddingIndicator) -> Self {
+        match value {
+            PaddingIndicator::NoFurtherIndication => 0x00,
+            PaddingIndicator::Iso9797Method2 => 0x01,
+            PaddingIndicator::NoPadding => 0x02,
+            PaddingIndicator::Other(value) => value,
+        }
+    }
+}
+
+/// Handling of the status conveyed in the `99` data object of protected responses
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatusPolicy {
+    /// The `99` data object, if present, must match the plain status of the response
+    #[default]
+    Verify,
+    /// The `99` data object must be present and match the plain status of the response
+    Require,
+    /// The `99` data object, if present, replaces the plain status of the response
+    Adopt,
+}
+
+/// Block cipher used to encrypt the data of a secure messaging session
+pub trait SmCipher {
+    type Error: Debug;
+
+    /// Block size of the cipher, at most [`MAX_BLOCK_SIZE`]
+    fn block_size(&self) -> usize;
+
+    /// Encrypt `data` in place. Its length is a multiple of the block size.
+    fn encrypt(&mut self, data: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Decrypt `data` in place. Its length is a multiple of the block size.
+    fn decrypt(&mut self, data: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+impl<C: SmCipher + ?Sized> SmCipher for &mut C {
+    type Error = C::Error;
+
+    fn block_size(&self) -> usize {
+        C::block_size(self)
+    }
+
+    fn encrypt(&mut self, data: &mut [u8]) -> Result<(), Self::Error> {
+        C::encrypt(self, data)
+    }
+
+    fn decrypt(&mut self, data: &mut [u8]) -> Result<(), Self::Error> {
+        C::decrypt(self, data)
+    }
+}
+
+/// Message authentication code of a secure messaging session
+///
+/// The MAC input is written to the MAC through its [`Writer`] implementation, in one or more
+/// chunks, so that any [`DataStream`](crate::command::DataStream) can be authenticated without
+/// being copied to a contiguous buffer first.
+pub trait SmMac: Writer {
+    /// Write the MAC of the data written since the last call to `mac`, returning its length.
+    ///
+    /// `mac` is [`MAX_MAC_LEN`] bytes long. The MAC is then ready for a new input.
+    fn finalize(&mut self, mac: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error<C, M> {
+    Cipher(C),
+    Mac(M),
+    /// The block size is 0 or larger than [`MAX_BLOCK_SIZE`]
+    InvalidBlockSize,
+    /// The protected data or the plain data do not fit in the buffer
+    BufferFull,
+    /// The protected response contains malformed or unexpected data objects
+    InvalidData,
+    /// The protected response has no `8E` data object
+    MissingMac,
+    /// The MAC of the protected response is not valid
+    InvalidMac,
+    /// The padding of the decrypted data is not valid
+    InvalidPadding,
+    /// The padding-content indicator is not supported
+    UnsupportedPadding(u8),
+    /// The status of the `99` data object is missing or does not match the plain status
+    SmIntegrity,
+    /// The secure channel is closed or was aborted
+    Closed,
+}
+
+/// Protected data field being written
+struct Output<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl Output<'_> {
+    fn reserve<C, M>(&mut self, len: usize) -> Result<&mut [u8], Error<C, M>> {
+        let reserved = self
+            .buffer
+            .get_mut(self.len..self.len + len)
+            .ok_or(Error::BufferFull)?;
+        self.len += len;
+        Ok(reserved)
+    }
+
+    fn write<C, M>(&mut self, data: &[u8]) -> Result<(), Error<C, M>> {
+        self.reserve(data.len())?.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn header<C, M>(&mut self, tag: u8, len: usize) -> Result<(), Error<C, M>> {
+        self.write(&[tag])?;
+        self.write(&serialize_len(len).ok_or(Error::BufferFull)?)
+    }
+
+    fn data_object<C, M>(&mut self, tag: u8, value: &[u8]) -> Result<(), Error<C, M>> {
+        self.header(tag, value.len())?;
+        self.write(value)
+    }
+}
+
+/// Error of [`wrap`] and [`unwrap`] for the given cipher and MAC
+pub type SessionError<C, M> = Error<<C as SmCipher>::Error, <M as Writer>::Error>;
+
+fn check_block_size<C: SmCipher + ?Sized, M: SmMac>(
+    cipher: &C,
+) -> Result<usize, SessionError<C, M>> {
+    let block_size = cipher.block_size();
+    if block_size == 0 || block_size > MAX_BLOCK_SIZE {
+        return Err(Error::InvalidBlockSize);
+    }
+    Ok(block_size)
+}
+
+/// Compute the MAC of `input`
+fn compute_mac<M: SmMac, D: DataStream<M> + ?Sized>(
+    mac: &mut M,
+    input: &D,
+    out: &mut [u8; MAX_MAC_LEN],
+) -> Result<usize, M::Error> {
+    input.to_writer(mac)?;
+    mac.finalize(out)
+}
+
+/// Compute the MAC of `input` and compare it with `received`
+fn verify_mac<C, M: SmMac, D: DataStream<M> + ?Sized>(
+    mac: &mut M,
+    input: &D,
+    received: &[u8],
+) -> Result<(), Error<C, M::Error>> {
+    let mut checksum = [0; MAX_MAC_LEN];
+    let checksum_len = compute_mac(mac, input, &mut checksum).map_err(Error::Mac)?;
+    let difference = checksum[..checksum_len]
+        .iter()
+        .zip(received)
+        .fold(0, |acc, (a, b)| acc | (a ^ b));
+    if checksum_len != received.len() || difference != 0 {
+        return Err(Error::InvalidMac);
+    }
+    Ok(())
+}
+
+/// Write the `87`, `85` data object with the encrypted `data`
+fn write_cryptogram<C: SmCipher + ?Sized, M: SmMac>(
+    cipher: &mut C,
+    output: &mut Output<'_>,
+    instruction: u8,
+    data: &[u8],
+    block_size: usize,
+) -> Result<(), SessionError<C, M>> {
+    let cryptogram_len = Method::Two.padded_len(data.len(), block_size);
+    // Odd instructions have BER-TLV encoded data fields
+    if instruction & 1 == 1 {
+        output.header(CRYPTOGRAM, cryptogram_len)?;
+    } else {
+        output.header(PADDED_CRYPTOGRAM, cryptogram_len + 1)?;
+        output.write(&[PaddingIndicator::Iso9797Method2.into()])?;
+    }
+    let cryptogram = output.reserve(cryptogram_len)?;
+    cryptogram[..data.len()].copy_from_slice(data);
+    Method::Two.pad(cryptogram, data.len(), block_size);
+    cipher.encrypt(cryptogram).map_err(Error::Cipher)
+}
+
+/// Data objects of a protected data field
+struct Objects<'a> {
+    /// `81`, `85` or `87` data object
+    value: Option<(u8, &'a [u8])>,
+    le: Option<&'a [u8]>,
+    status: Option<&'a [u8]>,
+    /// Data objects preceding the MAC
+    mac_input: &'a [u8],
+    mac: &'a [u8],
+}
+
+impl<'a> Objects<'a> {
+    fn parse<C, M>(data: &'a [u8]) -> Result<Self, Error<C, M>> {
+        let mut value = None;
+        let mut le = None;
+        let mut status = None;
+        let mut remaining = data;
+        let (mac_input, mac) = loop {
+            let mac_input = &data[..data.len() - remaining.len()];
+            let (&tag, rest) = remaining.split_first().ok_or(Error::MissingMac)?;
+            let (len, rest) = take_len(rest).ok_or(Error::InvalidData)?;
+            if rest.len() < len {
+                return Err(Error::InvalidData);
+            }
+            let (object, rest) = rest.split_at(len);
+            remaining = rest;
+            match tag {
+                PLAIN_VALUE | CRYPTOGRAM | PADDED_CRYPTOGRAM if value.is_none() => {
+                    value = Some((tag, object))
+                }
+                LE if le.is_none() => le = Some(object),
+                STATUS if status.is_none() => status = Some(object),
+                MAC => break (mac_input, object),
+                _ => return Err(Error::InvalidData),
+            }
+        };
+        if !remaining.is_empty() {
+            return Err(Error::InvalidData);
+        }
+        Ok(Self {
+            value,
+            le,
+            status,
+            mac_input,
+            mac,
+        })
+    }
+
+    /// Write the plain value to `plain`, returning its length
+    fn decrypt<C: SmCipher + ?Sized, M: SmMac>(
+        &self,
+        cipher: &mut C,
+        block_size: usize,
+        plain: &mut [u8],
+    ) -> Result<usize, SessionError<C, M>> {
+        Ok(match self.value {
+            None => 0,
+            Some((PLAIN_VALUE, object)) => {
+                plain
+                    .get_mut(..object.len())
+                    .ok_or(Error::BufferFull)?
+                    .copy_from_slice(object);
+                object.len()
+            }
+            Some((tag, object)) => {
+                let (padding, cryptogram) = if tag == PADDED_CRYPTOGRAM {
+                    let (&indicator, cryptogram) =
+                        object.split_first().ok_or(Error::InvalidData)?;
+                    (PaddingIndicator::from(indicator), cryptogram)
+                } else {
+                    (PaddingIndicator::Iso9797Method2, object)
+                };
+                if cryptogram.len() % block_size != 0 {
+                    return Err(Error::InvalidData);
+                }
+                let decrypted = plain.get_mut(..cryptogram.len()).ok_or(Error::BufferFull)?;
+                decrypted.copy_from_slice(cryptogram);
+                cipher.decrypt(decrypted).map_err(Error::Cipher)?;
+                match padding {
+                    PaddingIndicator::Iso9797Method2 => Method::Two
+                        .unpad(decrypted, block_size)
+                        .map_err(|_| Error::InvalidPadding)?
+                        .len(),
+                    PaddingIndicator::NoPadding => decrypted.len(),
+                    other => return Err(Error::UnsupportedPadding(other.into())),
+                }
+            }
+        })
+    }
+}
+
+/// Write the protected data field of `command` to `protected`, returning its length.
+///
+/// `header` is the header of the protected command, included in the MAC.
+pub fn wrap<C: SmCipher + ?Sized, M: SmMac>(
+    cipher: &mut C,
+    mac: &mut M,
+    header: [u8; 4],
+    command: &CommandView<'_>,
+    protected: &mut [u8],
+) -> Result<usize, SessionError<C, M>> {
+    let block_size = check_block_size::<C, M>(cipher)?;
+    let mut output = Output {
+        buffer: protected,
+        len: 0,
+    };
+
+    let data = command.data();
+    if !data.is_empty() {
+        write_cryptogram::<C, M>(cipher, &mut output, header[1], data, block_size)?;
+    }
+
+    let expected = command.expected();
+    if expected != 0 {
+        // The maximum length is encoded as 0
+        if command.extended {
+            output.data_object(LE, &(expected as u16).to_be_bytes())?;
+        } else {
+            output.data_object(LE, &[expected as u8])?;
+        }
+    }
+
+    let mut checksum = [0; MAX_MAC_LEN];
+    let mac_input = (
+        Padded::new(&header, Method::Two, block_size),
+        &output.buffer[..output.len],
+    );
+    let checksum_len = compute_mac(mac, &mac_input, &mut checksum).map_err(Error::Mac)?;
+    output.data_object(MAC, &checksum[..checksum_len])?;
+    Ok(output.len)
+}
+
+/// Verify and decrypt the protected response `data`, writing the plain response data to `plain`.
+///
+/// `status` is the plain status of the response, checked against the `99` data object according to `policy`.
+/// Returns the length of the plain data and the status of the response.
+pub fn unwrap<C: SmCipher + ?Sized, M: SmMac>(
+    cipher: &mut C,
+    mac: &mut M,
+    data: &[u8],
+    status: Status,
+    policy: StatusPolicy,
+    plain: &mut [u8],
+) -> Result<(usize, Status), SessionError<C, M>> {
+    let block_size = check_block_size::<C, M>(cipher)?;
+    let objects = Objects::parse(data)?;
+    if objects.le.is_some() {
+        return Err(Error::InvalidData);
+    }
+    verify_mac(mac, objects.mac_input, objects.mac)?;
+
+    let status = match (objects.status, policy) {
+        (Some(&[sw1, sw2]), StatusPolicy::Adopt) => Status::from((sw1, sw2)),
+        (Some(&[sw1, sw2]), _) if <[u8; 2]>::from(status) == [sw1, sw2] => status,
+        (Some(&[_, _]), _) | (None, StatusPolicy::Require) => return Err(Error::SmIntegrity),
+        (Some(_), _) => return Err(Error::InvalidData),
+        (None, _) => status,
+    };
+    let len = objects.decrypt::<C, M>(cipher, block_size, plain)?;
+    Ok((len, status))
+}
+
+/// Verify and decrypt the protected `command`, returning the plain command.
+///
+/// This is the card-side counterpart of [`wrap`]. The plain command keeps the header of the
+/// protected command. Its data field is decrypted into `plain`, or points into the protected command
+/// for a `81` data object, and its expected length is taken from the `97` data object.
+pub fn unwrap_command<'a, C: SmCipher + ?Sized, M: SmMac>(
+    cipher: &mut C,
+    mac: &mut M,
+    command: &CommandView<'a>,
+    plain: &'a mut [u8],
+) -> Result<CommandView<'a>, SessionError<C, M>> {
+    let block_size = check_block_size::<C, M>(cipher)?;
+    let objects = Objects::parse(command.data())?;
+    if objects.status.is_some() {
+        return Err(Error::InvalidData);
+    }
+    let header = [
+        command.class().into_inner(),
+        command.instruction().into(),
+        command.p1,
+        command.p2,
+    ];
+    let mac_input = (
+        Padded::new(&header, Method::Two, block_size),
+        objects.mac_input,
+    );
+    verify_mac(mac, &mac_input, objects.mac)?;
+
+    let expected = match objects.le {
+        None | Some([]) => 0,
+        Some(&[0]) => 256,
+        Some(&[0, 0]) => 65536,
+        Some(&[le]) => le.into(),
+        Some(&[le1, le2]) => u16::from_be_bytes([le1, le2]).into(),
+        Some(_) => return Err(Error::InvalidData),
+    };
+    let data = match objects.value {
+        Some((PLAIN_VALUE, object)) => object,
+        _ => {
+            let len = objects.decrypt::<C, M>(cipher, block_size, plain)?;
+            &plain[..len]
+        }
+    };
+    Ok(command.with_body(data, expected))
+}
+
+/// Write the protected response data field for the plain response `data` and `status` to `protected`,
+/// returning its length.
+///
+/// This is the card-side counterpart of [`unwrap`]. `instruction` is the instruction of the
+/// command, whose parity selects the `87` or `85` data object.
+pub fn wrap_response<C: SmCipher + ?Sized, M: SmMac>(
+    cipher: &mut C,
+    mac: &mut M,
+    instruction: Instruction,
+    data: &[u8],
+    status: Status,
+    protected: &mut [u8],
+) -> Result<usize, SessionError<C, M>> {
+    let block_size = check_block_size::<C, M>(cipher)?;
+    let mut output = Output {
+        buffer: protected,
+        len: 0,
+    };
+    if !data.is_empty() {
+        write_cryptogram::<C, M>(cipher, &mut output, instruction.into(), data, block_size)?;
+    }
+    output.data_object(STATUS, &<[u8; 2]>::from(status))?;
+
+    let mut checksum = [0; MAX_MAC_LEN];
+    let checksum_len =
+        compute_mac(mac, &output.buffer[..output.len], &mut checksum).map_err(Error::Mac)?;
+    output.data_object(MAC, &checksum[..checksum_len])?;
+    Ok(output.len)
+}
+
+/// Accept an unprotected response only if its status is an error
+fn unprotected<C: SmCipher + ?Sized, M: SmMac>(
+    status: Status,
+) -> Result<Status, SessionError<C, M>> {
+    if crate::transport::secure_messaging::is_error(status) {
+        Ok(status)
+    } else {
+        Err(Error::MissingMac)
+    }
+}
+
+/// [`SmSession`] applying [`wrap`] and [`unwrap`] with the given primitives
+pub struct Session<C, M> {
+    cipher: C,
+    mac: M,
+    status_policy: StatusPolicy,
+}
+
+impl<C, M> Session<C, M> {
+    pub fn new(cipher: C, mac: M) -> Self {
+        Self {
+            cipher,
+            mac,
+            status_policy: StatusPolicy::default(),
+        }
+    }
+
+    /// Handling of the `99` data object (default: [`StatusPolicy::Verify`])
+    pub fn status_policy(mut self, policy: StatusPolicy) -> Self {
+        self.status_policy = policy;
+        self
+    }
+
+    pub fn cipher(&mut self) -> &mut C {
+        &mut self.cipher
+    }
+
+    pub fn mac(&mut self) -> &mut M {
+        &mut self.mac
+    }
+
+    pub fn into_inner(self) -> (C, M) {
+        (self.cipher, self.mac)
+    }
+}
+
+impl<C: SmCipher, M: SmMac> SmSession for Session<C, M> {
+    type Error = SessionError<C, M>;
+
+    fn wrap(
+        &mut self,
+        header: [u8; 4],
+        command: CommandView<'_>,
+        protected: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        wrap(&mut self.cipher, &mut self.mac, header, &command, protected)
+    }
+
+    fn unwrap(
+        &mut self,
+        data: &[u8],
+        status: Status,
+        plain: &mut [u8],
+    ) -> Result<(usize, Status), Self::Error> {
+        unwrap(
+            &mut self.cipher,
+            &mut self.mac,
+            data,
+            status,
+            self.status_policy,
+            plain,
+        )
+    }
+
+    fn unprotected(&mut self, status: Status) -> Result<Status, Self::Error> {
+        unprotected::<C, M>(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::writer::BufferFull;
+    use hex_literal::hex;
+
+    /// "Encrypts" by xoring with `55`
+    pub(super) struct ToyCipher;
+
+    impl SmCipher for ToyCipher {
+        type Error = ();
+
+        fn block_size(&self) -> usize {
+            8
+        }
+
+        fn encrypt(&mut self, data: &mut [u8]) -> Result<(), ()> {
+            data.iter_mut().for_each(|b| *b ^= 0x55);
+            Ok(())
+        }
+
+        fn decrypt(&mut self, data: &mut [u8]) -> Result<(), ()> {
+            self.encrypt(data)
+        }
+    }
+
+    /// "MACs" by xoring the input into 4 bytes
+    #[derive(Default)]
+    pub(super) struct ToyMac {
+        state: [u8; 4],
+        len: usize,
+    }
+
+    impl Writer for ToyMac {
+        type Error = BufferFull;
+
+        fn write(&mut self, data: &[u8]) -> Result<usize, BufferFull> {
+            for b in data {
+                self.state[self.len % 4] ^= b;
+                self.len += 1;
+            }
+            Ok(data.len())
+        }
+    }
+
+    impl SmMac for ToyMac {
+        fn finalize(&mut self, mac: &mut [u8]) -> Result<usize, BufferFull> {
+            mac[..4].copy_from_slice(&self.state);
+            *self = Self::default();
+            Ok(4)
+        }
+    }
+
+    fn toy_mac(data: &[u8]) -> [u8; 4] {
+        let mut mac = ToyMac::default();
+        mac.write_all(data).unwrap();
+        mac.state
+    }
+
+    #[test]
+    fn wrap_command() {
+        let mut session = Session::new(ToyCipher, ToyMac::default());
+        let mut protected = [0; 64];
+        let command = CommandView::try_from(&hex!("00A4020C 02 011E")[..]).unwrap();
+        let len = session
+            .wrap(hex!("0CA4020C"), command, &mut protected)
+            .unwrap();
+        assert_eq!(
+            &protected[..len],
+            &hex!("87 09 01 544BD55555555555 8E 04 152D030D")
+        );
+
+        // Odd instruction, Le
+        let command = CommandView::try_from(&hex!("00CB3FFF 02 5C00 00")[..]).unwrap();
+        let len = session
+            .wrap(hex!("0CCB3FFF"), command, &mut protected)
+            .unwrap();
+        assert_eq!(
+            &protected[..len - 4],
+            &hex!("85 08 0955D55555555555 97 01 00 8E 04")
+        );
+
+        let command = CommandView::try_from(&hex!("00B00000 00")[..]).unwrap();
+        let len = session
+            .wrap(hex!("0CB00000"), command, &mut protected)
+            .unwrap();
+        assert_eq!(&protected[..len - 4], &hex!("97 01 00 8E 04"));
+
+        assert!(matches!(
+            session.wrap(hex!("0CB00000"), command, &mut protected[..4]),
+            Err(Error::BufferFull)
+        ));
+    }
+
+    #[test]
+    fn unwrap_response() {
+        let mut session = Session::new(ToyCipher, ToyMac::default());
+        let mut response = hex!("87 09 01 544BD55555555555 99 02 9000 8E 04 00000000").to_vec();
+        let mac = toy_mac(&response[..15]);
+        response[17..].copy_from_slice(&mac);
+
+        let mut plain = [0; 16];
+        let (len, status) = session
+            .unwrap(&response, Status::Success, &mut plain)
+            .unwrap();
+        assert_eq!(&plain[..len], &hex!("011E"));
+        assert_eq!(status, Status::Success);
+
+        response[3] ^= 1;
+        assert!(matches!(
+            session.unwrap(&response, Status::Success, &mut plain),
+            Err(Error::InvalidMac)
+        ));
+        assert!(matches!(
+            session.unwrap(&hex!("99 02 6A82"), Status::Success, &mut plain),
+            Err(Error::MissingMac)
+        ));
+
+        // Status only
+        let mut response = hex!("99 02 6A82 8E 04 00000000");
+        let mac = toy_mac(&response[..4]);
+        response[6..].copy_from_slice(&mac);
+        assert!(matches!(
+            session.unwrap(&response, Status::NotFound, &mut plain),
+            Ok((0, Status::NotFound))
+        ));
+    }
+
+    #[test]
+    fn unwrap_command_view() {
+        let mut protected = [0; 64];
+        let command = CommandView::try_from(&hex!("00D60000 03 010203")[..]).unwrap();
+        let len = wrap(
+            &mut ToyCipher,
+            &mut ToyMac::default(),
+            hex!("0CD60000"),
+            &command,
+            &mut protected,
+        )
+        .unwrap();
+        let mut apdu = hex!("0CD60000").to_vec();
+        apdu.push(len as u8);
+        apdu.extend_from_slice(&protected[..len]);
+        let protected_command = CommandView::try_from(&apdu[..]).unwrap();
+
+        let mut plain = [0; 16];
+        let plain_command = unwrap_command(
+            &mut ToyCipher,
+            &mut ToyMac::default(),
+            &protected_command,
+            &mut plain,
+        )
+        .unwrap();
+        assert_eq!(plain_command.class().into_inner(), 0x0C);
+        assert_eq!(plain_command.instruction(), Instruction::UpdateBinary);
+        assert_eq!(plain_command.data(), &hex!("010203"));
+        assert_eq!(plain_command.expected(), 0);
+
+        // Plain value: no copy
+        let mut apdu = hex!("0CD60000 0B 81 03 010203 8E 04 00000000").to_vec();
+        let mut mac = ToyMac::default();
+        mac.write_all(&hex!("0CD60000 80000000 81 03 010203"))
+            .unwrap();
+        apdu[12..].copy_from_slice(&mac.state);
+        let protected_command = CommandView::try_from(&apdu[..]).unwrap();
+        let plain_command = unwrap_command(
+            &mut ToyCipher,
+            &mut ToyMac::default(),
+            &protected_command,
+            &mut [],
+        )
+        .unwrap();
+        assert_eq!(plain_command.data(), &hex!("010203"));
+        assert!(core::ptr::eq(
+            plain_command.data(),
+            &protected_command.data()[2..5]
+        ));
+    }
+
+    #[test]
+    fn status_policy() {
+        let mut plain = [0; 16];
+        let mut response = hex!("99 02 6A82 8E 04 00000000");
+        let mac = toy_mac(&response[..4]);
+        response[6..].copy_from_slice(&mac);
+
+        let mut session = Session::new(ToyCipher, ToyMac::default());
+        assert!(matches!(
+            session.unwrap(&response, Status::Success, &mut plain),
+            Err(Error::SmIntegrity)
+        ));
+
+        let mut session = session.status_policy(StatusPolicy::Adopt);
+        assert!(matches!(
+            session.unwrap(&response, Status::Success, &mut plain),
+            Ok((0, Status::NotFound))
+        ));
+
+        let mut response = hex!("81 01 AA 8E 04 00000000");
+        let mac = toy_mac(&response[..3]);
+        response[5..].copy_from_slice(&mac);
+        assert!(matches!(
+            session.unwrap(&response, Status::Success, &mut plain),
+            Ok((1, Status::Success))
+        ));
+        let mut session = session.status_policy(StatusPolicy::Require);
+        assert!(matches!(
+            session.unwrap(&response, Status::Success, &mut plain),
+            Err(Error::SmIntegrity)
+        ));
+    }
+
+    #[test]
+    fn stripped_response() {
+        use crate::transport::secure_messaging::{SmError, SmTransport};
+        use crate::transport::tests::Script;
+        use crate::transport::Transport;
+
+        // The secure messaging data objects of the response were removed by an attacker
+        let session =
+            Session::new(ToyCipher, ToyMac::default()).status_policy(StatusPolicy::Require);
+        let mut transport =
+            SmTransport::<_, _, 64>::new(Script::new(&[&hex!("9000"), &hex!("6300")]), session);
+        let mut response = [0; 16];
+        let verify = hex!("00200081 04 31323334");
+        assert!(matches!(
+            transport.transmit(&verify, &mut response),
+            Err(SmError::Session(Error::MissingMac))
+        ));
+        assert!(matches!(
+            transport.transmit(&verify, &mut response),
+            Err(SmError::Session(Error::MissingMac))
+        ));
+
+        let mut session = Session::new(ToyCipher, ToyMac::default());
+        assert!(matches!(
+            session.unprotected(Status::Success),
+            Err(Error::MissingMac)
+        ));
+        assert!(matches!(
+            session.unprotected(Status::RemainingRetries(2)),
+            Err(Error::MissingMac)
+        ));
+
+        // Errors can be sent without secure messaging
+        let session = Session::new(ToyCipher, ToyMac::default());
+        let mut transport = SmTransport::<_, _, 64>::new(Script::new(&[&hex!("6982")]), session);
+        assert!(matches!(transport.transmit(&verify, &mut response), Ok(2)));
+        assert_eq!(response[..2], hex!("6982"));
+    }
+}
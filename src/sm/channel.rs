@@ -0,0 +1,320 @@
+//! Secure channel lifecycle and send sequence counter
+//!
+//! [`SecureChannel`] owns the session keys (through the cipher and the MAC) and the send sequence
+//! counter (SSC) of an ISO 7816-4 secure messaging session, as used by eMRTD and PACE.
+//! The counter is incremented before protecting or verifying each command and each response, and
+//! is prepended to the MAC input.
+//!
+//! The channel is torn down as soon as something goes wrong: a failure to protect or verify data,
+//! an unprotected response on the host side, or a `6987`/`6988` status. Once aborted, it refuses
+//! to process anything until new keys are established.
+//!
+//! The same type is used on the host side, as a [`SmSession`] for
+//! [`SmTransport`](crate::transport::secure_messaging::SmTransport), and on the card side with
+//! [`unwrap_command`](SecureChannel::unwrap_command) and [`wrap_response`](SecureChannel::wrap_response).
+
+use super::{
+    unprotected, unwrap, unwrap_command, wrap, wrap_response, Error, SessionError, SmCipher, SmMac,
+    StatusPolicy,
+};
+use crate::command::{CommandView, Instruction};
+use crate::transport::secure_messaging::SmSession;
+use crate::Status;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Open,
+    /// Closed by the application
+    Closed,
+    /// Torn down after an error
+    Aborted,
+}
+
+/// Whether the card discards the session after sending this status
+fn tears_down(status: Status) -> bool {
+    matches!(
+        status,
+        Status::ExectedSecureMessagingDataObjectsMissing
+            | Status::IncorrectSecureMessagingDataObjects
+    )
+}
+
+/// Secure messaging session with a `N` bytes send sequence counter
+pub struct SecureChannel<C, M, const N: usize> {
+    cipher: C,
+    mac: M,
+    counter: [u8; N],
+    state: State,
+    status_policy: StatusPolicy,
+}
+
+impl<C, M, const N: usize> SecureChannel<C, M, N> {
+    /// Open channel, `counter` being the initial value of the send sequence counter
+    pub fn new(cipher: C, mac: M, counter: [u8; N]) -> Self {
+        Self {
+            cipher,
+            mac,
+            counter,
+            state: State::Open,
+            status_policy: StatusPolicy::default(),
+        }
+    }
+
+    /// Handling of the `99` data object on the host side (default: [`StatusPolicy::Verify`])
+    pub fn status_policy(mut self, policy: StatusPolicy) -> Self {
+        self.status_policy = policy;
+        self
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state == State::Open
+    }
+
+    /// Current value of the send sequence counter
+    pub fn counter(&self) -> &[u8; N] {
+        &self.counter
+    }
+
+    pub fn close(&mut self) {
+        self.state = State::Closed;
+    }
+
+    pub fn abort(&mut self) {
+        self.state = State::Aborted;
+    }
+
+    pub fn into_inner(self) -> (C, M) {
+        (self.cipher, self.mac)
+    }
+}
+
+impl<C: SmCipher, M: SmMac, const N: usize> SecureChannel<C, M, N> {
+    /// Increment the counter and start the MAC input with it
+    fn next(&mut self) -> Result<(), SessionError<C, M>> {
+        if self.state != State::Open {
+            return Err(Error::Closed);
+        }
+        for byte in self.counter.iter_mut().rev() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                break;
+            }
+        }
+        if self.counter.iter().all(|&b| b == 0) {
+            // Wrapped around: the counter must never be reused
+            self.state = State::Aborted;
+            return Err(Error::Closed);
+        }
+        self.mac.write_all(&self.counter).map_err(Error::Mac)
+    }
+
+    /// Abort the channel if `result` is an error
+    fn check<T>(&mut self, result: Result<T, SessionError<C, M>>) -> Result<T, SessionError<C, M>> {
+        if result.is_err() {
+            self.abort();
+        }
+        result
+    }
+
+    /// Verify and decrypt a protected command, see [`unwrap_command`](super::unwrap_command)
+    ///
+    /// On error, the channel is aborted and the command should be rejected with `6987` or `6988`.
+    pub fn unwrap_command<'a>(
+        &mut self,
+        command: &CommandView<'a>,
+        plain: &'a mut [u8],
+    ) -> Result<CommandView<'a>, SessionError<C, M>> {
+        let result = self
+            .next()
+            .and_then(|()| unwrap_command(&mut self.cipher, &mut self.mac, command, plain));
+        self.check(result)
+    }
+
+    /// Protect a response, see [`wrap_response`](super::wrap_response)
+    pub fn wrap_response(
+        &mut self,
+        instruction: Instruction,
+        data: &[u8],
+        status: Status,
+        protected: &mut [u8],
+    ) -> Result<usize, SessionError<C, M>> {
+        let result = self.next().and_then(|()| {
+            wrap_response(
+                &mut self.cipher,
+                &mut self.mac,
+                instruction,
+                data,
+                status,
+                protected,
+            )
+        });
+        let result = self.check(result);
+        if tears_down(status) {
+            self.abort();
+        }
+        result
+    }
+}
+
+impl<C: SmCipher, M: SmMac, const N: usize> SmSession for SecureChannel<C, M, N> {
+    type Error = SessionError<C, M>;
+
+    fn wrap(
+        &mut self,
+        header: [u8; 4],
+        command: CommandView<'_>,
+        protected: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let result = self
+            .next()
+            .and_then(|()| wrap(&mut self.cipher, &mut self.mac, header, &command, protected));
+        self.check(result)
+    }
+
+    fn unwrap(
+        &mut self,
+        data: &[u8],
+        status: Status,
+        plain: &mut [u8],
+    ) -> Result<(usize, Status), Self::Error> {
+        let result = self.next().and_then(|()| {
+            unwrap(
+                &mut self.cipher,
+                &mut self.mac,
+                data,
+                status,
+                self.status_policy,
+                plain,
+            )
+        });
+        let result = self.check(result);
+        if matches!(result, Ok((_, status)) if tears_down(status)) {
+            self.abort();
+        }
+        result
+    }
+
+    fn unprotected(&mut self, status: Status) -> Result<Status, Self::Error> {
+        // Protected responses always have data objects: the card has discarded the session
+        self.abort();
+        unprotected::<C, M>(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{ToyCipher, ToyMac};
+    use super::*;
+    use hex_literal::hex;
+
+    type Channel = SecureChannel<ToyCipher, ToyMac, 8>;
+
+    fn channel() -> Channel {
+        SecureChannel::new(ToyCipher, ToyMac::default(), hex!("00000000000000FE"))
+    }
+
+    #[test]
+    fn exchange() {
+        let mut host = channel();
+        let mut card = channel();
+        let mut protected = [0; 64];
+        let mut plain = [0; 64];
+
+        for _ in 0..3 {
+            let command = CommandView::try_from(&hex!("00B00000 00")[..]).unwrap();
+            let len = host
+                .wrap(hex!("0CB00000"), command, &mut protected)
+                .unwrap();
+            let mut apdu = hex!("0CB00000").to_vec();
+            apdu.push(len as u8);
+            apdu.extend_from_slice(&protected[..len]);
+            apdu.push(0);
+            let protected_command = CommandView::try_from(&apdu[..]).unwrap();
+            let plain_command = card.unwrap_command(&protected_command, &mut plain).unwrap();
+            assert_eq!(
+                plain_command,
+                CommandView::try_from(&hex!("0CB00000 00")[..]).unwrap()
+            );
+
+            let len = card
+                .wrap_response(
+                    Instruction::ReadBinary,
+                    &hex!("0102"),
+                    Status::Success,
+                    &mut protected,
+                )
+                .unwrap();
+            let (len, status) = host
+                .unwrap(&protected[..len], Status::Success, &mut plain)
+                .unwrap();
+            assert_eq!(status, Status::Success);
+            assert_eq!(&plain[..len], &hex!("0102"));
+        }
+        assert_eq!(host.counter(), &hex!("0000000000000104"));
+        assert_eq!(card.counter(), host.counter());
+    }
+
+    #[test]
+    fn teardown() {
+        let mut host = channel();
+        let mut card = channel();
+        let mut protected = [0; 64];
+        let mut plain = [0; 64];
+
+        // The card missed a command: the counters are out of sync
+        let command = CommandView::try_from(&hex!("00B00000 00")[..]).unwrap();
+        host.wrap(hex!("0CB00000"), command, &mut protected)
+            .unwrap();
+        let len = card
+            .wrap_response(
+                Instruction::ReadBinary,
+                &[],
+                Status::Success,
+                &mut protected,
+            )
+            .unwrap();
+        assert!(matches!(
+            host.unwrap(&protected[..len], Status::Success, &mut plain),
+            Err(Error::InvalidMac)
+        ));
+        assert_eq!(host.state(), State::Aborted);
+        assert!(matches!(
+            host.wrap(hex!("0CB00000"), command, &mut protected),
+            Err(Error::Closed)
+        ));
+
+        let mut host = channel();
+        assert!(matches!(
+            host.unprotected(Status::IncorrectSecureMessagingDataObjects),
+            Ok(Status::IncorrectSecureMessagingDataObjects)
+        ));
+        assert_eq!(host.state(), State::Aborted);
+        let mut host = channel();
+        assert!(matches!(
+            host.unprotected(Status::Success),
+            Err(Error::MissingMac)
+        ));
+        assert_eq!(host.state(), State::Aborted);
+
+        let mut card = channel();
+        card.wrap_response(
+            Instruction::ReadBinary,
+            &[],
+            Status::ExectedSecureMessagingDataObjectsMissing,
+            &mut protected,
+        )
+        .unwrap();
+        assert!(!card.is_open());
+
+        let mut card = SecureChannel::new(ToyCipher, ToyMac::default(), [0xFF; 8]);
+        let command = CommandView::try_from(&hex!("0CB00000 00")[..]).unwrap();
+        assert!(matches!(
+            card.unwrap_command(&command, &mut plain),
+            Err(Error::Closed)
+        ));
+    }
+}
@@ -0,0 +1,182 @@
+//! ISO/IEC 9797-1 padding methods 1 and 2
+//!
+//! [`Padded`] appends the padding while the data is written to a [`Writer`], so that padded data
+//! can be fed to a MAC without being copied to a larger buffer first. [`Method::unpad`] validates
+//! and removes the padding of decrypted data.
+//!
+//! ```
+//! use iso7816::command::DataStream;
+//! use iso7816::sm::padding::{Method, Padded};
+//! let mut padded = heapless::Vec::<u8, 16>::new();
+//! Padded::new(&[0x01, 0x02][..], Method::Two, 4).to_writer(&mut padded).unwrap();
+//! assert_eq!(&*padded, &[0x01, 0x02, 0x80, 0x00]);
+//! assert_eq!(Method::Two.unpad(&padded, 4), Ok(&[0x01, 0x02][..]));
+//! ```
+
+use crate::command::{DataSource, DataStream, Fill, Writer};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// Zeroes up to the block size. Non-empty data already aligned on the block size is not padded.
+    One,
+    /// `80` followed by zeroes up to the block size. Padding is always added.
+    Two,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaddingError {
+    /// The padded data is empty or its length is not a multiple of the block size
+    InvalidLength,
+    /// The padding bytes are not valid
+    InvalidPadding,
+}
+
+impl Method {
+    /// Length of `len` bytes of data once padded to `block_size`
+    pub fn padded_len(self, len: usize, block_size: usize) -> usize {
+        match self {
+            Self::One if len != 0 && len % block_size == 0 => len,
+            _ => (len / block_size + 1) * block_size,
+        }
+    }
+
+    /// Pad the first `len` bytes of `buffer` in place, returning the padded length.
+    ///
+    /// Returns `None` if the padded data does not fit in `buffer`.
+    pub fn pad(self, buffer: &mut [u8], len: usize, block_size: usize) -> Option<usize> {
+        let padded_len = self.padded_len(len, block_size);
+        let padding = buffer.get_mut(len..padded_len)?;
+        padding.fill(0);
+        if let (Self::Two, Some(first)) = (self, padding.first_mut()) {
+            *first = 0x80;
+        }
+        Some(padded_len)
+    }
+
+    /// Validate the padding of `data`, returning the data without the padding.
+    ///
+    /// Zeroes padded with method 1 can't be told apart from the data, so they are not removed.
+    pub fn unpad(self, data: &[u8], block_size: usize) -> Result<&[u8], PaddingError> {
+        if data.is_empty() || data.len() % block_size != 0 {
+            return Err(PaddingError::InvalidLength);
+        }
+        match self {
+            Self::One => Ok(data),
+            Self::Two => {
+                let last = data
+                    .iter()
+                    .rposition(|&b| b != 0)
+                    .ok_or(PaddingError::InvalidPadding)?;
+                if data[last] != 0x80 || data.len() - last > block_size {
+                    return Err(PaddingError::InvalidPadding);
+                }
+                Ok(&data[..last])
+            }
+        }
+    }
+}
+
+/// Data padded when written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Padded<D> {
+    data: D,
+    method: Method,
+    block_size: usize,
+}
+
+impl<D> Padded<D> {
+    pub fn new(data: D, method: Method, block_size: usize) -> Self {
+        Self {
+            data,
+            method,
+            block_size,
+        }
+    }
+
+    pub fn into_inner(self) -> D {
+        self.data
+    }
+}
+
+impl<D: DataSource> DataSource for Padded<D> {
+    fn len(&self) -> usize {
+        self.method.padded_len(self.data.len(), self.block_size)
+    }
+
+    fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+impl<W: Writer, D: DataStream<W>> DataStream<W> for Padded<D> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        self.data.to_writer(writer)?;
+        let mut padding = self.len() - self.data.len();
+        if self.method == Method::Two {
+            writer.write_all(&[0x80])?;
+            padding -= 1;
+        }
+        Fill {
+            byte: 0,
+            len: padding,
+        }
+        .to_writer(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    fn padded(data: &[u8], method: Method, block_size: usize) -> Vec<u8> {
+        let mut padded = Vec::new();
+        Padded::new(data, method, block_size)
+            .to_writer(&mut padded)
+            .unwrap();
+        assert_eq!(padded.len(), Padded::new(data, method, block_size).len());
+        padded
+    }
+
+    #[test]
+    fn methods() {
+        assert_eq!(padded(&hex!("0102"), Method::One, 4), hex!("01020000"));
+        assert_eq!(padded(&hex!("01020304"), Method::One, 4), hex!("01020304"));
+        assert_eq!(padded(&[], Method::One, 4), hex!("00000000"));
+        assert_eq!(
+            padded(&hex!("01020304"), Method::Two, 4),
+            hex!("01020304 80000000")
+        );
+        assert_eq!(padded(&[0xAA; 3], Method::Two, 32).len(), 32);
+
+        let mut buffer = hex!("0102 FFFFFFFFFF");
+        assert_eq!(Method::Two.pad(&mut buffer, 2, 4), Some(4));
+        assert_eq!(buffer, hex!("01028000 FFFFFF"));
+        assert_eq!(Method::Two.pad(&mut buffer, 4, 4), None);
+    }
+
+    #[test]
+    fn unpad() {
+        assert_eq!(
+            Method::Two.unpad(&hex!("01020304 80000000"), 4),
+            Ok(&hex!("01020304")[..])
+        );
+        assert_eq!(Method::Two.unpad(&hex!("80000000"), 4), Ok(&[][..]));
+        assert_eq!(
+            Method::Two.unpad(&hex!("01020300"), 4),
+            Err(PaddingError::InvalidPadding)
+        );
+        assert_eq!(
+            Method::Two.unpad(&hex!("80000000 00000000"), 4),
+            Err(PaddingError::InvalidPadding)
+        );
+        assert_eq!(
+            Method::Two.unpad(&hex!("018000"), 4),
+            Err(PaddingError::InvalidLength)
+        );
+        assert_eq!(
+            Method::One.unpad(&hex!("01020000"), 4),
+            Ok(&hex!("01020000")[..])
+        );
+    }
+}
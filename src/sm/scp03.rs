@@ -0,0 +1,354 @@
+//! GlobalPlatform Secure Channel Protocol 03 (Amendment D)
+//!
+//! [`Scp03`] protects commands and responses once the session keys have been derived and the
+//! channel has been opened by the caller with INITIALIZE UPDATE and EXTERNAL AUTHENTICATE.
+//!
+//! - C-MAC: AES-CMAC with S-MAC over the MAC chaining value, the modified header (CLA with the
+//!   secure messaging bit, INS, P1, P2 and Lc including the MAC) and the data field, truncated
+//!   to 8 bytes. The full CMAC becomes the next MAC chaining value.
+//! - C-DECRYPTION: AES-CBC with S-ENC over the padded data field, with an ICV obtained by
+//!   encrypting the encryption counter.
+//! - R-MAC: AES-CMAC with S-RMAC over the MAC chaining value, the response data and the status.
+//! - R-ENCRYPTION: AES-CBC with S-ENC, with an ICV obtained by encrypting the encryption counter
+//!   with its first byte set to `80`.
+//!
+//! The cipher must implement AES-CBC with a zero IV under S-ENC: the ICV is applied by [`Scp03`].
+//! The MACs must implement AES-CMAC and write the full 16 bytes.
+
+use super::padding::Method;
+use super::{Error, SmCipher, SmMac, MAX_MAC_LEN};
+use crate::command::class::{Class, SecureMessaging};
+use crate::command::{CommandView, Writer};
+use crate::transport::secure_messaging::SmSession;
+use crate::Status;
+
+/// AES block size
+const BLOCK_SIZE: usize = 16;
+/// Length of the truncated C-MAC and R-MAC
+const MAC_LEN: usize = 8;
+
+/// Security level of the session, as set by EXTERNAL AUTHENTICATE
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SecurityLevel(pub u8);
+
+impl SecurityLevel {
+    pub const C_MAC: Self = Self(0x01);
+    pub const C_DECRYPTION: Self = Self(0x02);
+    pub const R_MAC: Self = Self(0x10);
+    pub const R_ENCRYPTION: Self = Self(0x20);
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// Error of [`Scp03`]
+pub type Scp03Error<C, M> = Error<<C as SmCipher>::Error, <M as Writer>::Error>;
+
+/// SCP03 secure channel session
+///
+/// `cipher` uses S-ENC, `mac` uses S-MAC and `rmac` uses S-RMAC.
+pub struct Scp03<C, M> {
+    cipher: C,
+    mac: M,
+    rmac: M,
+    level: SecurityLevel,
+    chaining_value: [u8; BLOCK_SIZE],
+    counter: u32,
+}
+
+impl<C, M> Scp03<C, M> {
+    /// Session starting with a zero MAC chaining value, to protect EXTERNAL AUTHENTICATE
+    pub fn new(cipher: C, mac: M, rmac: M, level: SecurityLevel) -> Self {
+        Self {
+            cipher,
+            mac,
+            rmac,
+            level,
+            chaining_value: [0; BLOCK_SIZE],
+            counter: 0,
+        }
+    }
+
+    pub fn level(&self) -> SecurityLevel {
+        self.level
+    }
+
+    /// Current MAC chaining value
+    pub fn chaining_value(&self) -> &[u8; BLOCK_SIZE] {
+        &self.chaining_value
+    }
+
+    /// Current encryption counter
+    pub fn counter(&self) -> u32 {
+        self.counter
+    }
+}
+
+impl<C: SmCipher, M: SmMac> Scp03<C, M> {
+    /// ICV for the current encryption counter, `first` being `80` for responses
+    fn icv(&mut self, first: u8) -> Result<[u8; BLOCK_SIZE], Scp03Error<C, M>> {
+        let mut icv = [0; BLOCK_SIZE];
+        icv[BLOCK_SIZE - 4..].copy_from_slice(&self.counter.to_be_bytes());
+        icv[0] = first;
+        self.cipher.encrypt(&mut icv).map_err(Error::Cipher)?;
+        Ok(icv)
+    }
+}
+
+fn compute_mac<M: SmMac>(mac: &mut M, chunks: &[&[u8]]) -> Result<[u8; MAX_MAC_LEN], M::Error> {
+    let mut checksum = [0; MAX_MAC_LEN];
+    for chunk in chunks {
+        mac.write_all(chunk)?;
+    }
+    mac.finalize(&mut checksum)?;
+    Ok(checksum)
+}
+
+fn xor_block(data: &mut [u8], icv: &[u8; BLOCK_SIZE]) {
+    for (b, i) in data.iter_mut().zip(icv) {
+        *b ^= i;
+    }
+}
+
+impl<C: SmCipher, M: SmMac> SmSession for Scp03<C, M> {
+    type Error = Scp03Error<C, M>;
+
+    fn class(&self, class: Class) -> Class {
+        if self.level.contains(SecurityLevel::C_MAC) {
+            class.with_secure_messaging(SecureMessaging::Proprietary)
+        } else {
+            class
+        }
+    }
+
+    fn wrap(
+        &mut self,
+        header: [u8; 4],
+        command: CommandView<'_>,
+        protected: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let data = command.data();
+        let len = if self.level.contains(SecurityLevel::C_DECRYPTION) {
+            // Incremented for every command, even those without data
+            self.counter = self.counter.wrapping_add(1);
+            if data.is_empty() {
+                0
+            } else {
+                let len = Method::Two.padded_len(data.len(), BLOCK_SIZE);
+                let icv = self.icv(0x00)?;
+                let cryptogram = protected.get_mut(..len).ok_or(Error::BufferFull)?;
+                cryptogram[..data.len()].copy_from_slice(data);
+                Method::Two.pad(cryptogram, data.len(), BLOCK_SIZE);
+                xor_block(cryptogram, &icv);
+                self.cipher.encrypt(cryptogram).map_err(Error::Cipher)?;
+                len
+            }
+        } else {
+            protected
+                .get_mut(..data.len())
+                .ok_or(Error::BufferFull)?
+                .copy_from_slice(data);
+            data.len()
+        };
+
+        if !self.level.contains(SecurityLevel::C_MAC) {
+            return Ok(len);
+        }
+        let lc = len + MAC_LEN;
+        let [lc1, lc2] = (lc as u16).to_be_bytes();
+        let lc: &[u8] = if command.extended || lc > 255 {
+            &[0, lc1, lc2]
+        } else {
+            &[lc2]
+        };
+        let checksum = compute_mac(
+            &mut self.mac,
+            &[&self.chaining_value, &header, lc, &protected[..len]],
+        )
+        .map_err(Error::Mac)?;
+        self.chaining_value.copy_from_slice(&checksum[..BLOCK_SIZE]);
+        protected
+            .get_mut(len..len + MAC_LEN)
+            .ok_or(Error::BufferFull)?
+            .copy_from_slice(&checksum[..MAC_LEN]);
+        Ok(len + MAC_LEN)
+    }
+
+    fn unwrap(
+        &mut self,
+        data: &[u8],
+        status: Status,
+        plain: &mut [u8],
+    ) -> Result<(usize, Status), Self::Error> {
+        let body = if self.level.contains(SecurityLevel::R_MAC) {
+            let body_len = data.len().checked_sub(MAC_LEN).ok_or(Error::InvalidData)?;
+            let (body, received_mac) = data.split_at(body_len);
+            let sw: [u8; 2] = status.into();
+            let checksum = compute_mac(&mut self.rmac, &[&self.chaining_value, body, &sw])
+                .map_err(Error::Mac)?;
+            let difference = checksum[..MAC_LEN]
+                .iter()
+                .zip(received_mac)
+                .fold(0, |acc, (a, b)| acc | (a ^ b));
+            if difference != 0 {
+                return Err(Error::InvalidMac);
+            }
+            body
+        } else {
+            data
+        };
+
+        if body.is_empty() || !self.level.contains(SecurityLevel::R_ENCRYPTION) {
+            plain
+                .get_mut(..body.len())
+                .ok_or(Error::BufferFull)?
+                .copy_from_slice(body);
+            return Ok((body.len(), status));
+        }
+        if body.len() % BLOCK_SIZE != 0 {
+            return Err(Error::InvalidData);
+        }
+        let icv = self.icv(0x80)?;
+        let decrypted = plain.get_mut(..body.len()).ok_or(Error::BufferFull)?;
+        decrypted.copy_from_slice(body);
+        self.cipher.decrypt(decrypted).map_err(Error::Cipher)?;
+        xor_block(decrypted, &icv);
+        let len = Method::Two
+            .unpad(decrypted, BLOCK_SIZE)
+            .map_err(|_| Error::InvalidPadding)?
+            .len();
+        Ok((len, status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::writer::BufferFull;
+    use hex_literal::hex;
+
+    /// "Encrypts" by xoring with `55`, which is compatible with the ICV handling
+    struct ToyCipher;
+
+    impl SmCipher for ToyCipher {
+        type Error = ();
+
+        fn block_size(&self) -> usize {
+            BLOCK_SIZE
+        }
+
+        fn encrypt(&mut self, data: &mut [u8]) -> Result<(), ()> {
+            data.iter_mut().for_each(|b| *b ^= 0x55);
+            Ok(())
+        }
+
+        fn decrypt(&mut self, data: &mut [u8]) -> Result<(), ()> {
+            self.encrypt(data)
+        }
+    }
+
+    /// Records the MAC inputs, the MAC being the last 16 bytes of the input
+    #[derive(Default)]
+    struct RecordingMac {
+        input: Vec<u8>,
+        inputs: Vec<Vec<u8>>,
+    }
+
+    impl Writer for RecordingMac {
+        type Error = BufferFull;
+
+        fn write(&mut self, data: &[u8]) -> Result<usize, BufferFull> {
+            self.input.extend_from_slice(data);
+            Ok(data.len())
+        }
+    }
+
+    impl SmMac for RecordingMac {
+        fn finalize(&mut self, mac: &mut [u8]) -> Result<usize, BufferFull> {
+            let input = core::mem::take(&mut self.input);
+            mac[..BLOCK_SIZE].copy_from_slice(&input[input.len() - BLOCK_SIZE..]);
+            self.inputs.push(input);
+            Ok(BLOCK_SIZE)
+        }
+    }
+
+    fn session(level: SecurityLevel) -> Scp03<ToyCipher, RecordingMac> {
+        Scp03::new(
+            ToyCipher,
+            RecordingMac::default(),
+            RecordingMac::default(),
+            level,
+        )
+    }
+
+    #[test]
+    fn c_mac() {
+        let mut scp03 = session(SecurityLevel::C_MAC);
+        let class = scp03.class(Class::try_from(0x80).unwrap());
+        assert_eq!(class.into_inner(), 0x84);
+        let command = CommandView::try_from(&hex!("80F22000 02 4F00 00")[..]).unwrap();
+        let mut protected = [0; 32];
+        let len = scp03
+            .wrap(hex!("84F22000"), command, &mut protected)
+            .unwrap();
+        assert_eq!(&protected[..len], &hex!("4F00 0000000000000000"));
+        assert_eq!(
+            scp03.chaining_value(),
+            &hex!("000000000000000000 84F220000A4F00")
+        );
+        assert_eq!(
+            scp03.mac.inputs,
+            [hex!("00000000000000000000000000000000 84F22000 0A 4F00").to_vec()]
+        );
+        assert_eq!(scp03.counter(), 0);
+    }
+
+    #[test]
+    fn encryption() {
+        let level = SecurityLevel::C_MAC
+            .union(SecurityLevel::C_DECRYPTION)
+            .union(SecurityLevel::R_MAC)
+            .union(SecurityLevel::R_ENCRYPTION);
+        let mut scp03 = session(level);
+        let command = CommandView::try_from(&hex!("80E20000 01 AA")[..]).unwrap();
+        let mut protected = [0; 32];
+        let len = scp03
+            .wrap(hex!("84E20000"), command, &mut protected)
+            .unwrap();
+        assert_eq!(scp03.counter(), 1);
+        // ICV = 00..01 ^ 55 = 55..54, cryptogram = (AA 80 00.. ^ ICV) ^ 55
+        let cryptogram = hex!("AA800000000000000000000000000001");
+        assert_eq!(&protected[..16], &cryptogram);
+        assert_eq!(len, 24);
+        assert_eq!(
+            &scp03.mac.inputs[0][16..],
+            &hex!("84E20000 18 AA800000000000000000000000000001")
+        );
+
+        // Response ICV = 80..01 ^ 55
+        let body = hex!("01800000000000000000000000000001");
+        let mut mac_input = scp03.chaining_value().to_vec();
+        mac_input.extend_from_slice(&body);
+        mac_input.extend_from_slice(&hex!("9000"));
+        let mut response = body.to_vec();
+        response.extend_from_slice(&mac_input[mac_input.len() - 16..][..8]);
+        let mut plain = [0; 32];
+        let (len, status) = scp03
+            .unwrap(&response, Status::Success, &mut plain)
+            .unwrap();
+        assert_eq!(status, Status::Success);
+        assert_eq!(&plain[..len], &hex!("81"));
+        assert_eq!(scp03.rmac.inputs, [mac_input]);
+
+        response[20] ^= 1;
+        assert!(matches!(
+            scp03.unwrap(&response, Status::Success, &mut plain),
+            Err(Error::InvalidMac)
+        ));
+    }
+}
@@ -0,0 +1,229 @@
+//! [`proptest`] strategies generating command APDUs
+//!
+//! The lengths of the data field and the expected length are biased towards the boundaries of
+//! the short and extended encodings, where most encoding bugs hide.
+//!
+//! ```
+//! use iso7816::command::CommandView;
+//! use proptest::prelude::*;
+//!
+//! proptest!(|(apdu in iso7816::strategy::apdu(300))| {
+//!     let view = CommandView::try_from(&*apdu).unwrap();
+//!     prop_assert!(view.data().len() <= 300);
+//! });
+//! ```
+//!
+//! This module requires the `proptest` feature.
+
+use std::vec::Vec;
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::command::{class::Class, CommandBuilder, ExpectedLen, Instruction};
+
+/// Any valid class
+pub fn class() -> impl Strategy<Value = Class> {
+    (0..=0xFEu8).prop_map(|cla| Class::try_from(cla).unwrap())
+}
+
+pub fn instruction() -> impl Strategy<Value = Instruction> {
+    any::<u8>().prop_map(Instruction::from)
+}
+
+/// Lengths up to `max`, often at the limits of the short and extended encodings
+pub fn length(max: usize) -> impl Strategy<Value = usize> {
+    let edges = [0, 1, 255, 256, 257, 65_535, max]
+        .into_iter()
+        .filter(|&len| len <= max)
+        .collect::<Vec<_>>();
+    prop_oneof![
+        1 => 0..=max,
+        1 => proptest::sample::select(edges),
+    ]
+}
+
+/// Expected lengths, often at the limits of the short and extended encodings
+pub fn expected_len() -> impl Strategy<Value = ExpectedLen> {
+    prop_oneof![
+        2 => any::<u16>().prop_map(ExpectedLen::Ne),
+        2 => proptest::sample::select(vec![0, 1, 255, 256, 257, 65_535]).prop_map(ExpectedLen::Ne),
+        1 => Just(ExpectedLen::Max),
+    ]
+}
+
+/// Fields of a command APDU
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandParts {
+    pub class: Class,
+    pub instruction: Instruction,
+    pub p1: u8,
+    pub p2: u8,
+    pub data: Vec<u8>,
+    pub le: ExpectedLen,
+    /// Use the extended encoding even if the lengths fit in the short one
+    pub force_extended: bool,
+}
+
+impl CommandParts {
+    pub fn builder(&self) -> CommandBuilder<&[u8]> {
+        let builder = CommandBuilder::new(
+            self.class,
+            self.instruction,
+            self.p1,
+            self.p2,
+            &*self.data,
+            self.le,
+        );
+        if self.force_extended {
+            builder.force_extended()
+        } else {
+            builder
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.builder().serialize_to_vec()
+    }
+}
+
+/// Commands with up to `max_data` bytes of data, mixing short and extended encodings
+pub fn command(max_data: usize) -> impl Strategy<Value = CommandParts> {
+    let max_data = max_data.min(u16::MAX as usize);
+    (
+        class(),
+        instruction(),
+        any::<u8>(),
+        any::<u8>(),
+        length(max_data).prop_flat_map(|len| vec(any::<u8>(), len)),
+        expected_len(),
+        any::<bool>(),
+    )
+        .prop_map(
+            |(class, instruction, p1, p2, data, le, force_extended)| CommandParts {
+                class,
+                instruction,
+                p1,
+                p2,
+                data,
+                le,
+                force_extended,
+            },
+        )
+}
+
+/// Encoded command APDUs with up to `max_data` bytes of data
+pub fn apdu(max_data: usize) -> impl Strategy<Value = Vec<u8>> {
+    command(max_data).prop_map(|command| command.to_bytes())
+}
+
+/// Encoded command APDUs with one defect: a byte missing, a byte too many or a corrupted length
+///
+/// Most of them are invalid, but some happen to be valid encodings of another command, so
+/// parsers must not panic on them and serializers must not produce them for the original command.
+pub fn near_valid_apdu(max_data: usize) -> impl Strategy<Value = Vec<u8>> {
+    (
+        apdu(max_data),
+        0..3u8,
+        any::<u8>(),
+        any::<prop::sample::Index>(),
+    )
+        .prop_map(|(mut apdu, defect, byte, index)| {
+            match defect {
+                0 => {
+                    apdu.pop();
+                }
+                1 => apdu.push(byte),
+                // The length fields are within the first bytes of the body, or at the end
+                _ if apdu.len() > 4 => {
+                    let body_len = apdu.len() - 4;
+                    let offset = match index.index(2) {
+                        0 => 4 + index.index(body_len.min(3)),
+                        _ => apdu.len() - 1 - index.index(body_len.min(2)),
+                    };
+                    apdu[offset] ^= byte | 1;
+                }
+                _ => apdu.push(0),
+            }
+            apdu
+        })
+}
+
+/// Commands split into a sequence of chained short APDUs fitting in `frame_len` bytes
+///
+/// `frame_len` must be at least 6.
+pub fn chained(
+    max_data: usize,
+    frame_len: usize,
+) -> impl Strategy<Value = (CommandParts, Vec<Vec<u8>>)> {
+    assert!(frame_len >= 6);
+    command(max_data)
+        .prop_filter("chaining the class would make it invalid", |command| {
+            command.class.into_inner() | 0x10 != 0xFF
+        })
+        .prop_map(move |mut command| {
+            // Only the last command of the chain has the chaining bit unset
+            command.class = Class::try_from(command.class.into_inner() & !0x10).unwrap();
+            command.force_extended = false;
+            let le = match command.le {
+                ExpectedLen::Ne(le) => le.min(256),
+                ExpectedLen::Max => 256,
+            };
+            command.le = ExpectedLen::Ne(le);
+            let apdus = CommandBuilder::new_non_extended(
+                command.class,
+                command.instruction,
+                command.p1,
+                command.p2,
+                &command.data,
+                le,
+                Some(frame_len),
+            )
+            .map(|apdu| apdu.serialize_to_vec())
+            .collect();
+            (command, apdus)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{Command, CommandView};
+
+    proptest! {
+        #[test]
+        fn apdu_roundtrip(command in command(300)) {
+            let apdu = command.to_bytes();
+            let view = CommandView::try_from(&*apdu).unwrap();
+            prop_assert_eq!(view.data(), &*command.data);
+            prop_assert_eq!(view.class(), command.class);
+            // A command without data nor expected length has no length field to extend
+            let has_lengths = !command.data.is_empty() || command.le != ExpectedLen::Ne(0);
+            let extended = command.force_extended
+                || command.data.len() > 255
+                || !matches!(command.le, ExpectedLen::Ne(0..=256));
+            prop_assert_eq!(view.extended, extended && has_lengths);
+        }
+
+        #[test]
+        fn near_valid_no_panic(apdu in near_valid_apdu(300)) {
+            let _ = Command::<300>::try_from(&apdu);
+        }
+
+        #[test]
+        fn chained_reassembly((command, apdus) in chained(600, 64)) {
+            let mut reassembled: Option<Command<600>> = None;
+            for apdu in &apdus {
+                prop_assert!(apdu.len() <= 64);
+                let view = CommandView::try_from(&**apdu).unwrap();
+                prop_assert!(!view.extended);
+                match &mut reassembled {
+                    Some(command) => command.extend_from_command_view(view).unwrap(),
+                    None => reassembled = Some(view.to_owned().unwrap()),
+                }
+            }
+            let reassembled = reassembled.unwrap();
+            prop_assert_eq!(reassembled.data().as_slice(), &*command.data);
+        }
+    }
+}
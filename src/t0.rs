@@ -0,0 +1,353 @@
+//! Character transmission protocol T=0 (ISO 7816-3 10, 12.2)
+//!
+//! T=0 only transports TPDUs made of a five bytes header and an optional data field in a single
+//! direction. [`Host`] maps command APDUs onto TPDUs and interprets the procedure bytes sent by
+//! the card:
+//!
+//! - `60` (NULL) asks for more time,
+//! - INS (ACK) requests all remaining data bytes, or announces all the response bytes,
+//! - INS xor `FF` requests or announces the next data byte only,
+//! - `6X` (except `60`) and `9X` are SW1, followed by SW2.
+//!
+//! Response data is retrieved with GET RESPONSE when the card answers `61XX`, up to
+//! [`MAX_GET_RESPONSE`] times, and case 2 commands are sent again with the right length when it
+//! answers `6CXX`.
+//! Extended length APDUs are not supported.
+
+use core::fmt::Debug;
+
+use crate::command::CommandView;
+use crate::transport::{Transport, MAX_GET_RESPONSE};
+
+/// Byte-level link to the card, for example a UART
+pub trait Link {
+    type Error: Debug;
+
+    fn send(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Fill `data` with the next bytes sent by the card
+    fn receive(&mut self, data: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+impl<L: Link + ?Sized> Link for &mut L {
+    type Error = L::Error;
+
+    fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        L::send(self, data)
+    }
+
+    fn receive(&mut self, data: &mut [u8]) -> Result<(), Self::Error> {
+        L::receive(self, data)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    Link(E),
+    /// The command APDU could not be parsed
+    InvalidCommand,
+    /// Extended length APDUs can't be mapped onto TPDUs
+    ExtendedLength,
+    /// The card sent an unexpected procedure byte
+    InvalidProcedureByte(u8),
+    /// The card requested more data bytes than the command has, or sent more than expected
+    UnexpectedTransfer,
+    /// The response does not fit in the buffer
+    BufferFull,
+    /// The card still answered `61XX` after [`MAX_GET_RESPONSE`] GET RESPONSE commands
+    GetResponseLimit,
+}
+
+/// TPDU header for a command APDU, with P3 encoding either Lc or Le
+pub fn header<E>(command: &CommandView<'_>) -> Result<[u8; 5], Error<E>> {
+    if command.extended {
+        return Err(Error::ExtendedLength);
+    }
+    let data = command.data();
+    let p3 = if data.is_empty() {
+        // Case 1 and 2, 256 is encoded as 00
+        command.expected() as u8
+    } else {
+        data.len() as u8
+    };
+    Ok([
+        command.class().into_inner(),
+        command.instruction().into(),
+        command.p1,
+        command.p2,
+        p3,
+    ])
+}
+
+/// Interface device side of the protocol
+pub struct Host<L> {
+    link: L,
+    implicit_get_response: bool,
+}
+
+impl<L> Host<L> {
+    pub fn new(link: L) -> Self {
+        Self {
+            link,
+            implicit_get_response: true,
+        }
+    }
+
+    /// Whether to send GET RESPONSE when a case 4 command succeeds with `9000` (default: `true`).
+    ///
+    /// Since T=0 can't carry data in both directions, some cards expect the interface device to
+    /// fetch the response data of case 4 commands even if they do not answer `61XX`.
+    pub fn implicit_get_response(mut self, enabled: bool) -> Self {
+        self.implicit_get_response = enabled;
+        self
+    }
+
+    pub fn link(&mut self) -> &mut L {
+        &mut self.link
+    }
+
+    pub fn into_inner(self) -> L {
+        self.link
+    }
+}
+
+impl<L: Link> Host<L> {
+    fn receive_byte(&mut self) -> Result<u8, Error<L::Error>> {
+        let mut byte = [0];
+        self.link.receive(&mut byte).map_err(Error::Link)?;
+        Ok(byte[0])
+    }
+
+    /// Transmit one TPDU, returning the length of the response data written to `response` and SW1-SW2
+    pub fn transmit_tpdu(
+        &mut self,
+        header: [u8; 5],
+        data: &[u8],
+        response: &mut [u8],
+    ) -> Result<(usize, [u8; 2]), Error<L::Error>> {
+        let ins = header[1];
+        let expected = match (data.is_empty(), header[4]) {
+            (true, 0) => 256,
+            (true, p3) => usize::from(p3),
+            (false, _) => 0,
+        };
+        self.link.send(&header).map_err(Error::Link)?;
+        let mut sent = 0;
+        let mut received = 0;
+        loop {
+            let procedure = self.receive_byte()?;
+            let all = match procedure {
+                0x60 => continue,
+                0x61..=0x6F | 0x90..=0x9F => {
+                    let sw2 = self.receive_byte()?;
+                    return Ok((received, [procedure, sw2]));
+                }
+                _ if procedure == ins => true,
+                _ if procedure == ins ^ 0xFF => false,
+                _ => return Err(Error::InvalidProcedureByte(procedure)),
+            };
+            if !data.is_empty() {
+                let end = if all { data.len() } else { sent + 1 };
+                let incoming = data
+                    .get(sent..end)
+                    .filter(|incoming| !incoming.is_empty())
+                    .ok_or(Error::UnexpectedTransfer)?;
+                self.link.send(incoming).map_err(Error::Link)?;
+                sent = end;
+            } else {
+                let end = if all { expected } else { received + 1 };
+                if end > expected || end == received {
+                    return Err(Error::UnexpectedTransfer);
+                }
+                let outgoing = response.get_mut(received..end).ok_or(Error::BufferFull)?;
+                self.link.receive(outgoing).map_err(Error::Link)?;
+                received = end;
+            }
+        }
+    }
+
+    /// Exchange a command APDU for a response APDU (data followed by SW1-SW2)
+    pub fn transceive(
+        &mut self,
+        apdu: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, Error<L::Error>> {
+        let command = CommandView::try_from(apdu).map_err(|_| Error::InvalidCommand)?;
+        let mut header = header(&command)?;
+        let data = command.data();
+        let case_4 = !data.is_empty() && command.expected() != 0;
+        // Keep room for SW1-SW2
+        let buffer_len = response.len().checked_sub(2).ok_or(Error::BufferFull)?;
+        let buffer = &mut response[..buffer_len];
+
+        let (mut len, mut sw) = self.transmit_tpdu(header, data, buffer)?;
+        if data.is_empty() && sw[0] == 0x6C {
+            header[4] = sw[1];
+            (len, sw) = self.transmit_tpdu(header, data, buffer)?;
+        }
+        if case_4 && self.implicit_get_response && sw == [0x90, 0x00] {
+            let get_response = [header[0], 0xC0, 0x00, 0x00, command.expected() as u8];
+            let (get_response_len, get_response_sw) =
+                self.transmit_tpdu(get_response, &[], buffer)?;
+            // Otherwise the card has no response data after all
+            if get_response_len != 0 || matches!(get_response_sw[0], 0x61 | 0x90) {
+                (len, sw) = (get_response_len, get_response_sw);
+            }
+        }
+        let mut rounds = 0;
+        while sw[0] == 0x61 {
+            if rounds == MAX_GET_RESPONSE {
+                return Err(Error::GetResponseLimit);
+            }
+            rounds += 1;
+            let get_response = [header[0], 0xC0, 0x00, 0x00, sw[1]];
+            let (get_response_len, get_response_sw) =
+                self.transmit_tpdu(get_response, &[], &mut buffer[len..])?;
+            len += get_response_len;
+            sw = get_response_sw;
+        }
+        response[len..][..2].copy_from_slice(&sw);
+        Ok(len + 2)
+    }
+}
+
+impl<L: Link> Transport for Host<L> {
+    type Error = Error<L::Error>;
+
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Self::Error> {
+        self.transceive(command, response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use std::collections::VecDeque;
+
+    #[derive(Default)]
+    struct Script {
+        card: VecDeque<u8>,
+        sent: Vec<Vec<u8>>,
+    }
+
+    impl Script {
+        fn new(card: &[u8]) -> Self {
+            Self {
+                card: card.iter().copied().collect(),
+                sent: Vec::new(),
+            }
+        }
+    }
+
+    impl Link for Script {
+        type Error = ();
+
+        fn send(&mut self, data: &[u8]) -> Result<(), ()> {
+            self.sent.push(data.to_vec());
+            Ok(())
+        }
+
+        fn receive(&mut self, data: &mut [u8]) -> Result<(), ()> {
+            for byte in data {
+                *byte = self.card.pop_front().ok_or(())?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn case_3_byte_by_byte() {
+        // NULL, one byte, then the remaining bytes
+        let mut host = Host::new(Script::new(&hex!("60 DF 20 9000")));
+        let mut response = [0; 16];
+        let len = host
+            .transceive(&hex!("00200081 03 313233"), &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], &hex!("9000"));
+        assert_eq!(
+            host.into_inner().sent,
+            [
+                hex!("0020008103").to_vec(),
+                hex!("31").to_vec(),
+                hex!("3233").to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn case_2_wrong_length() {
+        let mut host = Host::new(Script::new(&hex!("6C02 CA 0102 9000")));
+        let mut response = [0; 16];
+        let len = host
+            .transceive(&hex!("00CA006E 00"), &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], &hex!("0102 9000"));
+        assert_eq!(
+            host.into_inner().sent,
+            [hex!("00CA006E00").to_vec(), hex!("00CA006E02").to_vec()]
+        );
+    }
+
+    #[test]
+    fn case_4_get_response() {
+        let mut host = Host::new(Script::new(&hex!("A4 6102 C0 0102 9000")));
+        let mut response = [0; 16];
+        let len = host
+            .transceive(&hex!("00A40400 02 A000 00"), &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], &hex!("0102 9000"));
+        assert_eq!(
+            host.into_inner().sent,
+            [
+                hex!("00A4040002").to_vec(),
+                hex!("A000").to_vec(),
+                hex!("00C0000002").to_vec()
+            ]
+        );
+
+        // Implicit GET RESPONSE
+        let mut host = Host::new(Script::new(&hex!("A4 9000 C0 0102 9000")));
+        let len = host
+            .transceive(&hex!("00A40400 02 A000 02"), &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], &hex!("0102 9000"));
+
+        // No data after all
+        let mut host = Host::new(Script::new(&hex!("A4 9000 6A82")));
+        let len = host
+            .transceive(&hex!("00A40400 02 A000 00"), &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], &hex!("9000"));
+    }
+
+    #[test]
+    fn errors() {
+        let mut host = Host::new(Script::new(&hex!("20")));
+        let mut response = [0; 16];
+        assert_eq!(
+            host.transceive(&hex!("00CA006E 00"), &mut response),
+            Err(Error::InvalidProcedureByte(0x20))
+        );
+        assert_eq!(
+            host.transceive(&hex!("00CA006E 000000"), &mut response),
+            Err(Error::ExtendedLength)
+        );
+        assert_eq!(
+            host.transceive(&hex!("00CA006E 00"), &mut [0]),
+            Err(Error::BufferFull)
+        );
+    }
+
+    #[test]
+    fn get_response_limit() {
+        let card = hex!("6100").repeat(MAX_GET_RESPONSE + 2);
+        let mut host = Host::new(Script::new(&card));
+        let mut response = [0; 16];
+        assert_eq!(
+            host.transceive(&hex!("00CA006E 00"), &mut response),
+            Err(Error::GetResponseLimit)
+        );
+        assert_eq!(host.into_inner().sent.len(), MAX_GET_RESPONSE + 1);
+    }
+}
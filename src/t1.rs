@@ -0,0 +1,891 @@
+//! Block transmission protocol T=1 (ISO 7816-3 11)
+//!
+//! [`Block`] encodes and decodes the blocks exchanged over a [`Link`].
+//! [`Host`] runs the interface device side of the protocol and implements [`Transport`],
+//! [`Card`] runs the card side.
+//!
+//! When a card needs more than the block waiting time (BWT) to process a command, it sends a
+//! waiting time extension request S(WTX). The host grants a multiplier of the BWT for the next
+//! block, capped by its [`WtxPolicy`]. On the card side, long-running operations request more time
+//! through the [`WaitingTime`] hook.
+
+use core::fmt::Debug;
+use core::time::Duration;
+
+use crate::atr::T1Parameters;
+use crate::transport::Transport;
+
+/// Maximum size of the information field
+pub const MAX_IFS: u8 = 254;
+
+/// Default size of the information field, for both the card and the interface device
+pub const DEFAULT_IFS: u8 = 32;
+
+/// Maximum size of a block: prologue, information field and CRC
+pub const MAX_BLOCK_LEN: usize = 3 + MAX_IFS as usize + 2;
+
+/// Block waiting time for the default BWI at 372 clock cycles per ETU and 3.57 MHz
+pub const DEFAULT_BWT: Duration = Duration::from_millis(1600);
+
+/// Maximum number of consecutive retransmissions
+const MAX_RETRIES: u8 = 3;
+
+/// Error detection code
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Edc {
+    /// Longitudinal redundancy check, one byte
+    #[default]
+    Lrc,
+    /// Cyclic redundancy check (ISO/IEC 13239), two bytes
+    Crc,
+}
+
+impl Edc {
+    /// Length of the EDC field
+    pub fn size(self) -> usize {
+        match self {
+            Self::Lrc => 1,
+            Self::Crc => 2,
+        }
+    }
+
+    fn compute(self, data: &[u8], out: &mut [u8]) {
+        match self {
+            Self::Lrc => out[0] = data.iter().fold(0, |acc, b| acc ^ b),
+            Self::Crc => out.copy_from_slice(&crc(data).to_be_bytes()),
+        }
+    }
+}
+
+fn crc(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |mut crc, &b| {
+        crc ^= u16::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            };
+        }
+        crc
+    })
+}
+
+/// Error indicated by an R-block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RError {
+    None,
+    /// EDC or parity error
+    Edc,
+    Other,
+}
+
+/// S-block request or response
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SBlock {
+    Resync,
+    /// Information field size
+    Ifs(u8),
+    Abort,
+    /// Waiting time extension multiplier
+    Wtx(u8),
+}
+
+/// T=1 block, without the node address and the EDC
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Block<'a> {
+    /// Information block, `more` indicates chaining
+    I {
+        ns: bool,
+        more: bool,
+        data: &'a [u8],
+    },
+    /// Receive ready block
+    R { nr: bool, error: RError },
+    /// Supervisory block
+    S { response: bool, block: SBlock },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockError {
+    /// The block is shorter than its prologue and EDC
+    Truncated,
+    /// LEN does not match the length of the block, or exceeds [`MAX_IFS`]
+    InvalidLength,
+    InvalidEdc,
+    /// The PCB or the information field of an S-block is invalid
+    InvalidPcb,
+    /// The block does not fit in the output buffer
+    BufferFull,
+}
+
+impl<'a> Block<'a> {
+    /// Parse a block, returning its node address
+    pub fn parse(frame: &'a [u8], edc: Edc) -> Result<(u8, Self), BlockError> {
+        if frame.len() < 3 + edc.size() {
+            return Err(BlockError::Truncated);
+        }
+        let (body, check) = frame.split_at(frame.len() - edc.size());
+        let mut expected = [0; 2];
+        edc.compute(body, &mut expected[..edc.size()]);
+        if check != &expected[..edc.size()] {
+            return Err(BlockError::InvalidEdc);
+        }
+        let [nad, pcb, len] = [body[0], body[1], body[2]];
+        let data = &body[3..];
+        if data.len() != usize::from(len) || len > MAX_IFS {
+            return Err(BlockError::InvalidLength);
+        }
+        let block = match pcb {
+            pcb if pcb & 0x80 == 0 => Block::I {
+                ns: pcb & 0x40 != 0,
+                more: pcb & 0x20 != 0,
+                data,
+            },
+            pcb if pcb & 0xC0 == 0x80 => {
+                let error = match pcb & 0x2F {
+                    0 => RError::None,
+                    1 => RError::Edc,
+                    2 => RError::Other,
+                    _ => return Err(BlockError::InvalidPcb),
+                };
+                if !data.is_empty() {
+                    return Err(BlockError::InvalidLength);
+                }
+                Block::R {
+                    nr: pcb & 0x10 != 0,
+                    error,
+                }
+            }
+            pcb => {
+                let block = match (pcb & 0x1F, data) {
+                    (0, []) => SBlock::Resync,
+                    (1, &[ifs]) => SBlock::Ifs(ifs),
+                    (2, []) => SBlock::Abort,
+                    (3, &[multiplier]) => SBlock::Wtx(multiplier),
+                    _ => return Err(BlockError::InvalidPcb),
+                };
+                Block::S {
+                    response: pcb & 0x20 != 0,
+                    block,
+                }
+            }
+        };
+        Ok((nad, block))
+    }
+
+    /// Serialize the block to `out`, returning the length of the frame
+    pub fn serialize(&self, nad: u8, edc: Edc, out: &mut [u8]) -> Result<usize, BlockError> {
+        let mut inf = [0; 1];
+        let (pcb, data): (u8, &[u8]) = match *self {
+            Block::I { ns, more, data } => ((ns as u8) << 6 | (more as u8) << 5, data),
+            Block::R { nr, error } => {
+                let error = match error {
+                    RError::None => 0,
+                    RError::Edc => 1,
+                    RError::Other => 2,
+                };
+                (0x80 | (nr as u8) << 4 | error, &[])
+            }
+            Block::S { response, block } => {
+                let (kind, value) = match block {
+                    SBlock::Resync => (0, None),
+                    SBlock::Ifs(ifs) => (1, Some(ifs)),
+                    SBlock::Abort => (2, None),
+                    SBlock::Wtx(multiplier) => (3, Some(multiplier)),
+                };
+                let data = match value {
+                    Some(value) => {
+                        inf[0] = value;
+                        &inf[..]
+                    }
+                    None => &[],
+                };
+                (0xC0 | (response as u8) << 5 | kind, data)
+            }
+        };
+        let len = u8::try_from(data.len())
+            .ok()
+            .filter(|&len| len <= MAX_IFS)
+            .ok_or(BlockError::InvalidLength)?;
+        let total = 3 + data.len() + edc.size();
+        let out = out.get_mut(..total).ok_or(BlockError::BufferFull)?;
+        out[..3].copy_from_slice(&[nad, pcb, len]);
+        out[3..][..data.len()].copy_from_slice(data);
+        let (body, check) = out.split_at_mut(total - edc.size());
+        edc.compute(body, check);
+        Ok(total)
+    }
+}
+
+/// Block-level link to the other party, for example a UART or a CCID reader in TPDU mode
+pub trait Link {
+    type Error: Debug;
+
+    fn send(&mut self, frame: &[u8]) -> Result<(), Self::Error>;
+
+    /// Receive one block, returning its length. `None` waits indefinitely.
+    fn receive(
+        &mut self,
+        frame: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<usize, Self::Error>;
+}
+
+impl<L: Link + ?Sized> Link for &mut L {
+    type Error = L::Error;
+
+    fn send(&mut self, frame: &[u8]) -> Result<(), Self::Error> {
+        L::send(self, frame)
+    }
+
+    fn receive(
+        &mut self,
+        frame: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<usize, Self::Error> {
+        L::receive(self, frame, timeout)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    Link(E),
+    Block(BlockError),
+    /// The other party sent an unexpected block
+    Protocol,
+    /// The other party aborted the chain
+    Aborted,
+    /// Too many consecutive transmission errors
+    RetriesExceeded,
+    /// The received APDU does not fit in the buffer
+    BufferFull,
+}
+
+impl<E> From<BlockError> for Error<E> {
+    fn from(error: BlockError) -> Self {
+        Self::Block(error)
+    }
+}
+
+/// Host policy for waiting time extensions requested by the card
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WtxPolicy {
+    /// Maximum multiplier of the BWT granted to the card
+    pub max_multiplier: u8,
+}
+
+impl Default for WtxPolicy {
+    /// Grant all requests
+    fn default() -> Self {
+        Self {
+            max_multiplier: u8::MAX,
+        }
+    }
+}
+
+impl WtxPolicy {
+    /// Multiplier granted for the requested one
+    pub fn grant(&self, requested: u8) -> u8 {
+        requested.min(self.max_multiplier).max(1)
+    }
+}
+
+/// Interface device side of the protocol
+pub struct Host<L> {
+    link: L,
+    nad: u8,
+    edc: Edc,
+    ifsc: u8,
+    bwt: Duration,
+    wtx_policy: WtxPolicy,
+    ns: bool,
+    nr: bool,
+}
+
+impl<L> Host<L> {
+    pub fn new(link: L) -> Self {
+        Self {
+            link,
+            nad: 0,
+            edc: Edc::Lrc,
+            ifsc: DEFAULT_IFS,
+            bwt: DEFAULT_BWT,
+            wtx_policy: WtxPolicy::default(),
+            ns: false,
+            nr: false,
+        }
+    }
+
+    /// Use the IFSC and EDC indicated in the ATR
+    pub fn parameters(mut self, parameters: &T1Parameters) -> Self {
+        self.ifsc = parameters.ifsc.clamp(1, MAX_IFS);
+        self.edc = if parameters.crc { Edc::Crc } else { Edc::Lrc };
+        self
+    }
+
+    pub fn nad(mut self, nad: u8) -> Self {
+        self.nad = nad;
+        self
+    }
+
+    pub fn bwt(mut self, bwt: Duration) -> Self {
+        self.bwt = bwt;
+        self
+    }
+
+    pub fn wtx_policy(mut self, policy: WtxPolicy) -> Self {
+        self.wtx_policy = policy;
+        self
+    }
+
+    pub fn link(&mut self) -> &mut L {
+        &mut self.link
+    }
+
+    pub fn into_inner(self) -> L {
+        self.link
+    }
+}
+
+impl<L: Link> Host<L> {
+    /// Send `frame` and receive the reply of the card.
+    ///
+    /// S(WTX) and S(IFS) requests are answered transparently. If the reply is an R-block
+    /// requesting the retransmission of the I-block with sequence number `sent_ns`, the frame is
+    /// sent again.
+    fn exchange<'b>(
+        &mut self,
+        frame: &[u8],
+        buffer: &'b mut [u8; MAX_BLOCK_LEN],
+        sent_ns: Option<bool>,
+    ) -> Result<Block<'b>, Error<L::Error>> {
+        self.link.send(frame).map_err(Error::Link)?;
+        let mut multiplier = 1;
+        let mut errors = 0;
+        let len = loop {
+            let timeout = self.bwt * u32::from(multiplier);
+            let len = self
+                .link
+                .receive(buffer, Some(timeout))
+                .map_err(Error::Link)?;
+            multiplier = 1;
+            let mut reply = [0; 8];
+            let reply_len = match Block::parse(&buffer[..len], self.edc) {
+                Ok((
+                    _,
+                    Block::S {
+                        response: false,
+                        block: SBlock::Wtx(requested),
+                    },
+                )) => {
+                    multiplier = self.wtx_policy.grant(requested);
+                    Block::S {
+                        response: true,
+                        block: SBlock::Wtx(multiplier),
+                    }
+                    .serialize(self.nad, self.edc, &mut reply)?
+                }
+                Ok((
+                    _,
+                    Block::S {
+                        response: false,
+                        block: SBlock::Ifs(ifs),
+                    },
+                )) => {
+                    self.ifsc = ifs.clamp(1, MAX_IFS);
+                    Block::S {
+                        response: true,
+                        block: SBlock::Ifs(ifs),
+                    }
+                    .serialize(self.nad, self.edc, &mut reply)?
+                }
+                Ok((
+                    _,
+                    Block::S {
+                        response: false,
+                        block: SBlock::Abort,
+                    },
+                )) => return Err(Error::Aborted),
+                Ok((_, Block::R { nr, .. })) if Some(nr) == sent_ns => {
+                    errors += 1;
+                    if errors > MAX_RETRIES {
+                        return Err(Error::RetriesExceeded);
+                    }
+                    self.link.send(frame).map_err(Error::Link)?;
+                    continue;
+                }
+                Ok(_) => break len,
+                Err(error) => {
+                    errors += 1;
+                    if errors > MAX_RETRIES {
+                        return Err(error.into());
+                    }
+                    let error = match error {
+                        BlockError::InvalidEdc => RError::Edc,
+                        _ => RError::Other,
+                    };
+                    Block::R { nr: self.nr, error }.serialize(self.nad, self.edc, &mut reply)?
+                }
+            };
+            self.link.send(&reply[..reply_len]).map_err(Error::Link)?;
+        };
+        Ok(Block::parse(&buffer[..len], self.edc)?.1)
+    }
+
+    /// Exchange a command APDU for a response APDU, chaining in both directions as needed
+    pub fn transceive(
+        &mut self,
+        apdu: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, Error<L::Error>> {
+        let mut frame = [0; MAX_BLOCK_LEN];
+        let mut buffer = [0; MAX_BLOCK_LEN];
+        let mut offset = 0;
+        let mut block = loop {
+            let end = apdu.len().min(offset + usize::from(self.ifsc));
+            let more = end < apdu.len();
+            let len = Block::I {
+                ns: self.ns,
+                more,
+                data: &apdu[offset..end],
+            }
+            .serialize(self.nad, self.edc, &mut frame)?;
+            let sent_ns = self.ns;
+            let block = self.exchange(&frame[..len], &mut buffer, Some(sent_ns))?;
+            self.ns = !self.ns;
+            if !more {
+                break block;
+            }
+            match block {
+                Block::R { nr, .. } if nr == self.ns => offset = end,
+                _ => return Err(Error::Protocol),
+            }
+        };
+
+        let mut written = 0;
+        loop {
+            match block {
+                Block::I { ns, more, data } if ns == self.nr => {
+                    response
+                        .get_mut(written..written + data.len())
+                        .ok_or(Error::BufferFull)?
+                        .copy_from_slice(data);
+                    written += data.len();
+                    self.nr = !self.nr;
+                    if !more {
+                        return Ok(written);
+                    }
+                }
+                _ => return Err(Error::Protocol),
+            }
+            let len = Block::R {
+                nr: self.nr,
+                error: RError::None,
+            }
+            .serialize(self.nad, self.edc, &mut frame)?;
+            block = self.exchange(&frame[..len], &mut buffer, None)?;
+        }
+    }
+}
+
+impl<L: Link> Transport for Host<L> {
+    type Error = Error<L::Error>;
+
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Self::Error> {
+        self.transceive(command, response)
+    }
+}
+
+/// Hook for card-side operations that take longer than the block waiting time
+pub trait WaitingTime {
+    type Error: Debug;
+
+    /// Request `multiplier` times the BWT for the next block, returning the multiplier granted by the host
+    fn request_time(&mut self, multiplier: u8) -> Result<u8, Self::Error>;
+}
+
+impl<W: WaitingTime + ?Sized> WaitingTime for &mut W {
+    type Error = W::Error;
+
+    fn request_time(&mut self, multiplier: u8) -> Result<u8, Self::Error> {
+        W::request_time(self, multiplier)
+    }
+}
+
+/// Card side of the protocol
+pub struct Card<L> {
+    link: L,
+    nad: u8,
+    edc: Edc,
+    ifsd: u8,
+    ns: bool,
+    nr: bool,
+    last: heapless::Vec<u8, MAX_BLOCK_LEN>,
+}
+
+impl<L> Card<L> {
+    pub fn new(link: L, edc: Edc) -> Self {
+        Self {
+            link,
+            nad: 0,
+            edc,
+            ifsd: DEFAULT_IFS,
+            ns: false,
+            nr: false,
+            last: heapless::Vec::new(),
+        }
+    }
+
+    /// Information field size of the interface device, as negotiated with S(IFS)
+    pub fn ifsd(&self) -> u8 {
+        self.ifsd
+    }
+
+    pub fn link(&mut self) -> &mut L {
+        &mut self.link
+    }
+
+    pub fn into_inner(self) -> L {
+        self.link
+    }
+}
+
+impl<L: Link> Card<L> {
+    fn send(&mut self, block: Block<'_>) -> Result<(), Error<L::Error>> {
+        let mut frame = [0; MAX_BLOCK_LEN];
+        let len = block.serialize(self.nad, self.edc, &mut frame)?;
+        self.link.send(&frame[..len]).map_err(Error::Link)?;
+        if let Block::I { .. } = block {
+            self.last.clear();
+            self.last.extend_from_slice(&frame[..len]).ok();
+        }
+        Ok(())
+    }
+
+    fn resend_last(&mut self) -> Result<(), Error<L::Error>> {
+        self.link.send(&self.last).map_err(Error::Link)
+    }
+
+    /// Receive the next command APDU, acknowledging chained blocks and answering S-block requests
+    pub fn receive_command(&mut self, apdu: &mut [u8]) -> Result<usize, Error<L::Error>> {
+        let mut buffer = [0; MAX_BLOCK_LEN];
+        let mut written = 0;
+        let mut errors = 0;
+        loop {
+            let len = self.link.receive(&mut buffer, None).map_err(Error::Link)?;
+            let block = match Block::parse(&buffer[..len], self.edc) {
+                Ok((_, block)) => block,
+                Err(error) => {
+                    errors += 1;
+                    if errors > MAX_RETRIES {
+                        return Err(error.into());
+                    }
+                    let error = match error {
+                        BlockError::InvalidEdc => RError::Edc,
+                        _ => RError::Other,
+                    };
+                    self.send(Block::R { nr: self.nr, error })?;
+                    continue;
+                }
+            };
+            errors = 0;
+            match block {
+                Block::I { ns, more, data } if ns == self.nr => {
+                    apdu.get_mut(written..written + data.len())
+                        .ok_or(Error::BufferFull)?
+                        .copy_from_slice(data);
+                    written += data.len();
+                    self.nr = !self.nr;
+                    if !more {
+                        return Ok(written);
+                    }
+                    self.send(Block::R {
+                        nr: self.nr,
+                        error: RError::None,
+                    })?;
+                }
+                // The host did not receive the last block of the previous response
+                Block::R { nr, .. } if nr != self.ns && !self.last.is_empty() => {
+                    self.resend_last()?
+                }
+                Block::S {
+                    response: false,
+                    block: SBlock::Ifs(ifs),
+                } => {
+                    self.ifsd = ifs.clamp(1, MAX_IFS);
+                    self.send(Block::S {
+                        response: true,
+                        block: SBlock::Ifs(ifs),
+                    })?;
+                }
+                Block::S {
+                    response: false,
+                    block: SBlock::Resync,
+                } => {
+                    self.ns = false;
+                    self.nr = false;
+                    written = 0;
+                    self.send(Block::S {
+                        response: true,
+                        block: SBlock::Resync,
+                    })?;
+                }
+                Block::S {
+                    response: false,
+                    block: SBlock::Abort,
+                } => return Err(Error::Aborted),
+                _ => return Err(Error::Protocol),
+            }
+        }
+    }
+
+    /// Send the response APDU, chaining it according to the IFSD
+    pub fn send_response(&mut self, response: &[u8]) -> Result<(), Error<L::Error>> {
+        let mut buffer = [0; MAX_BLOCK_LEN];
+        let mut offset = 0;
+        loop {
+            let end = response.len().min(offset + usize::from(self.ifsd));
+            let more = end < response.len();
+            self.send(Block::I {
+                ns: self.ns,
+                more,
+                data: &response[offset..end],
+            })?;
+            self.ns = !self.ns;
+            if !more {
+                return Ok(());
+            }
+            let mut errors = 0;
+            loop {
+                let len = self.link.receive(&mut buffer, None).map_err(Error::Link)?;
+                match Block::parse(&buffer[..len], self.edc) {
+                    Ok((_, Block::R { nr, .. })) if nr == self.ns => break,
+                    Ok((_, Block::R { .. })) | Err(_) if errors < MAX_RETRIES => {
+                        errors += 1;
+                        self.resend_last()?;
+                    }
+                    Ok((
+                        _,
+                        Block::S {
+                            response: false,
+                            block: SBlock::Abort,
+                        },
+                    )) => return Err(Error::Aborted),
+                    Ok(_) => return Err(Error::Protocol),
+                    Err(_) => return Err(Error::RetriesExceeded),
+                }
+            }
+            offset = end;
+        }
+    }
+}
+
+impl<L: Link> WaitingTime for Card<L> {
+    type Error = Error<L::Error>;
+
+    fn request_time(&mut self, multiplier: u8) -> Result<u8, Self::Error> {
+        let mut buffer = [0; MAX_BLOCK_LEN];
+        self.send(Block::S {
+            response: false,
+            block: SBlock::Wtx(multiplier),
+        })?;
+        let len = self.link.receive(&mut buffer, None).map_err(Error::Link)?;
+        match Block::parse(&buffer[..len], self.edc)?.1 {
+            Block::S {
+                response: true,
+                block: SBlock::Wtx(granted),
+            } => Ok(granted),
+            _ => Err(Error::Protocol),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use std::collections::VecDeque;
+
+    /// Link replaying scripted frames
+    #[derive(Default)]
+    struct Script {
+        received: VecDeque<Vec<u8>>,
+        sent: Vec<Vec<u8>>,
+        timeouts: Vec<Option<Duration>>,
+    }
+
+    impl Script {
+        fn new(blocks: &[Block<'_>]) -> Self {
+            Self {
+                received: blocks.iter().map(frame).collect(),
+                ..Default::default()
+            }
+        }
+    }
+
+    fn frame(block: &Block<'_>) -> Vec<u8> {
+        let mut frame = [0; MAX_BLOCK_LEN];
+        let len = block.serialize(0, Edc::Lrc, &mut frame).unwrap();
+        frame[..len].to_vec()
+    }
+
+    impl Link for Script {
+        type Error = ();
+
+        fn send(&mut self, frame: &[u8]) -> Result<(), ()> {
+            self.sent.push(frame.to_vec());
+            Ok(())
+        }
+
+        fn receive(&mut self, frame: &mut [u8], timeout: Option<Duration>) -> Result<usize, ()> {
+            self.timeouts.push(timeout);
+            let next = self.received.pop_front().ok_or(())?;
+            frame[..next.len()].copy_from_slice(&next);
+            Ok(next.len())
+        }
+    }
+
+    #[test]
+    fn blocks() {
+        let mut frame = [0; MAX_BLOCK_LEN];
+        let block = Block::I {
+            ns: false,
+            more: false,
+            data: &hex!("00A40400"),
+        };
+        let len = block.serialize(0, Edc::Lrc, &mut frame).unwrap();
+        assert_eq!(&frame[..len], &hex!("00 00 04 00A40400 A4"));
+        assert_eq!(Block::parse(&frame[..len], Edc::Lrc), Ok((0, block)));
+
+        let wtx = Block::S {
+            response: false,
+            block: SBlock::Wtx(1),
+        };
+        let len = wtx.serialize(0, Edc::Lrc, &mut frame).unwrap();
+        assert_eq!(&frame[..len], &hex!("00 C3 01 01 C3"));
+
+        let len = wtx.serialize(0, Edc::Crc, &mut frame).unwrap();
+        assert_eq!(Block::parse(&frame[..len], Edc::Crc), Ok((0, wtx)));
+        frame[3] ^= 1;
+        assert_eq!(
+            Block::parse(&frame[..len], Edc::Crc),
+            Err(BlockError::InvalidEdc)
+        );
+        assert_eq!(crc(b"123456789"), 0x6F91);
+    }
+
+    #[test]
+    fn host_wtx() {
+        let script = Script::new(&[
+            Block::S {
+                response: false,
+                block: SBlock::Wtx(5),
+            },
+            Block::I {
+                ns: false,
+                more: false,
+                data: &hex!("9000"),
+            },
+        ]);
+        let mut host = Host::new(script).wtx_policy(WtxPolicy { max_multiplier: 2 });
+        let mut response = [0; 16];
+        let len = host.transceive(&hex!("00A40400"), &mut response).unwrap();
+        assert_eq!(&response[..len], &hex!("9000"));
+        let script = host.into_inner();
+        assert_eq!(
+            script.sent,
+            [
+                hex!("00 00 04 00A40400 A4").to_vec(),
+                frame(&Block::S {
+                    response: true,
+                    block: SBlock::Wtx(2)
+                })
+            ]
+        );
+        assert_eq!(script.timeouts, [Some(DEFAULT_BWT), Some(DEFAULT_BWT * 2)]);
+    }
+
+    #[test]
+    fn host_chaining() {
+        let script = Script::new(&[
+            Block::R {
+                nr: true,
+                error: RError::None,
+            },
+            Block::I {
+                ns: false,
+                more: true,
+                data: &hex!("0102"),
+            },
+            Block::I {
+                ns: true,
+                more: false,
+                data: &hex!("9000"),
+            },
+        ]);
+        let mut host = Host::new(script).parameters(&T1Parameters {
+            ifsc: 3,
+            ..Default::default()
+        });
+        let mut response = [0; 16];
+        let len = host
+            .transceive(&hex!("00CA0000 01 00"), &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], &hex!("0102 9000"));
+        assert_eq!(
+            host.into_inner().sent,
+            [
+                frame(&Block::I {
+                    ns: false,
+                    more: true,
+                    data: &hex!("00CA00")
+                }),
+                frame(&Block::I {
+                    ns: true,
+                    more: false,
+                    data: &hex!("00 01 00")
+                }),
+                frame(&Block::R {
+                    nr: true,
+                    error: RError::None
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn card_wtx() {
+        let script = Script::new(&[
+            Block::I {
+                ns: false,
+                more: false,
+                data: &hex!("002A9E9A 01 00"),
+            },
+            Block::S {
+                response: true,
+                block: SBlock::Wtx(3),
+            },
+        ]);
+        let mut card = Card::new(script, Edc::Lrc);
+        let mut command = [0; 16];
+        let len = card.receive_command(&mut command).unwrap();
+        assert_eq!(&command[..len], &hex!("002A9E9A 01 00"));
+        assert_eq!(card.request_time(3), Ok(3));
+        card.send_response(&hex!("9000")).unwrap();
+        assert_eq!(
+            card.into_inner().sent,
+            [
+                frame(&Block::S {
+                    response: false,
+                    block: SBlock::Wtx(3)
+                }),
+                frame(&Block::I {
+                    ns: false,
+                    more: false,
+                    data: &hex!("9000")
+                }),
+            ]
+        );
+    }
+}
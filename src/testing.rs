@@ -0,0 +1,837 @@
+//! Fakes for testing code built on this crate
+//!
+//! [`WriteMock`] is a [`Writer`] with a configurable capacity, to exercise the buffer handling of
+//! serializers, and [`ScriptedTransport`] is a [`Transport`] answering a scripted sequence of
+//! commands with canned responses:
+//!
+//! ```
+//! use iso7816::command::CommandBuilder;
+//! use iso7816::testing::ScriptedTransport;
+//! use iso7816::transport::Channel;
+//! use iso7816::{Data, Status};
+//!
+//! let transport = ScriptedTransport::new()
+//!     .expect(&[0x00, 0xCA, 0x00, 0x5A, 0x00], &[0x01, 0x02, 0x61, 0x01])
+//!     .expect(&[0x00, 0xC0, 0x00, 0x00, 0x01], &[0x03, 0x90, 0x00]);
+//! let mut channel = Channel::<_, 300>::new(transport);
+//! let get_data = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0x00, 0x5A, &[0u8; 0][..], 256);
+//! let mut response = Data::<16>::new();
+//! assert_eq!(channel.exchange(get_data, &mut response), Ok(Status::Success));
+//! assert_eq!(response, [0x01, 0x02, 0x03]);
+//! channel.transport().finish();
+//! ```
+//!
+//! [`assert_roundtrip`] and [`assert_parse_eq`] check the encoding of commands, reporting
+//! mismatches with a hex dump of both encodings.
+//!
+//! [`MockCard`] answers the commands matching the patterns it is programmed with, and records
+//! the commands it receives. [`FragmentingCard`] answers with its payloads split over `61XX`
+//! responses and corrects the expected length with `6CXX`, to exercise the host-side response
+//! reassembly.
+//!
+//! These types panic when they are misused, with a message rendering the APDUs involved, so that
+//! failures are reported at the faulty exchange.
+//!
+//! The [`conformance`] module provides encoding test vectors.
+//!
+//! This module requires the `test-utils` feature.
+
+pub mod conformance;
+
+use core::convert::Infallible;
+use core::fmt::{self, Display, Formatter, Write as _};
+use core::ops::Deref;
+use std::collections::VecDeque;
+use std::string::String;
+use std::vec::Vec;
+
+use crate::command::select::{Select, SelectTarget};
+use crate::command::writer::{BufferFull, Writer};
+use crate::command::{CommandBuilder, CommandView, DataStream, ExpectedLen};
+use crate::trace::{self, Redaction};
+use crate::transport::Transport;
+use crate::{Aid, App, Status};
+
+/// [`Writer`] accepting at most `capacity` bytes
+///
+/// Dereferences to the data written so far.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WriteMock {
+    buffer: Vec<u8>,
+    capacity: usize,
+}
+
+impl WriteMock {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Discard the data written so far, keeping the capacity
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+impl Deref for WriteMock {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl Writer for WriteMock {
+    type Error = BufferFull;
+    fn write(&mut self, data: &[u8]) -> Result<usize, BufferFull> {
+        let written = self.remaining_len().min(data.len());
+        if written == 0 && !data.is_empty() {
+            return Err(BufferFull::BufferFull);
+        }
+        self.buffer.extend_from_slice(&data[..written]);
+        Ok(written)
+    }
+    fn remaining_len(&self) -> usize {
+        self.capacity - self.buffer.len()
+    }
+}
+
+/// [`Transport`] expecting a scripted sequence of commands and answering each with its response
+///
+/// Panics if a command doesn't match the script, or if the script is exhausted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScriptedTransport {
+    exchanges: VecDeque<(Vec<u8>, Vec<u8>)>,
+    transmitted: usize,
+}
+
+impl ScriptedTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect `command` next, and answer with the response APDU `response`
+    pub fn expect(mut self, command: &[u8], response: &[u8]) -> Self {
+        self.push(command, response);
+        self
+    }
+
+    /// Same as [`expect`](Self::expect), by reference
+    pub fn push(&mut self, command: &[u8], response: &[u8]) {
+        self.exchanges.push_back((command.into(), response.into()));
+    }
+
+    /// Number of commands received so far
+    pub fn transmitted(&self) -> usize {
+        self.transmitted
+    }
+
+    /// Number of exchanges left in the script
+    pub fn remaining(&self) -> usize {
+        self.exchanges.len()
+    }
+
+    /// Panics if some scripted exchanges did not happen
+    #[track_caller]
+    pub fn finish(&self) {
+        if let Some((command, _)) = self.exchanges.front() {
+            panic!(
+                "{} scripted exchanges did not happen, the next one expected\n{}",
+                self.exchanges.len(),
+                trace::command(command, Redaction::NONE)
+            );
+        }
+    }
+}
+
+impl Transport for ScriptedTransport {
+    type Error = Infallible;
+
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Infallible> {
+        let Some((expected, answer)) = self.exchanges.pop_front() else {
+            panic!(
+                "unexpected command after the end of the script\n{}",
+                trace::command(command, Redaction::NONE)
+            );
+        };
+        if command != expected {
+            panic!(
+                "command {} does not match the script\nexpected: {}\n     got: {}",
+                self.transmitted,
+                trace::command(&expected, Redaction::NONE),
+                trace::command(command, Redaction::NONE)
+            );
+        }
+        assert!(
+            answer.len() <= response.len(),
+            "response buffer too small for the scripted response {}",
+            trace::response(&answer)
+        );
+        self.transmitted += 1;
+        response[..answer.len()].copy_from_slice(&answer);
+        Ok(answer.len())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+enum DataPattern {
+    #[default]
+    Any,
+    Exact(Vec<u8>),
+    Prefix(Vec<u8>),
+}
+
+/// Pattern of the commands a [`MockCard`] rule applies to
+///
+/// The fields that are not set match any value:
+///
+/// ```
+/// use iso7816::command::CommandView;
+/// use iso7816::testing::Matcher;
+/// let get_data = Matcher::any().ins(0xCA).data_prefix(&[0x5C]);
+/// let command = [0x00, 0xCA, 0x3F, 0xFF, 0x02, 0x5C, 0x00];
+/// assert!(get_data.matches(&CommandView::try_from(&command[..]).unwrap()));
+/// let command = [0x00, 0xCA, 0x00, 0x5A, 0x00];
+/// assert!(!get_data.matches(&CommandView::try_from(&command[..]).unwrap()));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Matcher {
+    cla: Option<u8>,
+    ins: Option<u8>,
+    p1: Option<u8>,
+    p2: Option<u8>,
+    data: DataPattern,
+}
+
+impl Matcher {
+    /// Match all commands
+    pub fn any() -> Self {
+        Self::default()
+    }
+
+    pub fn cla(mut self, cla: u8) -> Self {
+        self.cla = Some(cla);
+        self
+    }
+
+    pub fn ins(mut self, ins: u8) -> Self {
+        self.ins = Some(ins);
+        self
+    }
+
+    pub fn p1(mut self, p1: u8) -> Self {
+        self.p1 = Some(p1);
+        self
+    }
+
+    pub fn p2(mut self, p2: u8) -> Self {
+        self.p2 = Some(p2);
+        self
+    }
+
+    /// Match commands whose data field is exactly `data`
+    pub fn data(mut self, data: &[u8]) -> Self {
+        self.data = DataPattern::Exact(data.into());
+        self
+    }
+
+    /// Match commands whose data field starts with `prefix`
+    pub fn data_prefix(mut self, prefix: &[u8]) -> Self {
+        self.data = DataPattern::Prefix(prefix.into());
+        self
+    }
+
+    pub fn matches(&self, command: &CommandView<'_>) -> bool {
+        let data = match &self.data {
+            DataPattern::Any => true,
+            DataPattern::Exact(data) => command.data() == data,
+            DataPattern::Prefix(prefix) => command.data().starts_with(prefix),
+        };
+        let header = [
+            (self.cla, command.class().into_inner()),
+            (self.ins, command.instruction().into()),
+            (self.p1, command.p1),
+            (self.p2, command.p2),
+        ];
+        data && header
+            .iter()
+            .all(|(pattern, value)| pattern.iter().all(|pattern| pattern == value))
+    }
+}
+
+/// Card answering commands according to rules, and recording the commands it receives
+///
+/// The response of the first rule matching the command is returned. Commands matching no rule
+/// are answered with `9000` if they select the [`aid`](App::aid) of the card, and with the
+/// fallback status otherwise, which defaults to [`Status::InstructionNotSupportedOrInvalid`].
+/// Invalid command APDUs are answered with [`Status::WrongLength`].
+///
+/// ```
+/// use iso7816::command::{CommandBuilder, CommandView};
+/// use iso7816::testing::{Matcher, MockCard};
+/// use iso7816::transport::Channel;
+/// use iso7816::{Aid, Data, Status};
+///
+/// const PIV: Aid = Aid::new_truncatable(&[0xA0, 0x00, 0x00, 0x03, 0x08, 0x00, 0x00, 0x10], 5);
+/// let card = MockCard::new(PIV).on(Matcher::any().ins(0x20).data(b"123456"), &[0x90, 0x00]);
+/// let mut channel = Channel::<_, 300>::new(card);
+/// let mut response = Data::<16>::new();
+/// let class = 0.try_into().unwrap();
+///
+/// let select = CommandBuilder::new(class, 0xA4.into(), 0x04, 0x00, PIV.truncated(), 256);
+/// assert_eq!(channel.exchange(select, &mut response), Ok(Status::Success));
+/// let verify = CommandBuilder::new(class, 0x20.into(), 0x00, 0x80, &b"654321"[..], 0);
+/// let status = channel.exchange(verify, &mut response);
+/// assert_eq!(status, Ok(Status::InstructionNotSupportedOrInvalid));
+///
+/// let received = channel.transport().received();
+/// assert_eq!(received.len(), 2);
+/// assert_eq!(CommandView::try_from(&*received[1]).unwrap().data(), b"654321");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockCard {
+    aid: Aid,
+    rules: Vec<(Matcher, Vec<u8>)>,
+    fallback: Status,
+    received: Vec<Vec<u8>>,
+}
+
+impl MockCard {
+    pub fn new(aid: Aid) -> Self {
+        Self {
+            aid,
+            rules: Vec::new(),
+            fallback: Status::InstructionNotSupportedOrInvalid,
+            received: Vec::new(),
+        }
+    }
+
+    /// Answer the commands matching `matcher` with the response APDU `response`
+    pub fn on(mut self, matcher: Matcher, response: &[u8]) -> Self {
+        self.push(matcher, response);
+        self
+    }
+
+    /// Same as [`on`](Self::on), by reference
+    pub fn push(&mut self, matcher: Matcher, response: &[u8]) {
+        assert!(response.len() >= 2, "response APDUs end with a status word");
+        self.rules.push((matcher, response.into()));
+    }
+
+    /// Status of the commands matching no rule
+    pub fn fallback(mut self, status: Status) -> Self {
+        self.fallback = status;
+        self
+    }
+
+    /// Command APDUs received so far
+    pub fn received(&self) -> &[Vec<u8>] {
+        &self.received
+    }
+
+    pub fn clear_received(&mut self) {
+        self.received.clear();
+    }
+
+    /// Record `command` and return the response APDU
+    pub fn respond(&mut self, command: &[u8]) -> Vec<u8> {
+        self.received.push(command.into());
+        let Ok(view) = CommandView::try_from(command) else {
+            return <[u8; 2]>::from(Status::WrongLength).into();
+        };
+        if let Some((_, response)) = self.rules.iter().find(|(m, _)| m.matches(&view)) {
+            return response.clone();
+        }
+        let status = match Select::try_from(&view).map(Select::into_target) {
+            Ok(SelectTarget::DfName(name)) if self.aid.matches(name) => Status::Success,
+            _ => self.fallback,
+        };
+        <[u8; 2]>::from(status).into()
+    }
+}
+
+impl App for MockCard {
+    fn aid(&self) -> Aid {
+        self.aid
+    }
+}
+
+impl Transport for MockCard {
+    type Error = Infallible;
+
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Infallible> {
+        let answer = self.respond(command);
+        assert!(
+            answer.len() <= response.len(),
+            "response buffer too small for the response {}",
+            trace::response(&answer)
+        );
+        response[..answer.len()].copy_from_slice(&answer);
+        Ok(answer.len())
+    }
+}
+
+/// Card answering with its payloads in fragments, collected with GET RESPONSE
+///
+/// Each response carries at most `fragment_len` bytes of the payload, and announces the rest with
+/// `61XX`. With [`wrong_le`](Self::wrong_le), commands whose `Le` is not the exact length of a
+/// payload of up to 256 bytes are first answered with `6CXX`. Commands matching no payload are
+/// answered like [`MockCard`] does without rules, and GET RESPONSE without pending data with
+/// [`Status::ConditionsOfUseNotSatisfied`].
+///
+/// ```
+/// use iso7816::command::CommandBuilder;
+/// use iso7816::testing::{FragmentingCard, Matcher};
+/// use iso7816::transport::Channel;
+/// use iso7816::{Aid, Data, Status};
+///
+/// let card = FragmentingCard::new(Aid::new(&[0xA0, 0x00, 0x00, 0x03, 0x08]), 8)
+///     .on(Matcher::any().ins(0xCA), &[0x42; 20])
+///     .wrong_le(true);
+/// let mut channel = Channel::<_, 300>::new(card);
+/// let get_data = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0x00, 0x7F, &[0u8; 0][..], 256);
+/// let mut response = Data::<32>::new();
+/// assert_eq!(channel.exchange(get_data, &mut response), Ok(Status::Success));
+/// assert_eq!(response, [0x42; 20]);
+/// // 6C14, then 8 bytes and 610C, 8 bytes and 6104, and the last 4 bytes with 9000
+/// assert_eq!(channel.transport().received().len(), 4);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FragmentingCard {
+    aid: Aid,
+    fragment_len: usize,
+    wrong_le: bool,
+    payloads: Vec<(Matcher, Vec<u8>)>,
+    pending: VecDeque<u8>,
+    received: Vec<Vec<u8>>,
+}
+
+impl FragmentingCard {
+    /// `fragment_len` must not be 0
+    pub fn new(aid: Aid, fragment_len: usize) -> Self {
+        assert!(fragment_len > 0, "fragments must carry data");
+        Self {
+            aid,
+            fragment_len,
+            wrong_le: false,
+            payloads: Vec::new(),
+            pending: VecDeque::new(),
+            received: Vec::new(),
+        }
+    }
+
+    /// Answer the commands matching `matcher` with `payload`
+    pub fn on(mut self, matcher: Matcher, payload: &[u8]) -> Self {
+        self.push(matcher, payload);
+        self
+    }
+
+    /// Same as [`on`](Self::on), by reference
+    pub fn push(&mut self, matcher: Matcher, payload: &[u8]) {
+        self.payloads.push((matcher, payload.into()));
+    }
+
+    /// Answer `6CXX` to the commands not expecting the exact length of the payload
+    pub fn wrong_le(mut self, enabled: bool) -> Self {
+        self.wrong_le = enabled;
+        self
+    }
+
+    /// Number of bytes of the payload not yet returned
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Command APDUs received so far
+    pub fn received(&self) -> &[Vec<u8>] {
+        &self.received
+    }
+
+    pub fn clear_received(&mut self) {
+        self.received.clear();
+    }
+
+    /// Record `command` and return the response APDU
+    pub fn respond(&mut self, command: &[u8]) -> Vec<u8> {
+        self.received.push(command.into());
+        let Ok(view) = CommandView::try_from(command) else {
+            return <[u8; 2]>::from(Status::WrongLength).into();
+        };
+        if view.instruction() == 0xC0.into() {
+            if self.pending.is_empty() {
+                return <[u8; 2]>::from(Status::ConditionsOfUseNotSatisfied).into();
+            }
+            return self.fragment(view.expected());
+        }
+        if let Some((_, payload)) = self.payloads.iter().find(|(m, _)| m.matches(&view)) {
+            if self.wrong_le && payload.len() <= 256 && view.expected() != payload.len() {
+                self.pending.clear();
+                return <[u8; 2]>::from(Status::WrongLeField(payload.len() as u8)).into();
+            }
+            self.pending = payload.iter().copied().collect();
+            return self.fragment(view.expected());
+        }
+        self.pending.clear();
+        let status = match Select::try_from(&view).map(Select::into_target) {
+            Ok(SelectTarget::DfName(name)) if self.aid.matches(name) => Status::Success,
+            _ => Status::InstructionNotSupportedOrInvalid,
+        };
+        <[u8; 2]>::from(status).into()
+    }
+
+    /// Next fragment of the pending payload, with at most `expected` bytes
+    fn fragment(&mut self, expected: usize) -> Vec<u8> {
+        let len = self.fragment_len.min(expected).min(self.pending.len());
+        let mut response: Vec<u8> = self.pending.drain(..len).collect();
+        let status = match self.pending.len() {
+            0 => Status::Success,
+            remaining => Status::MoreAvailable(remaining.min(256) as u8),
+        };
+        response.extend_from_slice(&<[u8; 2]>::from(status));
+        response
+    }
+}
+
+impl App for FragmentingCard {
+    fn aid(&self) -> Aid {
+        self.aid
+    }
+}
+
+impl Transport for FragmentingCard {
+    type Error = Infallible;
+
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Infallible> {
+        let answer = self.respond(command);
+        assert!(
+            answer.len() <= response.len(),
+            "response buffer too small for the response {}",
+            trace::response(&answer)
+        );
+        response[..answer.len()].copy_from_slice(&answer);
+        Ok(answer.len())
+    }
+}
+
+/// Hex dump of the bytes, 16 per line
+struct HexDump<'a>(&'a [u8]);
+
+impl Display for HexDump<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, line) in self.0.chunks(16).enumerate() {
+            write!(f, "  {:04X}:", i * 16)?;
+            for byte in line {
+                write!(f, " {byte:02X}")?;
+            }
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// Canonical encoding of `view`, keeping the extended encoding if it was used
+fn encode(view: &CommandView<'_>) -> Vec<u8> {
+    let le = match view.expected() {
+        0x1_0000 => ExpectedLen::Max,
+        le => ExpectedLen::Ne(le as u16),
+    };
+    let command = CommandBuilder::new(
+        view.class(),
+        view.instruction(),
+        view.p1,
+        view.p2,
+        view.data(),
+        le,
+    );
+    let has_lengths = !view.data().is_empty() || view.expected() != 0;
+    if view.extended && has_lengths {
+        command.force_extended().serialize_to_vec()
+    } else {
+        command.serialize_to_vec()
+    }
+}
+
+/// Fields that differ between `expected` and `got`
+fn diff(expected: &CommandView<'_>, got: &CommandView<'_>) -> String {
+    let mut diff = String::new();
+    let mut field = |name: &str, expected: String, got: String| {
+        if expected != got {
+            writeln!(diff, "  {name}: expected {expected}, got {got}").ok();
+        }
+    };
+    let byte = |byte: u8| std::format!("{byte:02X}");
+    field(
+        "CLA",
+        byte(expected.class().into_inner()),
+        byte(got.class().into_inner()),
+    );
+    field(
+        "INS",
+        byte(expected.instruction().into()),
+        byte(got.instruction().into()),
+    );
+    field("P1", byte(expected.p1), byte(got.p1));
+    field("P2", byte(expected.p2), byte(got.p2));
+    field(
+        "data",
+        std::format!("{:02X?}", expected.data()),
+        std::format!("{:02X?}", got.data()),
+    );
+    field(
+        "Le",
+        expected.expected().to_string(),
+        got.expected().to_string(),
+    );
+    field(
+        "extended",
+        expected.extended.to_string(),
+        got.extended.to_string(),
+    );
+    diff
+}
+
+/// Serialize `command`, parse it back and check that it is encoded again identically
+///
+/// Returns the encoding of the command.
+///
+/// ```
+/// use iso7816::command::CommandBuilder;
+/// use iso7816::testing::assert_roundtrip;
+/// let command = CommandBuilder::new(0.try_into().unwrap(), 0xB0.into(), 0, 0, &[0u8; 0][..], 256);
+/// assert_eq!(assert_roundtrip(command), [0x00, 0xB0, 0x00, 0x00, 0x00]);
+/// ```
+#[track_caller]
+pub fn assert_roundtrip<D: DataStream<Vec<u8>>>(command: CommandBuilder<D>) -> Vec<u8> {
+    let encoded = command.serialize_to_vec();
+    let view = match CommandView::try_from(&*encoded) {
+        Ok(view) => view,
+        Err(err) => panic!(
+            "serialized command can't be parsed: {err:?}\n{}",
+            HexDump(&encoded)
+        ),
+    };
+    let reencoded = encode(&view);
+    if reencoded != encoded {
+        panic!(
+            "command does not round trip\nserialized:\n{}parsed and serialized again:\n{}",
+            HexDump(&encoded),
+            HexDump(&reencoded)
+        );
+    }
+    encoded
+}
+
+/// Parse `apdu` and check that it is equal to `expected`
+///
+/// ```
+/// use iso7816::command::{CommandBuilder, CommandView};
+/// use iso7816::testing::assert_parse_eq;
+/// let expected = [0x00, 0xA4, 0x04, 0x00, 0x02, 0xA0, 0x00];
+/// let expected = CommandView::try_from(&expected[..]).unwrap();
+/// assert_parse_eq(&[0x00, 0xA4, 0x04, 0x00, 0x02, 0xA0, 0x00], &expected);
+/// ```
+#[track_caller]
+pub fn assert_parse_eq(apdu: &[u8], expected: &CommandView<'_>) {
+    let got = match CommandView::try_from(apdu) {
+        Ok(view) => view,
+        Err(err) => panic!(
+            "command can't be parsed: {err:?}\nexpected:\n{}got:\n{}",
+            HexDump(&encode(expected)),
+            HexDump(apdu)
+        ),
+    };
+    if got != *expected {
+        panic!(
+            "parsed command differs\n{}expected:\n{}got:\n{}",
+            diff(expected, &got),
+            HexDump(&encode(expected)),
+            HexDump(apdu)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{CommandBuilder, DataStream};
+    use crate::tlv::{Tag, Tlv};
+    use crate::transport::Channel;
+    use crate::Data;
+    use hex_literal::hex;
+
+    #[test]
+    fn write_mock() {
+        let mut writer = WriteMock::new(4);
+        Tlv::new(Tag::from_u8(0x53), [1, 2])
+            .to_writer(&mut writer)
+            .unwrap();
+        assert_eq!(&*writer, hex!("53020102"));
+        assert!(writer.write_all(&[3]).is_err());
+
+        writer.clear();
+        let command =
+            CommandBuilder::new(0.try_into().unwrap(), 0xB0.into(), 0, 0, &[0u8; 0][..], 0);
+        command.serialize_into(&mut writer).unwrap();
+        assert_eq!(&*writer, hex!("00B00000"));
+        assert_eq!(writer.into_inner(), hex!("00B00000"));
+    }
+
+    #[test]
+    fn scripted_transport() {
+        let mut transport = ScriptedTransport::new().expect(&hex!("00A40400"), &hex!("9000"));
+        transport.push(&hex!("00B00000"), &hex!("6A82"));
+        let mut response = [0; 4];
+        assert_eq!(transport.transmit(&hex!("00A40400"), &mut response), Ok(2));
+        assert_eq!(response[..2], hex!("9000"));
+        assert_eq!(transport.remaining(), 1);
+        assert_eq!(transport.transmit(&hex!("00B00000"), &mut response), Ok(2));
+        assert_eq!(response[..2], hex!("6A82"));
+        assert_eq!(transport.transmitted(), 2);
+        transport.finish();
+    }
+
+    #[test]
+    #[should_panic = "does not match the script"]
+    fn scripted_transport_mismatch() {
+        let mut transport = ScriptedTransport::new().expect(&hex!("00A40400"), &hex!("9000"));
+        transport.transmit(&hex!("00A40000"), &mut [0; 4]).ok();
+    }
+
+    #[test]
+    #[should_panic = "did not happen"]
+    fn scripted_transport_unfinished() {
+        ScriptedTransport::new()
+            .expect(&hex!("00A40400"), &hex!("9000"))
+            .finish();
+    }
+
+    #[test]
+    fn mock_card() {
+        let mut card = MockCard::new(Aid::new(&hex!("A000000527 2101")))
+            .on(
+                Matcher::any().ins(0xCA).p1(0x00).p2(0x5A),
+                &hex!("0102 9000"),
+            )
+            .on(Matcher::any().cla(0x80).ins(0xCA), &hex!("6A88"))
+            .fallback(Status::ClassNotSupported);
+        card.push(Matcher::any().data_prefix(&hex!("5C")), &hex!("03 9000"));
+        assert_eq!(card.aid(), Aid::new(&hex!("A000000527 2101")));
+
+        assert_eq!(
+            card.respond(&hex!("00A4040007 A0000005272101")),
+            hex!("9000")
+        );
+        assert_eq!(
+            card.respond(&hex!("00A4040007 A0000005272102")),
+            hex!("6E00")
+        );
+        assert_eq!(card.respond(&hex!("00CA005A00")), hex!("0102 9000"));
+        assert_eq!(card.respond(&hex!("80CA005A00")), hex!("0102 9000"));
+        assert_eq!(card.respond(&hex!("80CA005B00")), hex!("6A88"));
+        assert_eq!(card.respond(&hex!("00CB3FFF 02 5C01")), hex!("03 9000"));
+        assert_eq!(card.respond(&hex!("00CB3FFF 02 5D01")), hex!("6E00"));
+        assert_eq!(card.respond(&hex!("00CB3FFF 03 5C01")), hex!("6700"));
+        assert_eq!(card.received().len(), 8);
+        assert_eq!(card.received()[2], hex!("00CA005A00"));
+        card.clear_received();
+        assert!(card.received().is_empty());
+    }
+
+    #[test]
+    fn fragmenting_card() {
+        let mut card = FragmentingCard::new(Aid::new(&hex!("A000000527 2101")), 100)
+            .on(Matcher::any().ins(0xCA), &[0x42; 300])
+            .on(Matcher::any().ins(0xCB), &hex!("010203"))
+            .wrong_le(true);
+        assert_eq!(
+            card.respond(&hex!("00A4040007 A0000005272101")),
+            hex!("9000")
+        );
+        assert_eq!(card.respond(&hex!("00C0000000")), hex!("6985"));
+        assert_eq!(card.respond(&hex!("00CB3FFF00")), hex!("6C03"));
+        assert_eq!(card.respond(&hex!("00CB3FFF02")), hex!("6C03"));
+        assert_eq!(card.respond(&hex!("00CB3FFF03")), hex!("010203 9000"));
+        assert_eq!(card.respond(&hex!("00B0000000")), hex!("6D00"));
+
+        // Payloads longer than 256 bytes cannot be announced with 6CXX
+        let response = card.respond(&hex!("00CA3FFF00"));
+        assert_eq!(response[100..], hex!("61C8"));
+        assert_eq!(card.pending(), 200);
+        let response = card.respond(&hex!("00C0000010"));
+        assert_eq!(response[0x10..], hex!("61B8"));
+        assert_eq!(card.pending(), 0xB8);
+
+        let mut channel = Channel::<_, 300>::new(card);
+        let command =
+            CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[0u8; 0][..], 256);
+        let mut response = Data::<512>::new();
+        assert_eq!(
+            channel.exchange(command, &mut response),
+            Ok(Status::Success)
+        );
+        assert_eq!(&*response, [0x42; 300]);
+        let received = &channel.transport().received()[8..];
+        assert_eq!(
+            received,
+            [
+                hex!("00CA000000").to_vec(),
+                hex!("00C00000C8").to_vec(),
+                hex!("00C0000064").to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let class = 0.try_into().unwrap();
+        let data = [0x42; 300];
+        for (data, le) in [
+            (&data[..0], 0),
+            (&data[..1], 256),
+            (&data[..], 0),
+            (&data[..3], 0xFFFF),
+        ] {
+            let command = CommandBuilder::new(class, 0xDB.into(), 0x3F, 0xFF, data, le);
+            let encoded = assert_roundtrip(command.clone());
+            assert_parse_eq(&encoded, &CommandView::try_from(&*encoded).unwrap());
+            assert_roundtrip(command.force_extended());
+        }
+        let encoded = assert_roundtrip(CommandBuilder::new(
+            class,
+            0xDB.into(),
+            0x3F,
+            0xFF,
+            &data[..],
+            ExpectedLen::Max,
+        ));
+        assert_eq!(encoded[encoded.len() - 2..], [0, 0]);
+    }
+
+    #[test]
+    #[should_panic = "  Le: expected 256, got 255"]
+    fn parse_eq_diff() {
+        let expected = hex!("00B0000000");
+        assert_parse_eq(
+            &hex!("00B00000FF"),
+            &CommandView::try_from(&expected[..]).unwrap(),
+        );
+    }
+
+    #[test]
+    fn hex_dump() {
+        let data: Vec<u8> = (0..20).collect();
+        assert_eq!(
+            HexDump(&data).to_string(),
+            "  0000: 00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F\n  0010: 10 11 12 13\n"
+        );
+    }
+}
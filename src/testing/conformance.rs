@@ -0,0 +1,407 @@
+//! ISO 7816-4 encoding test vectors
+//!
+//! The vectors cover the four cases of command APDUs in their short and extended forms at the
+//! boundaries of the length fields, encodings that must be rejected, command chaining and status
+//! words. [`run`] checks an [`Implementation`] against all of them, so that alternative backends
+//! and wrappers can prove that they are byte-exact compatible with this crate:
+//!
+//! ```
+//! use iso7816::testing::conformance::{self, Native};
+//! let report = conformance::run(&mut Native);
+//! assert!(report.failures.is_empty(), "{report}");
+//! ```
+
+use core::fmt::{self, Display, Formatter};
+use std::vec::Vec;
+
+use crate::command::{CommandBuilder, CommandView, ExpectedLen};
+use crate::Status;
+
+/// Bytes `start`, `start + 1`, ... wrapping around, so that data fields of any length can be static
+const fn pattern<const N: usize>(start: u8) -> [u8; N] {
+    let mut data = [0; N];
+    let mut i = 0;
+    while i < N {
+        data[i] = start.wrapping_add(i as u8);
+        i += 1;
+    }
+    data
+}
+
+static DATA_255: [u8; 255] = pattern(0);
+static DATA_256: [u8; 256] = pattern(0);
+static DATA_300: [u8; 300] = pattern(0);
+static DATA_65535: [u8; 65535] = pattern(0);
+static DATA_45: [u8; 45] = pattern(255);
+
+/// Fields of a command APDU
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fields {
+    /// CLA, INS, P1 and P2
+    pub header: [u8; 4],
+    pub data: Vec<u8>,
+    /// Maximum number of bytes expected in the response, `0` if absent
+    pub ne: usize,
+}
+
+/// A valid command APDU and its encoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommandVector {
+    pub name: &'static str,
+    pub header: [u8; 4],
+    /// Encoding of the Lc field
+    pub lc: &'static [u8],
+    pub data: &'static [u8],
+    /// Encoding of the Le field
+    pub le: &'static [u8],
+    /// Maximum number of bytes expected in the response, `0` if absent
+    pub ne: usize,
+}
+
+impl CommandVector {
+    /// Whether the lengths use the extended encoding
+    pub fn extended(&self) -> bool {
+        self.lc.len() == 3 || self.le.len() > 1
+    }
+
+    pub fn fields(&self) -> Fields {
+        Fields {
+            header: self.header,
+            data: self.data.into(),
+            ne: self.ne,
+        }
+    }
+
+    pub fn encoding(&self) -> Vec<u8> {
+        [&self.header[..], self.lc, self.data, self.le].concat()
+    }
+}
+
+macro_rules! vector {
+    ($name:literal, $header:expr, $lc:expr, $data:expr, $le:expr, $ne:expr) => {
+        CommandVector {
+            name: $name,
+            header: $header,
+            lc: &$lc,
+            data: &$data,
+            le: &$le,
+            ne: $ne,
+        }
+    };
+}
+
+/// Valid command APDUs
+#[rustfmt::skip]
+pub const COMMANDS: &[CommandVector] = &[
+    vector!("case 1", [0x00, 0xA4, 0x00, 0x00], [], [], [], 0),
+    vector!("case 2S Le=1", [0x00, 0xB0, 0x00, 0x00], [], [], [0x01], 1),
+    vector!("case 2S Le=255", [0x00, 0xB0, 0x00, 0x00], [], [], [0xFF], 255),
+    vector!("case 2S Le=256", [0x00, 0xB0, 0x00, 0x00], [], [], [0x00], 256),
+    vector!("case 3S Lc=1", [0x00, 0xD6, 0x00, 0x00], [0x01], [0x00], [], 0),
+    vector!("case 3S Lc=255", [0x00, 0xD6, 0x00, 0x00], [0xFF], DATA_255, [], 0),
+    vector!("case 4S Lc=1 Le=1", [0x00, 0xA4, 0x04, 0x00], [0x01], [0x00], [0x01], 1),
+    vector!("case 4S Lc=255 Le=256", [0x00, 0xA4, 0x04, 0x00], [0xFF], DATA_255, [0x00], 256),
+    vector!("case 2E Le=257", [0x00, 0xB0, 0x00, 0x00], [], [], [0x00, 0x01, 0x01], 257),
+    vector!("case 2E Le=65535", [0x00, 0xB0, 0x00, 0x00], [], [], [0x00, 0xFF, 0xFF], 65535),
+    vector!("case 2E Le=65536", [0x00, 0xB0, 0x00, 0x00], [], [], [0x00, 0x00, 0x00], 65536),
+    vector!("case 3E Lc=256", [0x00, 0xD6, 0x00, 0x00], [0x00, 0x01, 0x00], DATA_256, [], 0),
+    vector!("case 3E Lc=65535", [0x00, 0xD6, 0x00, 0x00], [0x00, 0xFF, 0xFF], DATA_65535, [], 0),
+    vector!("case 4E Lc=1 Le=257", [0x00, 0x2A, 0x9E, 0x9A], [0x00, 0x00, 0x01], [0x00], [0x01, 0x01], 257),
+    vector!("case 4E Lc=256 Le=65536", [0x00, 0x2A, 0x80, 0x86], [0x00, 0x01, 0x00], DATA_256, [0x00, 0x00], 65536),
+    vector!("chained case 3S", [0x10, 0xDB, 0x3F, 0xFF], [0x01], [0x53], [], 0),
+    vector!("proprietary class", [0x80, 0xCA, 0x00, 0x5A], [], [], [0x00], 256),
+];
+
+/// Encodings that are not valid command APDUs
+pub const INVALID: &[(&str, &[u8])] = &[
+    ("empty", &[]),
+    ("incomplete header", &[0x00, 0xA4, 0x04]),
+    ("invalid class", &[0xFF, 0xA4, 0x04, 0x00]),
+    ("missing data", &[0x00, 0xD6, 0x00, 0x00, 0x02, 0x01]),
+    (
+        "trailing bytes",
+        &[0x00, 0xD6, 0x00, 0x00, 0x01, 0x01, 0x02, 0x03],
+    ),
+    (
+        "missing extended data",
+        &[0x00, 0xD6, 0x00, 0x00, 0x00, 0x00, 0x02, 0x01],
+    ),
+    (
+        "truncated extended Le",
+        &[0x00, 0xB0, 0x00, 0x00, 0x00, 0x01],
+    ),
+    (
+        "short Le after extended Lc",
+        &[0x00, 0x2A, 0x9E, 0x9A, 0x00, 0x00, 0x01, 0x00, 0x01],
+    ),
+];
+
+/// A command split into chained short APDUs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainVector {
+    pub name: &'static str,
+    /// Header of the command, without the chaining bit
+    pub header: [u8; 4],
+    pub data: &'static [u8],
+    pub ne: usize,
+    /// Maximum length of the APDUs of the chain
+    pub frame_len: usize,
+    pub parts: &'static [CommandVector],
+}
+
+/// Commands that must be split with command chaining
+pub const CHAINS: &[ChainVector] = &[ChainVector {
+    name: "300 bytes in two parts",
+    header: [0x00, 0xDB, 0x3F, 0xFF],
+    data: &DATA_300,
+    ne: 0,
+    frame_len: 262,
+    parts: &[
+        vector!("first", [0x10, 0xDB, 0x3F, 0xFF], [0xFF], DATA_255, [], 0),
+        vector!("last", [0x00, 0xDB, 0x3F, 0xFF], [0x2D], DATA_45, [], 0),
+    ],
+}];
+
+/// Status words and their meaning
+pub const STATUS_WORDS: &[([u8; 2], Status)] = &[
+    ([0x90, 0x00], Status::Success),
+    ([0x61, 0x00], Status::MoreAvailable(0x00)),
+    ([0x61, 0x10], Status::MoreAvailable(0x10)),
+    ([0x62, 0x81], Status::CorruptedData),
+    ([0x63, 0xC0], Status::RemainingRetries(0)),
+    ([0x63, 0xC3], Status::RemainingRetries(3)),
+    ([0x67, 0x00], Status::WrongLength),
+    ([0x68, 0x84], Status::CommandChainingNotSupported),
+    ([0x69, 0x82], Status::SecurityStatusNotSatisfied),
+    ([0x6A, 0x82], Status::NotFound),
+    ([0x6A, 0x86], Status::IncorrectP1OrP2Parameter),
+    ([0x6C, 0x00], Status::WrongLeField(0x00)),
+    ([0x6C, 0x20], Status::WrongLeField(0x20)),
+    ([0x6D, 0x00], Status::InstructionNotSupportedOrInvalid),
+    ([0x6E, 0x00], Status::ClassNotSupported),
+    ([0x6F, 0x00], Status::UnspecifiedCheckingError),
+];
+
+/// Parser and serializer under test
+pub trait Implementation {
+    /// Parse a command APDU, returning `None` if it is invalid
+    fn parse(&mut self, apdu: &[u8]) -> Option<Fields>;
+
+    /// Serialize a command, with the shortest encoding of its lengths
+    fn serialize(&mut self, command: &Fields) -> Vec<u8>;
+
+    /// Split a command into chained APDUs of at most `frame_len` bytes
+    ///
+    /// The default implementation returns `None`, to skip the chaining vectors.
+    fn chain(&mut self, _command: &Fields, _frame_len: usize) -> Option<Vec<Vec<u8>>> {
+        None
+    }
+
+    /// Interpret a status word
+    ///
+    /// The default implementation returns `None`, to skip the status word vectors.
+    fn status(&mut self, _sw: [u8; 2]) -> Option<Status> {
+        None
+    }
+}
+
+/// Vector that an [`Implementation`] does not comply with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub name: &'static str,
+    /// What was checked: `parse`, `serialize`, `reject`, `chain` or `status`
+    pub check: &'static str,
+}
+
+/// Result of [`run`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub passed: usize,
+    pub skipped: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    fn check(&mut self, name: &'static str, check: &'static str, passed: bool) {
+        if passed {
+            self.passed += 1;
+        } else {
+            self.failures.push(Failure { name, check });
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} passed, {} failed, {} skipped",
+            self.passed,
+            self.failures.len(),
+            self.skipped
+        )?;
+        for Failure { name, check } in &self.failures {
+            write!(f, "\n  {check}: {name}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check `implementation` against all the vectors
+pub fn run(implementation: &mut impl Implementation) -> Report {
+    let mut report = Report::default();
+    for vector in COMMANDS {
+        let encoding = vector.encoding();
+        let parsed = implementation.parse(&encoding);
+        report.check(vector.name, "parse", parsed == Some(vector.fields()));
+        // Chained commands can't be serialized from their fields only
+        if vector.header[0] & 0x10 == 0 {
+            let serialized = implementation.serialize(&vector.fields());
+            report.check(vector.name, "serialize", serialized == encoding);
+        }
+    }
+    for &(name, encoding) in INVALID {
+        report.check(name, "reject", implementation.parse(encoding).is_none());
+    }
+    for vector in CHAINS {
+        let command = Fields {
+            header: vector.header,
+            data: vector.data.into(),
+            ne: vector.ne,
+        };
+        match implementation.chain(&command, vector.frame_len) {
+            Some(parts) => {
+                let expected: Vec<_> = vector.parts.iter().map(|p| p.encoding()).collect();
+                report.check(vector.name, "chain", parts == expected);
+            }
+            None => report.skipped += 1,
+        }
+    }
+    for &(sw, status) in STATUS_WORDS {
+        match implementation.status(sw) {
+            Some(interpreted) => report.check("status word", "status", interpreted == status),
+            None => report.skipped += 1,
+        }
+    }
+    report
+}
+
+/// The implementation of this crate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Native;
+
+impl Native {
+    fn builder(command: &Fields) -> Option<CommandBuilder<&[u8]>> {
+        let [cla, ins, p1, p2] = command.header;
+        let le = match command.ne {
+            0x1_0000 => ExpectedLen::Max,
+            ne => ExpectedLen::Ne(ne.try_into().ok()?),
+        };
+        Some(CommandBuilder::new(
+            cla.try_into().ok()?,
+            ins.into(),
+            p1,
+            p2,
+            &*command.data,
+            le,
+        ))
+    }
+}
+
+impl Implementation for Native {
+    fn parse(&mut self, apdu: &[u8]) -> Option<Fields> {
+        let view = CommandView::try_from(apdu).ok()?;
+        Some(Fields {
+            header: [
+                view.class().into_inner(),
+                view.instruction().into(),
+                view.p1,
+                view.p2,
+            ],
+            data: view.data().into(),
+            ne: view.expected(),
+        })
+    }
+
+    fn serialize(&mut self, command: &Fields) -> Vec<u8> {
+        Self::builder(command)
+            .map(CommandBuilder::serialize_to_vec)
+            .unwrap_or_default()
+    }
+
+    fn chain(&mut self, command: &Fields, frame_len: usize) -> Option<Vec<Vec<u8>>> {
+        let builder = Self::builder(command)?;
+        let le = command.ne.min(256) as u16;
+        let parts = CommandBuilder::new_non_extended(
+            builder.class(),
+            builder.instruction(),
+            command.header[2],
+            command.header[3],
+            &command.data,
+            le,
+            Some(frame_len),
+        );
+        Some(parts.map(CommandBuilder::serialize_to_vec).collect())
+    }
+
+    fn status(&mut self, sw: [u8; 2]) -> Option<Status> {
+        Some(sw.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn native() {
+        let report = run(&mut Native);
+        assert!(report.failures.is_empty(), "{report}");
+        assert_eq!(report.skipped, 0);
+    }
+
+    #[test]
+    fn vectors() {
+        for vector in COMMANDS {
+            assert_eq!(
+                CommandView::try_from(&*vector.encoding()).unwrap().extended,
+                vector.extended(),
+                "{}",
+                vector.name
+            );
+        }
+        for vector in CHAINS {
+            let data: Vec<u8> = vector.parts.iter().flat_map(|p| p.data).copied().collect();
+            assert_eq!(data, vector.data);
+            assert!(vector
+                .parts
+                .iter()
+                .all(|p| p.encoding().len() <= vector.frame_len));
+        }
+    }
+
+    #[test]
+    fn failures() {
+        struct NoExtended;
+        impl Implementation for NoExtended {
+            fn parse(&mut self, apdu: &[u8]) -> Option<Fields> {
+                let fields = Native.parse(apdu)?;
+                (fields.data.len() < 256 && fields.ne <= 256).then_some(fields)
+            }
+            fn serialize(&mut self, command: &Fields) -> Vec<u8> {
+                Native.serialize(command)
+            }
+        }
+
+        let report = run(&mut NoExtended);
+        assert_eq!(report.failures.len(), 7);
+        assert_eq!(
+            report.failures[0],
+            Failure {
+                name: "case 2E Le=257",
+                check: "parse"
+            }
+        );
+        assert_eq!(report.skipped, CHAINS.len() + STATUS_WORDS.len());
+    }
+}
@@ -1,32 +1,225 @@
 //! BER-TLV writer and parser
 
-use crate::command::{writer::Error as _, DataSource, DataStream, Writer};
+use crate::command::{writer::Error as _, CachedLen, DataSource, DataStream, Writer};
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
-pub struct Tag([u8; 3]);
+mod builder;
+mod editor;
+mod macros;
+mod mapping;
+mod query;
+mod set_of;
+mod streaming;
+pub mod tags;
+pub use builder::TlvBuilder;
+pub use editor::{EditError, TlvEditor};
+pub use mapping::{Fields, FromTlv, FromTlvError, Repeated, ToTlv};
+pub use query::{query, Match, Step};
+pub use set_of::{sort_set_of, SetOf};
+pub use streaming::StreamingTlv;
+
+/// Longest encoding of a [`Tag`], allowing tag numbers up to 28 bits
+pub const MAX_TAG_LEN: usize = 5;
+
+/// Class of a [`Tag`], bits 8 and 7 of its first byte
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum TagClass {
+    Universal,
+    Application,
+    ContextSpecific,
+    Private,
+}
+
+/// The bytes are not a valid encoding of a [`Tag`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TagError {
+    /// No bytes at all
+    Empty,
+    /// The last subsequent byte is missing
+    Truncated,
+    /// The tag is longer than [`MAX_TAG_LEN`]
+    TooLong,
+    /// The tag number has leading zeros
+    NonMinimal,
+    /// Bytes remain after the end of the tag
+    TrailingData,
+    /// The tag number does not fit in [`MAX_TAG_LEN`] bytes
+    NumberTooLarge,
+}
+
+/// BER-TLV tag, stored as its encoding
+///
+/// The encoding is checked when the tag is created, so that [`serialize`](Self::serialize)
+/// always produces a valid tag.
+///
+/// ```
+/// use iso7816::tlv::{Tag, TagClass, TagError};
+/// let tag = Tag::from_u16(0x7F49);
+/// assert_eq!(tag.class(), TagClass::Application);
+/// assert!(tag.is_constructed());
+/// assert_eq!(tag.number(), 0x49);
+/// assert_eq!(tag.as_bytes(), &[0x7F, 0x49]);
+/// assert_eq!(Tag::new(TagClass::Application, true, 0x49), Ok(tag));
+///
+/// assert_eq!(Tag::try_from_bytes(&[0x7F]), Err(TagError::Truncated));
+/// assert_eq!(Tag::try_from_bytes(&[0x5C, 0x01]), Err(TagError::TrailingData));
+/// ```
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Tag([u8; MAX_TAG_LEN]);
 
 impl Tag {
+    /// Tag with the given class and number, using the shortest encoding
+    pub const fn new(class: TagClass, constructed: bool, number: u32) -> Result<Self, TagError> {
+        let mut first = match class {
+            TagClass::Universal => 0x00,
+            TagClass::Application => 0x40,
+            TagClass::ContextSpecific => 0x80,
+            TagClass::Private => 0xC0,
+        };
+        if constructed {
+            first |= 0x20;
+        }
+        if number < 0x1F {
+            return Ok(Self::from_u8(first | number as u8));
+        }
+        if number >> (7 * (MAX_TAG_LEN - 1)) != 0 {
+            return Err(TagError::NumberTooLarge);
+        }
+        let mut tag = [0; MAX_TAG_LEN];
+        let mut remaining = number;
+        let mut i = MAX_TAG_LEN;
+        while remaining != 0 {
+            i -= 1;
+            tag[i] = remaining as u8 & 0x7F;
+            if i != MAX_TAG_LEN - 1 {
+                tag[i] |= 0x80;
+            }
+            remaining >>= 7;
+        }
+        tag[i - 1] = first | 0x1F;
+        Ok(Self(tag))
+    }
+
+    /// Check that `bytes` is exactly one tag
+    pub const fn try_from_bytes(bytes: &[u8]) -> Result<Self, TagError> {
+        let len = match tag_len(bytes) {
+            Ok(len) => len,
+            Err(err) => return Err(err),
+        };
+        if len != bytes.len() {
+            return Err(TagError::TrailingData);
+        }
+        let mut tag = [0; MAX_TAG_LEN];
+        let mut i = 0;
+        while i < len {
+            tag[MAX_TAG_LEN - len + i] = bytes[i];
+            i += 1;
+        }
+        Ok(Self(tag))
+    }
+
+    /// Tag encoded in `value`, without the leading zero byte for single byte tags
+    pub const fn try_from_u16(value: u16) -> Result<Self, TagError> {
+        match value.to_be_bytes() {
+            [0, b2] => Self::try_from_bytes(&[b2]),
+            bytes => Self::try_from_bytes(&bytes),
+        }
+    }
+
+    /// Tag encoded in `value`, without leading zero bytes
+    pub const fn try_from_u32(value: u32) -> Result<Self, TagError> {
+        match value.to_be_bytes() {
+            [0, 0, 0, b4] => Self::try_from_bytes(&[b4]),
+            [0, 0, b3, b4] => Self::try_from_bytes(&[b3, b4]),
+            [0, b2, b3, b4] => Self::try_from_bytes(&[b2, b3, b4]),
+            bytes => Self::try_from_bytes(&bytes),
+        }
+    }
+
+    const fn expect(result: Result<Self, TagError>) -> Self {
+        match result {
+            Ok(tag) => tag,
+            Err(_) => panic!("Invalid tag encoding"),
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `value` is the first byte of a multi-byte tag.
     pub const fn from_u8(value: u8) -> Self {
-        Tag([0, 0, value])
+        Self::expect(Self::try_from_bytes(&[value]))
     }
 
+    /// # Panics
+    ///
+    /// Panics if `value` is not a valid encoding, see [`try_from_u16`](Self::try_from_u16).
     pub const fn from_u16(value: u16) -> Self {
         Tag::from_2(value.to_be_bytes())
     }
 
+    /// # Panics
+    ///
+    /// Panics if `value` is not a valid encoding, see [`try_from_u32`](Self::try_from_u32).
+    pub const fn from_u32(value: u32) -> Self {
+        Self::expect(Self::try_from_u32(value))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the bytes are not a valid encoding, the first one being ignored if it is zero.
     pub const fn from_2([b1, b2]: [u8; 2]) -> Self {
         if b1 == 0 {
-            Tag([0, 0, b2])
+            Self::from_u8(b2)
         } else {
-            Tag([0, b1, b2])
+            Self::expect(Self::try_from_bytes(&[b1, b2]))
         }
     }
+
+    /// # Panics
+    ///
+    /// Panics if the bytes are not a valid encoding, the first one being ignored if it is zero.
     pub const fn from_3([b1, b2, b3]: [u8; 3]) -> Self {
         if b1 == 0 {
             Self::from_2([b2, b3])
         } else {
-            Tag([b1, b2, b3])
+            Self::expect(Self::try_from_bytes(&[b1, b2, b3]))
+        }
+    }
+}
+
+/// Length of the tag at the start of `data`, checking its encoding
+const fn tag_len(data: &[u8]) -> Result<usize, TagError> {
+    let [b1, subsequent @ ..] = data else {
+        return Err(TagError::Empty);
+    };
+    if *b1 & 0x1F != 0x1F {
+        return Ok(1);
+    }
+    let [b2, ..] = subsequent else {
+        return Err(TagError::Truncated);
+    };
+    // Tag numbers below 31 in subsequent bytes are accepted, as used by EMV (`9F02`, `BF0C`)
+    if *b2 == 0x80 {
+        return Err(TagError::NonMinimal);
+    }
+    // Subsequent bytes have bit 8 set, except the last one
+    let mut i = 0;
+    while i < subsequent.len() {
+        if i + 1 == MAX_TAG_LEN {
+            return Err(TagError::TooLong);
         }
+        if subsequent[i] & 0x80 == 0 {
+            return Ok(i + 2);
+        }
+        i += 1;
+    }
+    Err(TagError::Truncated)
+}
+
+impl TryFrom<&[u8]> for Tag {
+    type Error = TagError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Self::try_from_bytes(value)
     }
 }
 
@@ -61,63 +254,125 @@ impl From<[u8; 3]> for Tag {
 }
 
 impl Tag {
-    pub fn serialize(&self) -> heapless::Vec<u8, 3> {
-        let [b1, b2, b3] = self.0;
-        if b1 == 0 {
-            if b2 == 0 {
-                debug_assert_ne!(b3 & 0b11111, 0b11111, "Invalid encoding for 1 byte tag");
-                heapless::Vec::try_from([b3].as_slice()).unwrap()
-            } else {
-                debug_assert_eq!(
-                    b3 & 0b11111,
-                    0b11111,
-                    "Invalid encoding for first byte of tag"
-                );
-                debug_assert!(
-                    (0x1F..=0x7F).contains(&b3),
-                    "Invalid encoding for first byte of tag"
-                );
-                heapless::Vec::try_from([b2, b3].as_slice()).unwrap()
-            }
-        } else {
-            debug_assert_eq!(
-                b1 & 0b11111,
-                0b11111,
-                "Invalid encoding for first byte of tag"
-            );
-            debug_assert!(b2 > 0x80);
-            debug_assert!((0x00..0x7F).contains(&b3));
-            heapless::Vec::try_from([b1, b2, b3].as_slice()).unwrap()
+    /// Encoding of the tag
+    pub fn as_bytes(&self) -> &[u8] {
+        // Only the first byte of a tag can be zero, for single byte tags
+        let start = self
+            .0
+            .iter()
+            .position(|&b| b != 0)
+            .unwrap_or(MAX_TAG_LEN - 1);
+        &self.0[start..]
+    }
+
+    pub fn serialize(&self) -> heapless::Vec<u8, MAX_TAG_LEN> {
+        heapless::Vec::from_slice(self.as_bytes()).unwrap()
+    }
+
+    fn first_byte(&self) -> u8 {
+        self.as_bytes()[0]
+    }
+
+    pub fn class(&self) -> TagClass {
+        match self.first_byte() >> 6 {
+            0b00 => TagClass::Universal,
+            0b01 => TagClass::Application,
+            0b10 => TagClass::ContextSpecific,
+            _ => TagClass::Private,
+        }
+    }
+
+    /// Whether the value of the data object is made of data objects
+    pub fn is_constructed(&self) -> bool {
+        self.first_byte() & 0x20 != 0
+    }
+
+    /// Tag number, without the class and constructed bits
+    pub fn number(&self) -> u32 {
+        match self.as_bytes() {
+            [b1] => u32::from(b1 & 0b11111),
+            [_, subsequent @ ..] => subsequent
+                .iter()
+                .fold(0, |number, b| number << 7 | u32::from(b & 0x7F)),
+            [] => unreachable!(),
         }
     }
 }
 
 pub fn get_data_object<'input>(tag_path: &[Tag], data: &'input [u8]) -> Option<&'input [u8]> {
-    let mut to_ret = data;
-    let mut remainder = data;
-    for tag in tag_path {
+    try_get_data_object(tag_path, data).ok()
+}
+
+/// Like [`get_data_object`], with the reason of the failure
+///
+/// ```
+/// use iso7816::tlv::{try_get_data_object, Tag, TlvErrorKind};
+/// let data = [0x7F, 0x49, 0x04, 0x86, 0x03, 0x04, 0x01];
+/// let path = [Tag::from_u16(0x7F49), Tag::from_u8(0x86)];
+/// let error = try_get_data_object(&path, &data).unwrap_err();
+/// assert_eq!(error.kind(), TlvErrorKind::Truncated);
+/// assert_eq!(error.offset(), 3);
+///
+/// let error = try_get_data_object(&[Tag::from_u8(0x5C)], &data).unwrap_err();
+/// assert_eq!(error.kind(), TlvErrorKind::TagNotFound(Tag::from_u8(0x5C)));
+/// ```
+pub fn try_get_data_object<'input>(
+    tag_path: &[Tag],
+    data: &'input [u8],
+) -> Result<&'input [u8], TlvError> {
+    let mut value = data;
+    let mut offset = 0;
+    for &tag in tag_path {
+        let mut objects = iter(value);
         loop {
-            let (cur_tag, cur_value, cur_remainder) = take_data_object(remainder)?;
-            remainder = cur_remainder;
-            if *tag == cur_tag {
-                to_ret = cur_value;
-                remainder = cur_value;
+            let Some(object) = objects.next() else {
+                return Err(TlvError {
+                    kind: TlvErrorKind::TagNotFound(tag),
+                    offset,
+                });
+            };
+            let (cur_tag, cur_value) = object.map_err(|err| TlvError {
+                kind: err.kind,
+                offset: offset + err.offset,
+            })?;
+            if cur_tag == tag {
+                // `cur_value` is a subslice of `value`
+                offset += cur_value.as_ptr() as usize - value.as_ptr() as usize;
+                value = cur_value;
                 break;
             }
         }
     }
-    Some(to_ret)
+    Ok(value)
 }
 
 /// Returns (tag, data, remainder)
 pub fn take_data_object(data: &[u8]) -> Option<(Tag, &[u8], &[u8])> {
-    let (tag, remainder) = take_tag(data)?;
-    let (len, remainder) = take_len(remainder)?;
+    parse_object(data, false).ok()
+}
+
+/// Like [`take_data_object`], with the reason of the failure
+pub fn try_take_data_object(data: &[u8]) -> Result<(Tag, &[u8], &[u8]), TlvError> {
+    parse_object(data, false).map_err(|kind| TlvError { kind, offset: 0 })
+}
+
+/// Like [`take_data_object`], but enforcing the DER rules, see [`iter_der`]
+pub fn take_data_object_der(data: &[u8]) -> Result<(Tag, &[u8], &[u8]), TlvError> {
+    parse_object(data, true).map_err(|kind| TlvError { kind, offset: 0 })
+}
+
+fn parse_object(data: &[u8], der: bool) -> Result<(Tag, &[u8], &[u8]), TlvErrorKind> {
+    let (tag, remainder) = data.split_at(tag_len(data).map_err(TlvErrorKind::Tag)?);
+    if der && tag.len() == 2 && tag[1] < 0x1F {
+        return Err(TlvErrorKind::NonMinimalTag);
+    }
+    let tag = Tag::try_from_bytes(tag).map_err(TlvErrorKind::Tag)?;
+    let (len, remainder) = parse_len(remainder, der)?;
     if remainder.len() < len {
-        None
+        Err(TlvErrorKind::Truncated)
     } else {
         let (value, remainder) = remainder.split_at(len);
-        Some((tag, value, remainder))
+        Ok((tag, value, remainder))
     }
 }
 
@@ -125,80 +380,440 @@ pub fn take_data_object(data: &[u8]) -> Option<(Tag, &[u8], &[u8])> {
 // https://www.emvco.com/wp-content/uploads/2017/05/EMV_v4.3_Book_3_Application_Specification_20120607062110791.pdf
 // Annex B1
 pub fn take_tag(data: &[u8]) -> Option<(Tag, &[u8])> {
-    let b1 = *data.first()?;
-    if (b1 & 0x1f) == 0x1f {
-        let b2 = *data.get(1)?;
-        if (0x00..0x1E).contains(&b2) || b2 == 0x80 {
-            return None;
-        }
+    try_take_tag(data).ok()
+}
 
-        if (0x81..0xFF).contains(&b2) {
-            let b3 = *data.get(2)?;
-            if (0x81..0xFF).contains(&b3) {
-                return None;
-            }
+/// Like [`take_tag`], with the reason of the failure
+pub fn try_take_tag(data: &[u8]) -> Result<(Tag, &[u8]), TlvError> {
+    let error = |err| TlvError {
+        kind: TlvErrorKind::Tag(err),
+        offset: 0,
+    };
+    let (tag, remainder) = data.split_at(tag_len(data).map_err(error)?);
+    Ok((Tag::try_from_bytes(tag).map_err(error)?, remainder))
+}
 
-            Some((Tag([b1, b2, b3]), &data[3..]))
-        } else {
-            Some((Tag([0, b1, b2]), &data[2..]))
-        }
-    } else {
-        Some((Tag([0, 0, b1]), &data[1..]))
+pub fn take_len(data: &[u8]) -> Option<(usize, &[u8])> {
+    parse_len(data, false).ok()
+}
+
+/// Like [`take_len`], with the reason of the failure
+pub fn try_take_len(data: &[u8]) -> Result<(usize, &[u8]), TlvError> {
+    parse_len(data, false).map_err(|kind| TlvError { kind, offset: 0 })
+}
+
+fn parse_len(data: &[u8], der: bool) -> Result<(usize, &[u8]), TlvErrorKind> {
+    let (&l1, remainder) = data.split_first().ok_or(TlvErrorKind::Truncated)?;
+    let n = match l1 {
+        0x00..=0x7F => return Ok((l1.into(), remainder)),
+        0x80 => return Err(TlvErrorKind::IndefiniteLength),
+        0x81..=0x84 => usize::from(l1 & 0x7F),
+        _ => return Err(TlvErrorKind::UnsupportedLength),
+    };
+    if remainder.len() < n {
+        return Err(TlvErrorKind::Truncated);
+    }
+    let (bytes, remainder) = remainder.split_at(n);
+    let len = bytes.iter().fold(0u32, |len, &b| len << 8 | u32::from(b));
+    // The shortest form has no leading zero byte, and `81` is only used from `80` on
+    let min = if n == 1 { 0x80 } else { 1 << (8 * (n - 1)) };
+    if der && len < min {
+        return Err(TlvErrorKind::NonMinimalLength);
     }
+    let len = usize::try_from(len).map_err(|_| TlvErrorKind::UnsupportedLength)?;
+    Ok((len, remainder))
 }
 
-pub fn take_len(data: &[u8]) -> Option<(usize, &[u8])> {
-    let l1 = *data.first()?;
-    if l1 <= 0x7F {
-        Some((l1 as usize, &data[1..]))
-    } else if l1 == 0x81 {
-        Some((*data.get(1)? as usize, &data[2..]))
-    } else {
-        if l1 != 0x82 {
+/// Reason for a [`TlvError`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TlvErrorKind {
+    /// The tag is not valid
+    Tag(TagError),
+    /// The length or the value goes past the end of the data
+    Truncated,
+    /// The length uses the indefinite form (`80`)
+    IndefiniteLength,
+    /// The length uses more than four bytes, or does not fit in a `usize`
+    UnsupportedLength,
+    /// DER only: the length is not encoded in the shortest form
+    NonMinimalLength,
+    /// DER only: the tag number is below 31 but encoded in subsequent bytes
+    NonMinimalTag,
+    /// No data object has the searched tag
+    TagNotFound(Tag),
+}
+
+/// The data is not a valid sequence of BER-TLV data objects
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TlvError {
+    kind: TlvErrorKind,
+    offset: usize,
+}
+
+impl TlvError {
+    pub fn kind(&self) -> TlvErrorKind {
+        self.kind
+    }
+
+    /// Offset of the data object that could not be parsed, or for
+    /// [`TagNotFound`](TlvErrorKind::TagNotFound) of the data that was searched
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+/// Iterator over sibling data objects, see [`iter`]
+#[derive(Clone, Debug)]
+pub struct Iter<'a> {
+    data: &'a [u8],
+    offset: usize,
+    der: bool,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Result<(Tag, &'a [u8]), TlvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
             return None;
         }
-        let l2 = *data.get(1)?;
-        let l3 = *data.get(2)?;
-        let len = u16::from_be_bytes([l2, l3]) as usize;
-        Some((len, &data[3..]))
+        match parse_object(self.data, self.der) {
+            Ok((tag, value, remainder)) => {
+                self.offset += self.data.len() - remainder.len();
+                self.data = remainder;
+                Some(Ok((tag, value)))
+            }
+            Err(kind) => {
+                // Stop after the first error
+                self.data = &[];
+                Some(Err(TlvError {
+                    kind,
+                    offset: self.offset,
+                }))
+            }
+        }
     }
 }
 
-fn serialize_len(len: usize) -> Option<heapless::Vec<u8, 3>> {
-    let mut buf = heapless::Vec::new();
-    if let Ok(len) = u8::try_from(len) {
-        if len <= 0x7f {
-            buf.extend_from_slice(&[len]).ok();
+impl core::iter::FusedIterator for Iter<'_> {}
+
+/// Iterate over the data objects of `data` at one nesting level, without entering constructed
+/// data objects
+///
+/// ```
+/// use iso7816::tlv::{iter, Tag, TlvErrorKind};
+/// let fci = [0x84, 0x02, 0xA0, 0x00, 0xA5, 0x03, 0x88, 0x01, 0x02];
+/// let mut objects = iter(&fci);
+/// assert_eq!(objects.next(), Some(Ok((Tag::from_u8(0x84), &fci[2..4]))));
+/// assert_eq!(objects.next(), Some(Ok((Tag::from_u8(0xA5), &fci[6..]))));
+/// assert_eq!(objects.next(), None);
+///
+/// let error = iter(&fci[..8]).nth(1).unwrap().unwrap_err();
+/// assert_eq!(error.kind(), TlvErrorKind::Truncated);
+/// assert_eq!(error.offset(), 4);
+/// ```
+pub fn iter(data: &[u8]) -> Iter<'_> {
+    Iter {
+        data,
+        offset: 0,
+        der: false,
+    }
+}
+
+/// Like [`iter`], but rejecting encodings that DER does not allow
+///
+/// Lengths must use the shortest form and tag numbers below 31 must use a single byte. Structures
+/// that are signed or hashed, such as public keys and certificates, should be parsed with these
+/// rules so that each value has a single encoding.
+///
+/// ```
+/// use iso7816::tlv::{iter, iter_der, TlvErrorKind};
+/// let data = [0x86, 0x81, 0x01, 0x04];
+/// assert!(iter(&data).next().unwrap().is_ok());
+/// let error = iter_der(&data).next().unwrap().unwrap_err();
+/// assert_eq!(error.kind(), TlvErrorKind::NonMinimalLength);
+/// ```
+pub fn iter_der(data: &[u8]) -> Iter<'_> {
+    Iter {
+        data,
+        offset: 0,
+        der: true,
+    }
+}
+
+/// Data object borrowed from a buffer
+///
+/// The value of a constructed data object can be walked with [`children`](Self::children),
+/// giving a navigable tree over a response without copying it. Data objects parsed with
+/// [`parse_der`](Self::parse_der) also parse their children with the DER rules.
+///
+/// ```
+/// use iso7816::tlv::{Tag, TlvRef};
+/// // FCI template with a proprietary template
+/// let fci = [0x6F, 0x0A, 0x84, 0x02, 0xA0, 0x00, 0xA5, 0x04, 0x9F, 0x65, 0x01, 0xFF];
+/// let (root, rest) = TlvRef::parse(&fci).unwrap();
+/// assert!(rest.is_empty());
+/// assert!(root.is_constructed());
+/// let proprietary = root.child(Tag::from_u8(0xA5)).unwrap();
+/// let len = proprietary.child(Tag::from_u16(0x9F65)).unwrap();
+/// assert!(!len.is_constructed());
+/// assert_eq!(len.value(), &[0xFF]);
+/// assert_eq!(root.children().count(), 2);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct TlvRef<'a> {
+    tag: Tag,
+    value: &'a [u8],
+    der: bool,
+}
+
+impl PartialEq for TlvRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag == other.tag && self.value == other.value
+    }
+}
+
+impl Eq for TlvRef<'_> {}
+
+impl<'a> TlvRef<'a> {
+    fn parse_with(data: &'a [u8], der: bool) -> Result<(Self, &'a [u8]), TlvError> {
+        let (tag, value, remainder) =
+            parse_object(data, der).map_err(|kind| TlvError { kind, offset: 0 })?;
+        Ok((Self { tag, value, der }, remainder))
+    }
+
+    /// Parse the first data object of `data`, returning it with the remaining data
+    pub fn parse(data: &'a [u8]) -> Result<(Self, &'a [u8]), TlvError> {
+        Self::parse_with(data, false)
+    }
+
+    /// Like [`parse`](Self::parse), with the rules of [`iter_der`]
+    pub fn parse_der(data: &'a [u8]) -> Result<(Self, &'a [u8]), TlvError> {
+        Self::parse_with(data, true)
+    }
+
+    /// Iterate over the data objects of `data` at one nesting level
+    pub fn iter(data: &'a [u8]) -> Children<'a> {
+        Children(iter(data))
+    }
+
+    /// Like [`iter`](Self::iter), with the rules of [`iter_der`]
+    pub fn iter_der(data: &'a [u8]) -> Children<'a> {
+        Children(iter_der(data))
+    }
+
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+
+    /// Whether the value is made of data objects (bit 6 of the first byte of the tag)
+    pub fn is_constructed(&self) -> bool {
+        self.tag.is_constructed()
+    }
+
+    /// Data objects of the value, empty for primitive data objects
+    ///
+    /// Error offsets are relative to the start of the value.
+    pub fn children(&self) -> Children<'a> {
+        let value = if self.is_constructed() {
+            self.value
         } else {
-            buf.extend_from_slice(&[0x81, len]).ok();
-        }
-    } else if let Ok(len) = u16::try_from(len) {
-        let [ar1, ar2] = len.to_be_bytes();
-        buf.extend_from_slice(&[0x82, ar1, ar2]).ok();
-    } else {
+            &[]
+        };
+        Children(Iter {
+            data: value,
+            offset: 0,
+            der: self.der,
+        })
+    }
+
+    /// First child with the given tag, stopping at the first malformed child
+    pub fn child(&self, tag: Tag) -> Option<Self> {
+        self.children()
+            .map_while(Result::ok)
+            .find(|child| child.tag == tag)
+    }
+}
+
+/// Iterator over the children of a [`TlvRef`]
+#[derive(Clone, Debug)]
+pub struct Children<'a>(Iter<'a>);
+
+impl<'a> Iterator for Children<'a> {
+    type Item = Result<TlvRef<'a>, TlvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let der = self.0.der;
+        self.0
+            .next()
+            .map(|object| object.map(|(tag, value)| TlvRef { tag, value, der }))
+    }
+}
+
+impl core::iter::FusedIterator for Children<'_> {}
+
+pub(crate) fn serialize_len(len: usize) -> Option<heapless::Vec<u8, 5>> {
+    if len > 0xFFFF {
         return None;
     }
+    serialize_long_len(len)
+}
+
+/// Like [`serialize_len`], also using the three and four byte forms (`83` and `84`)
+pub(crate) fn serialize_long_len(len: usize) -> Option<heapless::Vec<u8, 5>> {
+    let len = u32::try_from(len).ok()?;
+    let bytes = len.to_be_bytes();
+    let significant = bytes.iter().position(|&b| b != 0).unwrap_or(3);
+    let mut buf = heapless::Vec::new();
+    if len > 0x7F {
+        buf.push(0x80 | (4 - significant) as u8).ok();
+    }
+    buf.extend_from_slice(&bytes[significant..]).ok();
     Some(buf)
 }
 
+/// Data object to serialize, with a value of any [`DataSource`]
+///
+/// Constructed data objects are built by nesting `Tlv`s, using tuples, arrays or options for
+/// their value:
+///
+/// ```
+/// use iso7816::command::DataStream;
+/// use iso7816::tlv::{Tag, Tlv};
+/// let aid = [0xA0, 0x00, 0x00, 0x03, 0x08];
+/// let fci = Tlv::new(
+///     Tag::from_u8(0x6F),
+///     (
+///         Tlv::new(Tag::from_u8(0x84), &aid[..]),
+///         Tlv::new(Tag::from_u8(0xA5), Some(Tlv::new(Tag::from_u8(0x88), [0x01]))),
+///     ),
+/// );
+/// assert_eq!(fci.tag(), Tag::from_u8(0x6F));
+/// let mut buf = heapless::Vec::<u8, 32>::new();
+/// fci.to_writer(&mut buf).unwrap();
+/// assert_eq!(
+///     &*buf,
+///     &[0x6F, 0x0C, 0x84, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08, 0xA5, 0x03, 0x88, 0x01, 0x01]
+/// );
+/// ```
+///
+/// Values longer than `0xFFFF` bytes fail to serialize unless
+/// [`with_long_lengths`](Self::with_long_lengths) is used, as not all cards support the longer
+/// length forms.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Tlv<S> {
     tag: Tag,
-    data: S,
+    data: CachedLen<S>,
+    long_lengths: bool,
 }
 
-impl<S> Tlv<S> {
+impl<S: DataSource> Tlv<S> {
     pub fn new(tag: Tag, data: S) -> Self {
-        Self { tag, data }
+        Self {
+            tag,
+            data: CachedLen::new(data),
+            long_lengths: false,
+        }
+    }
+}
+
+impl<S> Tlv<S> {
+    /// Allow the three and four byte length forms (`83` and `84`), for values longer than `0xFFFF`
+    pub fn with_long_lengths(mut self) -> Self {
+        self.long_lengths = true;
+        self
+    }
+
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    pub fn value(&self) -> &S {
+        self.data.get()
+    }
+
+    pub fn into_value(self) -> S {
+        self.data.into_inner()
+    }
+}
+
+impl<'a> Tlv<&'a [u8]> {
+    /// Parse the first data object of `data`, returning it with the remaining data
+    ///
+    /// ```
+    /// use iso7816::command::DataStream;
+    /// use iso7816::tlv::{Tag, Tlv};
+    /// let data = [0x5F, 0x2D, 0x02, 0x65, 0x6E, 0x90];
+    /// let (tlv, rest) = Tlv::parse(&data).unwrap();
+    /// assert_eq!(tlv, Tlv::new(Tag::from_u16(0x5F2D), &b"en"[..]));
+    /// assert_eq!(rest, &[0x90]);
+    ///
+    /// let mut buf = heapless::Vec::<u8, 8>::new();
+    /// tlv.to_writer(&mut buf).unwrap();
+    /// assert_eq!(&*buf, &data[..5]);
+    /// ```
+    pub fn parse(data: &'a [u8]) -> Result<(Self, &'a [u8]), TlvError> {
+        TlvRef::parse(data).map(|(tlv, rest)| (tlv.into(), rest))
+    }
+
+    /// Like [`parse`](Self::parse), with the rules of [`iter_der`]
+    pub fn parse_der(data: &'a [u8]) -> Result<(Self, &'a [u8]), TlvError> {
+        TlvRef::parse_der(data).map(|(tlv, rest)| (tlv.into(), rest))
     }
 }
 
-impl<S: DataSource> DataSource for Tlv<S> {
+impl<'a> From<TlvRef<'a>> for Tlv<&'a [u8]> {
+    fn from(value: TlvRef<'a>) -> Self {
+        Self::new(value.tag, value.value)
+    }
+}
+
+/// Length of the tag and length fields of a data object
+fn header_len(tag: Tag, len: usize, long_lengths: bool) -> usize {
+    let len = if long_lengths {
+        serialize_long_len(len)
+    } else {
+        serialize_len(len)
+    };
+    tag.as_bytes().len() + len.map(|l| l.len()).unwrap_or_default()
+}
+
+/// Encoded tag and length of a data object
+///
+/// This is kept out of [`write_header`] so that it is not duplicated for each writer type.
+fn encode_header(
+    tag: Tag,
+    len: usize,
+    long_lengths: bool,
+) -> Result<heapless::Vec<u8, { MAX_TAG_LEN + 5 }>, &'static str> {
+    let len = if long_lengths {
+        serialize_long_len(len).ok_or("Data is longer than 0xFFFFFFFF bytes")
+    } else {
+        serialize_len(len).ok_or("Data is longer than 0xFFFF bytes")
+    }?;
+    let mut header = heapless::Vec::new();
+    header.extend_from_slice(tag.as_bytes()).ok();
+    header.extend_from_slice(&len).ok();
+    Ok(header)
+}
+
+pub(crate) fn write_header<W: Writer>(
+    writer: &mut W,
+    tag: Tag,
+    len: usize,
+    long_lengths: bool,
+) -> Result<(), W::Error> {
+    let header = encode_header(tag, len, long_lengths).map_err(W::Error::failed_serialization)?;
+    writer.write_all(&header)
+}
+
+impl<S> DataSource for Tlv<S> {
     fn len(&self) -> usize {
-        let tag = self.tag.serialize();
-        let len = serialize_len(self.data.len())
-            .map(|l| l.len())
-            .unwrap_or_default();
-        tag.len() + len + self.data.len()
+        header_len(self.tag, self.data.len(), self.long_lengths) + self.data.len()
     }
 
     fn is_empty(&self) -> bool {
@@ -208,12 +823,7 @@ impl<S: DataSource> DataSource for Tlv<S> {
 
 impl<W: Writer, S: DataStream<W>> DataStream<W> for Tlv<S> {
     fn to_writer(&self, writer: &mut W) -> Result<(), <W as Writer>::Error> {
-        writer.write_all(&self.tag.serialize())?;
-        writer.write_all(
-            &serialize_len(self.data.len()).ok_or_else(|| {
-                W::Error::failed_serialization("Data is longer than 0xFFFF bytes")
-            })?,
-        )?;
+        write_header(writer, self.tag, self.data.len(), self.long_lengths)?;
         self.data.to_writer(writer)
     }
 }
@@ -241,12 +851,447 @@ mod tests {
         );
     }
 
+    #[test]
+    fn errors() {
+        let error = |kind, offset| TlvError { kind, offset };
+        assert_eq!(
+            try_take_tag(&hex!("5F")),
+            Err(error(TlvErrorKind::Tag(TagError::Truncated), 0))
+        );
+        assert_eq!(
+            try_take_tag(&hex!("BF 81 82 83 84 05")),
+            Err(error(TlvErrorKind::Tag(TagError::TooLong), 0))
+        );
+        assert_eq!(try_take_len(&hex!("05 AA")), Ok((5, &hex!("AA")[..])));
+        assert_eq!(
+            try_take_len(&hex!("85 0000000001")),
+            Err(error(TlvErrorKind::UnsupportedLength, 0))
+        );
+        assert_eq!(
+            try_take_len(&hex!("82 01")),
+            Err(error(TlvErrorKind::Truncated, 0))
+        );
+        assert_eq!(
+            try_take_data_object(&hex!("53 02 AA")),
+            Err(error(TlvErrorKind::Truncated, 0))
+        );
+        assert_eq!(
+            try_take_data_object(&hex!("53 80 AA 0000")),
+            Err(error(TlvErrorKind::IndefiniteLength, 0))
+        );
+
+        let data = hex!("A6 0A 02 01 AA 7F49 04 86 02 0401 90 00");
+        let path = |tags: &[u16]| tags.iter().map(|&t| Tag::from(t)).collect::<Vec<_>>();
+        assert_eq!(
+            try_get_data_object(&path(&[0xA6, 0x7F49, 0x86]), &data),
+            Ok(&hex!("0401")[..])
+        );
+        assert_eq!(
+            try_get_data_object(&path(&[0xA6, 0x7F49, 0x87]), &data),
+            Err(error(TlvErrorKind::TagNotFound(Tag::from_u8(0x87)), 8))
+        );
+        assert_eq!(
+            try_get_data_object(&path(&[0x91]), &data),
+            Err(error(TlvErrorKind::TagNotFound(Tag::from_u8(0x91)), 0))
+        );
+        assert_eq!(
+            try_get_data_object(&path(&[0xA6, 0x7F49, 0x86]), &data[..11]),
+            Err(error(TlvErrorKind::Truncated, 0))
+        );
+        let data = hex!("A6 05 02 01 AA 7F 80");
+        assert_eq!(
+            try_get_data_object(&path(&[0xA6, 0x86]), &data),
+            Err(error(TlvErrorKind::Tag(TagError::NonMinimal), 5))
+        );
+        assert_eq!(get_data_object(&path(&[0xA6, 0x86]), &data), None);
+    }
+
+    #[test]
+    fn iterate() {
+        let data = hex!("5F2F 01 31 7F49 03 860100 53 00");
+        let objects: Vec<_> = iter(&data).collect();
+        assert_eq!(
+            objects,
+            [
+                Ok((Tag::from_u16(0x5F2F), &hex!("31")[..])),
+                Ok((Tag::from_u16(0x7F49), &hex!("860100")[..])),
+                Ok((Tag::from_u8(0x53), &[][..])),
+            ]
+        );
+        assert_eq!(iter(&[]).next(), None);
+
+        let mut objects = iter(&data[..9]);
+        assert!(objects.next().unwrap().is_ok());
+        assert_eq!(
+            objects.next(),
+            Some(Err(TlvError {
+                kind: TlvErrorKind::Truncated,
+                offset: 4
+            }))
+        );
+        assert_eq!(objects.next(), None);
+    }
+
+    #[test]
+    fn tree() {
+        let data = hex!("A6 0D 7F49 08 86 02 0411 81 02 AABB 90 00 01");
+        let (root, rest) = TlvRef::parse(&data).unwrap();
+        assert_eq!(rest, &hex!("01"));
+        assert_eq!(root.tag(), Tag::from_u8(0xA6));
+
+        let key = root.child(Tag::from_u16(0x7F49)).unwrap();
+        assert!(key.is_constructed());
+        let fields: Vec<_> = key
+            .children()
+            .map(|child| child.map(|c| (c.tag(), c.value())))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                Ok((Tag::from_u8(0x86), &hex!("0411")[..])),
+                Ok((Tag::from_u8(0x81), &hex!("AABB")[..])),
+            ]
+        );
+
+        // Primitive data objects have no children
+        let empty = root.child(Tag::from_u8(0x90)).unwrap();
+        assert_eq!(empty.value(), &[]);
+        assert_eq!(empty.children().next(), None);
+        let (primitive, _) = TlvRef::parse(&hex!("04 02 A000")).unwrap();
+        assert_eq!(primitive.children().next(), None);
+
+        // Truncated child
+        let (broken, _) = TlvRef::parse(&hex!("A5 04 8001 01 82")).unwrap();
+        let mut children = broken.children();
+        assert!(children.next().unwrap().is_ok());
+        assert_eq!(
+            children.next(),
+            Some(Err(TlvError {
+                kind: TlvErrorKind::Truncated,
+                offset: 3
+            }))
+        );
+        assert_eq!(broken.child(Tag::from_u8(0x82)), None);
+        assert_eq!(
+            TlvRef::parse(&hex!("A5 04 8001")).map_err(|err| err.kind()),
+            Err(TlvErrorKind::Truncated)
+        );
+    }
+
     #[test]
     fn tlv() {
         let mut buf = [0u8; 4];
         Tlv::new(Tag::from_u8(0x41), hex!("012A"))
             .to_writer(&mut buf.as_mut_slice())
             .unwrap();
-        assert_eq!(buf.as_slice(), &hex!("41 02 012A"))
+        assert_eq!(buf.as_slice(), &hex!("41 02 012A"));
+
+        let point = Tlv::new(Tag::from_u8(0x86), [0x04; 2]);
+        let tlv = Tlv::new(Tag::from_u16(0x7F49), (point, point));
+        assert_eq!(tlv.tag(), Tag::from_u16(0x7F49));
+        assert_eq!(tlv.value().1.value(), &[0x04; 2]);
+        assert_eq!(tlv.len(), 11);
+        let mut buf = [0u8; 11];
+        tlv.to_writer(&mut buf.as_mut_slice()).unwrap();
+        assert_eq!(buf, hex!("7F49 08 86020404 86020404"));
+        assert_eq!(tlv.into_value().0, point);
+    }
+
+    #[test]
+    fn cached_len() {
+        use core::cell::Cell;
+
+        struct Counted<'a>(&'a Cell<usize>);
+
+        impl DataSource for Counted<'_> {
+            fn len(&self) -> usize {
+                self.0.set(self.0.get() + 1);
+                2
+            }
+        }
+
+        impl<W: Writer> DataStream<W> for Counted<'_> {
+            fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+                writer.write_all(&[0xAA, 0xBB])
+            }
+        }
+
+        let calls = Cell::new(0);
+        let tag = Tag::from_u8(0xA0);
+        let nested = Tlv::new(
+            tag,
+            Tlv::new(
+                tag,
+                Tlv::new(tag, Tlv::new(Tag::from_u8(0x80), Counted(&calls))),
+            ),
+        );
+        let mut buf = Vec::new();
+        nested.to_writer(&mut buf).unwrap();
+        assert_eq!(buf, hex!("A008 A006 A004 8002AABB"));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn text() {
+        let name: heapless::String<8> = "ALICE".into();
+        let tlv = Tlv::new(
+            tags::CARDHOLDER_RELATED_DATA,
+            (
+                Tlv::new(tags::NAME, name),
+                Tlv::new(tags::LANGUAGE_PREFERENCES, "en"),
+            ),
+        );
+        assert_eq!(tlv.len(), 14);
+        let mut buf = [0u8; 14];
+        tlv.to_writer(&mut buf.as_mut_slice()).unwrap();
+        assert_eq!(buf, *b"\x65\x0C\x5B\x05ALICE\x5F\x2D\x02en");
+    }
+
+    #[test]
+    fn tag_fields() {
+        let tag = Tag::from_u8(0x04);
+        assert_eq!(tag.class(), TagClass::Universal);
+        assert!(!tag.is_constructed());
+        assert_eq!(tag.number(), 4);
+
+        let tag = Tag::from_u8(0xA5);
+        assert_eq!(tag.class(), TagClass::ContextSpecific);
+        assert!(tag.is_constructed());
+        assert_eq!(tag.number(), 5);
+
+        let tag = Tag::from_3(hex!("DF8101"));
+        assert_eq!(tag.class(), TagClass::Private);
+        assert!(!tag.is_constructed());
+        assert_eq!(tag.number(), 0x81);
+
+        assert_eq!(Tag::from_u8(0).as_bytes(), &[0]);
+        assert_eq!(Tag::from_u16(0x5F20).number(), 0x20);
+    }
+
+    #[test]
+    fn long_tags() {
+        let data = hex!("BF 81 82 83 04 01 AA");
+        let (tag, value, rest) = take_data_object(&data).unwrap();
+        assert_eq!(tag.as_bytes(), &data[..5]);
+        assert_eq!(tag.number(), 1 << 21 | 2 << 14 | 3 << 7 | 4);
+        assert!(tag.is_constructed());
+        assert_eq!(value, &hex!("AA"));
+        assert!(rest.is_empty());
+        assert_eq!(*tag.serialize(), data[..5]);
+
+        let (tag, rest) = take_tag(&hex!("9F 81 01 00")).unwrap();
+        assert_eq!(tag, Tag::from_3(hex!("9F8101")));
+        assert_eq!(rest, &hex!("00"));
+
+        // Too long or truncated
+        assert_eq!(take_tag(&hex!("BF 81 82 83 84 05")), None);
+        assert_eq!(take_tag(&hex!("BF 81 82")), None);
+    }
+
+    #[test]
+    fn tag_errors() {
+        assert_eq!(Tag::try_from_bytes(&[]), Err(TagError::Empty));
+        assert_eq!(Tag::try_from_bytes(&hex!("5F")), Err(TagError::Truncated));
+        assert_eq!(Tag::try_from_bytes(&hex!("5F81")), Err(TagError::Truncated));
+        assert_eq!(
+            Tag::try_from_bytes(&hex!("9F02")).map(|tag| tag.number()),
+            Ok(2)
+        );
+        assert_eq!(
+            Tag::try_from_bytes(&hex!("5F8001")),
+            Err(TagError::NonMinimal)
+        );
+        assert_eq!(
+            Tag::try_from_bytes(&hex!("5F 81 82 83 84 05")),
+            Err(TagError::TooLong)
+        );
+        assert_eq!(
+            Tag::try_from_bytes(&hex!("5F2001")),
+            Err(TagError::TrailingData)
+        );
+        assert_eq!(Tag::try_from_u16(0x001F), Err(TagError::Truncated));
+        assert_eq!(Tag::try_from_u16(0x005C), Ok(Tag::from_u8(0x5C)));
+        assert_eq!(Tag::try_from_u32(0x5C), Ok(Tag::from_u8(0x5C)));
+        assert_eq!(Tag::try_from_u32(0x5FC102), Ok(Tag::from_3(hex!("5FC102"))));
+        assert_eq!(
+            Tag::try_from_u32(0xBF818201).map(|t| t.number()),
+            Ok(1 << 14 | 2 << 7 | 1)
+        );
+        assert_eq!(Tag::try_from_u32(0x5F80), Err(TagError::NonMinimal));
+        assert_eq!(
+            Tag::try_from(&hex!("DF8101")[..]),
+            Ok(Tag::from_3(hex!("DF8101")))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_tag() {
+        let _ = Tag::from(0x5F81u16);
+    }
+
+    #[test]
+    fn new_tag() {
+        assert_eq!(
+            Tag::new(TagClass::Universal, false, 4),
+            Ok(Tag::from_u8(0x04))
+        );
+        assert_eq!(
+            Tag::new(TagClass::ContextSpecific, true, 0x0C),
+            Ok(Tag::from_u8(0xAC))
+        );
+        assert_eq!(
+            Tag::new(TagClass::Application, false, 0x20),
+            Ok(Tag::from_u16(0x5F20))
+        );
+        assert_eq!(
+            Tag::new(TagClass::Private, false, 0x81),
+            Ok(Tag::from_3(hex!("DF8101")))
+        );
+        let tag = Tag::new(TagClass::Private, true, (1 << 28) - 1).unwrap();
+        assert_eq!(tag.as_bytes(), hex!("FF FFFFFF7F"));
+        assert_eq!(tag.number(), (1 << 28) - 1);
+        assert_eq!(
+            Tag::new(TagClass::Private, true, 1 << 28),
+            Err(TagError::NumberTooLarge)
+        );
+    }
+
+    #[test]
+    fn parse_tlv() {
+        let data = hex!("7F49 05 8603 040102 9F65 01 FF");
+        let (key, rest) = Tlv::parse(&data).unwrap();
+        assert_eq!(key.tag(), Tag::from_u16(0x7F49));
+        let (point, empty) = Tlv::parse(key.value()).unwrap();
+        assert_eq!(point, Tlv::new(Tag::from_u8(0x86), &hex!("040102")[..]));
+        assert!(empty.is_empty());
+        let (len, empty) = Tlv::parse(rest).unwrap();
+        assert_eq!(*len.value(), hex!("FF"));
+        assert!(empty.is_empty());
+
+        // Serializing a parsed data object gives back its encoding
+        let mut buf = Vec::new();
+        key.to_writer(&mut buf).unwrap();
+        assert_eq!(buf, data[..8]);
+
+        assert_eq!(
+            Tlv::parse(&hex!("7F49 05 8603")).map_err(|err| err.kind()),
+            Err(TlvErrorKind::Truncated)
+        );
+        assert_eq!(
+            Tlv::parse(&[]).map_err(|err| err.kind()),
+            Err(TlvErrorKind::Tag(TagError::Empty))
+        );
+    }
+
+    #[test]
+    fn der() {
+        fn kind(data: &[u8]) -> Option<TlvErrorKind> {
+            iter_der(data).find_map(Result::err).map(|err| err.kind())
+        }
+        // Valid for both BER and DER
+        let valid = [
+            &hex!("04 7F")[..],
+            &[0; 0x7F],
+            &hex!("04 8180"),
+            &[0; 0x80],
+            &hex!("04 820100"),
+            &[0; 0x100],
+            &hex!("5F1F 00"),
+        ]
+        .concat();
+        assert_eq!(kind(&valid), None);
+        assert_eq!(iter_der(&valid).count(), 4);
+
+        for invalid in [&hex!("04 8101 00")[..], &hex!("04 820001 00")[..]] {
+            assert!(iter(invalid).all(|object| object.is_ok()));
+            assert_eq!(kind(invalid), Some(TlvErrorKind::NonMinimalLength));
+        }
+        assert!(iter(&hex!("9F02 00")).all(|object| object.is_ok()));
+        assert_eq!(kind(&hex!("9F02 00")), Some(TlvErrorKind::NonMinimalTag));
+        assert_eq!(
+            kind(&hex!("30 80 0000")),
+            Some(TlvErrorKind::IndefiniteLength)
+        );
+        assert_eq!(
+            kind(&hex!("04 83 000001 00")),
+            Some(TlvErrorKind::NonMinimalLength)
+        );
+        assert_eq!(
+            kind(&hex!("04 85 0000000001 00")),
+            Some(TlvErrorKind::UnsupportedLength)
+        );
+        assert_eq!(
+            take_data_object_der(&hex!("0481 01 00")).map_err(|err| err.kind()),
+            Err(TlvErrorKind::NonMinimalLength)
+        );
+
+        // Children of strictly parsed data objects are parsed strictly
+        let data = hex!("7F49 04 8681 01 04");
+        let (lax, _) = TlvRef::parse(&data).unwrap();
+        assert!(lax.children().all(|child| child.is_ok()));
+        let (strict, _) = TlvRef::parse_der(&data).unwrap();
+        assert_eq!(strict, lax);
+        let error = strict.children().next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), TlvErrorKind::NonMinimalLength);
+        assert!(Tlv::parse_der(&data).is_ok());
+    }
+
+    #[test]
+    fn lengths() {
+        for (len, encoded) in [
+            (0, &hex!("00")[..]),
+            (0x7F, &hex!("7F")),
+            (0x80, &hex!("8180")),
+            (0xFF, &hex!("81FF")),
+            (0x100, &hex!("820100")),
+            (0xFFFF, &hex!("82FFFF")),
+            (0x10000, &hex!("83010000")),
+            (0xFFFFFF, &hex!("83FFFFFF")),
+            (0x1000000, &hex!("8401000000")),
+        ] {
+            assert_eq!(*serialize_long_len(len).unwrap(), *encoded);
+            assert_eq!(serialize_len(len).is_some(), len <= 0xFFFF);
+            let data = [encoded, &[0xAA]].concat();
+            assert_eq!(take_len(&data), Some((len, &[0xAA][..])));
+            assert!(parse_len(&data, true).is_ok());
+        }
+        assert_eq!(
+            try_take_len(&hex!("83 0100")).map_err(|e| e.kind()),
+            Err(TlvErrorKind::Truncated)
+        );
+        assert_eq!(
+            parse_len(&hex!("83 00FFFF"), true),
+            Err(TlvErrorKind::NonMinimalLength)
+        );
+        assert_eq!(
+            parse_len(&hex!("84 00FFFFFF"), true),
+            Err(TlvErrorKind::NonMinimalLength)
+        );
+        assert_eq!(take_len(&hex!("84 00FFFFFF")), Some((0xFFFFFF, &[][..])));
+    }
+
+    #[test]
+    fn long_tlv() {
+        let value = vec![0x5A; 0x10001];
+        let tlv = Tlv::new(Tag::from_u16(0x5F2E), &value[..]);
+        let mut buf = Vec::new();
+        assert!(tlv.to_writer(&mut buf).is_err());
+
+        let tlv = tlv.with_long_lengths();
+        assert_eq!(tlv.len(), 6 + 0x10001);
+        let mut buf = Vec::new();
+        tlv.to_writer(&mut buf).unwrap();
+        assert_eq!(buf[..6], hex!("5F2E 83010001"));
+        let (parsed, rest) = Tlv::parse(&buf).unwrap();
+        assert_eq!(parsed.value().len(), 0x10001);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn serialize_tag() {
+        assert_eq!(*Tag::from_u8(0x5C).serialize(), hex!("5C"));
+        assert_eq!(*Tag::from_u16(0x7F49).serialize(), hex!("7F49"));
+        assert_eq!(*Tag::from_3(hex!("5FC102")).serialize(), hex!("5FC102"));
     }
 }
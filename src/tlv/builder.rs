@@ -0,0 +1,196 @@
+//! Nested data objects written straight to a [`Writer`]
+
+use super::{header_len, write_header, Tag};
+use crate::command::{DataStream, Writer};
+
+/// Writes nested data objects to a [`Writer`]
+///
+/// Primitive data objects take their length from [`DataSource::len`](crate::command::DataSource::len).
+/// The content of constructed data objects is given as a closure, called a first time to compute
+/// the length of the value and a second time to write it, so it must write the same data objects
+/// both times. As for [`Tlv`](super::Tlv), values longer than `0xFFFF` bytes require
+/// [`with_long_lengths`](Self::with_long_lengths).
+///
+/// ```
+/// use iso7816::tlv::{Tag, TlvBuilder};
+/// let aid = [0xA0, 0x00, 0x00, 0x03, 0x08];
+/// let mut buf = heapless::Vec::<u8, 32>::new();
+/// TlvBuilder::new(&mut buf)
+///     .constructed(Tag::from_u8(0x6F), |fci| {
+///         fci.primitive(Tag::from_u8(0x84), &aid[..])?
+///             .constructed(Tag::from_u8(0xA5), |proprietary| {
+///                 proprietary.constructed(Tag::from_u16(0xBF0C), |discretionary| {
+///                     discretionary.primitive(Tag::from_u16(0x9F6E), &[0x01, 0x02])?;
+///                     Ok(())
+///                 })?;
+///                 Ok(())
+///             })?;
+///         Ok(())
+///     })
+///     .unwrap();
+/// assert_eq!(
+///     &*buf,
+///     &[
+///         0x6F, 0x11, 0x84, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08, 0xA5, 0x08, 0xBF, 0x0C, 0x05, 0x9F,
+///         0x6E, 0x02, 0x01, 0x02,
+///     ]
+/// );
+/// ```
+pub struct TlvBuilder<'w, W: Writer> {
+    /// `None` while computing the length of a constructed data object
+    writer: Option<&'w mut W>,
+    len: usize,
+    long_lengths: bool,
+}
+
+impl<'w, W: Writer> TlvBuilder<'w, W> {
+    pub fn new(writer: &'w mut W) -> Self {
+        Self {
+            writer: Some(writer),
+            len: 0,
+            long_lengths: false,
+        }
+    }
+
+    /// Allow the three and four byte length forms (`83` and `84`)
+    pub fn with_long_lengths(mut self) -> Self {
+        self.long_lengths = true;
+        self
+    }
+
+    /// Number of bytes written so far
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Write a data object with `value` as its value
+    pub fn primitive<S>(&mut self, tag: Tag, value: &S) -> Result<&mut Self, W::Error>
+    where
+        S: DataStream<W> + ?Sized,
+    {
+        let len = value.len();
+        if let Some(writer) = &mut self.writer {
+            write_header(*writer, tag, len, self.long_lengths)?;
+            value.to_writer(writer)?;
+        }
+        self.len += header_len(tag, len, self.long_lengths) + len;
+        Ok(self)
+    }
+
+    /// Write already encoded data, for example a [`Tlv`](super::Tlv)
+    pub fn raw<S>(&mut self, data: &S) -> Result<&mut Self, W::Error>
+    where
+        S: DataStream<W> + ?Sized,
+    {
+        if let Some(writer) = &mut self.writer {
+            data.to_writer(writer)?;
+        }
+        self.len += data.len();
+        Ok(self)
+    }
+
+    /// Write a data object whose value is written by `content`
+    pub fn constructed<F>(&mut self, tag: Tag, content: F) -> Result<&mut Self, W::Error>
+    where
+        F: Fn(&mut TlvBuilder<'_, W>) -> Result<(), W::Error>,
+    {
+        let mut measure = TlvBuilder {
+            writer: None,
+            len: 0,
+            long_lengths: self.long_lengths,
+        };
+        content(&mut measure)?;
+        let len = measure.len;
+        if let Some(writer) = &mut self.writer {
+            write_header(*writer, tag, len, self.long_lengths)?;
+            let mut inner = TlvBuilder {
+                writer: Some(&mut **writer),
+                len: 0,
+                long_lengths: self.long_lengths,
+            };
+            content(&mut inner)?;
+            debug_assert_eq!(inner.len, len, "Content changed between the two passes");
+        }
+        self.len += header_len(tag, len, self.long_lengths) + len;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tlv::Tlv;
+    use hex_literal::hex;
+
+    #[test]
+    fn nested() {
+        let mut buf = Vec::new();
+        let mut builder = TlvBuilder::new(&mut buf);
+        builder
+            .primitive(Tag::from_u8(0x5C), &hex!("5FC102"))
+            .unwrap()
+            .constructed(Tag::from_u8(0x7C), |template| {
+                template
+                    .primitive(Tag::from_u8(0x82), &[0u8; 0])?
+                    .raw(&Tlv::new(Tag::from_u8(0x81), [0xAA; 3]))?;
+                Ok(())
+            })
+            .unwrap()
+            .constructed(Tag::from_u8(0xA0), |_| Ok(()))
+            .unwrap();
+        assert_eq!(builder.len(), 16);
+        assert_eq!(buf, hex!("5C03 5FC102 7C07 8200 8103AAAAAA A000"));
+    }
+
+    #[test]
+    fn long_value() {
+        let value = [0x11; 0x100];
+        let mut buf = Vec::new();
+        TlvBuilder::new(&mut buf)
+            .constructed(Tag::from_u16(0x7F21), |certificate| {
+                certificate.primitive(Tag::from_u8(0x04), &value)?;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(buf[..9], hex!("7F21 820104 04820100"));
+        assert_eq!(buf.len(), 9 + 0x100);
+    }
+
+    #[test]
+    fn long_lengths() {
+        let value = vec![0x11; 0x10000];
+        let build = |mut builder: TlvBuilder<'_, Vec<u8>>| {
+            builder
+                .constructed(Tag::from_u16(0x7F21), |certificate| {
+                    certificate.primitive(Tag::from_u8(0x04), &value[..])?;
+                    Ok(())
+                })
+                .map(|b| b.len())
+        };
+        let mut buf = Vec::new();
+        assert!(build(TlvBuilder::new(&mut buf)).is_err());
+        let mut buf = Vec::new();
+        assert_eq!(
+            build(TlvBuilder::new(&mut buf).with_long_lengths()).unwrap(),
+            0x10000 + 11
+        );
+        assert_eq!(buf[..11], hex!("7F21 83010005 04 83010000"));
+    }
+
+    #[test]
+    fn buffer_full() {
+        let mut buf = [0; 4];
+        let mut writer = &mut buf[..];
+        let result = TlvBuilder::new(&mut writer)
+            .constructed(Tag::from_u8(0x7C), |template| {
+                template.primitive(Tag::from_u8(0x81), &[0; 3])?;
+                Ok(())
+            })
+            .map(|_| ());
+        assert!(result.is_err());
+    }
+}
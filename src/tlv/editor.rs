@@ -0,0 +1,299 @@
+//! In-place editing of encoded data objects
+
+use core::ops::Range;
+
+use super::{parse_object, serialize_len, Tag, TlvError};
+
+/// Error returned by [`TlvEditor`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditError {
+    /// No data object matches the path
+    NotFound,
+    /// The data to edit is not valid BER-TLV
+    Malformed(TlvError),
+    /// The buffer is too small for the result
+    BufferFull,
+    /// A value would be longer than `0xFFFF` bytes
+    TooLong,
+}
+
+impl From<TlvError> for EditError {
+    fn from(error: TlvError) -> Self {
+        Self::Malformed(error)
+    }
+}
+
+enum Operation<'v> {
+    Replace(&'v [u8]),
+    Delete,
+    Insert(Tag, &'v [u8]),
+}
+
+/// Replace, insert or delete data objects in a buffer of encoded data objects
+///
+/// Data objects are identified by their tag path, as for
+/// [`get_data_object`](super::get_data_object). The data following an edited data object is
+/// shifted, and the lengths of the enclosing constructed data objects are updated. Those lengths
+/// are written in their shortest form.
+///
+/// ```
+/// use iso7816::tlv::{Tag, TlvEditor};
+/// let mut buf = [0; 16];
+/// let data = [0x65, 0x05, 0x5B, 0x03, 0x41, 0x4C, 0x49];
+/// buf[..data.len()].copy_from_slice(&data);
+/// let mut editor = TlvEditor::new(&mut buf, data.len()).unwrap();
+/// let name = [Tag::from_u8(0x65), Tag::from_u8(0x5B)];
+/// editor.replace(&name, b"BOB!").unwrap();
+/// editor.insert(&name[..1], Tag::from_u16(0x5F2D), b"en").unwrap();
+/// assert_eq!(
+///     editor.as_bytes(),
+///     &[0x65, 0x0B, 0x5B, 0x04, b'B', b'O', b'B', b'!', 0x5F, 0x2D, 0x02, b'e', b'n']
+/// );
+/// editor.delete(&name).unwrap();
+/// assert_eq!(editor.as_bytes(), &[0x65, 0x05, 0x5F, 0x2D, 0x02, b'e', b'n']);
+/// ```
+#[derive(Debug)]
+pub struct TlvEditor<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> TlvEditor<'a> {
+    /// Edit the first `len` bytes of `buf`, the rest being available for growing
+    ///
+    /// Returns `None` if `len` is larger than the buffer.
+    pub fn new(buf: &'a mut [u8], len: usize) -> Option<Self> {
+        (len <= buf.len()).then_some(Self { buf, len })
+    }
+
+    /// Encoded data objects
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Replace the value of the data object at `path`
+    pub fn replace(&mut self, path: &[Tag], value: &[u8]) -> Result<(), EditError> {
+        self.edit(path, Operation::Replace(value))
+    }
+
+    /// Remove the data object at `path`
+    pub fn delete(&mut self, path: &[Tag]) -> Result<(), EditError> {
+        self.edit(path, Operation::Delete)
+    }
+
+    /// Add a data object at the end of the value of the constructed data object at `parent`,
+    /// or at the end of the data if `parent` is empty
+    pub fn insert(&mut self, parent: &[Tag], tag: Tag, value: &[u8]) -> Result<(), EditError> {
+        self.edit(parent, Operation::Insert(tag, value))
+    }
+
+    fn edit(&mut self, path: &[Tag], operation: Operation<'_>) -> Result<(), EditError> {
+        if path.is_empty() && !matches!(operation, Operation::Insert(..)) {
+            return Err(EditError::NotFound);
+        }
+        // Measure first so that a failed edit leaves the data unchanged
+        let mut len = self.len;
+        self.edit_in(&mut len, 0..self.len, path, &operation, false)?;
+        if len > self.buf.len() {
+            return Err(EditError::BufferFull);
+        }
+        let mut len = self.len;
+        self.edit_in(&mut len, 0..self.len, path, &operation, true)?;
+        self.len = len;
+        Ok(())
+    }
+
+    /// Apply `operation` in `region`, returning the new end of the region
+    ///
+    /// Data objects are located before anything is shifted, so the measuring pass (`write` set to
+    /// false) sees the same data as the writing one.
+    fn edit_in(
+        &mut self,
+        len: &mut usize,
+        region: Range<usize>,
+        path: &[Tag],
+        operation: &Operation<'_>,
+        write: bool,
+    ) -> Result<usize, EditError> {
+        let Some((tag, rest)) = path.split_first() else {
+            let Operation::Insert(tag, value) = operation else {
+                unreachable!("only insertions stop at the parent");
+            };
+            let value_len = serialize_len(value.len()).ok_or(EditError::TooLong)?;
+            let parts = [tag.as_bytes(), &value_len, value];
+            self.splice(len, region.end..region.end, &parts, write);
+            return Ok(region.end + parts.iter().map(|part| part.len()).sum::<usize>());
+        };
+        let object = self.find(region.clone(), *tag)?;
+        match operation {
+            Operation::Replace(value) if rest.is_empty() => {
+                let value_len = serialize_len(value.len()).ok_or(EditError::TooLong)?;
+                let parts = [tag.as_bytes(), &value_len, value];
+                let new = parts.iter().map(|part| part.len()).sum::<usize>();
+                let old = object.all.len();
+                self.splice(len, object.all, &parts, write);
+                return Ok(region.end + new - old);
+            }
+            Operation::Delete if rest.is_empty() => {
+                let old = object.all.len();
+                self.splice(len, object.all, &[], write);
+                return Ok(region.end - old);
+            }
+            _ => {}
+        }
+        let value_end = self.edit_in(len, object.value.clone(), rest, operation, write)?;
+        // Update the length of this constructed data object
+        let value_len = serialize_len(value_end - object.value.start).ok_or(EditError::TooLong)?;
+        let old = object.length.len();
+        self.splice(len, object.length, &[&value_len], write);
+        Ok(region.end + value_end + value_len.len() - object.value.end - old)
+    }
+
+    fn find(&self, region: Range<usize>, tag: Tag) -> Result<Object, EditError> {
+        let mut start = region.start;
+        while start < region.end {
+            let data = &self.buf[start..region.end];
+            let (found, value, rest) = parse_object(data, false).map_err(|kind| {
+                EditError::Malformed(TlvError {
+                    kind,
+                    offset: start,
+                })
+            })?;
+            let end = region.end - rest.len();
+            let value_start = end - value.len();
+            if found == tag {
+                let tag_end = start + found.as_bytes().len();
+                return Ok(Object {
+                    all: start..end,
+                    length: tag_end..value_start,
+                    value: value_start..end,
+                });
+            }
+            start = end;
+        }
+        Err(EditError::NotFound)
+    }
+
+    /// Replace `range` with the concatenation of `parts`, shifting the following data
+    fn splice(&mut self, len: &mut usize, range: Range<usize>, parts: &[&[u8]], write: bool) {
+        let new: usize = parts.iter().map(|part| part.len()).sum();
+        if write {
+            self.buf.copy_within(range.end..*len, range.start + new);
+            let mut offset = range.start;
+            for part in parts {
+                self.buf[offset..][..part.len()].copy_from_slice(part);
+                offset += part.len();
+            }
+        }
+        *len = *len - range.len() + new;
+    }
+}
+
+/// Ranges of the fields of a data object in the buffer
+struct Object {
+    all: Range<usize>,
+    length: Range<usize>,
+    value: Range<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tlv::TlvErrorKind;
+    use hex_literal::hex;
+
+    fn load<'a>(buf: &'a mut [u8], data: &[u8]) -> TlvEditor<'a> {
+        buf[..data.len()].copy_from_slice(data);
+        TlvEditor::new(buf, data.len()).unwrap()
+    }
+
+    const A: Tag = Tag::from_u8(0xA1);
+    const B: Tag = Tag::from_u8(0x81);
+    const C: Tag = Tag::from_u8(0x82);
+
+    #[test]
+    fn top_level() {
+        let mut buf = [0; 16];
+        let mut editor = load(&mut buf, &hex!("8101AA 8201BB"));
+        editor.replace(&[B], &hex!("1122")).unwrap();
+        assert_eq!(editor.as_bytes(), &hex!("81021122 8201BB"));
+        editor.delete(&[B]).unwrap();
+        assert_eq!(editor.as_bytes(), &hex!("8201BB"));
+        editor.insert(&[], B, &[]).unwrap();
+        assert_eq!(editor.as_bytes(), &hex!("8201BB 8100"));
+        assert_eq!(editor.len(), 5);
+    }
+
+    #[test]
+    fn nested() {
+        let mut buf = [0; 32];
+        let mut editor = load(&mut buf, &hex!("A108 A106 8101AA 8201BB 8401CC"));
+        editor.replace(&[A, A, C], &hex!("BBBB")).unwrap();
+        assert_eq!(editor.as_bytes(), &hex!("A109 A107 8101AA 8202BBBB 8401CC"));
+        editor.insert(&[A], C, &hex!("DD")).unwrap();
+        assert_eq!(
+            editor.as_bytes(),
+            &hex!("A10C A107 8101AA 8202BBBB 8201DD 8401CC")
+        );
+        editor.delete(&[A, A]).unwrap();
+        assert_eq!(editor.as_bytes(), &hex!("A103 8201DD 8401CC"));
+        assert_eq!(editor.delete(&[A, B]), Err(EditError::NotFound));
+        assert_eq!(editor.delete(&[]), Err(EditError::NotFound));
+    }
+
+    #[test]
+    fn length_field_grows() {
+        let mut buf = [0; 300];
+        let mut editor = load(&mut buf, &hex!("A105 A103 8101AA"));
+        editor.replace(&[A, A, B], &[0x55; 0x80]).unwrap();
+        let data = editor.as_bytes();
+        assert_eq!(&data[..10], &hex!("A181 86 A181 83 818180 55"));
+        assert_eq!(data.len(), 6 + 3 + 0x80);
+        editor.replace(&[A, A, B], &[]).unwrap();
+        assert_eq!(editor.as_bytes(), &hex!("A104 A102 8100"));
+    }
+
+    #[test]
+    fn buffer_full() {
+        let data = hex!("A103 8101AA");
+        let mut buf = data;
+        let mut editor = load(&mut buf, &data);
+        assert_eq!(
+            editor.replace(&[A, B], &hex!("AABB")),
+            Err(EditError::BufferFull)
+        );
+        assert_eq!(editor.as_bytes(), &data);
+        editor.replace(&[A, B], &hex!("BB")).unwrap();
+        assert_eq!(editor.as_bytes(), &hex!("A103 8101BB"));
+
+        // The value fits, but not the larger length of the enclosing data object
+        let mut buf = [0; 0x83];
+        let mut editor = load(&mut buf, &data);
+        assert_eq!(
+            editor.replace(&[A, B], &[0; 0x7F]),
+            Err(EditError::BufferFull)
+        );
+        assert_eq!(editor.as_bytes(), &data);
+        assert!(TlvEditor::new(&mut [0; 2], 3).is_none());
+    }
+
+    #[test]
+    fn malformed() {
+        let mut buf = [0; 16];
+        let mut editor = load(&mut buf, &hex!("8101AA A105 8101"));
+        let Err(EditError::Malformed(error)) = editor.delete(&[A, B]) else {
+            panic!("expected an error");
+        };
+        assert_eq!(error.kind(), TlvErrorKind::Truncated);
+        assert_eq!(error.offset(), 3);
+        assert_eq!(editor.delete(&[B]), Ok(()));
+    }
+}
@@ -0,0 +1,130 @@
+//! Macros building data objects and tag paths from literals
+
+/// Build a nested [`Tlv`](crate::tlv::Tlv) from `tag => value` pairs
+///
+/// Tags are integer literals, checked at compile time, or any expression convertible to a
+/// [`Tag`](crate::tlv::Tag) in parentheses. A value in braces is a list of `tag => value` pairs
+/// forming a constructed data object; any other value is a [`DataStream`](crate::command::DataStream).
+///
+/// ```
+/// use iso7816::command::DataStream;
+/// use iso7816::tlv::Tag;
+/// let aid = [0xA0, 0x00, 0x00, 0x03, 0x08];
+/// let label = Tag::from_u8(0x50);
+/// let fci = iso7816::tlv!(0x6F => {
+///     0x84 => &aid[..],
+///     0xA5 => {
+///         (label) => *b"PIV",
+///         0xBF0C => {},
+///     },
+/// });
+/// let mut buf = heapless::Vec::<u8, 32>::new();
+/// fci.to_writer(&mut buf).unwrap();
+/// assert_eq!(
+///     &*buf,
+///     &[
+///         0x6F, 0x11, 0x84, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08, 0xA5, 0x08, 0x50, 0x03, b'P',
+///         b'I', b'V', 0xBF, 0x0C, 0x00,
+///     ]
+/// );
+/// ```
+///
+/// Invalid tag literals do not compile:
+///
+/// ```compile_fail
+/// let _ = iso7816::tlv!(0x5F => [0x00]);
+/// ```
+#[macro_export]
+macro_rules! tlv {
+    ($tag:tt => { $($content:tt)* } $(,)?) => {
+        $crate::tlv::Tlv::new($crate::tlv!(@tag $tag), $crate::tlv!(@content $($content)*))
+    };
+    ($tag:tt => $value:expr $(,)?) => {
+        $crate::tlv::Tlv::new($crate::tlv!(@tag $tag), $value)
+    };
+
+    // The content of constructed data objects is a list of nested pairs ending with `()`
+    (@content) => { () };
+    (@content $tag:tt => { $($content:tt)* } $(, $($rest:tt)*)?) => {
+        ($crate::tlv!($tag => { $($content)* }), $crate::tlv!(@content $($($rest)*)?))
+    };
+    (@content $tag:tt => $value:expr $(, $($rest:tt)*)?) => {
+        ($crate::tlv!($tag => $value), $crate::tlv!(@content $($($rest)*)?))
+    };
+
+    (@tag $tag:literal) => {{
+        const TAG: $crate::tlv::Tag = $crate::tlv::Tag::from_u32($tag);
+        TAG
+    }};
+    (@tag $tag:tt) => {
+        $crate::tlv::Tag::from($tag)
+    };
+}
+
+/// Build an array of [`Tag`](crate::tlv::Tag)s, for example for
+/// [`get_data_object`](crate::tlv::get_data_object)
+///
+/// Tags are given as for [`tlv!`](crate::tlv!).
+///
+/// ```
+/// use iso7816::tlv::get_data_object;
+/// let data = [0x7F, 0x49, 0x04, 0x86, 0x02, 0x04, 0x01];
+/// let point = get_data_object(&iso7816::tag_path![0x7F49, 0x86], &data);
+/// assert_eq!(point, Some(&[0x04, 0x01][..]));
+/// ```
+#[macro_export]
+macro_rules! tag_path {
+    ($($tag:tt),* $(,)?) => {
+        [$($crate::tlv!(@tag $tag)),*]
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::DataSource;
+    use crate::tlv::{Tag, Tlv};
+    use hex_literal::hex;
+
+    #[test]
+    fn tlv() {
+        let point = hex!("0401");
+        let key = crate::tlv!(0x7F49 => { 0x86 => point });
+        assert_eq!(
+            key,
+            Tlv::new(
+                Tag::from_u16(0x7F49),
+                (Tlv::new(Tag::from_u8(0x86), point), ())
+            )
+        );
+        assert_eq!(key.len(), 7);
+
+        let algorithm = Tag::from_u8(0x80);
+        let crt = crate::tlv!(0xAC => {
+            (algorithm) => [0x11],
+            (Tag::from_u8(0x84)) => [0x9A],
+            0xA0 => { 0x81 => [0u8; 0] },
+        });
+        let mut buf = Vec::new();
+        crate::command::DataStream::to_writer(&crt, &mut buf).unwrap();
+        assert_eq!(buf, hex!("AC 0A 800111 84019A A002 8100"));
+
+        let empty = crate::tlv!(0x53 => {});
+        assert_eq!(empty.len(), 2);
+    }
+
+    #[test]
+    fn tag_path() {
+        let path = crate::tag_path![0x6F, 0xA5, 0xBF0C, (Tag::from_u16(0x9F6E))];
+        assert_eq!(
+            path,
+            [
+                Tag::from_u8(0x6F),
+                Tag::from_u8(0xA5),
+                Tag::from_u16(0xBF0C),
+                Tag::from_u16(0x9F6E)
+            ]
+        );
+        let empty: [Tag; 0] = crate::tag_path![];
+        assert!(empty.is_empty());
+    }
+}
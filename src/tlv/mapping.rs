@@ -0,0 +1,289 @@
+//! Mapping of Rust types to and from templates of data objects
+//!
+//! A template is the value of a constructed data object, made of tagged fields, like the FCI
+//! returned by SELECT. [`ToTlv`] writes a type as such a sequence of data objects, and [`FromTlv`]
+//! reads it back, with [`Fields`] doing the lookup of required, optional and repeated fields.
+//!
+//! ```
+//! use iso7816::tlv::{Fields, FromTlv, FromTlvError, Tag, TlvBuilder, ToTlv};
+//! use iso7816::command::Writer;
+//!
+//! const NAME: Tag = Tag::from_u8(0x5B);
+//! const LANGUAGE: Tag = Tag::from_u16(0x5F2D);
+//!
+//! #[derive(Debug, PartialEq)]
+//! struct Holder<'a> {
+//!     name: &'a [u8],
+//!     language: Option<&'a [u8]>,
+//! }
+//!
+//! impl ToTlv for Holder<'_> {
+//!     fn to_tlv<W: Writer>(&self, builder: &mut TlvBuilder<'_, W>) -> Result<(), W::Error> {
+//!         builder.primitive(NAME, self.name)?;
+//!         if let Some(language) = self.language {
+//!             builder.primitive(LANGUAGE, language)?;
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! impl<'a> FromTlv<'a> for Holder<'a> {
+//!     fn from_fields(fields: &Fields<'a>) -> Result<Self, FromTlvError> {
+//!         Ok(Self {
+//!             name: fields.required(NAME)?,
+//!             language: fields.optional(LANGUAGE)?,
+//!         })
+//!     }
+//! }
+//!
+//! let holder = Holder { name: b"ALICE", language: Some(b"en") };
+//! let mut buf = heapless::Vec::<u8, 32>::new();
+//! holder.write_tlv(&mut buf).unwrap();
+//! assert_eq!(&buf, b"\x5B\x05ALICE\x5F\x2D\x02en");
+//! assert_eq!(Holder::from_tlv(&buf), Ok(holder));
+//! assert_eq!(Holder::from_tlv(b"\x5F\x2D\x02en"), Err(FromTlvError::Missing(NAME)));
+//! ```
+
+use crate::command::Writer;
+use crate::Status;
+
+use super::{iter, Iter, Tag, TlvBuilder, TlvError};
+
+/// Types written as a sequence of data objects
+pub trait ToTlv {
+    /// Write the fields of `self`
+    ///
+    /// This can be called twice for a nested template, once to compute its length and once to
+    /// write it, see [`TlvBuilder::constructed`], so it must write the same data both times.
+    fn to_tlv<W: Writer>(&self, builder: &mut TlvBuilder<'_, W>) -> Result<(), W::Error>;
+
+    fn write_tlv<W: Writer>(&self, writer: &mut W) -> Result<(), W::Error> {
+        self.to_tlv(&mut TlvBuilder::new(writer))
+    }
+}
+
+/// Types read from a sequence of data objects
+pub trait FromTlv<'a>: Sized {
+    fn from_fields(fields: &Fields<'a>) -> Result<Self, FromTlvError>;
+
+    fn from_tlv(data: &'a [u8]) -> Result<Self, FromTlvError> {
+        Self::from_fields(&Fields::new(data)?)
+    }
+}
+
+/// Error returned by [`FromTlv`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FromTlvError {
+    /// The data is not valid BER-TLV
+    Malformed(TlvError),
+    /// A required field is missing
+    Missing(Tag),
+    /// A field that can only be present once is repeated
+    Duplicate(Tag),
+    /// The value of a field could not be parsed
+    InvalidValue(Tag),
+}
+
+impl From<TlvError> for FromTlvError {
+    fn from(error: TlvError) -> Self {
+        Self::Malformed(error)
+    }
+}
+
+impl From<FromTlvError> for Status {
+    fn from(_: FromTlvError) -> Self {
+        Status::IncorrectDataParameter
+    }
+}
+
+/// Fields of a template, for implementing [`FromTlv`]
+///
+/// Fields with unknown tags are ignored, and the order of the fields does not matter.
+#[derive(Clone, Copy, Debug)]
+pub struct Fields<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    /// Check that `data` is a valid sequence of data objects
+    pub fn new(data: &'a [u8]) -> Result<Self, TlvError> {
+        iter(data).try_for_each(|object| object.map(drop))?;
+        Ok(Self { data })
+    }
+
+    /// Value of the field `tag`, which can be absent
+    pub fn optional(&self, tag: Tag) -> Result<Option<&'a [u8]>, FromTlvError> {
+        let mut values = self.repeated(tag);
+        let value = values.next();
+        if values.next().is_some() {
+            return Err(FromTlvError::Duplicate(tag));
+        }
+        Ok(value)
+    }
+
+    /// Value of the field `tag`, which must be present
+    pub fn required(&self, tag: Tag) -> Result<&'a [u8], FromTlvError> {
+        self.optional(tag)?.ok_or(FromTlvError::Missing(tag))
+    }
+
+    /// Values of all the fields `tag`, in order
+    pub fn repeated(&self, tag: Tag) -> Repeated<'a> {
+        Repeated {
+            iter: iter(self.data),
+            tag,
+        }
+    }
+
+    /// Like [`optional`](Self::optional), converting the value with [`TryFrom`]
+    pub fn optional_as<T: TryFrom<&'a [u8]>>(&self, tag: Tag) -> Result<Option<T>, FromTlvError> {
+        self.optional(tag)?
+            .map(|value| T::try_from(value).map_err(|_| FromTlvError::InvalidValue(tag)))
+            .transpose()
+    }
+
+    /// Like [`required`](Self::required), converting the value with [`TryFrom`]
+    pub fn required_as<T: TryFrom<&'a [u8]>>(&self, tag: Tag) -> Result<T, FromTlvError> {
+        self.optional_as(tag)?.ok_or(FromTlvError::Missing(tag))
+    }
+
+    /// Nested template `tag`, which can be absent
+    pub fn optional_template<T: FromTlv<'a>>(&self, tag: Tag) -> Result<Option<T>, FromTlvError> {
+        self.optional(tag)?.map(T::from_tlv).transpose()
+    }
+
+    /// Nested template `tag`, which must be present
+    pub fn required_template<T: FromTlv<'a>>(&self, tag: Tag) -> Result<T, FromTlvError> {
+        self.optional_template(tag)?
+            .ok_or(FromTlvError::Missing(tag))
+    }
+}
+
+/// Iterator over the values of a repeated field, see [`Fields::repeated`]
+#[derive(Clone, Debug)]
+pub struct Repeated<'a> {
+    iter: Iter<'a>,
+    tag: Tag,
+}
+
+impl<'a> Iterator for Repeated<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The data was checked by `Fields::new`
+        self.iter
+            .by_ref()
+            .map_while(Result::ok)
+            .find(|(tag, _)| *tag == self.tag)
+            .map(|(_, value)| value)
+    }
+}
+
+impl core::iter::FusedIterator for Repeated<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::key_reference::KeyReference;
+    use hex_literal::hex;
+
+    const TEMPLATE: Tag = Tag::from_u8(0xA4);
+    const REFERENCE: Tag = Tag::from_u8(0x83);
+    const ALGORITHM: Tag = Tag::from_u8(0x80);
+
+    #[derive(Debug, PartialEq)]
+    struct Crt<'a> {
+        reference: Option<KeyReference>,
+        algorithms: heapless::Vec<&'a [u8], 2>,
+    }
+
+    impl ToTlv for Crt<'_> {
+        fn to_tlv<W: Writer>(&self, builder: &mut TlvBuilder<'_, W>) -> Result<(), W::Error> {
+            if let Some(reference) = self.reference {
+                builder.primitive(REFERENCE, reference.as_slice())?;
+            }
+            for algorithm in &self.algorithms {
+                builder.primitive(ALGORITHM, *algorithm)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl<'a> FromTlv<'a> for Crt<'a> {
+        fn from_fields(fields: &Fields<'a>) -> Result<Self, FromTlvError> {
+            Ok(Self {
+                reference: fields.optional_as(REFERENCE)?,
+                algorithms: fields.repeated(ALGORITHM).take(2).collect(),
+            })
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Environment<'a> {
+        crt: Crt<'a>,
+    }
+
+    impl ToTlv for Environment<'_> {
+        fn to_tlv<W: Writer>(&self, builder: &mut TlvBuilder<'_, W>) -> Result<(), W::Error> {
+            builder.constructed(TEMPLATE, |builder| self.crt.to_tlv(builder))?;
+            Ok(())
+        }
+    }
+
+    impl<'a> FromTlv<'a> for Environment<'a> {
+        fn from_fields(fields: &Fields<'a>) -> Result<Self, FromTlvError> {
+            Ok(Self {
+                crt: fields.required_template(TEMPLATE)?,
+            })
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let environment = Environment {
+            crt: Crt {
+                reference: Some(KeyReference::specific(1)),
+                algorithms: [&[0x11][..], &[0x22]].into_iter().collect(),
+            },
+        };
+        let mut buf = Vec::new();
+        environment.write_tlv(&mut buf).unwrap();
+        assert_eq!(buf, hex!("A409 830181 800111 800122"));
+        assert_eq!(Environment::from_tlv(&buf), Ok(environment));
+
+        let empty = Environment {
+            crt: Crt {
+                reference: None,
+                algorithms: heapless::Vec::new(),
+            },
+        };
+        assert_eq!(Environment::from_tlv(&hex!("A400 9000")), Ok(empty));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Environment::from_tlv(&hex!("B400")),
+            Err(FromTlvError::Missing(TEMPLATE))
+        );
+        assert_eq!(
+            Environment::from_tlv(&hex!("A400 A400")),
+            Err(FromTlvError::Duplicate(TEMPLATE))
+        );
+        assert_eq!(
+            Environment::from_tlv(&hex!("A404 83028100")),
+            Err(FromTlvError::InvalidValue(REFERENCE))
+        );
+        assert!(matches!(
+            Environment::from_tlv(&hex!("A403 8302")),
+            Err(FromTlvError::Malformed(_))
+        ));
+        assert!(matches!(
+            Crt::from_tlv(&hex!("800111 80")),
+            Err(FromTlvError::Malformed(_))
+        ));
+        assert_eq!(
+            Status::from(FromTlvError::Missing(TEMPLATE)),
+            Status::IncorrectDataParameter
+        );
+    }
+}
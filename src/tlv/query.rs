@@ -0,0 +1,183 @@
+//! Path queries over nested data objects
+
+use super::{iter, Tag};
+
+/// Step of a [`query`] path
+///
+/// A step matches data objects with a given tag, or any tag. By default the first matching data
+/// object for which the rest of the path matches is used; [`nth`](Self::nth) selects a single
+/// occurrence instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step {
+    tag: Option<Tag>,
+    occurrence: Option<usize>,
+}
+
+impl Step {
+    /// Data objects with the tag `tag`
+    pub const fn tag(tag: Tag) -> Self {
+        Self {
+            tag: Some(tag),
+            occurrence: None,
+        }
+    }
+
+    /// Data objects with any tag
+    pub const fn any() -> Self {
+        Self {
+            tag: None,
+            occurrence: None,
+        }
+    }
+
+    /// Only the `n`-th matching data object, starting from 0
+    pub const fn nth(mut self, n: usize) -> Self {
+        self.occurrence = Some(n);
+        self
+    }
+
+    fn matches(&self, tag: Tag) -> bool {
+        self.tag.is_none() || self.tag == Some(tag)
+    }
+}
+
+impl From<Tag> for Step {
+    fn from(tag: Tag) -> Self {
+        Self::tag(tag)
+    }
+}
+
+/// Data object found by [`query`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Match<'a> {
+    tag: Tag,
+    value: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Match<'a> {
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+
+    /// Offset of the value in the data given to [`query`]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+/// Find the data object at the end of `path`
+///
+/// Each step but the last only matches constructed data objects, whose value is searched by the
+/// next step. Malformed data ends the search at its level.
+///
+/// ```
+/// use iso7816::tlv::{query, Step, Tag};
+/// // GET STATUS response with two applications
+/// let data = [
+///     0xE3, 0x05, 0x4F, 0x03, 0xA0, 0x00, 0x01, //
+///     0xE3, 0x08, 0x4F, 0x02, 0xA0, 0x02, 0x9F, 0x70, 0x01, 0x07,
+/// ];
+/// let aid = Tag::from_u8(0x4F);
+/// let second = query(&[Step::tag(Tag::from_u8(0xE3)).nth(1), aid.into()], &data).unwrap();
+/// assert_eq!(second.value(), &[0xA0, 0x02]);
+/// assert_eq!(second.offset(), 11);
+///
+/// // First application with a life cycle state
+/// let state = query(&[Step::any(), Step::tag(Tag::from_u16(0x9F70))], &data).unwrap();
+/// assert_eq!(state.value(), &[0x07]);
+/// ```
+pub fn query<'a>(path: &[Step], data: &'a [u8]) -> Option<Match<'a>> {
+    find(path, data, 0)
+}
+
+fn find<'a>(path: &[Step], data: &'a [u8], base: usize) -> Option<Match<'a>> {
+    let (step, rest) = path.split_first()?;
+    let mut occurrence = 0;
+    for object in iter(data) {
+        let (tag, value) = object.ok()?;
+        if !step.matches(tag) || !(rest.is_empty() || tag.is_constructed()) {
+            continue;
+        }
+        // `value` is a subslice of `data`
+        let offset = base + (value.as_ptr() as usize - data.as_ptr() as usize);
+        let found = if rest.is_empty() {
+            Some(Match { tag, value, offset })
+        } else {
+            find(rest, value, offset)
+        };
+        match step.occurrence {
+            Some(n) if n == occurrence => return found,
+            Some(_) => occurrence += 1,
+            None if found.is_some() => return found,
+            None => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    // EF.DIR with two application templates
+    const DIR: [u8; 24] = hex!("61 09 4F 04 A0000001 50 01 41 61 0B 4F 03 A00002 50 04 42424242");
+
+    #[test]
+    fn repeated() {
+        let template = Step::tag(Tag::from_u8(0x61));
+        let aid = Step::tag(Tag::from_u8(0x4F));
+        let label = Step::tag(Tag::from_u8(0x50));
+
+        let first = query(&[template, aid], &DIR).unwrap();
+        assert_eq!(first.tag(), Tag::from_u8(0x4F));
+        assert_eq!(first.value(), &hex!("A0000001"));
+        assert_eq!(first.offset(), 4);
+
+        let second = query(&[template.nth(1), label], &DIR).unwrap();
+        assert_eq!(second.value(), b"BBBB");
+        assert_eq!(&DIR[second.offset()..][..4], b"BBBB");
+
+        assert_eq!(query(&[template.nth(2), aid], &DIR), None);
+        assert_eq!(query(&[template, aid.nth(1)], &DIR), None);
+    }
+
+    #[test]
+    fn wildcards() {
+        // The first template has no `5F50`, the search continues in the second one
+        let data = hex!("A1 03 800101 A2 05 5F50 02 6162");
+        let url = query(&[Step::any(), Step::tag(Tag::from_u16(0x5F50))], &data).unwrap();
+        assert_eq!(url.value(), b"ab");
+        assert_eq!(url.offset(), 10);
+
+        let last = query(&[Step::any().nth(1), Step::any()], &data).unwrap();
+        assert_eq!(last.tag(), Tag::from_u16(0x5F50));
+
+        // Primitive data objects are not searched
+        let data = hex!("80 03 800101 A0 03 800102");
+        let inner = query(&[Step::any(), Step::any()], &data).unwrap();
+        assert_eq!(inner.value(), &hex!("02"));
+        assert_eq!(inner.offset(), 9);
+        assert_eq!(
+            query(&[Step::any()], &data).map(|m| m.tag()),
+            Some(Tag::from_u8(0x80))
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(query(&[], &DIR), None);
+        assert_eq!(
+            query(&[Step::tag(Tag::from_u8(0x50))], &hex!("61 09 4F")),
+            None
+        );
+        // Data before the malformed data object is still searched
+        let found = query(&[Step::tag(Tag::from_u8(0x4F))], &hex!("4F 01 AA 50 05"));
+        assert_eq!(found.map(|m| m.value()), Some(&hex!("AA")[..]));
+    }
+}
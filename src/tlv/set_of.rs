@@ -0,0 +1,123 @@
+//! Canonical ordering of DER SET OF
+
+use core::cmp::Ordering;
+
+use super::{take_len, take_tag};
+use crate::command::{DataSource, DataStream, Writer};
+
+/// Tag, length and value of an encoded data object, or the whole data if it is malformed
+fn key(object: &[u8]) -> (&[u8], usize, &[u8]) {
+    let Some((_, rest)) = take_tag(object) else {
+        return (object, 0, &[]);
+    };
+    let tag = &object[..object.len() - rest.len()];
+    match take_len(rest) {
+        Some((len, value)) => (tag, len, value),
+        None => (object, 0, &[]),
+    }
+}
+
+fn compare(a: &&[u8], b: &&[u8]) -> Ordering {
+    key(a).cmp(&key(b))
+}
+
+/// Sort encoded data objects in the canonical order of DER SET OF
+///
+/// Data objects are ordered by the encoding of their tag, then by length, then by value.
+///
+/// ```
+/// use iso7816::tlv::sort_set_of;
+/// let mut objects = [&[0x81, 0x01, 0x02][..], &[0x04, 0x01, 0xFF], &[0x81, 0x00]];
+/// sort_set_of(&mut objects);
+/// assert_eq!(objects, [&[0x04, 0x01, 0xFF][..], &[0x81, 0x00], &[0x81, 0x01, 0x02]]);
+/// ```
+pub fn sort_set_of(objects: &mut [&[u8]]) {
+    objects.sort_unstable_by(compare);
+}
+
+/// Encoded data objects written in the canonical order of DER SET OF, see [`sort_set_of`]
+///
+/// ```
+/// use iso7816::command::DataStream;
+/// use iso7816::tlv::{SetOf, Tag, Tlv};
+/// let mut objects = [&[0x04, 0x01, 0x02][..], &[0x04, 0x01, 0x01]];
+/// let set = Tlv::new(Tag::from_u8(0x31), SetOf::new(&mut objects));
+/// let mut buf = heapless::Vec::<u8, 8>::new();
+/// set.to_writer(&mut buf).unwrap();
+/// assert_eq!(&*buf, &[0x31, 0x06, 0x04, 0x01, 0x01, 0x04, 0x01, 0x02]);
+/// ```
+#[derive(Debug, PartialEq, Eq)]
+pub struct SetOf<'a, 'b> {
+    objects: &'b [&'a [u8]],
+}
+
+impl<'a, 'b> SetOf<'a, 'b> {
+    /// Sort `objects` and write them in that order
+    pub fn new(objects: &'b mut [&'a [u8]]) -> Self {
+        sort_set_of(objects);
+        Self { objects }
+    }
+
+    pub fn objects(&self) -> &'b [&'a [u8]] {
+        self.objects
+    }
+}
+
+impl DataSource for SetOf<'_, '_> {
+    fn len(&self) -> usize {
+        self.objects.iter().map(|object| object.len()).sum()
+    }
+}
+
+impl<W: Writer> DataStream<W> for SetOf<'_, '_> {
+    fn to_writer(&self, writer: &mut W) -> Result<(), W::Error> {
+        for object in self.objects {
+            writer.write_all(object)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn order() {
+        let long = [&hex!("04 8180")[..], &[0; 0x80]].concat();
+        let mut objects = [
+            &hex!("5F20 01 00")[..],
+            &long,
+            &hex!("04 02 0001"),
+            &hex!("04 01 FF"),
+            &hex!("30 00"),
+            &hex!("04 01 00"),
+            &hex!("80 00"),
+        ];
+        sort_set_of(&mut objects);
+        assert_eq!(
+            objects,
+            [
+                &hex!("04 01 00")[..],
+                &hex!("04 01 FF"),
+                &hex!("04 02 0001"),
+                &long,
+                &hex!("30 00"),
+                &hex!("5F20 01 00"),
+                &hex!("80 00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn write() {
+        let mut objects = [&hex!("0C 01 62")[..], &hex!("0C 01 61")];
+        let set = SetOf::new(&mut objects);
+        assert_eq!(set.len(), 6);
+        assert_eq!(set.objects()[0], &hex!("0C 01 61"));
+        let mut buf = Vec::new();
+        set.to_writer(&mut buf).unwrap();
+        assert_eq!(buf, hex!("0C0161 0C0162"));
+    }
+}
@@ -0,0 +1,155 @@
+//! Data objects whose length is only known once their value is written
+
+use crate::command::{writer::Error as _, Backpatch};
+
+use super::{serialize_len, serialize_long_len, Tag};
+
+/// Data object being written, whose length is filled in by [`finish`](Self::finish)
+///
+/// [`Tlv`](super::Tlv) and [`TlvBuilder`](super::TlvBuilder) need the length of a value before
+/// writing it. When the value is generated on the fly, `StreamingTlv` writes the tag and reserves
+/// room for the longest length field, then the value is written directly to the writer. Once it is
+/// complete, [`finish`](Self::finish) writes the actual length and moves the value back if the
+/// length field is shorter than reserved, so the result is the same as with [`Tlv`](super::Tlv).
+///
+/// ```
+/// use iso7816::command::Writer;
+/// use iso7816::tlv::{StreamingTlv, Tag};
+/// let mut buf = heapless::Vec::<u8, 16>::new();
+/// let signature = StreamingTlv::start(&mut buf, Tag::from_u8(0x9E)).unwrap();
+/// for chunk in [&[0x01, 0x02][..], &[0x03]] {
+///     buf.write_all(chunk).unwrap();
+/// }
+/// signature.finish(&mut buf).unwrap();
+/// assert_eq!(&buf, &[0x9E, 0x03, 0x01, 0x02, 0x03]);
+/// ```
+///
+/// Data objects can be nested, finishing the innermost first. The writer needs room for the
+/// reserved length fields (3 bytes, or 5 with [`start_with_long_lengths`](Self::start_with_long_lengths))
+/// while the value is written.
+#[derive(Debug)]
+#[must_use = "the length is only written by `finish`"]
+pub struct StreamingTlv {
+    /// Offset of the reserved length field
+    len_offset: usize,
+    /// Offset of the value
+    value_offset: usize,
+    long_lengths: bool,
+}
+
+impl StreamingTlv {
+    /// Write `tag` and reserve a length field for values up to `0xFFFF` bytes
+    pub fn start<W: Backpatch>(writer: &mut W, tag: Tag) -> Result<Self, W::Error> {
+        Self::start_inner(writer, tag, false)
+    }
+
+    /// Like [`start`](Self::start), allowing the three and four byte length forms (`83` and `84`)
+    pub fn start_with_long_lengths<W: Backpatch>(
+        writer: &mut W,
+        tag: Tag,
+    ) -> Result<Self, W::Error> {
+        Self::start_inner(writer, tag, true)
+    }
+
+    fn start_inner<W: Backpatch>(
+        writer: &mut W,
+        tag: Tag,
+        long_lengths: bool,
+    ) -> Result<Self, W::Error> {
+        writer.write_all(tag.as_bytes())?;
+        let len_offset = writer.written_mut().len();
+        let reserved: &[u8] = if long_lengths {
+            &[0x84, 0, 0, 0, 0]
+        } else {
+            &[0x82, 0, 0]
+        };
+        writer.write_all(reserved)?;
+        Ok(Self {
+            len_offset,
+            value_offset: len_offset + reserved.len(),
+            long_lengths,
+        })
+    }
+
+    /// Write the length of the data written since [`start`](Self::start)
+    pub fn finish<W: Backpatch>(self, writer: &mut W) -> Result<(), W::Error> {
+        let written = writer.written_mut();
+        debug_assert!(
+            written.len() >= self.value_offset,
+            "Data was removed from the writer"
+        );
+        let value_len = written.len() - self.value_offset;
+        let len = if self.long_lengths {
+            serialize_long_len(value_len).ok_or_else(|| {
+                W::Error::failed_serialization("Data is longer than 0xFFFFFFFF bytes")
+            })
+        } else {
+            serialize_len(value_len)
+                .ok_or_else(|| W::Error::failed_serialization("Data is longer than 0xFFFF bytes"))
+        }?;
+        let end = self.len_offset + len.len();
+        written.copy_within(self.value_offset.., end);
+        written[self.len_offset..end].copy_from_slice(&len);
+        writer.truncate(end + value_len);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{DataStream, Writer};
+    use crate::tlv::Tlv;
+    use hex_literal::hex;
+
+    #[test]
+    fn nested() {
+        let mut buf = Vec::new();
+        let outer = StreamingTlv::start(&mut buf, Tag::from_u8(0x7C)).unwrap();
+        let inner = StreamingTlv::start(&mut buf, Tag::from_u8(0x82)).unwrap();
+        buf.write_all(&[0x55; 0x80]).unwrap();
+        inner.finish(&mut buf).unwrap();
+        let empty = StreamingTlv::start(&mut buf, Tag::from_u16(0x5F2D)).unwrap();
+        empty.finish(&mut buf).unwrap();
+        outer.finish(&mut buf).unwrap();
+
+        let expected = Tlv::new(
+            Tag::from_u8(0x7C),
+            (
+                Tlv::new(Tag::from_u8(0x82), [0x55; 0x80]),
+                Tlv::new(Tag::from_u16(0x5F2D), [0u8; 0]),
+            ),
+        );
+        let mut serialized = Vec::new();
+        expected.to_writer(&mut serialized).unwrap();
+        assert_eq!(buf, serialized);
+        assert_eq!(&buf[..7], &hex!("7C 81 86 82 81 80 55"));
+    }
+
+    #[test]
+    fn long_lengths() {
+        let mut buf = Vec::new();
+        let tlv = StreamingTlv::start_with_long_lengths(&mut buf, Tag::from_u8(0x53)).unwrap();
+        buf.write_all(&[0; 0x10000]).unwrap();
+        tlv.finish(&mut buf).unwrap();
+        assert_eq!(&buf[..5], &hex!("53 83 010000"));
+        assert_eq!(buf.len(), 5 + 0x10000);
+
+        let mut buf = Vec::new();
+        let tlv = StreamingTlv::start(&mut buf, Tag::from_u8(0x53)).unwrap();
+        buf.write_all(&[0; 0x10000]).unwrap();
+        assert!(tlv.finish(&mut buf).is_err());
+    }
+
+    #[test]
+    fn buffer_full() {
+        // The reserved length field does not fit, even though the result would
+        let mut buf = heapless::Vec::<u8, 3>::new();
+        assert!(StreamingTlv::start(&mut buf, Tag::from_u8(0x53)).is_err());
+        let mut buf = heapless::Vec::<u8, 5>::new();
+        let tlv = StreamingTlv::start(&mut buf, Tag::from_u8(0x53)).unwrap();
+        assert!(buf.write_all(&[0; 2]).is_err());
+        tlv.finish(&mut buf).unwrap();
+        assert_eq!(&buf, &hex!("53 01 00"));
+    }
+}
@@ -0,0 +1,207 @@
+//! Interindustry data object tags (ISO 7816-6)
+//!
+//! Tags of the data objects whose meaning is defined across applications, for code to read
+//! `tags::APPLICATION_LABEL` instead of `Tag::from_u8(0x50)`. The templates used by the commands
+//! of ISO 7816-4 are listed too, the tags of their fields are context specific and live with the
+//! commands, see for example [`file::fci`](crate::file::fci).
+//!
+//! ```
+//! use iso7816::tlv::{get_data_object, tags};
+//! let data = [0x61, 0x07, 0x50, 0x05, b'H', b'E', b'L', b'L', b'O'];
+//! let label = get_data_object(&[tags::APPLICATION_TEMPLATE, tags::APPLICATION_LABEL], &data);
+//! assert_eq!(label, Some(&b"HELLO"[..]));
+//! ```
+
+use super::Tag;
+
+/// Country code and national data
+pub const COUNTRY_CODE_AND_NATIONAL_DATA: Tag = Tag::from_u8(0x41);
+/// Issuer identification number
+pub const ISSUER_IDENTIFICATION_NUMBER: Tag = Tag::from_u8(0x42);
+/// Card service data, see [`CardServiceData`](crate::atr::historical::CardServiceData)
+pub const CARD_SERVICE_DATA: Tag = Tag::from_u8(0x43);
+/// Initial access data
+pub const INITIAL_ACCESS_DATA: Tag = Tag::from_u8(0x44);
+/// Card issuer's data
+pub const CARD_ISSUER_DATA: Tag = Tag::from_u8(0x45);
+/// Pre-issuing data
+pub const PRE_ISSUING_DATA: Tag = Tag::from_u8(0x46);
+/// Card capabilities
+pub const CARD_CAPABILITIES: Tag = Tag::from_u8(0x47);
+/// Status information
+pub const STATUS_INFORMATION: Tag = Tag::from_u8(0x48);
+/// Extended header list
+pub const EXTENDED_HEADER_LIST: Tag = Tag::from_u8(0x4D);
+/// Application identifier, see [`Aid`](crate::Aid)
+pub const APPLICATION_IDENTIFIER: Tag = Tag::from_u8(0x4F);
+/// Application label
+pub const APPLICATION_LABEL: Tag = Tag::from_u8(0x50);
+/// File reference, a path
+pub const FILE_REFERENCE: Tag = Tag::from_u8(0x51);
+/// Command to perform
+pub const COMMAND_TO_PERFORM: Tag = Tag::from_u8(0x52);
+/// Discretionary data
+pub const DISCRETIONARY_DATA: Tag = Tag::from_u8(0x53);
+/// Track 1 of the application
+pub const APPLICATION_TRACK_1: Tag = Tag::from_u8(0x56);
+/// Track 2 of the application
+pub const APPLICATION_TRACK_2: Tag = Tag::from_u8(0x57);
+/// Track 3 of the application
+pub const APPLICATION_TRACK_3: Tag = Tag::from_u8(0x58);
+/// Card expiration date
+pub const CARD_EXPIRATION_DATE: Tag = Tag::from_u8(0x59);
+/// Primary account number
+pub const PRIMARY_ACCOUNT_NUMBER: Tag = Tag::from_u8(0x5A);
+/// Name of an individual
+pub const NAME: Tag = Tag::from_u8(0x5B);
+/// Tag list
+pub const TAG_LIST: Tag = Tag::from_u8(0x5C);
+/// Header list
+pub const HEADER_LIST: Tag = Tag::from_u8(0x5D);
+/// Login data
+pub const LOGIN_DATA: Tag = Tag::from_u8(0x5E);
+
+/// Cardholder name
+pub const CARDHOLDER_NAME: Tag = Tag::from_u16(0x5F20);
+/// Track 1 of the card
+pub const CARD_TRACK_1: Tag = Tag::from_u16(0x5F21);
+/// Track 2 of the card
+pub const CARD_TRACK_2: Tag = Tag::from_u16(0x5F22);
+/// Track 3 of the card
+pub const CARD_TRACK_3: Tag = Tag::from_u16(0x5F23);
+/// Application expiration date
+pub const APPLICATION_EXPIRATION_DATE: Tag = Tag::from_u16(0x5F24);
+/// Application effective date
+pub const APPLICATION_EFFECTIVE_DATE: Tag = Tag::from_u16(0x5F25);
+/// Card effective date
+pub const CARD_EFFECTIVE_DATE: Tag = Tag::from_u16(0x5F26);
+/// Interchange control
+pub const INTERCHANGE_CONTROL: Tag = Tag::from_u16(0x5F27);
+/// Country code
+pub const COUNTRY_CODE: Tag = Tag::from_u16(0x5F28);
+/// Interchange profile
+pub const INTERCHANGE_PROFILE: Tag = Tag::from_u16(0x5F29);
+/// Currency code
+pub const CURRENCY_CODE: Tag = Tag::from_u16(0x5F2A);
+/// Date of birth
+pub const DATE_OF_BIRTH: Tag = Tag::from_u16(0x5F2B);
+/// Cardholder nationality
+pub const CARDHOLDER_NATIONALITY: Tag = Tag::from_u16(0x5F2C);
+/// Language preferences
+pub const LANGUAGE_PREFERENCES: Tag = Tag::from_u16(0x5F2D);
+/// Cardholder biometric data
+pub const CARDHOLDER_BIOMETRIC_DATA: Tag = Tag::from_u16(0x5F2E);
+/// PIN usage policy
+pub const PIN_USAGE_POLICY: Tag = Tag::from_u16(0x5F2F);
+/// Service code
+pub const SERVICE_CODE: Tag = Tag::from_u16(0x5F30);
+/// Transaction counter
+pub const TRANSACTION_COUNTER: Tag = Tag::from_u16(0x5F32);
+/// Transaction date
+pub const TRANSACTION_DATE: Tag = Tag::from_u16(0x5F33);
+/// Card sequence number
+pub const CARD_SEQUENCE_NUMBER: Tag = Tag::from_u16(0x5F34);
+/// Sex
+pub const SEX: Tag = Tag::from_u16(0x5F35);
+/// Currency exponent
+pub const CURRENCY_EXPONENT: Tag = Tag::from_u16(0x5F36);
+/// Static internal authentication, one step
+pub const STATIC_INTERNAL_AUTHENTICATION: Tag = Tag::from_u16(0x5F37);
+/// Cardholder portrait image
+pub const CARDHOLDER_PORTRAIT_IMAGE: Tag = Tag::from_u16(0x5F40);
+/// Element list
+pub const ELEMENT_LIST: Tag = Tag::from_u16(0x5F41);
+/// Address
+pub const ADDRESS: Tag = Tag::from_u16(0x5F42);
+/// Cardholder handwritten signature image
+pub const CARDHOLDER_SIGNATURE_IMAGE: Tag = Tag::from_u16(0x5F43);
+/// Application image
+pub const APPLICATION_IMAGE: Tag = Tag::from_u16(0x5F44);
+/// Display message
+pub const DISPLAY_MESSAGE: Tag = Tag::from_u16(0x5F45);
+/// Timer
+pub const TIMER: Tag = Tag::from_u16(0x5F46);
+/// Message reference
+pub const MESSAGE_REFERENCE: Tag = Tag::from_u16(0x5F47);
+/// Cardholder private key
+pub const CARDHOLDER_PRIVATE_KEY: Tag = Tag::from_u16(0x5F48);
+/// Cardholder public key
+pub const CARDHOLDER_PUBLIC_KEY: Tag = Tag::from_u16(0x5F49);
+/// Public key of the certification authority
+pub const CERTIFICATION_AUTHORITY_PUBLIC_KEY: Tag = Tag::from_u16(0x5F4A);
+/// Certificate holder authorization
+pub const CERTIFICATE_HOLDER_AUTHORIZATION: Tag = Tag::from_u16(0x5F4C);
+/// Integrated circuit manufacturer identifier
+pub const IC_MANUFACTURER_IDENTIFIER: Tag = Tag::from_u16(0x5F4D);
+/// Certificate content
+pub const CERTIFICATE_CONTENT: Tag = Tag::from_u16(0x5F4E);
+/// Uniform resource locator
+pub const UNIFORM_RESOURCE_LOCATOR: Tag = Tag::from_u16(0x5F50);
+/// Answer to reset, see [`Atr`](crate::atr::Atr)
+pub const ANSWER_TO_RESET: Tag = Tag::from_u16(0x5F51);
+/// Historical bytes, see [`HistoricalBytes`](crate::atr::historical::HistoricalBytes)
+pub const HISTORICAL_BYTES: Tag = Tag::from_u16(0x5F52);
+
+/// Application template
+pub const APPLICATION_TEMPLATE: Tag = Tag::from_u8(0x61);
+/// File control parameters template, see [`Fci`](crate::file::fci::Fci)
+pub const FCP_TEMPLATE: Tag = Tag::from_u8(0x62);
+/// Wrapper
+pub const WRAPPER: Tag = Tag::from_u8(0x63);
+/// File management data template
+pub const FMD_TEMPLATE: Tag = Tag::from_u8(0x64);
+/// Cardholder related data
+pub const CARDHOLDER_RELATED_DATA: Tag = Tag::from_u8(0x65);
+/// Card data
+pub const CARD_DATA: Tag = Tag::from_u8(0x66);
+/// Authentication data
+pub const AUTHENTICATION_DATA: Tag = Tag::from_u8(0x67);
+/// Special user requirements
+pub const SPECIAL_USER_REQUIREMENTS: Tag = Tag::from_u8(0x68);
+/// Login template
+pub const LOGIN_TEMPLATE: Tag = Tag::from_u8(0x6A);
+/// Qualified name
+pub const QUALIFIED_NAME: Tag = Tag::from_u8(0x6B);
+/// Cardholder image template
+pub const CARDHOLDER_IMAGE_TEMPLATE: Tag = Tag::from_u8(0x6C);
+/// Application image template
+pub const APPLICATION_IMAGE_TEMPLATE: Tag = Tag::from_u8(0x6D);
+/// Application related data
+pub const APPLICATION_RELATED_DATA: Tag = Tag::from_u8(0x6E);
+/// File control information template
+pub const FCI_TEMPLATE: Tag = Tag::from_u8(0x6F);
+/// Discretionary data objects
+pub const DISCRETIONARY_TEMPLATE: Tag = Tag::from_u8(0x73);
+/// Compatible tag allocation authority
+pub const COMPATIBLE_TAG_ALLOCATION_AUTHORITY: Tag = Tag::from_u8(0x78);
+/// Coexistent tag allocation authority
+pub const COEXISTENT_TAG_ALLOCATION_AUTHORITY: Tag = Tag::from_u8(0x79);
+/// Security support template
+pub const SECURITY_SUPPORT_TEMPLATE: Tag = Tag::from_u8(0x7A);
+/// Security environment template
+pub const SECURITY_ENVIRONMENT_TEMPLATE: Tag = Tag::from_u8(0x7B);
+/// Dynamic authentication template, the data of GENERAL AUTHENTICATE
+pub const DYNAMIC_AUTHENTICATION_TEMPLATE: Tag = Tag::from_u8(0x7C);
+/// Secure messaging template
+pub const SECURE_MESSAGING_TEMPLATE: Tag = Tag::from_u8(0x7D);
+/// Interindustry template for nesting
+pub const NESTING_TEMPLATE: Tag = Tag::from_u8(0x7E);
+
+/// Display control template
+pub const DISPLAY_CONTROL_TEMPLATE: Tag = Tag::from_u16(0x7F20);
+/// Cardholder certificate
+pub const CARDHOLDER_CERTIFICATE: Tag = Tag::from_u16(0x7F21);
+/// Biometric data template
+pub const BIOMETRIC_DATA_TEMPLATE: Tag = Tag::from_u16(0x7F2E);
+/// Cardholder public key template
+pub const CARDHOLDER_PUBLIC_KEY_TEMPLATE: Tag = Tag::from_u16(0x7F49);
+/// Certificate holder authorization template
+pub const CERTIFICATE_HOLDER_AUTHORIZATION_TEMPLATE: Tag = Tag::from_u16(0x7F4C);
+/// Certificate content template
+pub const CERTIFICATE_CONTENT_TEMPLATE: Tag = Tag::from_u16(0x7F4E);
+/// Biometric information template
+pub const BIOMETRIC_INFORMATION_TEMPLATE: Tag = Tag::from_u16(0x7F60);
+/// Biometric information group template
+pub const BIOMETRIC_INFORMATION_GROUP_TEMPLATE: Tag = Tag::from_u16(0x7F61);
+/// Extended length information, the maximum command and response lengths supported by the card
+pub const EXTENDED_LENGTH_INFORMATION: Tag = Tag::from_u16(0x7F66);
@@ -0,0 +1,570 @@
+//! Annotated rendering of APDU exchanges for logs
+//!
+//! ```
+//! use iso7816::trace::{command, response, Redaction};
+//! let verify = [0x00, 0x20, 0x00, 0x81, 0x04, 0x31, 0x32, 0x33, 0x34];
+//! assert_eq!(
+//!     command(&verify, Redaction::DEFAULT).to_string(),
+//!     "> 00 20 00 81 Lc=4 <4 bytes redacted> (VERIFY)",
+//! );
+//! assert_eq!(response(&[0x63, 0xC2]).to_string(), "< 63C2 (RemainingRetries(2))");
+//! ```
+//!
+//! The data field of commands whose instruction is listed in the [`Redaction`] is not rendered,
+//! so that logs can be shared without leaking PINs or keys.
+//!
+//! [`exchange`] renders a command and its response over multiple lines, decoding the class, the
+//! parameters of known instructions, the data objects of the data fields and the status word:
+//!
+//! ```
+//! use iso7816::trace::{exchange, Redaction};
+//! let select = [0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08, 0x00];
+//! let fci = [0x6F, 0x07, 0x84, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08, 0x90, 0x00];
+//! assert_eq!(
+//!     exchange(&select, &fci, Redaction::DEFAULT).to_string(),
+//!     "\
+//! > 00 A4 04 00 Lc=5 A000000308 Le=256 (SELECT)
+//!   CLA 00: first interindustry, channel 0, last or only command, no secure messaging
+//!   INS A4: SELECT
+//!   P1 04: select by DF name
+//!   P2 00: return the FCI, first or only occurrence
+//!   data (5 bytes): A000000308
+//! < 6F078405A000000308 9000 (Success)
+//!   data (9 bytes):
+//!     6F (7 bytes)
+//!       84 (5 bytes): A000000308
+//!   SW 9000: Success
+//! "
+//! );
+//! ```
+
+use core::fmt::{self, Display, Formatter};
+
+use crate::command::class::{Chain, Class, Interindustry, Range, SecureMessaging};
+use crate::command::CommandView;
+use crate::tlv::{self, TlvRef};
+use crate::Status;
+
+/// Instructions whose data field must not be rendered
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Redaction<'a> {
+    instructions: &'a [u8],
+}
+
+impl<'a> Redaction<'a> {
+    /// VERIFY, CHANGE REFERENCE DATA, RESET RETRY COUNTER and PUT DATA
+    pub const DEFAULT: Redaction<'static> = Redaction {
+        instructions: &[0x20, 0x21, 0x24, 0x25, 0x2C, 0xDA, 0xDB],
+    };
+
+    /// Render all data fields
+    pub const NONE: Redaction<'static> = Redaction { instructions: &[] };
+
+    pub const fn new(instructions: &'a [u8]) -> Self {
+        Self { instructions }
+    }
+
+    pub fn redacts(&self, instruction: u8) -> bool {
+        self.instructions.contains(&instruction)
+    }
+}
+
+impl Default for Redaction<'static> {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Name of the interindustry instructions defined in ISO 7816-4
+pub fn instruction_name(ins: u8) -> Option<&'static str> {
+    Some(match ins {
+        0x04 => "DEACTIVATE FILE",
+        0x0C => "ERASE RECORD",
+        0x0E | 0x0F => "ERASE BINARY",
+        0x10 => "PERFORM SCQL OPERATION",
+        0x12 => "PERFORM TRANSACTION OPERATION",
+        0x14 => "PERFORM USER OPERATION",
+        0x20 | 0x21 => "VERIFY",
+        0x22 => "MANAGE SECURITY ENVIRONMENT",
+        0x24 | 0x25 => "CHANGE REFERENCE DATA",
+        0x26 => "DISABLE VERIFICATION REQUIREMENT",
+        0x28 => "ENABLE VERIFICATION REQUIREMENT",
+        0x2A => "PERFORM SECURITY OPERATION",
+        0x2C => "RESET RETRY COUNTER",
+        0x44 => "ACTIVATE FILE",
+        0x46 | 0x47 => "GENERATE ASYMMETRIC KEY PAIR",
+        0x70 => "MANAGE CHANNEL",
+        0x82 => "EXTERNAL AUTHENTICATE",
+        0x84 => "GET CHALLENGE",
+        0x86 | 0x87 => "GENERAL AUTHENTICATE",
+        0x88 => "INTERNAL AUTHENTICATE",
+        0xA0 | 0xA1 => "SEARCH BINARY",
+        0xA2 => "SEARCH RECORD",
+        0xA4 => "SELECT",
+        0xB0 | 0xB1 => "READ BINARY",
+        0xB2 | 0xB3 => "READ RECORD",
+        0xC0 => "GET RESPONSE",
+        0xC2 | 0xC3 => "ENVELOPE",
+        0xCA | 0xCB => "GET DATA",
+        0xD0 | 0xD1 => "WRITE BINARY",
+        0xD2 => "WRITE RECORD",
+        0xD6 | 0xD7 => "UPDATE BINARY",
+        0xDA | 0xDB => "PUT DATA",
+        0xDC | 0xDD => "UPDATE RECORD",
+        0xE0 => "CREATE FILE",
+        0xE2 => "APPEND RECORD",
+        0xE4 => "DELETE FILE",
+        0xE6 => "TERMINATE DF",
+        0xE8 => "TERMINATE EF",
+        0xFE => "TERMINATE CARD USAGE",
+        _ => return None,
+    })
+}
+
+fn write_hex(f: &mut Formatter<'_>, data: &[u8]) -> fmt::Result {
+    for byte in data {
+        write!(f, "{byte:02X}")?;
+    }
+    Ok(())
+}
+
+/// Rendering of a command APDU, see [`command`]
+#[derive(Clone, Copy, Debug)]
+pub struct CommandTrace<'a> {
+    apdu: &'a [u8],
+    redaction: Redaction<'a>,
+}
+
+/// Render a command APDU
+pub fn command<'a>(apdu: &'a [u8], redaction: Redaction<'a>) -> CommandTrace<'a> {
+    CommandTrace { apdu, redaction }
+}
+
+impl Display for CommandTrace<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("> ")?;
+        let Ok(view) = CommandView::try_from(self.apdu) else {
+            // Can't locate the data field, so redact everything but the header
+            let (header, body) = self.apdu.split_at(self.apdu.len().min(4));
+            write_hex(f, header)?;
+            if let Some(&ins) = header.get(1) {
+                if self.redaction.redacts(ins) && !body.is_empty() {
+                    return write!(f, " <{} bytes redacted> (invalid)", body.len());
+                }
+            }
+            f.write_str(" ")?;
+            write_hex(f, body)?;
+            return f.write_str(" (invalid)");
+        };
+        let ins: u8 = view.instruction().into();
+        write!(
+            f,
+            "{:02X} {:02X} {:02X} {:02X}",
+            view.class().into_inner(),
+            ins,
+            view.p1,
+            view.p2
+        )?;
+        let data = view.data();
+        if !data.is_empty() {
+            write!(f, " Lc={}", data.len())?;
+            if self.redaction.redacts(ins) {
+                write!(f, " <{} bytes redacted>", data.len())?;
+            } else {
+                f.write_str(" ")?;
+                write_hex(f, data)?;
+            }
+        }
+        if view.expected() != 0 {
+            write!(f, " Le={}", view.expected())?;
+        }
+        let class = view.class();
+        if class.chain().not_the_last() {
+            f.write_str(" [chained]")?;
+        }
+        if !interindustry(class) {
+            return Ok(());
+        }
+        if !class.secure_messaging().none() {
+            write!(f, " [SM {:?}]", class.secure_messaging())?;
+        }
+        if let Some(name) = instruction_name(ins) {
+            write!(f, " ({name})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the instruction and secure messaging indication of commands with this class follow ISO 7816-4
+fn interindustry(class: Class) -> bool {
+    !matches!(class.range(), Range::Proprietary)
+        && !matches!(class.secure_messaging(), SecureMessaging::Unknown)
+}
+
+/// Rendering of a response APDU, see [`response`]
+#[derive(Clone, Copy, Debug)]
+pub struct ResponseTrace<'a> {
+    apdu: &'a [u8],
+}
+
+/// Render a response APDU
+pub fn response(apdu: &[u8]) -> ResponseTrace<'_> {
+    ResponseTrace { apdu }
+}
+
+impl Display for ResponseTrace<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("< ")?;
+        if self.apdu.len() < 2 {
+            write_hex(f, self.apdu)?;
+            return f.write_str(" (invalid)");
+        }
+        let (data, sw) = self.apdu.split_at(self.apdu.len() - 2);
+        if !data.is_empty() {
+            write_hex(f, data)?;
+            f.write_str(" ")?;
+        }
+        write_hex(f, sw)?;
+        write!(f, " ({:?})", Status::from((sw[0], sw[1])))
+    }
+}
+
+/// Annotated multi-line rendering of a command and its response, see [`exchange`]
+#[derive(Clone, Copy, Debug)]
+pub struct ExchangeTrace<'a> {
+    command: &'a [u8],
+    response: &'a [u8],
+    redaction: Redaction<'a>,
+}
+
+/// Render a command APDU and its response APDU over multiple lines
+pub fn exchange<'a>(
+    command: &'a [u8],
+    response: &'a [u8],
+    redaction: Redaction<'a>,
+) -> ExchangeTrace<'a> {
+    ExchangeTrace {
+        command,
+        response,
+        redaction,
+    }
+}
+
+/// Deepest nesting of data objects rendered as a tree
+const MAX_DEPTH: usize = 8;
+
+fn write_class(f: &mut Formatter<'_>, class: Class) -> fmt::Result {
+    write!(f, "  CLA {:02X}: ", class.into_inner())?;
+    f.write_str(match class.range() {
+        Range::Interindustry(Interindustry::First) => "first interindustry",
+        Range::Interindustry(Interindustry::Further) => "further interindustry",
+        Range::Interindustry(Interindustry::Reserved) => "reserved",
+        Range::Proprietary => "proprietary",
+    })?;
+    if let Some(channel) = class.channel() {
+        write!(f, ", channel {channel}")?;
+    }
+    f.write_str(match class.chain() {
+        Chain::LastOrOnly => ", last or only command",
+        Chain::NotTheLast => ", not the last command of a chain",
+        Chain::Unknown => "",
+    })?;
+    f.write_str(match class.secure_messaging() {
+        SecureMessaging::None => ", no secure messaging",
+        SecureMessaging::Proprietary => ", proprietary secure messaging",
+        SecureMessaging::Standard => ", secure messaging",
+        SecureMessaging::Authenticated => ", secure messaging with authenticated header",
+        SecureMessaging::Unknown => "",
+    })?;
+    writeln!(f)
+}
+
+fn write_key_reference(f: &mut Formatter<'_>, reference: u8) -> fmt::Result {
+    match reference {
+        0 => f.write_str("no particular reference"),
+        _ if reference & 0x80 == 0 => write!(f, "global reference {}", reference & 0x1F),
+        _ => write!(f, "specific reference {}", reference & 0x1F),
+    }
+}
+
+/// Meaning of P1 and P2 for the instructions that define them
+fn write_parameters(f: &mut Formatter<'_>, ins: u8, p1: u8, p2: u8) -> fmt::Result {
+    match ins {
+        0xA4 => {
+            let target = match p1 {
+                0x00 => "select MF, DF or EF by identifier",
+                0x01 => "select child DF",
+                0x02 => "select EF under the current DF",
+                0x03 => "select the parent DF",
+                0x04 => "select by DF name",
+                0x08 => "select by path from the MF",
+                0x09 => "select by path from the current DF",
+                _ => "unknown selection",
+            };
+            let template = match p2 & 0x0C {
+                0x00 => "return the FCI",
+                0x04 => "return the FCP",
+                0x08 => "return the FMD",
+                _ => "no response data",
+            };
+            let occurrence = match p2 & 0x03 {
+                0x00 => "first or only occurrence",
+                0x01 => "last occurrence",
+                0x02 => "next occurrence",
+                _ => "previous occurrence",
+            };
+            writeln!(f, "  P1 {p1:02X}: {target}")?;
+            writeln!(f, "  P2 {p2:02X}: {template}, {occurrence}")
+        }
+        0xB0 | 0xD0 | 0xD6 | 0x0E if p1 & 0x80 != 0 => {
+            writeln!(f, "  P1 {p1:02X}: short EF identifier {}", p1 & 0x1F)?;
+            writeln!(f, "  P2 {p2:02X}: offset {p2}")
+        }
+        0xB0 | 0xD0 | 0xD6 | 0x0E => {
+            writeln!(
+                f,
+                "  P1-P2 {p1:02X}{p2:02X}: offset {}",
+                u16::from_be_bytes([p1, p2])
+            )
+        }
+        0xB2 | 0xDC => {
+            writeln!(f, "  P1 {p1:02X}: record {p1}")?;
+            write!(f, "  P2 {p2:02X}: ")?;
+            match p2 >> 3 {
+                0 => f.write_str("current EF")?,
+                0x1F => f.write_str("reserved")?,
+                sfi => write!(f, "short EF identifier {sfi}")?,
+            }
+            writeln!(f)
+        }
+        0x20 | 0x21 | 0x24 | 0x25 | 0x2C => {
+            write!(f, "  P2 {p2:02X}: ")?;
+            write_key_reference(f, p2)?;
+            writeln!(f)
+        }
+        0x86 | 0x87 | 0x88 | 0x82 | 0x46 | 0x47 => {
+            writeln!(f, "  P1 {p1:02X}: algorithm reference")?;
+            write!(f, "  P2 {p2:02X}: ")?;
+            write_key_reference(f, p2)?;
+            writeln!(f)
+        }
+        0xCA | 0xCB | 0xDA | 0xDB => match (p1, p2) {
+            (0x3F, 0xFF) => writeln!(f, "  P1-P2 3FFF: current DF"),
+            _ => writeln!(f, "  P1-P2 {p1:02X}{p2:02X}: tag {p1:02X}{p2:02X}"),
+        },
+        0x2A => {
+            let operation = match (p1, p2) {
+                (0x9E, 0x9A) => "compute digital signature",
+                (0x00, 0xA8) => "verify digital signature",
+                (0x90, 0x80) => "hash",
+                (0x80, 0x86) => "decipher",
+                (0x86, 0x80) => "encipher",
+                (0x00, 0xA2) => "verify cryptographic checksum",
+                (0x8E, 0x80) => "compute cryptographic checksum",
+                _ => "unknown operation",
+            };
+            writeln!(f, "  P1-P2 {p1:02X}{p2:02X}: {operation}")
+        }
+        0xC0 => writeln!(f, "  P1-P2 {p1:02X}{p2:02X}"),
+        _ => write_raw_parameters(f, p1, p2),
+    }
+}
+
+fn write_raw_parameters(f: &mut Formatter<'_>, p1: u8, p2: u8) -> fmt::Result {
+    writeln!(f, "  P1 {p1:02X}")?;
+    writeln!(f, "  P2 {p2:02X}")
+}
+
+/// Whether `data` is entirely made of BER-TLV data objects
+fn is_tlv(data: &[u8]) -> bool {
+    !data.is_empty() && tlv::iter(data).all(|object| object.is_ok())
+}
+
+fn write_objects(f: &mut Formatter<'_>, data: &[u8], depth: usize) -> fmt::Result {
+    for object in TlvRef::iter(data).flatten() {
+        write!(f, "{:width$}", "", width = 2 * depth + 4)?;
+        write_hex(f, object.tag().as_bytes())?;
+        write!(f, " ({} bytes)", object.value().len())?;
+        if object.is_constructed() && depth < MAX_DEPTH && is_tlv(object.value()) {
+            writeln!(f)?;
+            write_objects(f, object.value(), depth + 1)?;
+        } else if object.value().is_empty() {
+            writeln!(f)?;
+        } else {
+            f.write_str(": ")?;
+            write_hex(f, object.value())?;
+            writeln!(f)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_data(f: &mut Formatter<'_>, data: &[u8]) -> fmt::Result {
+    if is_tlv(data) {
+        writeln!(f, "  data ({} bytes):", data.len())?;
+        write_objects(f, data, 0)
+    } else {
+        write!(f, "  data ({} bytes): ", data.len())?;
+        write_hex(f, data)?;
+        writeln!(f)
+    }
+}
+
+fn write_status(f: &mut Formatter<'_>, sw: [u8; 2]) -> fmt::Result {
+    let status = Status::from(sw);
+    write!(f, "  SW {:02X}{:02X}: ", sw[0], sw[1])?;
+    match status {
+        Status::MoreAvailable(0) => f.write_str("success, 256 or more bytes available")?,
+        Status::MoreAvailable(n) => write!(f, "success, {n} bytes available")?,
+        Status::WrongLeField(n) => write!(f, "wrong Le, {n} bytes available")?,
+        Status::RemainingRetries(n) => write!(f, "verification failed, {n} retries left")?,
+        _ => write!(f, "{status:?}")?,
+    }
+    writeln!(f)
+}
+
+impl Display for ExchangeTrace<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", command(self.command, self.redaction))?;
+        if let Ok(view) = CommandView::try_from(self.command) {
+            let ins = view.instruction().into();
+            let class = view.class();
+            write_class(f, class)?;
+            write!(f, "  INS {ins:02X}: ")?;
+            if interindustry(class) {
+                f.write_str(instruction_name(ins).unwrap_or("unknown"))?;
+                writeln!(f)?;
+                write_parameters(f, ins, view.p1, view.p2)?;
+            } else {
+                f.write_str(match class.range() {
+                    Range::Proprietary => "proprietary",
+                    _ => "unknown",
+                })?;
+                writeln!(f)?;
+                write_raw_parameters(f, view.p1, view.p2)?;
+            }
+            let data = view.data();
+            if self.redaction.redacts(ins) && !data.is_empty() {
+                writeln!(f, "  data: <{} bytes redacted>", data.len())?;
+            } else if !data.is_empty() {
+                write_data(f, data)?;
+            }
+        }
+        writeln!(f, "{}", response(self.response))?;
+        if self.response.len() >= 2 {
+            let (data, sw) = self.response.split_at(self.response.len() - 2);
+            if !data.is_empty() {
+                write_data(f, data)?;
+            }
+            write_status(f, [sw[0], sw[1]])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            command(&hex!("00A40400 05 A000000308 00"), Redaction::DEFAULT).to_string(),
+            "> 00 A4 04 00 Lc=5 A000000308 Le=256 (SELECT)"
+        );
+        assert_eq!(
+            command(&hex!("1CDB3FFF 02 0102"), Redaction::DEFAULT).to_string(),
+            "> 1C DB 3F FF Lc=2 <2 bytes redacted> [chained] [SM Authenticated] (PUT DATA)"
+        );
+        assert_eq!(
+            command(&hex!("1CDB3FFF 02 0102"), Redaction::NONE).to_string(),
+            "> 1C DB 3F FF Lc=2 0102 [chained] [SM Authenticated] (PUT DATA)"
+        );
+        assert_eq!(
+            command(&hex!("00200081 08 0102"), Redaction::DEFAULT).to_string(),
+            "> 00200081 <3 bytes redacted> (invalid)"
+        );
+        // GlobalPlatform DELETE, not DELETE FILE
+        assert_eq!(
+            command(&hex!("80E40000 03 4F01A0 00"), Redaction::NONE).to_string(),
+            "> 80 E4 00 00 Lc=3 4F01A0 Le=256"
+        );
+    }
+
+    #[test]
+    fn responses() {
+        assert_eq!(
+            response(&hex!("0102 9000")).to_string(),
+            "< 0102 9000 (Success)"
+        );
+        assert_eq!(response(&hex!("90")).to_string(), "< 90 (invalid)");
+    }
+
+    #[test]
+    fn exchanges() {
+        assert_eq!(
+            exchange(&hex!("0C20 0081 08 0102030405060708"), &hex!("63C2"), Redaction::DEFAULT)
+                .to_string(),
+            "\
+> 0C 20 00 81 Lc=8 <8 bytes redacted> [SM Authenticated] (VERIFY)
+  CLA 0C: first interindustry, channel 0, last or only command, secure messaging with authenticated header
+  INS 20: VERIFY
+  P2 81: specific reference 1
+  data: <8 bytes redacted>
+< 63C2 (RemainingRetries(2))
+  SW 63C2: verification failed, 2 retries left
+"
+        );
+        assert_eq!(
+            exchange(
+                &hex!("00CB3FFF 05 5C035FC102 00"),
+                &hex!("53 03 A1 01 FF 6100"),
+                Redaction::DEFAULT
+            )
+            .to_string(),
+            "\
+> 00 CB 3F FF Lc=5 5C035FC102 Le=256 (GET DATA)
+  CLA 00: first interindustry, channel 0, last or only command, no secure messaging
+  INS CB: GET DATA
+  P1-P2 3FFF: current DF
+  data (5 bytes):
+    5C (3 bytes): 5FC102
+< 5303A101FF 6100 (MoreAvailable(0))
+  data (5 bytes):
+    53 (3 bytes): A101FF
+  SW 6100: success, 256 or more bytes available
+"
+        );
+        assert_eq!(
+            exchange(
+                &hex!("80 10 01 02 03 010203"),
+                &hex!("90"),
+                Redaction::DEFAULT
+            )
+            .to_string(),
+            "\
+> 80 10 01 02 Lc=3 010203
+  CLA 80: proprietary, last or only command
+  INS 10: proprietary
+  P1 01
+  P2 02
+  data (3 bytes): 010203
+< 90 (invalid)
+"
+        );
+        // Proprietary use of B2, not decoded as READ RECORD
+        assert_eq!(
+            exchange(&hex!("80B2 4002 00"), &hex!("6A88"), Redaction::DEFAULT).to_string(),
+            "\
+> 80 B2 40 02 Le=256
+  CLA 80: proprietary, last or only command
+  INS B2: proprietary
+  P1 40
+  P2 02
+< 6A88 (KeyReferenceNotFound)
+  SW 6A88: KeyReferenceNotFound
+"
+        );
+    }
+}
@@ -0,0 +1,799 @@
+//! Host-side APDU exchanges
+//!
+//! [`Transport`] is the minimal interface a reader backend (PC/SC, CCID, NFC, ...) must provide:
+//! sending one command APDU and receiving the raw response APDU.
+//!
+//! [`Channel`] wraps a transport and takes care of command chaining (ISO 7816-4 5.3.3)
+//! and of fetching the remaining response data with GET RESPONSE when the card answers `61XX`,
+//! up to [`MAX_GET_RESPONSE`] times.
+//! When the card answers `6CXX`, the command is sent again with the `Le` field indicated by the card.
+//! The response data is collected into one buffer, or into [`Segments`] with [`Channel::exchange_segmented`].
+//!
+//! The timeout, retries and backoff applied to each transmission are configured with [`Config`].
+
+use core::fmt::Debug;
+use core::time::Duration;
+
+use crate::command::{class::Class, ChainedCommandIterator, CommandBuilder, Instruction};
+use crate::segments::Segments;
+use crate::{Capabilities, Data, Status};
+
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(any(feature = "std", test))]
+pub mod capture;
+pub mod fault;
+pub mod monitor;
+pub mod secure_messaging;
+
+/// Raw APDU transport
+pub trait Transport {
+    type Error: Debug;
+
+    /// Send the `command` APDU and write the response APDU (data followed by SW1-SW2) to `response`.
+    ///
+    /// Returns the length of the response APDU
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Self::Error>;
+
+    /// Set the timeout of the following transmissions. `None` means no timeout.
+    ///
+    /// The default implementation ignores the timeout.
+    fn set_timeout(&mut self, _timeout: Option<Duration>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Whether the error is transient, meaning the transmission can be retried.
+    ///
+    /// The default implementation considers all errors fatal.
+    fn is_transient(_error: &Self::Error) -> bool {
+        false
+    }
+
+    /// Wait for `duration` before retrying a transmission.
+    ///
+    /// The default implementation returns immediately.
+    fn delay(&mut self, _duration: Duration) {}
+
+    /// Acquire exclusive access to the card.
+    ///
+    /// Until [`end_transaction`](Self::end_transaction) is called, no other client may exchange APDUs with the card.
+    /// This maps to `SCardBeginTransaction` for PC/SC or to locking a mutex for shared embedded links.
+    /// The default implementation does nothing, which is correct for transports that are never shared.
+    fn begin_transaction(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Release the exclusive access acquired with [`begin_transaction`](Self::begin_transaction)
+    fn end_transaction(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Run `f` with exclusive access to the card.
+    ///
+    /// The transaction is ended even if `f` fails.
+    fn transaction<R, E, F>(&mut self, f: F) -> Result<R, E>
+    where
+        Self: Sized,
+        E: From<Self::Error>,
+        F: FnOnce(&mut Self) -> Result<R, E>,
+    {
+        self.begin_transaction()?;
+        let res = f(self);
+        let end = self.end_transaction();
+        let res = res?;
+        end?;
+        Ok(res)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    type Error = T::Error;
+
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Self::Error> {
+        T::transmit(self, command, response)
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Self::Error> {
+        T::set_timeout(self, timeout)
+    }
+
+    fn is_transient(error: &Self::Error) -> bool {
+        T::is_transient(error)
+    }
+
+    fn delay(&mut self, duration: Duration) {
+        T::delay(self, duration)
+    }
+
+    fn begin_transaction(&mut self) -> Result<(), Self::Error> {
+        T::begin_transaction(self)
+    }
+
+    fn end_transaction(&mut self) -> Result<(), Self::Error> {
+        T::end_transaction(self)
+    }
+}
+
+/// Maximum number of GET RESPONSE commands sent to collect the response to one command
+///
+/// 256 GET RESPONSE commands of 256 bytes cover the 65536 bytes of the longest extended `Le`.
+pub const MAX_GET_RESPONSE: usize = 256;
+
+/// Step of an exchange
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Serialization of a command into the frame buffer
+    Serialize,
+    /// Transmission of the command, or of one of its chained parts
+    Transmit,
+    /// Collection of the response with GET RESPONSE or a corrected `Le`
+    Reassemble,
+}
+
+/// Error returned by [`Channel::exchange`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The underlying transport failed
+    Transport { stage: Stage, error: E },
+    /// The command could not be serialized into the frame buffer
+    BufferFull,
+    /// The response APDU is shorter than the two status bytes, or longer than the frame it was received into
+    InvalidResponse,
+    /// The response data does not fit in the response buffer
+    ResponseTooLong,
+    /// The card still answered `61XX` after [`MAX_GET_RESPONSE`] GET RESPONSE commands
+    GetResponseLimit,
+}
+
+impl<E> Error<E> {
+    /// Step of the exchange that failed
+    pub fn stage(&self) -> Stage {
+        match self {
+            Self::Transport { stage, .. } => *stage,
+            Self::BufferFull => Stage::Serialize,
+            Self::InvalidResponse | Self::ResponseTooLong | Self::GetResponseLimit => {
+                Stage::Reassemble
+            }
+        }
+    }
+}
+
+/// Configuration of a [`Channel`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Timeout of each transmission, set with [`Transport::set_timeout`]
+    pub timeout: Option<Duration>,
+    /// Number of times a transmission failing with a [transient](Transport::is_transient) error is retried
+    pub retries: u8,
+    /// Delay before the first retry, doubled for each following retry
+    pub backoff: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            retries: 0,
+            backoff: Duration::from_millis(10),
+        }
+    }
+}
+
+/// APDU exchange over a [`Transport`]
+///
+/// `N` is the size of the frame buffers used to serialize commands and receive responses.
+pub struct Channel<T, const N: usize> {
+    transport: T,
+    extended_length: bool,
+    config: Config,
+}
+
+impl<T, const N: usize> Channel<T, N> {
+    /// Assumes that extended length is supported
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            extended_length: true,
+            config: Config::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    /// Set whether the card supports extended length fields.
+    ///
+    /// Without extended length support, commands are sent with chaining and `Le` is capped to 256.
+    pub fn extended_length(mut self, supported: bool) -> Self {
+        self.extended_length = supported;
+        self
+    }
+
+    /// Adapt the exchanges to the capabilities of the interface, see [`extended_length`](Self::extended_length)
+    pub fn with_capabilities(self, capabilities: &Capabilities) -> Self {
+        self.extended_length(capabilities.extended_length)
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
+
+impl<T: Transport, const N: usize> Channel<T, N> {
+    /// Run `f` with exclusive access to the card, so that the exchanges it performs are not interleaved with those of other clients.
+    ///
+    /// See [`Transport::transaction`]
+    pub fn transaction<R, E, F>(&mut self, f: F) -> Result<R, E>
+    where
+        E: From<Error<T::Error>>,
+        F: FnOnce(&mut Self) -> Result<R, E>,
+    {
+        self.transport
+            .begin_transaction()
+            .map_err(transport_error(Stage::Transmit))?;
+        let res = f(self);
+        let end = self
+            .transport
+            .end_transaction()
+            .map_err(transport_error(Stage::Transmit));
+        let res = res?;
+        end?;
+        Ok(res)
+    }
+
+    /// Send a command and collect the complete response data into `response`.
+    ///
+    /// Returns the final status of the card.
+    /// If the card rejects a command of the chain, its status is returned immediately.
+    pub fn exchange<const R: usize>(
+        &mut self,
+        command: CommandBuilder<&[u8]>,
+        response: &mut Data<R>,
+    ) -> Result<Status, Error<T::Error>> {
+        response.clear();
+        self.exchange_into(
+            command,
+            &mut Contiguous {
+                frame: [0; N],
+                response,
+            },
+        )
+    }
+
+    /// Same as [`exchange`](Self::exchange), receiving each response APDU directly into a segment of `response`.
+    ///
+    /// This avoids copying the response data from the frame buffer into one contiguous buffer.
+    /// Each segment must be large enough for a whole response APDU, status included.
+    pub fn exchange_segmented<const S: usize, const M: usize>(
+        &mut self,
+        command: CommandBuilder<&[u8]>,
+        response: &mut Segments<[u8; S], M>,
+    ) -> Result<Status, Error<T::Error>> {
+        response.clear();
+        let status = self.exchange_into(command, response);
+        response.release();
+        status
+    }
+
+    fn exchange_into(
+        &mut self,
+        command: CommandBuilder<&[u8]>,
+        response: &mut impl Reassembly,
+    ) -> Result<Status, Error<T::Error>> {
+        self.transport
+            .set_timeout(self.config.timeout)
+            .map_err(transport_error(Stage::Transmit))?;
+        let mut exchange = Exchange::new(command, self.extended_length, N);
+        let mut step = exchange.first();
+        loop {
+            match step {
+                Step::Transmit(command, stage) => {
+                    let (len, status) = self.transmit(&command, response.frame()?, stage)?;
+                    step = exchange.received(len, status, response)?;
+                }
+                Step::Done(status) => return Ok(status),
+            }
+        }
+    }
+
+    /// Returns the length of the response data and the status
+    fn transmit(
+        &mut self,
+        command: &CommandBuilder<&[u8]>,
+        frame: &mut [u8],
+        stage: Stage,
+    ) -> Result<(usize, Status), Error<T::Error>> {
+        let buffer = serialize::<N, _>(command)?;
+        let mut retries = Retries::new(&self.config);
+        let len = loop {
+            match self.transport.transmit(&buffer, frame) {
+                Ok(len) => break len,
+                Err(error) => match retries.next(T::is_transient(&error)) {
+                    Some(delay) => self.transport.delay(delay),
+                    None => return Err(Error::Transport { stage, error }),
+                },
+            }
+        };
+        split_frame(frame, len)
+    }
+}
+
+/// Next step of an [`Exchange`]
+pub(crate) enum Step<'c> {
+    /// Transmit the command, receiving its response into the frame of the [`Reassembly`]
+    Transmit(CommandBuilder<&'c [u8]>, Stage),
+    /// The exchange is complete, with the final status of the card
+    Done(Status),
+}
+
+/// Sans-IO state of an exchange: command chaining, `6CXX` retry and GET RESPONSE
+///
+/// The blocking and asynchronous channels only perform the transmissions it asks for.
+pub(crate) struct Exchange<'c> {
+    chain: ChainedCommandIterator<'c>,
+    /// Last command taken from the chain
+    command: CommandBuilder<&'c [u8]>,
+    /// Whether the response to `command` is the first one, which can be `6CXX`
+    chaining: bool,
+    /// Number of GET RESPONSE commands sent
+    rounds: usize,
+}
+
+impl<'c> Exchange<'c> {
+    pub(crate) fn new(
+        command: CommandBuilder<&'c [u8]>,
+        extended_length: bool,
+        available_len: usize,
+    ) -> Self {
+        let mut chain = command.into_chained(extended_length, available_len);
+        let command = chain
+            .next()
+            .expect("Chaining always yields at least one command");
+        Self {
+            chain,
+            command,
+            chaining: true,
+            rounds: 0,
+        }
+    }
+
+    pub(crate) fn first(&self) -> Step<'c> {
+        Step::Transmit(self.command.clone(), Stage::Transmit)
+    }
+
+    /// Process the response to the last transmitted command, `len` bytes of data having been written to the frame of `response`
+    pub(crate) fn received<E>(
+        &mut self,
+        len: usize,
+        status: Status,
+        response: &mut impl Reassembly,
+    ) -> Result<Step<'c>, Error<E>> {
+        if self.chaining {
+            if let Some(next) = self.chain.next() {
+                if status != Status::Success {
+                    return Ok(Step::Done(status));
+                }
+                self.command = next;
+                return Ok(self.first());
+            }
+            self.chaining = false;
+            if let Status::WrongLeField(le) = status {
+                let retry = self.command.clone().with_expected_len(wrong_le(le));
+                return Ok(Step::Transmit(retry, Stage::Reassemble));
+            }
+        }
+        response.keep(len)?;
+        let Status::MoreAvailable(remaining) = status else {
+            return Ok(Step::Done(status));
+        };
+        if self.rounds == MAX_GET_RESPONSE {
+            return Err(Error::GetResponseLimit);
+        }
+        self.rounds += 1;
+        Ok(Step::Transmit(
+            get_response(self.command.class(), remaining),
+            Stage::Reassemble,
+        ))
+    }
+}
+
+/// Retries of a transmission failing with a [transient](Transport::is_transient) error
+pub(crate) struct Retries {
+    left: u8,
+    backoff: Duration,
+}
+
+impl Retries {
+    pub(crate) fn new(config: &Config) -> Self {
+        Self {
+            left: config.retries,
+            backoff: config.backoff,
+        }
+    }
+
+    /// Delay to wait before retrying a failed transmission, or `None` if the error must be returned
+    pub(crate) fn next(&mut self, transient: bool) -> Option<Duration> {
+        if !transient || self.left == 0 {
+            return None;
+        }
+        self.left -= 1;
+        let delay = self.backoff;
+        self.backoff = self.backoff.saturating_mul(2);
+        Some(delay)
+    }
+}
+
+/// Destination of the response data collected by a [`Channel`]
+pub(crate) trait Reassembly {
+    /// Buffer receiving the next response APDU
+    fn frame<E>(&mut self) -> Result<&mut [u8], Error<E>>;
+
+    /// Append the first `len` bytes of the last frame to the response data
+    fn keep<E>(&mut self, len: usize) -> Result<(), Error<E>>;
+}
+
+/// Response data copied from a frame buffer into one contiguous buffer
+pub(crate) struct Contiguous<'r, const N: usize, const R: usize> {
+    pub(crate) frame: [u8; N],
+    pub(crate) response: &'r mut Data<R>,
+}
+
+impl<const N: usize, const R: usize> Reassembly for Contiguous<'_, N, R> {
+    fn frame<E>(&mut self) -> Result<&mut [u8], Error<E>> {
+        Ok(&mut self.frame)
+    }
+
+    fn keep<E>(&mut self, len: usize) -> Result<(), Error<E>> {
+        let data = self.frame.get(..len).ok_or(Error::InvalidResponse)?;
+        append(self.response, data)
+    }
+}
+
+impl<const S: usize, const M: usize> Reassembly for Segments<[u8; S], M> {
+    fn frame<E>(&mut self) -> Result<&mut [u8], Error<E>> {
+        self.reserve().ok_or(Error::ResponseTooLong)
+    }
+
+    fn keep<E>(&mut self, len: usize) -> Result<(), Error<E>> {
+        if len > S {
+            return Err(Error::InvalidResponse);
+        }
+        self.commit(len);
+        Ok(())
+    }
+}
+
+pub(crate) fn transport_error<E>(stage: Stage) -> impl FnOnce(E) -> Error<E> {
+    move |error| Error::Transport { stage, error }
+}
+
+pub(crate) fn serialize<const N: usize, E>(
+    command: &CommandBuilder<&[u8]>,
+) -> Result<heapless::Vec<u8, N>, Error<E>> {
+    let mut buffer = heapless::Vec::new();
+    command
+        .serialize_into(&mut buffer)
+        .map_err(|_| Error::BufferFull)?;
+    Ok(buffer)
+}
+
+/// Split a response APDU into its data and status
+pub fn split_status<E>(response: &[u8]) -> Result<(&[u8], Status), Error<E>> {
+    if response.len() < 2 {
+        return Err(Error::InvalidResponse);
+    }
+    let (data, sw) = response.split_at(response.len() - 2);
+    Ok((data, Status::from((sw[0], sw[1]))))
+}
+
+/// Split the response APDU of `len` bytes received into `frame`, returning the length of its data and the status
+///
+/// `len` is reported by the transport, so it is checked against the length of `frame`.
+pub(crate) fn split_frame<E>(frame: &[u8], len: usize) -> Result<(usize, Status), Error<E>> {
+    let response = frame.get(..len).ok_or(Error::InvalidResponse)?;
+    let (data, status) = split_status(response)?;
+    Ok((data.len(), status))
+}
+
+/// GET RESPONSE command fetching the data announced by a `61XX` status
+pub(crate) fn get_response(class: Class, remaining: u8) -> CommandBuilder<&'static [u8]> {
+    CommandBuilder::new(
+        class,
+        Instruction::GetResponse,
+        0,
+        0,
+        &[][..],
+        wrong_le(remaining),
+    )
+}
+
+/// Expected length indicated by SW2 in `61XX` and `6CXX` statuses
+pub(crate) fn wrong_le(sw2: u8) -> u16 {
+    if sw2 == 0 {
+        256
+    } else {
+        sw2.into()
+    }
+}
+
+pub(crate) fn append<const R: usize, E>(
+    response: &mut Data<R>,
+    data: &[u8],
+) -> Result<(), Error<E>> {
+    response
+        .extend_from_slice(data)
+        .map_err(|_| Error::ResponseTooLong)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use std::collections::VecDeque;
+
+    /// Transport answering with canned responses and recording the commands
+    #[derive(Default)]
+    pub(crate) struct Script {
+        pub(crate) commands: Vec<Vec<u8>>,
+        pub(crate) responses: VecDeque<Vec<u8>>,
+        pub(crate) in_transaction: bool,
+        pub(crate) transactions: usize,
+        /// Number of transmissions to fail before answering
+        pub(crate) failures: usize,
+        pub(crate) delays: Vec<Duration>,
+    }
+
+    impl Script {
+        pub(crate) fn new(responses: &[&[u8]]) -> Self {
+            Self {
+                responses: responses.iter().map(|r| r.to_vec()).collect(),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl Transport for Script {
+        type Error = ();
+
+        fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, ()> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(());
+            }
+            self.commands.push(command.to_vec());
+            let answer = self.responses.pop_front().ok_or(())?;
+            response[..answer.len()].copy_from_slice(&answer);
+            Ok(answer.len())
+        }
+
+        fn is_transient(_error: &()) -> bool {
+            true
+        }
+
+        fn delay(&mut self, duration: Duration) {
+            self.delays.push(duration);
+        }
+
+        fn begin_transaction(&mut self) -> Result<(), ()> {
+            assert!(!self.in_transaction);
+            self.in_transaction = true;
+            Ok(())
+        }
+
+        fn end_transaction(&mut self) -> Result<(), ()> {
+            assert!(self.in_transaction);
+            self.in_transaction = false;
+            self.transactions += 1;
+            Ok(())
+        }
+    }
+
+    /// Transport reporting more bytes than the response buffer holds
+    pub(crate) struct Overlong;
+
+    impl Transport for Overlong {
+        type Error = ();
+
+        fn transmit(&mut self, _command: &[u8], response: &mut [u8]) -> Result<usize, ()> {
+            response.fill(0x90);
+            Ok(response.len() + 1)
+        }
+    }
+
+    #[test]
+    fn transaction() {
+        let mut channel = Channel::<_, 300>::new(Script::new(&[&hex!("9000"), &hex!("6982")]));
+        let mut response = Data::<16>::new();
+        let verify =
+            CommandBuilder::new(0.try_into().unwrap(), 0x20.into(), 0, 0x81, &[1, 2][..], 0);
+        let sign = CommandBuilder::new(0.try_into().unwrap(), 0x2A.into(), 0x9E, 0x9A, &[3][..], 0);
+        let res = channel.transaction(|channel| {
+            assert!(channel.transport().in_transaction);
+            let status = channel.exchange(verify, &mut response)?;
+            assert_eq!(status, Status::Success);
+            channel.exchange(sign, &mut response)
+        });
+        assert_eq!(res, Ok(Status::SecurityStatusNotSatisfied));
+        assert!(!channel.transport().in_transaction);
+        assert_eq!(channel.transport().transactions, 1);
+
+        // The transaction is ended on errors
+        let res: Result<(), Error<()>> = channel.transaction(|_| Err(Error::InvalidResponse));
+        assert_eq!(res, Err(Error::InvalidResponse));
+        assert_eq!(channel.transport().transactions, 2);
+    }
+
+    #[test]
+    fn get_response() {
+        let mut channel =
+            Channel::<_, 300>::new(Script::new(&[&hex!("0102 6102"), &hex!("0304 9000")]));
+        let mut response = Data::<16>::new();
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0);
+        let status = channel.exchange(command, &mut response).unwrap();
+        assert_eq!(status, Status::Success);
+        assert_eq!(&*response, &hex!("01020304"));
+        assert_eq!(
+            channel.transport().commands,
+            [hex!("00CA0000").to_vec(), hex!("00C0000002").to_vec()]
+        );
+    }
+
+    #[test]
+    fn retries() {
+        let mut script = Script::new(&[&hex!("9000")]);
+        script.failures = 2;
+        let mut channel = Channel::<_, 300>::new(script).with_config(Config {
+            retries: 1,
+            ..Default::default()
+        });
+        let mut response = Data::<16>::new();
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0);
+        let err = channel
+            .exchange(command.clone(), &mut response)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            Error::Transport {
+                stage: Stage::Transmit,
+                error: ()
+            }
+        );
+        assert_eq!(err.stage(), Stage::Transmit);
+
+        channel.transport().failures = 2;
+        channel.config_mut().retries = 2;
+        let status = channel.exchange(command, &mut response).unwrap();
+        assert_eq!(status, Status::Success);
+        assert_eq!(
+            channel.transport().delays,
+            [10, 10, 20].map(Duration::from_millis)
+        );
+    }
+
+    #[test]
+    fn wrong_le() {
+        let mut channel = Channel::<_, 300>::new(Script::new(&[
+            &hex!("6C03"),
+            &hex!("010203 6101"),
+            &hex!("04 9000"),
+        ]));
+        let mut response = Data::<16>::new();
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0x10);
+        let status = channel.exchange(command, &mut response).unwrap();
+        assert_eq!(status, Status::Success);
+        assert_eq!(&*response, &hex!("01020304"));
+        assert_eq!(
+            channel.transport().commands,
+            [
+                hex!("00CA000010").to_vec(),
+                hex!("00CA000003").to_vec(),
+                hex!("00C0000001").to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn exchange_segmented() {
+        let mut channel = Channel::<_, 300>::new(Script::new(&[
+            &hex!("6C03"),
+            &hex!("010203 6102"),
+            &hex!("0405 9000"),
+            &hex!("06 6101"),
+            &hex!("07 6101"),
+        ]));
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0x10);
+        let mut response = Segments::<[u8; 8], 2>::new();
+        let status = channel
+            .exchange_segmented(command.clone(), &mut response)
+            .unwrap();
+        assert_eq!(status, Status::Success);
+        assert_eq!(
+            response.segments().collect::<Vec<_>>(),
+            [&hex!("010203")[..], &hex!("0405")]
+        );
+        assert_eq!(response.len(), 5);
+
+        // A third segment would be needed for the next GET RESPONSE
+        assert_eq!(
+            channel.exchange_segmented(command, &mut response),
+            Err(Error::ResponseTooLong)
+        );
+        assert_eq!(response.bytes().collect::<Vec<_>>(), hex!("0607"));
+    }
+
+    #[test]
+    fn chaining() {
+        let mut channel = Channel::<_, 105>::new(Script::new(&[&hex!("9000"), &hex!("AA 9000")]))
+            .extended_length(false);
+        let mut response = Data::<16>::new();
+        let command =
+            CommandBuilder::new(0.try_into().unwrap(), 0xDB.into(), 0, 0, &[5; 200][..], 0);
+        let status = channel.exchange(command, &mut response).unwrap();
+        assert_eq!(status, Status::Success);
+        assert_eq!(&*response, &hex!("AA"));
+        let commands = &channel.transport().commands;
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0][..5], hex!("10DB000064"));
+        assert_eq!(commands[1][..5], hex!("00DB000064"));
+    }
+
+    #[test]
+    fn chaining_rejected() {
+        let mut channel =
+            Channel::<_, 105>::new(Script::new(&[&hex!("6884")])).extended_length(false);
+        let mut response = Data::<16>::new();
+        let command =
+            CommandBuilder::new(0.try_into().unwrap(), 0xDB.into(), 0, 0, &[5; 200][..], 0);
+        let status = channel.exchange(command, &mut response).unwrap();
+        assert_eq!(status, Status::CommandChainingNotSupported);
+        assert_eq!(channel.transport().commands.len(), 1);
+    }
+
+    #[test]
+    fn get_response_limit() {
+        let responses = [&hex!("6100")[..]; MAX_GET_RESPONSE + 2];
+        let mut channel = Channel::<_, 300>::new(Script::new(&responses));
+        let mut response = Data::<16>::new();
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0);
+        let err = channel.exchange(command, &mut response).unwrap_err();
+        assert_eq!(err, Error::GetResponseLimit);
+        assert_eq!(err.stage(), Stage::Reassemble);
+        assert_eq!(channel.transport().commands.len(), MAX_GET_RESPONSE + 1);
+    }
+
+    #[test]
+    fn invalid_response() {
+        let mut channel = Channel::<_, 300>::new(Script::new(&[&hex!("90")]));
+        let mut response = Data::<16>::new();
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0);
+        assert_eq!(
+            channel.exchange(command.clone(), &mut response),
+            Err(Error::InvalidResponse)
+        );
+
+        let mut channel = Channel::<_, 300>::new(Overlong);
+        assert_eq!(
+            channel.exchange(command.clone(), &mut response),
+            Err(Error::InvalidResponse)
+        );
+        let mut segments = Segments::<[u8; 8], 2>::new();
+        assert_eq!(
+            channel.exchange_segmented(command, &mut segments),
+            Err(Error::InvalidResponse)
+        );
+    }
+}
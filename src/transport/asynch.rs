@@ -0,0 +1,340 @@
+//! Asynchronous counterpart of the [`transport`](super) module
+//!
+//! The traits are not object safe but do not depend on a specific executor,
+//! so they can be implemented on top of `tokio` as well as of embedded async runtimes.
+
+use core::fmt::Debug;
+use core::time::Duration;
+
+use super::{
+    serialize, split_frame, transport_error, Config, Contiguous, Error, Exchange, Reassembly,
+    Retries, Stage, Step,
+};
+use crate::command::CommandBuilder;
+use crate::segments::Segments;
+use crate::{Capabilities, Data, Status};
+
+/// Raw APDU transport
+#[allow(async_fn_in_trait)]
+pub trait Transport {
+    type Error: Debug;
+
+    /// Send the `command` APDU and write the response APDU (data followed by SW1-SW2) to `response`.
+    ///
+    /// Returns the length of the response APDU
+    async fn transmit(&mut self, command: &[u8], response: &mut [u8])
+        -> Result<usize, Self::Error>;
+
+    /// Set the timeout of the following transmissions. `None` means no timeout.
+    ///
+    /// The default implementation ignores the timeout.
+    async fn set_timeout(&mut self, _timeout: Option<Duration>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Whether the error is transient, meaning the transmission can be retried.
+    ///
+    /// The default implementation considers all errors fatal.
+    fn is_transient(_error: &Self::Error) -> bool {
+        false
+    }
+
+    /// Wait for `duration` before retrying a transmission.
+    ///
+    /// The default implementation returns immediately.
+    async fn delay(&mut self, _duration: Duration) {}
+
+    /// Acquire exclusive access to the card.
+    ///
+    /// See [`transport::Transport::begin_transaction`](super::Transport::begin_transaction)
+    async fn begin_transaction(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Release the exclusive access acquired with [`begin_transaction`](Self::begin_transaction)
+    async fn end_transaction(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    type Error = T::Error;
+
+    async fn transmit(
+        &mut self,
+        command: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        T::transmit(self, command, response).await
+    }
+
+    async fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Self::Error> {
+        T::set_timeout(self, timeout).await
+    }
+
+    fn is_transient(error: &Self::Error) -> bool {
+        T::is_transient(error)
+    }
+
+    async fn delay(&mut self, duration: Duration) {
+        T::delay(self, duration).await
+    }
+
+    async fn begin_transaction(&mut self) -> Result<(), Self::Error> {
+        T::begin_transaction(self).await
+    }
+
+    async fn end_transaction(&mut self) -> Result<(), Self::Error> {
+        T::end_transaction(self).await
+    }
+}
+
+/// APDU exchange over an asynchronous [`Transport`]
+///
+/// See [`transport::Channel`](super::Channel)
+pub struct Channel<T, const N: usize> {
+    transport: T,
+    extended_length: bool,
+    config: Config,
+}
+
+impl<T, const N: usize> Channel<T, N> {
+    /// Assumes that extended length is supported
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            extended_length: true,
+            config: Config::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    /// Set whether the card supports extended length fields.
+    ///
+    /// Without extended length support, commands are sent with chaining and `Le` is capped to 256.
+    pub fn extended_length(mut self, supported: bool) -> Self {
+        self.extended_length = supported;
+        self
+    }
+
+    /// Adapt the exchanges to the capabilities of the interface, see [`extended_length`](Self::extended_length)
+    pub fn with_capabilities(self, capabilities: &Capabilities) -> Self {
+        self.extended_length(capabilities.extended_length)
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
+
+impl<T: Transport, const N: usize> Channel<T, N> {
+    /// See [`transport::Channel::transaction`](super::Channel::transaction)
+    ///
+    /// The transaction is ended even if `f` fails.
+    pub async fn transaction<R, E, F>(&mut self, f: F) -> Result<R, E>
+    where
+        E: From<Error<T::Error>>,
+        F: AsyncFnOnce(&mut Self) -> Result<R, E>,
+    {
+        self.transport
+            .begin_transaction()
+            .await
+            .map_err(transport_error(Stage::Transmit))?;
+        let res = f(self).await;
+        let end = self
+            .transport
+            .end_transaction()
+            .await
+            .map_err(transport_error(Stage::Transmit));
+        let res = res?;
+        end?;
+        Ok(res)
+    }
+
+    /// Send a command and collect the complete response data into `response`.
+    ///
+    /// Returns the final status of the card.
+    /// If the card rejects a command of the chain, its status is returned immediately.
+    pub async fn exchange<const R: usize>(
+        &mut self,
+        command: CommandBuilder<&[u8]>,
+        response: &mut Data<R>,
+    ) -> Result<Status, Error<T::Error>> {
+        response.clear();
+        self.exchange_into(
+            command,
+            &mut Contiguous {
+                frame: [0; N],
+                response,
+            },
+        )
+        .await
+    }
+
+    /// See [`super::Channel::exchange_segmented`]
+    pub async fn exchange_segmented<const S: usize, const M: usize>(
+        &mut self,
+        command: CommandBuilder<&[u8]>,
+        response: &mut Segments<[u8; S], M>,
+    ) -> Result<Status, Error<T::Error>> {
+        response.clear();
+        let status = self.exchange_into(command, response).await;
+        response.release();
+        status
+    }
+
+    async fn exchange_into(
+        &mut self,
+        command: CommandBuilder<&[u8]>,
+        response: &mut impl Reassembly,
+    ) -> Result<Status, Error<T::Error>> {
+        self.transport
+            .set_timeout(self.config.timeout)
+            .await
+            .map_err(transport_error(Stage::Transmit))?;
+        let mut exchange = Exchange::new(command, self.extended_length, N);
+        let mut step = exchange.first();
+        loop {
+            match step {
+                Step::Transmit(command, stage) => {
+                    let (len, status) = self.transmit(&command, response.frame()?, stage).await?;
+                    step = exchange.received(len, status, response)?;
+                }
+                Step::Done(status) => return Ok(status),
+            }
+        }
+    }
+
+    /// Returns the length of the response data and the status
+    async fn transmit(
+        &mut self,
+        command: &CommandBuilder<&[u8]>,
+        frame: &mut [u8],
+        stage: Stage,
+    ) -> Result<(usize, Status), Error<T::Error>> {
+        let buffer = serialize::<N, _>(command)?;
+        let mut retries = Retries::new(&self.config);
+        let len = loop {
+            match self.transport.transmit(&buffer, frame).await {
+                Ok(len) => break len,
+                Err(error) => match retries.next(T::is_transient(&error)) {
+                    Some(delay) => self.transport.delay(delay).await,
+                    None => return Err(Error::Transport { stage, error }),
+                },
+            }
+        };
+        split_frame(frame, len)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+    use hex_literal::hex;
+
+    /// Minimal executor for futures that never wait
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    impl Transport for super::super::tests::Script {
+        type Error = ();
+
+        async fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, ()> {
+            super::super::Transport::transmit(self, command, response)
+        }
+
+        async fn begin_transaction(&mut self) -> Result<(), ()> {
+            super::super::Transport::begin_transaction(self)
+        }
+
+        async fn end_transaction(&mut self) -> Result<(), ()> {
+            super::super::Transport::end_transaction(self)
+        }
+    }
+
+    #[test]
+    fn transaction() {
+        let mut channel = Channel::<_, 300>::new(super::super::tests::Script::new(&[
+            &hex!("9000"),
+            &hex!("6982"),
+        ]));
+        let mut response = Data::<16>::new();
+        let verify =
+            CommandBuilder::new(0.try_into().unwrap(), 0x20.into(), 0, 0x81, &[1, 2][..], 0);
+        let sign = CommandBuilder::new(0.try_into().unwrap(), 0x2A.into(), 0x9E, 0x9A, &[3][..], 0);
+        let res = block_on(channel.transaction(async |channel| {
+            assert!(channel.transport().in_transaction);
+            let status = channel.exchange(verify, &mut response).await?;
+            assert_eq!(status, Status::Success);
+            channel.exchange(sign, &mut response).await
+        }));
+        assert_eq!(res, Ok(Status::SecurityStatusNotSatisfied));
+        assert!(!channel.transport().in_transaction);
+        assert_eq!(channel.transport().transactions, 1);
+
+        // The transaction is ended on errors
+        let res: Result<(), Error<()>> =
+            block_on(channel.transaction(async |_| Err(Error::InvalidResponse)));
+        assert_eq!(res, Err(Error::InvalidResponse));
+        assert_eq!(channel.transport().transactions, 2);
+    }
+
+    #[test]
+    fn get_response() {
+        let mut channel = Channel::<_, 300>::new(super::super::tests::Script::new(&[
+            &hex!("0102 6100"),
+            &hex!("0304 9000"),
+        ]));
+        let mut response = Data::<16>::new();
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0);
+        let status = block_on(channel.exchange(command, &mut response)).unwrap();
+        assert_eq!(status, Status::Success);
+        assert_eq!(&*response, &hex!("01020304"));
+        assert_eq!(
+            channel.transport().commands,
+            [hex!("00CA0000").to_vec(), hex!("00C0000000").to_vec()]
+        );
+    }
+
+    #[test]
+    fn exchange_segmented() {
+        let mut channel = Channel::<_, 300>::new(super::super::tests::Script::new(&[
+            &hex!("0102 6102"),
+            &hex!("0304 9000"),
+        ]));
+        let mut response = Segments::<[u8; 8], 2>::new();
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0);
+        let status = block_on(channel.exchange_segmented(command, &mut response)).unwrap();
+        assert_eq!(status, Status::Success);
+        assert_eq!(
+            response.segments().collect::<Vec<_>>(),
+            [&hex!("0102"), &hex!("0304")]
+        );
+    }
+}
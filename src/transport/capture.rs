@@ -0,0 +1,384 @@
+//! Capture and replay of APDU sessions
+//!
+//! [`Recorder`] wraps a transport and records its exchanges in a [`Capture`], which can be saved
+//! as text and served again by [`Replay`], so that a session recorded in the field can be turned
+//! into a test.
+//!
+//! The text format has one exchange per line: the time elapsed since the start of the capture
+//! in seconds, the command APDU and the response APDU in hexadecimal, separated by spaces.
+//! Empty lines and lines starting with `#` are ignored.
+//!
+//! ```
+//! use iso7816::transport::capture::{Capture, Replay};
+//! use iso7816::transport::Transport;
+//!
+//! let capture: Capture = "
+//! ## Reading the PIV CHUID
+//! 0.000000 00A4040005A000000308 9000
+//! 0.012500 00CB3FFF055C035FC10200 530201029000
+//! "
+//! .parse()
+//! .unwrap();
+//! assert_eq!(capture.exchanges.len(), 2);
+//!
+//! let mut replay = Replay::new(capture);
+//! let mut response = [0; 16];
+//! let select = [0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08];
+//! let len = replay.transmit(&select, &mut response).unwrap();
+//! assert_eq!(&response[..len], [0x90, 0x00]);
+//! ```
+//!
+//! This module requires the `std` feature.
+
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
+use core::time::Duration;
+use std::io;
+use std::string::String;
+use std::time::Instant;
+use std::vec::Vec;
+
+use super::Transport;
+
+/// A command and its response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exchange {
+    /// Time of the command, since the start of the capture
+    pub time: Duration,
+    pub command: Vec<u8>,
+    pub response: Vec<u8>,
+}
+
+/// Recorded APDU session
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capture {
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the capture in the text format
+    pub fn write_to(&self, mut writer: impl io::Write) -> io::Result<()> {
+        write!(writer, "{self}")
+    }
+
+    /// Read a capture in the text format
+    pub fn read_from(mut reader: impl io::Read) -> io::Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        text.parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+fn write_hex(f: &mut Formatter<'_>, data: &[u8]) -> fmt::Result {
+    for byte in data {
+        write!(f, "{byte:02X}")?;
+    }
+    Ok(())
+}
+
+impl Display for Capture {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# iso7816 capture")?;
+        for exchange in &self.exchanges {
+            write!(
+                f,
+                "{}.{:06} ",
+                exchange.time.as_secs(),
+                exchange.time.subsec_micros()
+            )?;
+            write_hex(f, &exchange.command)?;
+            f.write_str(" ")?;
+            write_hex(f, &exchange.response)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Invalid line in a capture
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Number of the line, starting at 1
+    pub line: usize,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid exchange on line {} of the capture", self.line)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => u8::from_str_radix(core::str::from_utf8(&[*high, *low]).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_time(time: &str) -> Option<Duration> {
+    let (secs, fraction) = time.split_once('.').unwrap_or((time, ""));
+    if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse::<u32>().ok()? * 10u32.pow(9 - fraction.len() as u32)
+    };
+    Some(Duration::new(secs.parse().ok()?, nanos))
+}
+
+fn parse_exchange(line: &str) -> Option<Exchange> {
+    let mut fields = line.split_whitespace();
+    let exchange = Exchange {
+        time: parse_time(fields.next()?)?,
+        command: parse_hex(fields.next()?)?,
+        response: parse_hex(fields.next()?)?,
+    };
+    fields.next().is_none().then_some(exchange)
+}
+
+impl FromStr for Capture {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, ParseError> {
+        let exchanges = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(line, text)| parse_exchange(text).ok_or(ParseError { line }))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { exchanges })
+    }
+}
+
+/// Transport decorator recording the successful exchanges
+#[derive(Debug)]
+pub struct Recorder<T> {
+    transport: T,
+    capture: Capture,
+    start: Instant,
+}
+
+impl<T: Transport> Recorder<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            capture: Capture::new(),
+            start: Instant::now(),
+        }
+    }
+
+    pub fn capture(&self) -> &Capture {
+        &self.capture
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_parts(self) -> (T, Capture) {
+        (self.transport, self.capture)
+    }
+}
+
+impl<T: Transport> Transport for Recorder<T> {
+    type Error = T::Error;
+
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Self::Error> {
+        let time = self.start.elapsed();
+        let len = self.transport.transmit(command, response)?;
+        self.capture.exchanges.push(Exchange {
+            time,
+            command: command.into(),
+            response: response[..len].into(),
+        });
+        Ok(len)
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Self::Error> {
+        self.transport.set_timeout(timeout)
+    }
+
+    fn is_transient(error: &Self::Error) -> bool {
+        T::is_transient(error)
+    }
+
+    fn delay(&mut self, duration: Duration) {
+        self.transport.delay(duration)
+    }
+
+    fn begin_transaction(&mut self) -> Result<(), Self::Error> {
+        self.transport.begin_transaction()
+    }
+
+    fn end_transaction(&mut self) -> Result<(), Self::Error> {
+        self.transport.end_transaction()
+    }
+}
+
+/// Error returned by [`Replay`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The command differs from the one of the capture at `index`
+    UnexpectedCommand { index: usize },
+    /// All the exchanges of the capture were replayed
+    Exhausted,
+    /// The captured response does not fit in the response buffer
+    ResponseTooLong,
+}
+
+/// Transport answering with the responses of a capture
+///
+/// Each command must be the same as the one captured, in the same order.
+/// The timing of the capture is not reproduced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+    capture: Capture,
+    position: usize,
+}
+
+impl Replay {
+    pub fn new(capture: Capture) -> Self {
+        Self {
+            capture,
+            position: 0,
+        }
+    }
+
+    /// Number of exchanges left to replay
+    pub fn remaining(&self) -> usize {
+        self.capture.exchanges.len() - self.position
+    }
+}
+
+impl Transport for Replay {
+    type Error = ReplayError;
+
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, ReplayError> {
+        let index = self.position;
+        let exchange = self
+            .capture
+            .exchanges
+            .get(index)
+            .ok_or(ReplayError::Exhausted)?;
+        if exchange.command != command {
+            return Err(ReplayError::UnexpectedCommand { index });
+        }
+        let len = exchange.response.len();
+        response
+            .get_mut(..len)
+            .ok_or(ReplayError::ResponseTooLong)?
+            .copy_from_slice(&exchange.response);
+        self.position += 1;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::CommandBuilder;
+    use crate::transport::{tests::Script, Channel};
+    use crate::{Data, Status};
+    use hex_literal::hex;
+
+    #[test]
+    fn record_and_replay() {
+        let script = Script::new(&[&hex!("0102 6102"), &hex!("0304 9000")]);
+        let mut channel = Channel::<_, 300>::new(Recorder::new(script));
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0);
+        let mut response = Data::<16>::new();
+        assert_eq!(
+            channel.exchange(command.clone(), &mut response),
+            Ok(Status::Success)
+        );
+
+        let mut saved = Vec::new();
+        channel.transport().capture().write_to(&mut saved).unwrap();
+        let capture = Capture::read_from(&*saved).unwrap();
+        // The time is saved with a precision of one microsecond
+        for (read, recorded) in capture
+            .exchanges
+            .iter()
+            .zip(&channel.transport().capture().exchanges)
+        {
+            assert_eq!(read.command, recorded.command);
+            assert_eq!(read.response, recorded.response);
+            assert_eq!(read.time.as_micros(), recorded.time.as_micros());
+        }
+        assert_eq!(capture.exchanges[1].command, hex!("00C0000002"));
+        assert_eq!(capture.exchanges[1].response, hex!("0304 9000"));
+
+        let mut channel = Channel::<_, 300>::new(Replay::new(capture));
+        let mut replayed = Data::<16>::new();
+        assert_eq!(
+            channel.exchange(command, &mut replayed),
+            Ok(Status::Success)
+        );
+        assert_eq!(replayed, response);
+        assert_eq!(channel.transport().remaining(), 0);
+    }
+
+    #[test]
+    fn replay_errors() {
+        let capture: Capture = "1 00A40400 9000".parse().unwrap();
+        let mut replay = Replay::new(capture);
+        let mut response = [0; 2];
+        assert_eq!(
+            replay.transmit(&hex!("00A40000"), &mut response),
+            Err(ReplayError::UnexpectedCommand { index: 0 })
+        );
+        assert_eq!(
+            replay.transmit(&hex!("00A40400"), &mut response[..1]),
+            Err(ReplayError::ResponseTooLong)
+        );
+        assert_eq!(replay.transmit(&hex!("00A40400"), &mut response), Ok(2));
+        assert_eq!(
+            replay.transmit(&hex!("00A40400"), &mut response),
+            Err(ReplayError::Exhausted)
+        );
+    }
+
+    #[test]
+    fn format() {
+        let capture: Capture = "# comment\n\n  0.5 00B0 9000\n12.000001 00B1 6A82\n"
+            .parse()
+            .unwrap();
+        assert_eq!(capture.exchanges[0].time, Duration::from_millis(500));
+        assert_eq!(capture.exchanges[1].time, Duration::new(12, 1000));
+        assert_eq!(
+            capture.to_string(),
+            "# iso7816 capture\n0.500000 00B0 9000\n12.000001 00B1 6A82\n"
+        );
+
+        for (text, line) in [
+            ("0 00B 9000", 1),
+            ("\n0 00B0", 2),
+            ("0 00B0 9000 00", 1),
+            ("0.1234567890 00B0 9000", 1),
+            ("-1 00B0 9000", 1),
+            ("0 00B0 90G0", 1),
+        ] {
+            assert_eq!(text.parse::<Capture>(), Err(ParseError { line }), "{text}");
+        }
+    }
+}
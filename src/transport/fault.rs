@@ -0,0 +1,294 @@
+//! Fault injection
+//!
+//! [`FaultInjector`] wraps a transport and alters its exchanges according to a [`Schedule`],
+//! to test how host code recovers from lost, damaged or unexpected responses.
+//!
+//! ```
+//! use iso7816::transport::fault::{Fault, FaultError, FaultInjector, Sequence};
+//! use iso7816::transport::Transport;
+//! use iso7816::Status;
+//! # struct Card;
+//! # impl Transport for Card {
+//! #     type Error = ();
+//! #     fn transmit(&mut self, _: &[u8], response: &mut [u8]) -> Result<usize, ()> {
+//! #         response[..3].copy_from_slice(&[0x01, 0x90, 0x00]);
+//! #         Ok(3)
+//! #     }
+//! # }
+//!
+//! // Lose the first response, then answer the second command with a security error
+//! let faults = [Some(Fault::Drop), Some(Fault::Status(Status::SecurityStatusNotSatisfied))];
+//! let mut transport = FaultInjector::new(Card, Sequence::new(&faults));
+//! let mut response = [0; 16];
+//! assert_eq!(transport.transmit(&[0x00, 0xB0, 0x00, 0x00], &mut response), Err(FaultError::Dropped));
+//! assert_eq!(transport.transmit(&[0x00, 0xB0, 0x00, 0x00], &mut response), Ok(2));
+//! assert_eq!(response[..2], [0x69, 0x82]);
+//! assert_eq!(transport.transmit(&[0x00, 0xB0, 0x00, 0x00], &mut response), Ok(3));
+//! ```
+
+use core::time::Duration;
+
+use super::Transport;
+use crate::Status;
+
+/// Alteration of one exchange
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The command is transmitted but its response is lost, reported as [`FaultError::Dropped`]
+    Drop,
+    /// Only the first bytes of the response are returned
+    Truncate(usize),
+    /// The byte of the response at `offset` is XORed with `mask`, if the response is long enough
+    Corrupt { offset: usize, mask: u8 },
+    /// The command is transmitted after a delay, using [`Transport::delay`]
+    Delay(Duration),
+    /// The command is not transmitted and answered with this status
+    Status(Status),
+}
+
+/// Faults to inject in the successive exchanges
+pub trait Schedule {
+    /// Fault for the exchange number `index`, starting at 0
+    fn fault(&mut self, index: usize, command: &[u8]) -> Option<Fault>;
+}
+
+impl<F: FnMut(usize, &[u8]) -> Option<Fault>> Schedule for F {
+    fn fault(&mut self, index: usize, command: &[u8]) -> Option<Fault> {
+        self(index, command)
+    }
+}
+
+/// [`Schedule`] taking the faults from a slice, one per exchange
+///
+/// Once the slice is exhausted, the exchanges are left untouched, unless the sequence is [cycled](Sequence::cycle).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sequence<'a> {
+    faults: &'a [Option<Fault>],
+    cycle: bool,
+}
+
+impl<'a> Sequence<'a> {
+    pub fn new(faults: &'a [Option<Fault>]) -> Self {
+        Self {
+            faults,
+            cycle: false,
+        }
+    }
+
+    /// Repeat the faults once the slice is exhausted
+    pub fn cycle(mut self) -> Self {
+        self.cycle = true;
+        self
+    }
+}
+
+impl Schedule for Sequence<'_> {
+    fn fault(&mut self, index: usize, _command: &[u8]) -> Option<Fault> {
+        let index = match self.cycle {
+            true if !self.faults.is_empty() => index % self.faults.len(),
+            _ => index,
+        };
+        self.faults.get(index).copied().flatten()
+    }
+}
+
+/// Error returned by [`FaultInjector`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaultError<E> {
+    /// The response was dropped by [`Fault::Drop`]
+    Dropped,
+    /// The response buffer is too small for the injected status
+    ResponseTooLong,
+    /// The underlying transport failed
+    Transport(E),
+}
+
+/// Transport decorator injecting the faults of a [`Schedule`]
+///
+/// Dropped responses are [transient](Transport::is_transient) errors, like the timeouts they simulate.
+#[derive(Debug)]
+pub struct FaultInjector<T, S> {
+    transport: T,
+    schedule: S,
+    exchanges: usize,
+    injected: usize,
+}
+
+impl<T: Transport, S: Schedule> FaultInjector<T, S> {
+    pub fn new(transport: T, schedule: S) -> Self {
+        Self {
+            transport,
+            schedule,
+            exchanges: 0,
+            injected: 0,
+        }
+    }
+
+    /// Number of exchanges attempted so far
+    pub fn exchanges(&self) -> usize {
+        self.exchanges
+    }
+
+    /// Number of faults injected so far
+    pub fn injected(&self) -> usize {
+        self.injected
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
+
+impl<T: Transport, S: Schedule> Transport for FaultInjector<T, S> {
+    type Error = FaultError<T::Error>;
+
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Self::Error> {
+        let fault = self.schedule.fault(self.exchanges, command);
+        self.exchanges += 1;
+        if fault.is_some() {
+            self.injected += 1;
+        }
+        if let Some(Fault::Status(status)) = fault {
+            let sw: [u8; 2] = status.into();
+            response
+                .get_mut(..2)
+                .ok_or(FaultError::ResponseTooLong)?
+                .copy_from_slice(&sw);
+            return Ok(2);
+        }
+        if let Some(Fault::Delay(duration)) = fault {
+            self.transport.delay(duration);
+        }
+        let len = self
+            .transport
+            .transmit(command, response)
+            .map_err(FaultError::Transport)?;
+        match fault {
+            Some(Fault::Drop) => Err(FaultError::Dropped),
+            Some(Fault::Truncate(truncated)) => Ok(len.min(truncated)),
+            Some(Fault::Corrupt { offset, mask }) => {
+                if let Some(byte) = response[..len].get_mut(offset) {
+                    *byte ^= mask;
+                }
+                Ok(len)
+            }
+            _ => Ok(len),
+        }
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Self::Error> {
+        self.transport
+            .set_timeout(timeout)
+            .map_err(FaultError::Transport)
+    }
+
+    fn is_transient(error: &Self::Error) -> bool {
+        match error {
+            FaultError::Dropped => true,
+            FaultError::ResponseTooLong => false,
+            FaultError::Transport(error) => T::is_transient(error),
+        }
+    }
+
+    fn delay(&mut self, duration: Duration) {
+        self.transport.delay(duration)
+    }
+
+    fn begin_transaction(&mut self) -> Result<(), Self::Error> {
+        self.transport
+            .begin_transaction()
+            .map_err(FaultError::Transport)
+    }
+
+    fn end_transaction(&mut self) -> Result<(), Self::Error> {
+        self.transport
+            .end_transaction()
+            .map_err(FaultError::Transport)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::CommandBuilder;
+    use crate::transport::{tests::Script, Channel, Config, Error, Stage};
+    use crate::Data;
+    use hex_literal::hex;
+
+    #[test]
+    fn faults() {
+        let script = Script::new(&[&hex!("0102 9000"), &hex!("0102 9000"), &hex!("0102 9000")]);
+        let faults = [
+            Some(Fault::Truncate(1)),
+            Some(Fault::Status(Status::ConditionsOfUseNotSatisfied)),
+            Some(Fault::Corrupt {
+                offset: 1,
+                mask: 0xFF,
+            }),
+            Some(Fault::Delay(Duration::from_millis(5))),
+        ];
+        let mut transport = FaultInjector::new(script, Sequence::new(&faults));
+        let mut response = [0; 8];
+        assert_eq!(transport.transmit(&hex!("00B00000"), &mut response), Ok(1));
+        assert_eq!(response[..1], hex!("01"));
+        assert_eq!(transport.transmit(&hex!("00B00000"), &mut response), Ok(2));
+        assert_eq!(response[..2], hex!("6985"));
+        assert_eq!(transport.transmit(&hex!("00B00000"), &mut response), Ok(4));
+        assert_eq!(response[..4], hex!("01FD 9000"));
+        assert_eq!(transport.transmit(&hex!("00B00000"), &mut response), Ok(4));
+        assert_eq!(
+            transport.transmit(&hex!("00B00000"), &mut response),
+            Err(FaultError::Transport(()))
+        );
+        // The injected status never reaches the card
+        assert_eq!(transport.transport().commands.len(), 4);
+        assert_eq!(transport.transport().delays, [Duration::from_millis(5)]);
+        assert_eq!(transport.exchanges(), 5);
+        assert_eq!(transport.injected(), 4);
+    }
+
+    #[test]
+    fn channel_recovery() {
+        // The response to the GET RESPONSE is dropped once, and the channel retries it
+        let script = Script::new(&[&hex!("0102 6102"), &hex!("0304 9000"), &hex!("0304 9000")]);
+        let schedule =
+            |index, command: &[u8]| (index == 1 && command[1] == 0xC0).then_some(Fault::Drop);
+        let mut channel =
+            Channel::<_, 300>::new(FaultInjector::new(script, schedule)).with_config(Config {
+                retries: 1,
+                ..Default::default()
+            });
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0);
+        let mut response = Data::<16>::new();
+        assert_eq!(
+            channel.exchange(command.clone(), &mut response),
+            Ok(Status::Success)
+        );
+        assert_eq!(&*response, &hex!("01020304"));
+
+        let faults = [Some(Fault::Drop)];
+        let script = Script::new(&[&hex!("9000"), &hex!("9000")]);
+        let mut channel =
+            Channel::<_, 300>::new(FaultInjector::new(script, Sequence::new(&faults).cycle()))
+                .with_config(Config {
+                    retries: 1,
+                    ..Default::default()
+                });
+        assert_eq!(
+            channel.exchange(command, &mut response),
+            Err(Error::Transport {
+                stage: Stage::Transmit,
+                error: FaultError::Dropped
+            })
+        );
+        assert_eq!(channel.transport().transport().commands.len(), 2);
+    }
+}
@@ -0,0 +1,148 @@
+//! Card presence events
+//!
+//! Host applications need to know when the card was removed, inserted or reset to select
+//! their application again and re-establish secure channels.
+//! Backends that report events natively implement [`Monitor`] directly.
+//! Backends that can only report the current state of the slot implement [`Slot`] and are wrapped in [`Polling`].
+
+use core::fmt::Debug;
+
+/// State of a card slot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CardState {
+    Absent,
+    /// A card is present.
+    ///
+    /// The value identifies the card session: it must change whenever the card is inserted or reset.
+    /// It can for example be the PC/SC event counter.
+    Present(u32),
+}
+
+impl CardState {
+    pub fn is_present(&self) -> bool {
+        matches!(self, Self::Present(_))
+    }
+}
+
+/// Card presence event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Inserted,
+    Removed,
+    /// The card was reset, or replaced by another card in between two polls
+    Reset,
+}
+
+/// Source of card presence events
+pub trait Monitor {
+    type Error: Debug;
+
+    /// Return the next event if one occurred, without blocking
+    fn poll_event(&mut self) -> Result<Option<Event>, Self::Error>;
+
+    /// Wait for the next event, calling `idle` in between polls (for example to sleep)
+    fn wait_event(&mut self, mut idle: impl FnMut()) -> Result<Event, Self::Error>
+    where
+        Self: Sized,
+    {
+        loop {
+            if let Some(event) = self.poll_event()? {
+                return Ok(event);
+            }
+            idle();
+        }
+    }
+}
+
+/// Card slot able to report its current state
+pub trait Slot {
+    type Error: Debug;
+
+    fn state(&mut self) -> Result<CardState, Self::Error>;
+}
+
+/// [`Monitor`] implementation polling the state of a [`Slot`]
+pub struct Polling<S> {
+    slot: S,
+    last: CardState,
+}
+
+impl<S> Polling<S> {
+    /// The slot is assumed to be empty initially, so that a card already present is reported as [`Event::Inserted`]
+    pub fn new(slot: S) -> Self {
+        Self {
+            slot,
+            last: CardState::Absent,
+        }
+    }
+
+    /// State of the slot at the last poll
+    pub fn last_state(&self) -> CardState {
+        self.last
+    }
+
+    pub fn slot(&mut self) -> &mut S {
+        &mut self.slot
+    }
+
+    pub fn into_inner(self) -> S {
+        self.slot
+    }
+}
+
+impl<S: Slot> Monitor for Polling<S> {
+    type Error = S::Error;
+
+    fn poll_event(&mut self) -> Result<Option<Event>, S::Error> {
+        let state = self.slot.state()?;
+        let event = match (self.last, state) {
+            (CardState::Absent, CardState::Absent) => None,
+            (CardState::Absent, CardState::Present(_)) => Some(Event::Inserted),
+            (CardState::Present(_), CardState::Absent) => Some(Event::Removed),
+            (CardState::Present(last), CardState::Present(current)) => {
+                (last != current).then_some(Event::Reset)
+            }
+        };
+        self.last = state;
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct States(std::vec::IntoIter<CardState>);
+
+    impl Slot for States {
+        type Error = ();
+
+        fn state(&mut self) -> Result<CardState, ()> {
+            self.0.next().ok_or(())
+        }
+    }
+
+    #[test]
+    fn polling() {
+        let states = vec![
+            CardState::Absent,
+            CardState::Present(1),
+            CardState::Present(1),
+            CardState::Present(2),
+            CardState::Absent,
+            CardState::Absent,
+            CardState::Present(3),
+        ];
+        let mut monitor = Polling::new(States(states.into_iter()));
+        assert_eq!(monitor.poll_event(), Ok(None));
+        assert_eq!(monitor.poll_event(), Ok(Some(Event::Inserted)));
+        assert_eq!(monitor.poll_event(), Ok(None));
+        assert_eq!(monitor.poll_event(), Ok(Some(Event::Reset)));
+        assert_eq!(monitor.poll_event(), Ok(Some(Event::Removed)));
+
+        let mut idle = 0;
+        assert_eq!(monitor.wait_event(|| idle += 1), Ok(Event::Inserted));
+        assert_eq!(idle, 1);
+        assert_eq!(monitor.last_state(), CardState::Present(3));
+    }
+}
@@ -0,0 +1,381 @@
+//! Secure messaging layer for host-side exchanges
+//!
+//! [`SmTransport`] is a [`Transport`] decorator: every command sent through it is protected by a
+//! user-supplied [`SmSession`] before being handed to the inner transport, and every response is
+//! unprotected by the same session before being returned.
+//!
+//! Since the response to a protected command is larger than the plain response, the protected
+//! command always expects the maximum response length. `61XX` statuses of the protected
+//! response are handled with GET RESPONSE before the response is unwrapped, up to
+//! [`MAX_GET_RESPONSE`] times.
+
+use core::fmt::Debug;
+use core::time::Duration;
+
+use super::{get_response, split_frame, Transport, MAX_GET_RESPONSE};
+use crate::command::class::{Class, SecureMessaging};
+use crate::command::{CommandBuilder, CommandView, ExpectedLen};
+use crate::Status;
+
+/// Secure messaging session
+pub trait SmSession {
+    type Error: Debug;
+
+    /// Class byte of the protected command
+    fn class(&self, class: Class) -> Class {
+        class.with_secure_messaging(SecureMessaging::Authenticated)
+    }
+
+    /// Write the protected data field of `command` to `protected`, returning its length.
+    ///
+    /// `header` is the header of the protected command, with the class returned by [`class`](Self::class).
+    fn wrap(
+        &mut self,
+        header: [u8; 4],
+        command: CommandView<'_>,
+        protected: &mut [u8],
+    ) -> Result<usize, Self::Error>;
+
+    /// Write the plain response data to `plain`, returning its length and the status to return to the caller.
+    ///
+    /// `data` is empty if the card returned a success or warning status without secure messaging data objects,
+    /// which must be rejected unless the session does not authenticate responses.
+    fn unwrap(
+        &mut self,
+        data: &[u8],
+        status: Status,
+        plain: &mut [u8],
+    ) -> Result<(usize, Status), Self::Error>;
+
+    /// Called instead of [`unwrap`](Self::unwrap) when the response has no data, and therefore no secure messaging data objects,
+    /// and its status is an error.
+    ///
+    /// Returns the status to return to the caller, or an error if unprotected responses with
+    /// this status must be rejected. The default implementation accepts the status.
+    fn unprotected(&mut self, status: Status) -> Result<Status, Self::Error> {
+        Ok(status)
+    }
+}
+
+impl<S: SmSession + ?Sized> SmSession for &mut S {
+    type Error = S::Error;
+
+    fn class(&self, class: Class) -> Class {
+        S::class(self, class)
+    }
+
+    fn wrap(
+        &mut self,
+        header: [u8; 4],
+        command: CommandView<'_>,
+        protected: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        S::wrap(self, header, command, protected)
+    }
+
+    fn unwrap(
+        &mut self,
+        data: &[u8],
+        status: Status,
+        plain: &mut [u8],
+    ) -> Result<(usize, Status), Self::Error> {
+        S::unwrap(self, data, status, plain)
+    }
+
+    fn unprotected(&mut self, status: Status) -> Result<Status, Self::Error> {
+        S::unprotected(self, status)
+    }
+}
+
+/// Whether `status` is an error, which the card may send without secure messaging
+///
+/// Success (`9000`), `61XX` and warnings (`62XX` and `63XX`) are not errors.
+pub(crate) fn is_error(status: Status) -> bool {
+    let [sw1, _]: [u8; 2] = status.into();
+    status != Status::Success && !matches!(sw1, 0x61..=0x63)
+}
+
+/// Error returned by [`SmTransport`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmError<T, S> {
+    /// The underlying transport failed
+    Transport(T),
+    /// The secure messaging session failed
+    Session(S),
+    /// The plain command could not be parsed
+    InvalidCommand,
+    /// The protected response is shorter than the two status bytes, or longer than the frame it was received into
+    InvalidResponse,
+    /// The protected command or response do not fit in the frame buffers
+    BufferFull,
+    /// The card still answered `61XX` after [`MAX_GET_RESPONSE`] GET RESPONSE commands
+    GetResponseLimit,
+}
+
+/// [`Transport`] decorator applying secure messaging
+///
+/// `N` is the size of the frame buffers used for the protected commands and responses.
+pub struct SmTransport<T, S, const N: usize> {
+    transport: T,
+    session: S,
+}
+
+impl<T, S, const N: usize> SmTransport<T, S, N> {
+    pub fn new(transport: T, session: S) -> Self {
+        Self { transport, session }
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn session(&mut self) -> &mut S {
+        &mut self.session
+    }
+
+    pub fn into_inner(self) -> (T, S) {
+        (self.transport, self.session)
+    }
+}
+
+impl<T: Transport, S: SmSession, const N: usize> SmTransport<T, S, N> {
+    /// Transmit a protected command, fetching the remaining response bytes on `61XX`
+    ///
+    /// Returns the length of the response data in `frame` and the status
+    fn transmit_protected(
+        &mut self,
+        command: &[u8],
+        frame: &mut [u8; N],
+    ) -> Result<(usize, Status), SmError<T::Error, S::Error>> {
+        let class = command.first().copied().unwrap_or_default();
+        let len = self
+            .transport
+            .transmit(command, frame)
+            .map_err(SmError::Transport)?;
+        let (mut data_len, mut status) =
+            split_frame::<()>(frame, len).map_err(|_| SmError::InvalidResponse)?;
+        let mut rounds = 0;
+        while let Status::MoreAvailable(remaining) = status {
+            if rounds == MAX_GET_RESPONSE {
+                return Err(SmError::GetResponseLimit);
+            }
+            rounds += 1;
+            // The frame must have room for some of the announced data besides the status
+            let rest = &mut frame[data_len..];
+            if rest.len() <= 2 {
+                return Err(SmError::BufferFull);
+            }
+            let class = Class::try_from(class).map_err(|_| SmError::InvalidCommand)?;
+            let mut get_response_buffer = heapless::Vec::<u8, 5>::new();
+            get_response(class, remaining)
+                .serialize_into(&mut get_response_buffer)
+                .map_err(|_| SmError::BufferFull)?;
+            let len = self
+                .transport
+                .transmit(&get_response_buffer, rest)
+                .map_err(SmError::Transport)?;
+            let (len, next) = split_frame::<()>(rest, len).map_err(|_| SmError::InvalidResponse)?;
+            data_len += len;
+            status = next;
+        }
+        Ok((data_len, status))
+    }
+}
+
+impl<T: Transport, S: SmSession, const N: usize> Transport for SmTransport<T, S, N> {
+    type Error = SmError<T::Error, S::Error>;
+
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, Self::Error> {
+        let command = CommandView::try_from(command).map_err(|_| SmError::InvalidCommand)?;
+        let class = self.session.class(command.class());
+        let header = [
+            class.into_inner(),
+            command.instruction().into(),
+            command.p1,
+            command.p2,
+        ];
+
+        let mut protected = [0; N];
+        let protected_len = self
+            .session
+            .wrap(header, command, &mut protected)
+            .map_err(SmError::Session)?;
+        let le = if command.extended {
+            ExpectedLen::Max
+        } else {
+            ExpectedLen::Ne(256)
+        };
+        let protected_command = CommandBuilder::new(
+            class,
+            command.instruction(),
+            command.p1,
+            command.p2,
+            &protected[..protected_len],
+            le,
+        );
+        let mut buffer = heapless::Vec::<u8, N>::new();
+        protected_command
+            .serialize_into(&mut buffer)
+            .map_err(|_| SmError::BufferFull)?;
+
+        let mut frame = [0; N];
+        let (data_len, status) = self.transmit_protected(&buffer, &mut frame)?;
+        // Only errors can be returned without secure messaging data objects: an empty
+        // successful response must still be verified by the session
+        let (len, status) = if data_len == 0 && is_error(status) {
+            let status = self.session.unprotected(status).map_err(SmError::Session)?;
+            (0, status)
+        } else {
+            self.session
+                .unwrap(&frame[..data_len], status, response)
+                .map_err(SmError::Session)?
+        };
+        let sw: [u8; 2] = status.into();
+        response
+            .get_mut(len..len + 2)
+            .ok_or(SmError::BufferFull)?
+            .copy_from_slice(&sw);
+        Ok(len + 2)
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Self::Error> {
+        self.transport
+            .set_timeout(timeout)
+            .map_err(SmError::Transport)
+    }
+
+    fn is_transient(error: &Self::Error) -> bool {
+        matches!(error, SmError::Transport(error) if T::is_transient(error))
+    }
+
+    fn delay(&mut self, duration: Duration) {
+        self.transport.delay(duration)
+    }
+
+    fn begin_transaction(&mut self) -> Result<(), Self::Error> {
+        self.transport
+            .begin_transaction()
+            .map_err(SmError::Transport)
+    }
+
+    fn end_transaction(&mut self) -> Result<(), Self::Error> {
+        self.transport.end_transaction().map_err(SmError::Transport)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{Overlong, Script};
+    use super::super::{Channel, Error, Stage};
+    use super::*;
+    use crate::Data;
+    use hex_literal::hex;
+
+    /// Session "protecting" the data by inverting all bits
+    struct Invert;
+
+    impl SmSession for Invert {
+        type Error = ();
+
+        fn wrap(
+            &mut self,
+            _header: [u8; 4],
+            command: CommandView<'_>,
+            protected: &mut [u8],
+        ) -> Result<usize, ()> {
+            for (p, b) in protected.iter_mut().zip(command.data()) {
+                *p = !b;
+            }
+            Ok(command.data().len())
+        }
+
+        fn unwrap(
+            &mut self,
+            data: &[u8],
+            status: Status,
+            plain: &mut [u8],
+        ) -> Result<(usize, Status), ()> {
+            for (p, b) in plain.iter_mut().zip(data) {
+                *p = !b;
+            }
+            Ok((data.len(), status))
+        }
+    }
+
+    #[test]
+    fn wrap_unwrap() {
+        let transport = SmTransport::<_, _, 300>::new(
+            Script::new(&[&hex!("FEFD 6101"), &hex!("FC 9000")]),
+            Invert,
+        );
+        let mut channel = Channel::<_, 300>::new(transport);
+        let mut response = Data::<16>::new();
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[0x0F][..], 3);
+        let status = channel.exchange(command, &mut response).unwrap();
+        assert_eq!(status, Status::Success);
+        assert_eq!(&*response, &hex!("010203"));
+        assert_eq!(
+            channel.transport().transport().commands,
+            [
+                hex!("0CCA0000 01 F0 00").to_vec(),
+                hex!("0CC0000001").to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_error() {
+        let transport = SmTransport::<_, _, 300>::new(Script::new(&[&hex!("6982")]), Invert);
+        let mut channel = Channel::<_, 300>::new(transport);
+        let mut response = Data::<16>::new();
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0);
+        let status = channel.exchange(command, &mut response).unwrap();
+        assert_eq!(status, Status::SecurityStatusNotSatisfied);
+        assert!(response.is_empty());
+    }
+
+    #[test]
+    fn invalid_get_response() {
+        let command = CommandBuilder::new(0.try_into().unwrap(), 0xCA.into(), 0, 0, &[][..], 0);
+        let mut response = Data::<16>::new();
+
+        let responses = [&hex!("6100")[..]; MAX_GET_RESPONSE + 2];
+        let transport = SmTransport::<_, _, 300>::new(Script::new(&responses), Invert);
+        let mut channel = Channel::<_, 300>::new(transport);
+        assert_eq!(
+            channel.exchange(command.clone(), &mut response),
+            Err(Error::Transport {
+                stage: Stage::Transmit,
+                error: SmError::GetResponseLimit
+            })
+        );
+        assert_eq!(
+            channel.transport().transport().commands.len(),
+            MAX_GET_RESPONSE + 1
+        );
+
+        // The frame is full before the last GET RESPONSE
+        let transport = SmTransport::<_, _, 8>::new(
+            Script::new(&[&hex!("010203040506 6102"), &hex!("0708 9000")]),
+            Invert,
+        );
+        let mut channel = Channel::<_, 300>::new(transport);
+        assert_eq!(
+            channel.exchange(command.clone(), &mut response),
+            Err(Error::Transport {
+                stage: Stage::Transmit,
+                error: SmError::BufferFull
+            })
+        );
+
+        let transport = SmTransport::<_, _, 300>::new(Overlong, Invert);
+        let mut channel = Channel::<_, 300>::new(transport);
+        assert_eq!(
+            channel.exchange(command, &mut response),
+            Err(Error::Transport {
+                stage: Stage::Transmit,
+                error: SmError::InvalidResponse
+            })
+        );
+    }
+}